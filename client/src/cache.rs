@@ -1,11 +1,15 @@
 use std::{path::PathBuf, sync::LazyLock};
 
+use dioxus::logger::tracing::warn;
 use dioxus::signals::{Signal, Writable};
 use platform_dirs::AppDirs;
 use server::{AccountCredentials, MultiUserGroup, UserAccount};
 
 use crate::packet_sender::{PacketSender, PacketState};
-use shared::storage::{GeneralStorage, RawStorage};
+use shared::{
+    crypto::x3dh::X3DhReceiverKeysPublic,
+    storage::{GeneralStorage, MemoryFiles, RawStorage},
+};
 
 pub static FALLBACK_CACHE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
     let mut path = PathBuf::new();
@@ -14,8 +18,35 @@ pub static FALLBACK_CACHE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
     path
 });
 
+/// Result of comparing a freshly fetched identity with the cached one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentityCheck {
+    /// Identity wasn't cached before, so it is trusted on first use.
+    FirstSeen,
+    Unchanged,
+    /// Identity key differs from the cached one. Nothing should be encrypted to the new key
+    /// until the user accepts it with [`CacheStorage::accept_identity_change`].
+    Changed,
+}
+
+pub fn check_identity(
+    cached: Option<&X3DhReceiverKeysPublic>,
+    fetched: &X3DhReceiverKeysPublic,
+) -> IdentityCheck {
+    let Some(cached) = cached else {
+        return IdentityCheck::FirstSeen;
+    };
+    if cached.ik == fetched.ik && cached.algorithms == fetched.algorithms {
+        IdentityCheck::Unchanged
+    } else {
+        IdentityCheck::Changed
+    }
+}
+
 pub struct CacheStorage {
     base_path: PathBuf,
+    /// `None` if the cache is kept on the disk.
+    memory: Option<MemoryFiles>,
 }
 
 impl Default for CacheStorage {
@@ -24,6 +55,7 @@ impl Default for CacheStorage {
             .map_or(FALLBACK_CACHE_PATH.to_path_buf(), |dirs| dirs.cache_dir);
         Self {
             base_path: cache_dir,
+            memory: None,
         }
     }
 }
@@ -32,21 +64,81 @@ impl RawStorage for CacheStorage {
     fn get_base_path(&self) -> &PathBuf {
         &self.base_path
     }
+
+    fn memory_files(&self) -> Option<&MemoryFiles> {
+        self.memory.as_ref()
+    }
 }
 
 impl GeneralStorage for CacheStorage {}
 
 impl CacheStorage {
+    /// Cache which is dropped along with the app, e.g. for sessions which mustn't be remembered.
+    pub fn in_memory() -> Self {
+        Self {
+            base_path: PathBuf::new(),
+            memory: Some(MemoryFiles::default()),
+        }
+    }
+
     pub fn store_user_data(&self, user_id: u64, data: &UserAccount) {
-        self.store(&format!("user{user_id}.bin"), data);
+        // Already logged. The data is fetched again next time.
+        let _ = self.store(&format!("user{user_id}.bin"), data);
     }
 
     pub fn load_user_data(&self, user_id: u64) -> Option<UserAccount> {
         self.load(&format!("user{user_id}.bin"))
     }
 
+    /// Prekey bundle is cached separately from the profile, so that it can be fetched without
+    /// the profile when starting a conversation.
+    pub fn store_prekey_bundle(&self, user_id: u64, data: &X3DhReceiverKeysPublic) {
+        // Already logged. The data is fetched again next time.
+        let _ = self.store(&format!("prekeys{user_id}.bin"), data);
+    }
+
+    pub fn load_prekey_bundle(&self, user_id: u64) -> Option<X3DhReceiverKeysPublic> {
+        self.load(&format!("prekeys{user_id}.bin"))
+    }
+
+    /// Returns the last trusted identity of the user.
+    pub fn known_identity(&self, user_id: u64) -> Option<X3DhReceiverKeysPublic> {
+        self.load_prekey_bundle(user_id)
+            .or_else(|| self.load_user_data(user_id).map(|data| data.cryptoidentity))
+    }
+
+    /// Compares the fetched identity with the trusted one and caches it unless the identity key
+    /// has changed. In that case the old identity is kept until the change is accepted.
+    pub fn update_identity(&self, user_id: u64, data: &X3DhReceiverKeysPublic) -> IdentityCheck {
+        let check = check_identity(self.known_identity(user_id).as_ref(), data);
+        if check == IdentityCheck::Changed {
+            warn!("Identity key of user {user_id} has changed");
+        } else {
+            self.store_prekey_bundle(user_id, data);
+        }
+        check
+    }
+
+    /// Same as [`Self::update_identity`], but also caches the profile.
+    pub fn update_user_data(&self, user_id: u64, data: &UserAccount) -> IdentityCheck {
+        let check = self.update_identity(user_id, &data.cryptoidentity);
+        if check != IdentityCheck::Changed {
+            self.store_user_data(user_id, data);
+        }
+        check
+    }
+
+    pub fn accept_identity_change(&self, user_id: u64, data: &X3DhReceiverKeysPublic) {
+        self.store_prekey_bundle(user_id, data);
+        if let Some(mut user_data) = self.load_user_data(user_id) {
+            user_data.cryptoidentity = data.clone();
+            self.store_user_data(user_id, &user_data);
+        }
+    }
+
     pub fn store_group_data(&self, group_id: u64, data: &MultiUserGroup) {
-        self.store(&format!("group{group_id}.bin"), data);
+        // Already logged. The data is fetched again next time.
+        let _ = self.store(&format!("group{group_id}.bin"), data);
     }
 
     pub fn load_group_data(&self, group_id: u64) -> Option<MultiUserGroup> {
@@ -69,7 +161,7 @@ impl CacheStorage {
             .await;
 
         if let PacketState::Response(Some(ref data)) = signal() {
-            self.store_user_data(user_id, data);
+            self.update_user_data(user_id, data);
         }
     }
 
@@ -94,7 +186,7 @@ impl CacheStorage {
             .await;
 
         if let PacketState::Response(Some(ref data)) = signal()[index] {
-            self.store_user_data(user_id, data);
+            self.update_user_data(user_id, data);
         }
     }
 
@@ -120,3 +212,67 @@ impl CacheStorage {
 }
 
 pub static CACHE: LazyLock<CacheStorage> = LazyLock::new(Default::default);
+
+#[cfg(test)]
+mod tests {
+    use shared::crypto::{CryptoAlgorithms, PublicKey, x3dh::X3DhReceiverKeysPublic};
+
+    use super::{CacheStorage, IdentityCheck, check_identity};
+
+    fn identity(ik: &[u8], spk: &[u8]) -> X3DhReceiverKeysPublic {
+        X3DhReceiverKeysPublic {
+            algorithms: CryptoAlgorithms::from_string("test".to_owned()),
+            ik: PublicKey { pk: ik.into() },
+            spk: PublicKey { pk: spk.into() },
+            spk_signature: Box::new([]),
+            opks: vec![],
+        }
+    }
+
+    #[test]
+    fn test_first_seen() {
+        assert_eq!(
+            check_identity(None, &identity(&[1], &[2])),
+            IdentityCheck::FirstSeen
+        );
+    }
+
+    #[test]
+    fn test_unchanged_identity() {
+        let cached = identity(&[1], &[2]);
+        let mut fetched = identity(&[1], &[3]);
+        fetched.opks.push(PublicKey { pk: Box::new([5]) });
+        assert_eq!(
+            check_identity(Some(&cached), &fetched),
+            IdentityCheck::Unchanged
+        );
+    }
+
+    #[test]
+    fn test_changed_identity() {
+        let cached = identity(&[1], &[2]);
+        let fetched = identity(&[4], &[2]);
+        assert_eq!(
+            check_identity(Some(&cached), &fetched),
+            IdentityCheck::Changed
+        );
+    }
+
+    #[test]
+    fn test_identity_change_in_memory() {
+        let cache = CacheStorage::in_memory();
+        let original = identity(&[1], &[2]);
+        let changed = identity(&[4], &[2]);
+        assert_eq!(cache.known_identity(1), None);
+        assert_eq!(cache.update_identity(1, &original), IdentityCheck::FirstSeen);
+        assert_eq!(cache.update_identity(1, &original), IdentityCheck::Unchanged);
+
+        // The trusted identity is kept until the change is accepted.
+        assert_eq!(cache.update_identity(1, &changed), IdentityCheck::Changed);
+        assert_eq!(cache.known_identity(1), Some(original));
+        cache.accept_identity_change(1, &changed);
+        assert_eq!(cache.known_identity(1), Some(changed.clone()));
+        assert_eq!(cache.update_identity(1, &changed), IdentityCheck::Unchanged);
+        assert_eq!(cache.known_identity(2), None);
+    }
+}
@@ -0,0 +1,72 @@
+use dioxus::prelude::{GlobalSignal, Readable, ServerFnError, Signal};
+use server::ServerError;
+
+/// Number of requests in a row which have to fail to reach the server to consider it offline.
+pub const OFFLINE_AFTER_FAILURES: u32 = 3;
+
+/// Whether the server can be reached, judged by the requests made recently.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Connectivity {
+    failures: u32,
+}
+
+impl Connectivity {
+    pub fn record_success(&mut self) {
+        self.failures = 0;
+    }
+
+    pub fn record_failure(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+    }
+
+    pub fn is_offline(self) -> bool {
+        self.failures >= OFFLINE_AFTER_FAILURES
+    }
+}
+
+pub static CONNECTIVITY: GlobalSignal<Connectivity> = Signal::global(Connectivity::default);
+
+/// Returns whether the request didn't reach the server, as opposed to the server rejecting it.
+pub fn is_network_error(err: &ServerFnError<ServerError>) -> bool {
+    matches!(err, ServerFnError::Request(_))
+}
+
+fn update(update: impl FnOnce(&mut Connectivity)) {
+    let mut connectivity = *CONNECTIVITY.peek();
+    update(&mut connectivity);
+    // Most requests don't change anything, so the views showing it aren't rerendered then.
+    if connectivity != *CONNECTIVITY.peek() {
+        *CONNECTIVITY.write() = connectivity;
+    }
+}
+
+/// Updates the connectivity with the result of a request.
+pub fn track<T>(result: &Result<T, ServerFnError<ServerError>>) {
+    match result {
+        Err(err) if is_network_error(err) => update(Connectivity::record_failure),
+        _ => update(Connectivity::record_success),
+    }
+}
+
+/// Counts a request which timed out as a failure to reach the server.
+pub fn track_timeout() {
+    update(Connectivity::record_failure);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Connectivity, OFFLINE_AFTER_FAILURES};
+
+    #[test]
+    fn test_offline_after_failures() {
+        let mut connectivity = Connectivity::default();
+        for _ in 1..OFFLINE_AFTER_FAILURES {
+            connectivity.record_failure();
+        }
+        assert!(!connectivity.is_offline());
+        connectivity.record_failure();
+        assert!(connectivity.is_offline());
+        connectivity.record_success();
+        assert!(!connectivity.is_offline());
+    }
+}
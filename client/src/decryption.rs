@@ -0,0 +1,97 @@
+use std::fmt::Display;
+
+use shared::crypto::{self, CryptoAlgorithms};
+
+/// Reason why an encrypted message can't be shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecryptionError {
+    /// The key of the conversation isn't stored on this device.
+    MissingKey,
+    /// The message or the key is meant for an algorithm which this build doesn't support.
+    UnsupportedAlgorithm,
+    /// The message is corrupt or was encrypted with another key.
+    Failed,
+}
+
+impl Display for DecryptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::MissingKey => "No key to decrypt this message on this device",
+            Self::UnsupportedAlgorithm => "Message is encrypted with an unsupported algorithm",
+            Self::Failed => "Failed to decrypt message",
+        })
+    }
+}
+
+/// Decrypts a message sent with `encryption_method` with the stored key of its conversation, if
+/// there is one. Methods of other suites are reported as unsupported without trying the key.
+pub fn decrypt(
+    key: Option<&(CryptoAlgorithms, Box<[u8]>)>,
+    encryption_method: &str,
+    ciphertext: &[u8],
+) -> Result<Box<[u8]>, DecryptionError> {
+    if CryptoAlgorithms::from_encryption_method(encryption_method).is_none() {
+        return Err(DecryptionError::UnsupportedAlgorithm);
+    }
+    let (algorithms, key) = key.ok_or(DecryptionError::MissingKey)?;
+    match crypto::symmetric_decrypt(algorithms, ciphertext, key) {
+        Some(Some(plaintext)) => Ok(plaintext),
+        Some(None) => Err(DecryptionError::Failed),
+        None => Err(DecryptionError::UnsupportedAlgorithm),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::crypto::{self, CryptoAlgorithms};
+
+    use super::{DecryptionError, decrypt};
+
+    #[test]
+    fn test_decryption_errors() {
+        let algorithms = CryptoAlgorithms::prequantum_standard();
+        let key: Box<[u8]> = [0x42; 32].into();
+        let method = algorithms.encryption_method();
+        let ciphertext = crypto::symmetric_encrypt(&algorithms, b"Hello", &key).unwrap();
+
+        assert_eq!(
+            decrypt(
+                Some(&(algorithms.clone(), key.clone())),
+                &method,
+                &ciphertext
+            )
+            .as_deref(),
+            Ok(&b"Hello"[..])
+        );
+        assert_eq!(
+            decrypt(None, &method, &ciphertext),
+            Err(DecryptionError::MissingKey)
+        );
+        let other_key: Box<[u8]> = [0x24; 32].into();
+        assert_eq!(
+            decrypt(Some(&(algorithms.clone(), other_key)), &method, &ciphertext),
+            Err(DecryptionError::Failed)
+        );
+        let unknown = CryptoAlgorithms::from_string("unknown".to_owned());
+        assert_eq!(
+            decrypt(Some(&(unknown, key.clone())), &method, &ciphertext),
+            Err(DecryptionError::UnsupportedAlgorithm)
+        );
+    }
+
+    #[test]
+    fn test_unsupported_encryption_method() {
+        let algorithms = CryptoAlgorithms::prequantum_standard();
+        let key: Box<[u8]> = [0x42; 32].into();
+        let ciphertext = crypto::symmetric_encrypt(&algorithms, b"Hello", &key).unwrap();
+        // The key isn't even tried for a method of another suite.
+        assert_eq!(
+            decrypt(Some(&(algorithms, key)), "kyber-aes", &ciphertext),
+            Err(DecryptionError::UnsupportedAlgorithm)
+        );
+        assert_eq!(
+            decrypt(None, "kyber-aes", &ciphertext),
+            Err(DecryptionError::UnsupportedAlgorithm)
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::NaiveDateTime;
+use server::UserAccount;
+use shared::{crypto::CryptoAlgorithms, types::GroupPermissions};
+
+use crate::storage::STORAGE;
+
+pub fn display_name(account: &UserAccount, user_id: u64) -> String {
+    account
+        .username
+        .clone()
+        .or(account.email.clone())
+        .unwrap_or(format!("[Anonymous user {user_id}]"))
+}
+
+pub fn optional_display_name(account: Option<&UserAccount>, user_id: u64) -> String {
+    account.map_or(format!("[Deleted account {user_id}]"), |account| {
+        display_name(account, user_id)
+    })
+}
+
+pub fn nicknamed_display_name(
+    account: Option<&UserAccount>,
+    user_id: u64,
+    nickname: Option<String>,
+) -> String {
+    nickname.unwrap_or_else(|| optional_display_name(account, user_id))
+}
+
+/// Same as [`optional_display_name`], but prefers the locally stored nickname of the contact.
+pub fn contact_display_name(account: Option<&UserAccount>, user_id: u64) -> String {
+    nicknamed_display_name(account, user_id, STORAGE.contact_nickname(user_id))
+}
+
+/// Describes when an invite expiring at `expires_at` stops being valid, counting from `now`. Both
+/// times are in UTC. Returns `None` for invites which never expire.
+pub fn invite_expiry(expires_at: Option<NaiveDateTime>, now: NaiveDateTime) -> Option<String> {
+    let left = expires_at? - now;
+    Some(if left.num_seconds() <= 0 {
+        "Expired".to_owned()
+    } else if left.num_days() > 1 {
+        format!("Expires in {} days", left.num_days())
+    } else if left.num_hours() > 1 {
+        format!("Expires in {} hours", left.num_hours())
+    } else {
+        "Expires in less than 2 hours".to_owned()
+    })
+}
+
+/// Explains a login rejected because the local clock at `client_time` is too far off the server's
+/// `server_time`. Both are UNIX timestamps in seconds.
+pub fn clock_skew(client_time: u64, server_time: u64) -> String {
+    let (minutes, direction) = if client_time >= server_time {
+        ((client_time - server_time) / 60, "ahead of")
+    } else {
+        ((server_time - client_time) / 60, "behind")
+    };
+    let minutes = minutes.max(1);
+    let unit = if minutes == 1 { "minute" } else { "minutes" };
+    format!(
+        "Your clock is {minutes} {unit} {direction} the server's. Fix your system time and try again"
+    )
+}
+
+/// Short description of what a group member is allowed to do, e.g. "Can post, can invite".
+pub fn member_role(permissions: &GroupPermissions) -> String {
+    if permissions.is_admin() {
+        return "Administrator".to_owned();
+    }
+    let mut abilities = vec![];
+    if permissions.send_messages {
+        abilities.push("can post");
+    }
+    if permissions.invite_users {
+        abilities.push("can invite");
+    }
+    if !permissions.read_messages {
+        abilities.push("can't read messages");
+    }
+    let mut role = if abilities.is_empty() {
+        "No permissions".to_owned()
+    } else {
+        abilities.join(", ")
+    };
+    if let Some(first) = role.get_mut(..1) {
+        first.make_ascii_uppercase();
+    }
+    role
+}
+
+/// Returns the names of the libraries implementing the crypto suite, which tell the suites apart
+/// for the user.
+pub fn suite_name(algorithms: &CryptoAlgorithms) -> String {
+    let mut libraries: Vec<&str> = vec![];
+    for algorithm in [
+        &algorithms.hash,
+        &algorithms.kdf,
+        &algorithms.diffie_hellman,
+        &algorithms.signature,
+        &algorithms.symmetric_encryption,
+        &algorithms.aead,
+        &algorithms.rng,
+    ] {
+        let library = algorithm
+            .split_once("::")
+            .map_or(algorithm.as_str(), |(library, _)| library);
+        if !libraries.contains(&library) {
+            libraries.push(library);
+        }
+    }
+    libraries.join(" + ")
+}
+
+/// Returns the `data:` URL of an uploaded icon to be used as `img` source, or `None` if it isn't
+/// in a format the webview can display.
+pub fn icon_data_url(icon: &[u8]) -> Option<String> {
+    let mime = if icon.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "image/png"
+    } else if icon.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        "image/jpeg"
+    } else if icon.starts_with(b"GIF87a") || icon.starts_with(b"GIF89a") {
+        "image/gif"
+    } else if icon.len() >= 12 && icon.starts_with(b"RIFF") && &icon[8..12] == b"WEBP" {
+        "image/webp"
+    } else {
+        return None;
+    };
+    Some(format!("data:{mime};base64,{}", STANDARD.encode(icon)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, NaiveDateTime, TimeDelta};
+    use server::UserAccount;
+    use shared::{
+        crypto::{CryptoAlgorithms, PublicKey, x3dh::X3DhReceiverKeysPublic},
+        types::GroupPermissions,
+    };
+
+    use super::{
+        clock_skew, display_name, icon_data_url, invite_expiry, member_role,
+        nicknamed_display_name, optional_display_name, suite_name,
+    };
+
+    fn account(username: Option<&str>, email: Option<&str>) -> UserAccount {
+        UserAccount {
+            cryptoidentity: X3DhReceiverKeysPublic {
+                algorithms: CryptoAlgorithms::from_string("test".to_owned()),
+                ik: PublicKey { pk: Box::new([]) },
+                spk: PublicKey { pk: Box::new([]) },
+                spk_signature: Box::new([]),
+                opks: vec![],
+            },
+            public_key: Box::new([]),
+            email: email.map(str::to_owned),
+            username: username.map(str::to_owned),
+            icon: None,
+        }
+    }
+
+    #[test]
+    fn test_username_preferred() {
+        let account = account(Some("User"), Some("user@example.com"));
+        assert_eq!(display_name(&account, 1), "User");
+    }
+
+    #[test]
+    fn test_email_fallback() {
+        let account = account(None, Some("user@example.com"));
+        assert_eq!(display_name(&account, 1), "user@example.com");
+    }
+
+    #[test]
+    fn test_anonymous_fallback() {
+        let account = account(None, None);
+        assert_eq!(display_name(&account, 7), "[Anonymous user 7]");
+    }
+
+    #[test]
+    fn test_deleted_account() {
+        assert_eq!(optional_display_name(None, 3), "[Deleted account 3]");
+    }
+
+    #[test]
+    fn test_nickname_preferred() {
+        let account = account(Some("User"), None);
+        assert_eq!(
+            nicknamed_display_name(Some(&account), 1, Some("Friend".to_owned())),
+            "Friend"
+        );
+        assert_eq!(nicknamed_display_name(Some(&account), 1, None), "User");
+        assert_eq!(
+            nicknamed_display_name(None, 2, Some("Old friend".to_owned())),
+            "Old friend"
+        );
+    }
+
+    #[test]
+    fn test_invite_expiry() {
+        let now: NaiveDateTime = DateTime::from_timestamp(1_700_000_000, 0)
+            .unwrap()
+            .naive_utc();
+        assert_eq!(invite_expiry(None, now), None);
+        assert_eq!(
+            invite_expiry(Some(now + TimeDelta::days(14)), now).as_deref(),
+            Some("Expires in 14 days")
+        );
+        assert_eq!(
+            invite_expiry(Some(now + TimeDelta::hours(30)), now).as_deref(),
+            Some("Expires in 30 hours")
+        );
+        assert_eq!(
+            invite_expiry(Some(now + TimeDelta::minutes(5)), now).as_deref(),
+            Some("Expires in less than 2 hours")
+        );
+        assert_eq!(invite_expiry(Some(now), now).as_deref(), Some("Expired"));
+        assert_eq!(
+            invite_expiry(Some(now - TimeDelta::days(1)), now).as_deref(),
+            Some("Expired")
+        );
+    }
+
+    #[test]
+    fn test_clock_skew() {
+        assert_eq!(
+            clock_skew(1_700_000_600, 1_700_000_000),
+            "Your clock is 10 minutes ahead of the server's. Fix your system time and try again"
+        );
+        assert_eq!(
+            clock_skew(1_700_000_000, 1_700_000_090),
+            "Your clock is 1 minute behind the server's. Fix your system time and try again"
+        );
+        assert_eq!(
+            clock_skew(1_700_000_000, 1_700_000_000),
+            "Your clock is 1 minute ahead of the server's. Fix your system time and try again"
+        );
+    }
+
+    #[test]
+    fn test_member_role() {
+        assert_eq!(member_role(&GroupPermissions::admin()), "Administrator");
+        assert_eq!(
+            member_role(&GroupPermissions::default()),
+            "Can post, can invite"
+        );
+        let read_only = GroupPermissions {
+            send_messages: false,
+            read_messages: true,
+            invite_users: false,
+            custom_permissions: vec![],
+        };
+        assert_eq!(member_role(&read_only), "No permissions");
+        let inviter = GroupPermissions {
+            send_messages: false,
+            ..GroupPermissions::default()
+        };
+        assert_eq!(member_role(&inviter), "Can invite");
+    }
+
+    #[test]
+    fn test_icon_data_url() {
+        assert_eq!(
+            icon_data_url(b"\x89PNG\r\n\x1a\n").as_deref(),
+            Some("data:image/png;base64,iVBORw0KGgo=")
+        );
+        assert!(
+            icon_data_url(&[0xFF, 0xD8, 0xFF, 0xE0])
+                .unwrap()
+                .starts_with("data:image/jpeg;base64,")
+        );
+        assert!(
+            icon_data_url(b"RIFF\0\0\0\0WEBPVP8 ")
+                .unwrap()
+                .starts_with("data:image/webp;base64,")
+        );
+        assert_eq!(icon_data_url(b""), None);
+        assert_eq!(icon_data_url(b"RIFF\0\0\0\0WAVE"), None);
+        assert_eq!(icon_data_url(b"not an image"), None);
+    }
+
+    #[test]
+    fn test_suite_name() {
+        let algorithms = CryptoAlgorithms {
+            hash: "bee2-rs::bash512".to_owned(),
+            kdf: "bee2-rs::pbkdf2".to_owned(),
+            diffie_hellman: "dalek::x25519".to_owned(),
+            signature: "dalek::ed25519".to_owned(),
+            symmetric_encryption: "rustcrypto::aes-gcm".to_owned(),
+            aead: "rustcrypto::aes-gcm".to_owned(),
+            rng: "default".to_owned(),
+        };
+        assert_eq!(
+            suite_name(&algorithms),
+            "bee2-rs + dalek + rustcrypto + default"
+        );
+        assert_eq!(
+            suite_name(&CryptoAlgorithms::from_string("test".to_owned())),
+            "test"
+        );
+    }
+}
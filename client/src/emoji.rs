@@ -0,0 +1,184 @@
+/// Emojis offered in the picker, with the shortcodes which are expanded into them when typed
+/// between colons, like `:smile:`.
+pub const EMOJIS: &[(&str, &str)] = &[
+    ("smile", "😄"),
+    ("grin", "😁"),
+    ("joy", "😂"),
+    ("rofl", "🤣"),
+    ("slight_smile", "🙂"),
+    ("wink", "😉"),
+    ("blush", "😊"),
+    ("innocent", "😇"),
+    ("heart_eyes", "😍"),
+    ("kissing_heart", "😘"),
+    ("yum", "😋"),
+    ("stuck_out_tongue", "😛"),
+    ("thinking", "🤔"),
+    ("neutral_face", "😐"),
+    ("expressionless", "😑"),
+    ("smirk", "😏"),
+    ("unamused", "😒"),
+    ("roll_eyes", "🙄"),
+    ("grimacing", "😬"),
+    ("relieved", "😌"),
+    ("pensive", "😔"),
+    ("sleepy", "😪"),
+    ("sleeping", "😴"),
+    ("mask", "😷"),
+    ("sunglasses", "😎"),
+    ("nerd", "🤓"),
+    ("confused", "😕"),
+    ("worried", "😟"),
+    ("open_mouth", "😮"),
+    ("astonished", "😲"),
+    ("flushed", "😳"),
+    ("pleading", "🥺"),
+    ("cry", "😢"),
+    ("sob", "😭"),
+    ("scream", "😱"),
+    ("angry", "😠"),
+    ("rage", "😡"),
+    ("skull", "💀"),
+    ("poop", "💩"),
+    ("clown", "🤡"),
+    ("ghost", "👻"),
+    ("alien", "👽"),
+    ("robot", "🤖"),
+    ("heart", "❤️"),
+    ("broken_heart", "💔"),
+    ("sparkling_heart", "💖"),
+    ("fire", "🔥"),
+    ("sparkles", "✨"),
+    ("star", "⭐"),
+    ("zap", "⚡"),
+    ("tada", "🎉"),
+    ("gift", "🎁"),
+    ("trophy", "🏆"),
+    ("rocket", "🚀"),
+    ("eyes", "👀"),
+    ("wave", "👋"),
+    ("ok_hand", "👌"),
+    ("+1", "👍"),
+    ("thumbsup", "👍"),
+    ("-1", "👎"),
+    ("thumbsdown", "👎"),
+    ("clap", "👏"),
+    ("raised_hands", "🙌"),
+    ("pray", "🙏"),
+    ("muscle", "💪"),
+    ("point_up", "☝️"),
+    ("facepalm", "🤦"),
+    ("shrug", "🤷"),
+    ("100", "💯"),
+    ("check", "✅"),
+    ("x", "❌"),
+    ("warning", "⚠️"),
+    ("question", "❓"),
+    ("lock", "🔒"),
+    ("key", "🔑"),
+    ("coffee", "☕"),
+    ("beer", "🍺"),
+    ("pizza", "🍕"),
+    ("cake", "🍰"),
+    ("sun", "☀️"),
+    ("moon", "🌙"),
+    ("rainbow", "🌈"),
+    ("cat", "🐱"),
+    ("dog", "🐶"),
+    ("eagle", "🦅"),
+];
+
+/// Number of the recently picked emojis kept in the picker.
+pub const MAX_RECENT_EMOJIS: usize = 16;
+
+pub fn emoji_for(shortcode: &str) -> Option<&'static str> {
+    EMOJIS
+        .iter()
+        .find(|(code, _)| *code == shortcode)
+        .map(|(_, emoji)| *emoji)
+}
+
+/// Replaces the known `:shortcode:`s in the text with their emojis. Unknown ones, like the
+/// minutes in `12:30:00`, are kept as typed.
+pub fn expand_shortcodes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        result.push_str(&rest[..start]);
+        let after_colon = &rest[start + 1..];
+        let emoji = after_colon
+            .find(':')
+            .and_then(|end| emoji_for(&after_colon[..end]).map(|emoji| (end, emoji)));
+        match emoji {
+            Some((end, emoji)) => {
+                result.push_str(emoji);
+                rest = &after_colon[end + 1..];
+            }
+            None => {
+                // The closing colon of an unknown code may open the next one.
+                result.push(':');
+                rest = after_colon;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Moves the emoji to the front of the recently picked ones.
+pub fn push_recent(recents: &mut Vec<String>, emoji: &str) {
+    recents.retain(|recent| recent != emoji);
+    recents.insert(0, emoji.to_owned());
+    recents.truncate(MAX_RECENT_EMOJIS);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EMOJIS, MAX_RECENT_EMOJIS, emoji_for, expand_shortcodes, push_recent};
+
+    #[test]
+    fn test_expand_shortcodes() {
+        assert_eq!(expand_shortcodes("Hi :smile:"), "Hi 😄");
+        assert_eq!(expand_shortcodes(":+1::fire:!"), "👍🔥!");
+        assert_eq!(expand_shortcodes("no codes"), "no codes");
+        assert_eq!(expand_shortcodes(""), "");
+    }
+
+    #[test]
+    fn test_unknown_shortcodes_kept() {
+        assert_eq!(expand_shortcodes(":unknown:"), ":unknown:");
+        assert_eq!(expand_shortcodes("at 12:30:00"), "at 12:30:00");
+        assert_eq!(expand_shortcodes("a :b:smile: c"), "a :b😄 c");
+        assert_eq!(expand_shortcodes("::"), "::");
+        assert_eq!(expand_shortcodes(":smile"), ":smile");
+        assert_eq!(expand_shortcodes("::smile:"), ":😄");
+        assert_eq!(expand_shortcodes(":Smile:"), ":Smile:");
+    }
+
+    #[test]
+    fn test_shortcodes_unique() {
+        for (index, (code, _)) in EMOJIS.iter().enumerate() {
+            assert!(!code.contains(':'));
+            assert_eq!(
+                EMOJIS.iter().position(|(other, _)| other == code),
+                Some(index),
+                "{code}"
+            );
+        }
+        assert_eq!(emoji_for("eagle"), Some("🦅"));
+    }
+
+    #[test]
+    fn test_push_recent() {
+        let mut recents = vec![];
+        push_recent(&mut recents, "😄");
+        push_recent(&mut recents, "🔥");
+        push_recent(&mut recents, "😄");
+        assert_eq!(recents, vec!["😄", "🔥"]);
+        for (_, emoji) in EMOJIS {
+            push_recent(&mut recents, emoji);
+        }
+        assert_eq!(recents.len(), MAX_RECENT_EMOJIS);
+        assert_eq!(recents[0], EMOJIS.last().unwrap().1);
+    }
+}
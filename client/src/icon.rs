@@ -0,0 +1,92 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, ImageResult, codecs::jpeg::JpegEncoder};
+
+/// Largest width and height of an uploaded icon. Icons are only shown as small avatars, so
+/// larger pictures are downscaled to it.
+pub const MAX_ICON_DIMENSION: u32 = 256;
+const JPEG_QUALITY: u8 = 85;
+
+/// Downscales a picture chosen by the user to [`MAX_ICON_DIMENSION`] keeping its aspect ratio
+/// and re-encodes it, so that it fits into the icon size limits. Pictures with transparency are
+/// encoded as PNG, others as JPEG.
+pub fn prepare_icon(picture: &[u8]) -> ImageResult<Box<[u8]>> {
+    let mut image = image::load_from_memory(picture)?;
+    if image.width() > MAX_ICON_DIMENSION || image.height() > MAX_ICON_DIMENSION {
+        image = image.thumbnail(MAX_ICON_DIMENSION, MAX_ICON_DIMENSION);
+    }
+
+    let mut icon = Cursor::new(vec![]);
+    if image.color().has_alpha() {
+        image.to_rgba8().write_to(&mut icon, ImageFormat::Png)?;
+    } else {
+        image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut icon, JPEG_QUALITY))?;
+    }
+    Ok(icon.into_inner().into_boxed_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, RgbImage, RgbaImage};
+    use shared::limits::LIMITS;
+
+    use super::{MAX_ICON_DIMENSION, prepare_icon};
+
+    /// Returns a PNG of random pixels, which can't be compressed well.
+    fn noise_png(width: u32, height: u32) -> Vec<u8> {
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let image = RgbImage::from_fn(width, height, |_, _| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let [r, g, b, ..] = state.to_le_bytes();
+            image::Rgb([r, g, b])
+        });
+        let mut png = Cursor::new(vec![]);
+        image.write_to(&mut png, ImageFormat::Png).unwrap();
+        png.into_inner()
+    }
+
+    #[test]
+    fn test_large_picture_fits_limit() {
+        let picture = noise_png(1600, 1000);
+        assert!(picture.len() > LIMITS.max_group_icon_size);
+
+        let icon = prepare_icon(&picture).unwrap();
+        assert!(icon.len() <= LIMITS.max_group_icon_size);
+        assert!(icon.len() <= LIMITS.max_user_icon_size);
+        assert_eq!(image::guess_format(&icon).unwrap(), ImageFormat::Jpeg);
+        let icon = image::load_from_memory(&icon).unwrap();
+        assert_eq!(icon.width(), MAX_ICON_DIMENSION);
+        assert_eq!(icon.height(), 160);
+    }
+
+    #[test]
+    fn test_small_picture_keeps_size() {
+        let picture = noise_png(40, 30);
+        let icon = image::load_from_memory(&prepare_icon(&picture).unwrap()).unwrap();
+        assert_eq!((icon.width(), icon.height()), (40, 30));
+    }
+
+    #[test]
+    fn test_transparency_kept() {
+        let mut picture = Cursor::new(vec![]);
+        RgbaImage::from_pixel(300, 600, image::Rgba([0, 0, 0, 0]))
+            .write_to(&mut picture, ImageFormat::Png)
+            .unwrap();
+        let icon = prepare_icon(picture.get_ref()).unwrap();
+        assert_eq!(image::guess_format(&icon).unwrap(), ImageFormat::Png);
+        let icon = image::load_from_memory(&icon).unwrap();
+        assert_eq!((icon.width(), icon.height()), (128, MAX_ICON_DIMENSION));
+        assert!(icon.color().has_alpha());
+    }
+
+    #[test]
+    fn test_invalid_picture() {
+        assert!(prepare_icon(b"not a picture").is_err());
+    }
+}
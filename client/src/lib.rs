@@ -1,3 +1,19 @@
 pub mod cache;
+pub mod connectivity;
+pub mod decryption;
+pub mod display;
+pub mod emoji;
+pub mod icon;
+pub mod links;
+pub mod markdown;
+pub mod messages;
+pub mod notifications;
+pub mod outbox;
 pub mod packet_sender;
+pub mod prekeys;
+pub mod private_info;
+pub mod server_info;
 pub mod storage;
+pub mod theme;
+pub mod timeouts;
+pub mod transfer;
@@ -0,0 +1,263 @@
+use std::{fmt::Display, ops::Range, str::FromStr, sync::Mutex};
+
+use shared::crypto::fingerprint;
+
+pub const URL_SCHEME: &str = "peregrine";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeepLink {
+    User(u64),
+    Group(u64),
+}
+
+impl FromStr for DeepLink {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some(path) = s
+            .strip_prefix(URL_SCHEME)
+            .and_then(|rest| rest.strip_prefix("://"))
+        else {
+            return Err(());
+        };
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        let Some((kind, id)) = path.trim_end_matches('/').split_once('/') else {
+            return Err(());
+        };
+        let Ok(id) = id.parse::<u64>() else {
+            return Err(());
+        };
+        match kind {
+            "user" => Ok(Self::User(id)),
+            "group" => Ok(Self::Group(id)),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for DeepLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::User(id) => write!(f, "{URL_SCHEME}://user/{id}"),
+            Self::Group(id) => write!(f, "{URL_SCHEME}://group/{id}"),
+        }
+    }
+}
+
+/// Contents of a QR code used to add a contact in person. Besides the account id it carries the
+/// fingerprint of the identity key so that the key received from server can be verified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContactCode {
+    pub user_id: u64,
+    pub fingerprint: Box<[u8]>,
+}
+
+impl FromStr for ContactCode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Ok(DeepLink::User(user_id)) = s.parse() else {
+            return Err(());
+        };
+        let Some((_, query)) = s.split_once('?') else {
+            return Err(());
+        };
+        let Some(fingerprint) = query
+            .split('&')
+            .find_map(|param| param.strip_prefix("fingerprint="))
+            .and_then(fingerprint::from_hex)
+        else {
+            return Err(());
+        };
+        Ok(Self {
+            user_id,
+            fingerprint,
+        })
+    }
+}
+
+impl Display for ContactCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}?fingerprint={}",
+            DeepLink::User(self.user_id),
+            fingerprint::to_hex(&self.fingerprint),
+        )
+    }
+}
+
+/// Link the application was opened with. It is kept until the user is authenticated and then
+/// taken exactly once.
+static PENDING_LINK: Mutex<Option<DeepLink>> = Mutex::new(None);
+
+pub fn set_pending_link(link: DeepLink) {
+    *PENDING_LINK.lock().unwrap() = Some(link);
+}
+
+pub fn take_pending_link() -> Option<DeepLink> {
+    PENDING_LINK.lock().unwrap().take()
+}
+
+/// Returns the first deep link found in the command line arguments, if any.
+pub fn find_link_in_args(args: impl IntoIterator<Item = String>) -> Option<DeepLink> {
+    args.into_iter().find_map(|arg| arg.parse().ok())
+}
+
+/// Finds the start of the next URL with a supported scheme at or after `from`. A scheme directly
+/// after a letter or digit is a part of another word, like in `xhttps://`.
+fn next_url_start(lowercase_text: &str, mut from: usize) -> Option<(usize, usize)> {
+    let app_scheme = format!("{URL_SCHEME}://");
+    loop {
+        let (start, scheme_len) = ["https://", "http://", &app_scheme]
+            .into_iter()
+            .filter_map(|scheme| {
+                lowercase_text[from..]
+                    .find(scheme)
+                    .map(|index| (from + index, scheme.len()))
+            })
+            .min()?;
+        if lowercase_text[..start]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric)
+        {
+            from = start + scheme_len;
+            continue;
+        }
+        return Some((start, scheme_len));
+    }
+}
+
+/// Returns byte ranges of the web and deep links in the text of a message. Punctuation after a
+/// link is treated as a part of the sentence, unless it closes a bracket opened in the link.
+pub fn find_urls(text: &str) -> Vec<Range<usize>> {
+    let lowercase_text = text.to_ascii_lowercase();
+    let mut urls = vec![];
+    let mut from = 0;
+    while let Some((start, scheme_len)) = next_url_start(&lowercase_text, from) {
+        let rest = &text[start..];
+        let mut end = start
+            + rest
+                .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '`'))
+                .unwrap_or(rest.len());
+        while let Some(last) = text[start..end].chars().next_back() {
+            let url = &text[start..end];
+            let trailing = match last {
+                '.' | ',' | ':' | ';' | '!' | '?' | '\'' | '*' | '_' | '~' | '|' => true,
+                ')' => url.matches('(').count() < url.matches(')').count(),
+                ']' => url.matches('[').count() < url.matches(']').count(),
+                _ => false,
+            };
+            if !trailing {
+                break;
+            }
+            end -= last.len_utf8();
+        }
+        if end > start + scheme_len {
+            urls.push(start..end);
+        }
+        from = end.max(start + scheme_len);
+    }
+    urls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContactCode, DeepLink, find_link_in_args, find_urls};
+
+    #[test]
+    fn test_parse_links() {
+        assert_eq!("peregrine://user/12".parse(), Ok(DeepLink::User(12)));
+        assert_eq!("peregrine://group/3/".parse(), Ok(DeepLink::Group(3)));
+        assert_eq!("peregrine://user/".parse::<DeepLink>(), Err(()));
+        assert_eq!("peregrine://user/abc".parse::<DeepLink>(), Err(()));
+        assert_eq!("peregrine://channel/1".parse::<DeepLink>(), Err(()));
+        assert_eq!("https://user/1".parse::<DeepLink>(), Err(()));
+        assert_eq!(
+            "peregrine://user/4?fingerprint=00".parse(),
+            Ok(DeepLink::User(4))
+        );
+    }
+
+    #[test]
+    fn test_contact_code() {
+        let code = ContactCode {
+            user_id: 42,
+            fingerprint: Box::new([0xde, 0xad, 0xbe, 0xef]),
+        };
+        assert_eq!(code.to_string(), "peregrine://user/42?fingerprint=deadbeef");
+        assert_eq!(code.to_string().parse(), Ok(code));
+        assert_eq!("peregrine://user/42".parse::<ContactCode>(), Err(()));
+        assert_eq!(
+            "peregrine://group/42?fingerprint=deadbeef".parse::<ContactCode>(),
+            Err(())
+        );
+        assert_eq!(
+            "peregrine://user/42?fingerprint=xyz".parse::<ContactCode>(),
+            Err(())
+        );
+    }
+
+    #[test]
+    fn test_link_round_trip() {
+        for link in [DeepLink::User(0), DeepLink::Group(u64::MAX)] {
+            assert_eq!(link.to_string().parse(), Ok(link));
+        }
+    }
+
+    #[test]
+    fn test_find_link_in_args() {
+        let args = ["peregrine", "--flag", "peregrine://group/5"].map(str::to_owned);
+        assert_eq!(find_link_in_args(args), Some(DeepLink::Group(5)));
+        assert_eq!(find_link_in_args(["peregrine".to_owned()]), None);
+    }
+
+    fn urls(text: &str) -> Vec<&str> {
+        find_urls(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect()
+    }
+
+    #[test]
+    fn test_find_urls() {
+        assert_eq!(
+            urls("See https://example.com/a?b=c#d and http://test.org"),
+            vec!["https://example.com/a?b=c#d", "http://test.org"]
+        );
+        assert_eq!(
+            urls("Join peregrine://group/5."),
+            vec!["peregrine://group/5"]
+        );
+        assert_eq!(
+            urls("HTTPS://EXAMPLE.COM, then"),
+            vec!["HTTPS://EXAMPLE.COM"]
+        );
+        assert_eq!(urls("<https://example.com>"), vec!["https://example.com"]);
+        assert_eq!(
+            urls("no links: https:// and xhttps://a.b"),
+            Vec::<&str>::new()
+        );
+        assert_eq!(
+            urls("ftp://example.com javascript:alert(1)"),
+            Vec::<&str>::new()
+        );
+    }
+
+    #[test]
+    fn test_find_urls_brackets() {
+        assert_eq!(
+            urls("(see https://en.wikipedia.org/wiki/Rust_(programming_language))"),
+            vec!["https://en.wikipedia.org/wiki/Rust_(programming_language)"]
+        );
+        assert_eq!(
+            urls("(https://example.com/page)!"),
+            vec!["https://example.com/page"]
+        );
+        assert_eq!(
+            urls("Привет https://example.com/путь?"),
+            vec!["https://example.com/путь"]
+        );
+    }
+}
@@ -0,0 +1,716 @@
+use std::{
+    collections::HashSet,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{LazyLock, Mutex},
+};
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use serde::{Deserialize, Serialize};
+
+use crate::links::{self, URL_SCHEME};
+
+/// How the text of messages is shown, chosen in the settings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MessageFormat {
+    #[default]
+    Markdown,
+    PlainText,
+}
+
+impl MessageFormat {
+    pub const ALL: [Self; 2] = [Self::Markdown, Self::PlainText];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Markdown => "Markdown",
+            Self::PlainText => "Plain text",
+        }
+    }
+}
+
+/// Part of a paragraph. Raw HTML of the message is kept as [`Inline::Text`], so that it's shown
+/// as typed instead of being interpreted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inline {
+    Text(String),
+    Code(String),
+    Emphasis(Vec<Inline>),
+    Strong(Vec<Inline>),
+    Strikethrough(Vec<Inline>),
+    /// Only created for URLs passing [`is_safe_url`].
+    Link {
+        url: String,
+        children: Vec<Inline>,
+    },
+    LineBreak,
+    /// Text between `||`, hidden until clicked. The id is unique within the message.
+    Spoiler {
+        id: usize,
+        children: Vec<Inline>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    Paragraph(Vec<Inline>),
+    CodeBlock(String),
+    Quote(Vec<Block>),
+    List {
+        /// Number of the first item of an ordered list.
+        start: Option<u64>,
+        items: Vec<Vec<Block>>,
+    },
+    Rule,
+}
+
+/// Returns whether a link to the URL may be opened from a message. Schemes which execute code or
+/// access local files, like `javascript:` or `file:`, are rejected.
+pub fn is_safe_url(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    ["https://", "http://", "mailto:"]
+        .into_iter()
+        .any(|scheme| url.starts_with(scheme))
+        || url
+            .strip_prefix(URL_SCHEME)
+            .is_some_and(|rest| rest.starts_with("://"))
+}
+
+const SPOILER_DELIMITER: &str = "||";
+
+/// Spoilers revealed by the user. They are only kept in memory, so that they are hidden again
+/// after a restart.
+#[derive(Debug, Default)]
+pub struct RevealedSpoilers(Mutex<HashSet<(u64, usize)>>);
+
+impl RevealedSpoilers {
+    /// Identifies the message the spoilers belong to.
+    pub fn message_key(text: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn is_revealed(&self, message_key: u64, id: usize) -> bool {
+        self.0.lock().unwrap().contains(&(message_key, id))
+    }
+
+    /// Reveals the spoiler or hides it again, returning whether it's revealed now.
+    pub fn toggle(&self, message_key: u64, id: usize) -> bool {
+        let mut revealed = self.0.lock().unwrap();
+        if revealed.remove(&(message_key, id)) {
+            false
+        } else {
+            revealed.insert((message_key, id));
+            true
+        }
+    }
+}
+
+pub static REVEALED_SPOILERS: LazyLock<RevealedSpoilers> = LazyLock::new(Default::default);
+
+fn merge_text(inlines: Vec<Inline>) -> Vec<Inline> {
+    let mut result: Vec<Inline> = Vec::with_capacity(inlines.len());
+    for inline in inlines {
+        match (result.last_mut(), inline) {
+            (Some(Inline::Text(last)), Inline::Text(text)) => last.push_str(&text),
+            (_, inline) => result.push(inline),
+        }
+    }
+    result
+}
+
+/// Wraps the inlines between pairs of `||` into spoilers. Unpaired delimiters are kept as text.
+fn spoilers(inlines: Vec<Inline>) -> Vec<Inline> {
+    // `None` stands for a delimiter.
+    let mut pieces = vec![];
+    for inline in merge_text(inlines) {
+        match inline {
+            Inline::Text(text) if text.contains(SPOILER_DELIMITER) => {
+                for (index, part) in text.split(SPOILER_DELIMITER).enumerate() {
+                    if index > 0 {
+                        pieces.push(None);
+                    }
+                    if !part.is_empty() {
+                        pieces.push(Some(Inline::Text(part.to_owned())));
+                    }
+                }
+            }
+            inline => pieces.push(Some(inline)),
+        }
+    }
+    let mut result = vec![];
+    let mut spoiler: Option<Vec<Inline>> = None;
+    for piece in pieces {
+        match (piece, spoiler.take()) {
+            (Some(inline), Some(mut children)) => {
+                children.push(inline);
+                spoiler = Some(children);
+            }
+            (Some(inline), None) => result.push(inline),
+            (None, Some(children)) if children.is_empty() => {
+                result.push(Inline::Text(SPOILER_DELIMITER.repeat(2)));
+            }
+            (None, Some(children)) => result.push(Inline::Spoiler { id: 0, children }),
+            (None, None) => spoiler = Some(vec![]),
+        }
+    }
+    if let Some(children) = spoiler {
+        result.push(Inline::Text(SPOILER_DELIMITER.to_owned()));
+        result.extend(children);
+    }
+    merge_text(result)
+}
+
+fn number_spoilers(inlines: &mut [Inline], next_id: &mut usize) {
+    for inline in inlines {
+        match inline {
+            Inline::Spoiler { id, children } => {
+                *id = *next_id;
+                *next_id += 1;
+                number_spoilers(children, next_id);
+            }
+            Inline::Emphasis(children)
+            | Inline::Strong(children)
+            | Inline::Strikethrough(children)
+            | Inline::Link { children, .. } => number_spoilers(children, next_id),
+            Inline::Text(_) | Inline::Code(_) | Inline::LineBreak => {}
+        }
+    }
+}
+
+fn number_block_spoilers(blocks: &mut [Block], next_id: &mut usize) {
+    for block in blocks {
+        match block {
+            Block::Paragraph(inlines) => number_spoilers(inlines, next_id),
+            Block::Quote(blocks) => number_block_spoilers(blocks, next_id),
+            Block::List { items, .. } => {
+                for item in items {
+                    number_block_spoilers(item, next_id);
+                }
+            }
+            Block::CodeBlock(_) | Block::Rule => {}
+        }
+    }
+}
+
+/// Splits plain text into text and links to the URLs found in it.
+pub fn linkify(text: &str) -> Vec<Inline> {
+    let mut inlines = vec![];
+    let mut last_end = 0;
+    for range in links::find_urls(text) {
+        if range.start > last_end {
+            inlines.push(Inline::Text(text[last_end..range.start].to_owned()));
+        }
+        let url = text[range.clone()].to_owned();
+        inlines.push(Inline::Link {
+            url: url.clone(),
+            children: vec![Inline::Text(url)],
+        });
+        last_end = range.end;
+    }
+    if last_end < text.len() {
+        inlines.push(Inline::Text(text[last_end..].to_owned()));
+    }
+    inlines
+}
+
+/// Parses the text of a message shown without formatting, which only contains links and
+/// spoilers.
+pub fn parse_plain_text(text: &str) -> Vec<Inline> {
+    let mut inlines = spoilers(linkify(text));
+    number_spoilers(&mut inlines, &mut 0);
+    inlines
+}
+
+enum Frame {
+    Root(Vec<Block>),
+    Paragraph(Vec<Inline>),
+    Heading(Vec<Inline>),
+    Quote(Vec<Block>),
+    CodeBlock(String),
+    List(Option<u64>, Vec<Vec<Block>>),
+    /// Blocks of the item and the text of a tight list item, which isn't wrapped in a paragraph.
+    Item(Vec<Block>, Vec<Inline>),
+    Emphasis(Vec<Inline>),
+    Strong(Vec<Inline>),
+    Strikethrough(Vec<Inline>),
+    Link(String, Vec<Inline>),
+    /// Unsupported element, whose text is added to the parent.
+    Transparent(Vec<Inline>),
+}
+
+impl Frame {
+    fn new(tag: Tag) -> Self {
+        match tag {
+            Tag::Paragraph | Tag::HtmlBlock => Self::Paragraph(vec![]),
+            Tag::Heading { .. } => Self::Heading(vec![]),
+            Tag::BlockQuote(_) => Self::Quote(vec![]),
+            Tag::CodeBlock(_) => Self::CodeBlock(String::new()),
+            Tag::List(start) => Self::List(start, vec![]),
+            Tag::Item => Self::Item(vec![], vec![]),
+            Tag::Emphasis => Self::Emphasis(vec![]),
+            Tag::Strong => Self::Strong(vec![]),
+            Tag::Strikethrough => Self::Strikethrough(vec![]),
+            Tag::Link { dest_url, .. } => Self::Link(dest_url.into_string(), vec![]),
+            // Images would be loaded from any server, revealing that the message was read.
+            _ => Self::Transparent(vec![]),
+        }
+    }
+}
+
+fn flush_item_text(blocks: &mut Vec<Block>, text: &mut Vec<Inline>) {
+    if !text.is_empty() {
+        blocks.push(Block::Paragraph(spoilers(std::mem::take(text))));
+    }
+}
+
+fn push_inline(stack: &mut [Frame], inline: Inline) {
+    match stack.last_mut() {
+        Some(
+            Frame::Paragraph(inlines)
+            | Frame::Heading(inlines)
+            | Frame::Item(_, inlines)
+            | Frame::Emphasis(inlines)
+            | Frame::Strong(inlines)
+            | Frame::Strikethrough(inlines)
+            | Frame::Link(_, inlines)
+            | Frame::Transparent(inlines),
+        ) => inlines.push(inline),
+        Some(Frame::CodeBlock(code)) => {
+            if let Inline::Text(text) = inline {
+                code.push_str(&text);
+            }
+        }
+        Some(Frame::Root(blocks) | Frame::Quote(blocks)) => {
+            blocks.push(Block::Paragraph(vec![inline]));
+        }
+        Some(Frame::List(..)) | None => {}
+    }
+}
+
+fn push_block(stack: &mut [Frame], block: Block) {
+    match stack.last_mut() {
+        Some(Frame::Root(blocks) | Frame::Quote(blocks)) => blocks.push(block),
+        Some(Frame::Item(blocks, text)) => {
+            flush_item_text(blocks, text);
+            blocks.push(block);
+        }
+        _ => {}
+    }
+}
+
+fn finish(stack: &mut [Frame], frame: Frame) {
+    match frame {
+        Frame::Root(_) => {}
+        Frame::Paragraph(inlines) => push_block(stack, Block::Paragraph(spoilers(inlines))),
+        Frame::Heading(inlines) => push_block(
+            stack,
+            Block::Paragraph(vec![Inline::Strong(spoilers(inlines))]),
+        ),
+        Frame::Quote(blocks) => push_block(stack, Block::Quote(blocks)),
+        Frame::CodeBlock(code) => push_block(stack, Block::CodeBlock(code)),
+        Frame::List(start, items) => push_block(stack, Block::List { start, items }),
+        Frame::Item(mut blocks, mut text) => {
+            flush_item_text(&mut blocks, &mut text);
+            if let Some(Frame::List(_, items)) = stack.last_mut() {
+                items.push(blocks);
+            }
+        }
+        Frame::Emphasis(inlines) => push_inline(stack, Inline::Emphasis(spoilers(inlines))),
+        Frame::Strong(inlines) => push_inline(stack, Inline::Strong(spoilers(inlines))),
+        Frame::Strikethrough(inlines) => {
+            push_inline(stack, Inline::Strikethrough(spoilers(inlines)))
+        }
+        Frame::Link(url, children) if is_safe_url(&url) => {
+            let children = spoilers(children);
+            push_inline(stack, Inline::Link { url, children });
+        }
+        Frame::Link(_, inlines) | Frame::Transparent(inlines) => {
+            for inline in inlines {
+                push_inline(stack, inline);
+            }
+        }
+    }
+}
+
+/// Quotes and lists nested deeper than this are shown as typed, as the blocks are rendered
+/// recursively and a message could otherwise exhaust the stack.
+pub const MAX_NESTING_DEPTH: usize = 32;
+
+fn nesting_depth(stack: &[Frame]) -> usize {
+    stack
+        .iter()
+        .filter(|frame| matches!(frame, Frame::Quote(_) | Frame::List(..)))
+        .count()
+}
+
+/// Parses the text of a message. The result only contains the supported formatting, so that it
+/// can be shown without passing any part of the message to the webview as HTML.
+pub fn parse(text: &str) -> Vec<Block> {
+    let mut stack = vec![Frame::Root(vec![])];
+    // Depth of the element being skipped inside a too deeply nested one.
+    let mut skipped_depth = 0usize;
+    for (event, range) in Parser::new_ext(text, Options::ENABLE_STRIKETHROUGH).into_offset_iter() {
+        if skipped_depth > 0 {
+            match event {
+                Event::Start(_) => skipped_depth += 1,
+                Event::End(_) => skipped_depth -= 1,
+                _ => {}
+            }
+            continue;
+        }
+        match event {
+            Event::Start(Tag::BlockQuote(_) | Tag::List(_))
+                if nesting_depth(&stack) >= MAX_NESTING_DEPTH =>
+            {
+                push_block(
+                    &mut stack,
+                    Block::Paragraph(vec![Inline::Text(text[range].to_owned())]),
+                );
+                skipped_depth = 1;
+            }
+            Event::Start(tag) => stack.push(Frame::new(tag)),
+            Event::End(_) => {
+                if stack.len() > 1
+                    && let Some(frame) = stack.pop()
+                {
+                    finish(&mut stack, frame);
+                }
+            }
+            // Links can't be nested, and code is shown as typed.
+            Event::Text(text)
+                if !matches!(stack.last(), Some(Frame::Link(..) | Frame::CodeBlock(_))) =>
+            {
+                for inline in linkify(&text) {
+                    push_inline(&mut stack, inline);
+                }
+            }
+            Event::Text(text)
+            | Event::Html(text)
+            | Event::InlineHtml(text)
+            | Event::InlineMath(text)
+            | Event::DisplayMath(text)
+            | Event::FootnoteReference(text) => {
+                push_inline(&mut stack, Inline::Text(text.into_string()));
+            }
+            Event::Code(code) => push_inline(&mut stack, Inline::Code(code.into_string())),
+            Event::SoftBreak | Event::HardBreak => push_inline(&mut stack, Inline::LineBreak),
+            Event::Rule => push_block(&mut stack, Block::Rule),
+            Event::TaskListMarker(_) => {}
+        }
+    }
+    let mut blocks = match stack.into_iter().next() {
+        Some(Frame::Root(blocks)) => blocks,
+        _ => vec![],
+    };
+    number_block_spoilers(&mut blocks, &mut 0);
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Block, Inline, MAX_NESTING_DEPTH, RevealedSpoilers, is_safe_url, linkify, parse,
+        parse_plain_text,
+    };
+
+    fn text(text: &str) -> Inline {
+        Inline::Text(text.to_owned())
+    }
+
+    /// Returns all text of the blocks, to check that nothing of the message is lost.
+    fn plain_text(blocks: &[Block]) -> String {
+        fn inlines_text(inlines: &[Inline], result: &mut String) {
+            for inline in inlines {
+                match inline {
+                    Inline::Text(text) | Inline::Code(text) => result.push_str(text),
+                    Inline::Emphasis(children)
+                    | Inline::Strong(children)
+                    | Inline::Strikethrough(children)
+                    | Inline::Link { children, .. }
+                    | Inline::Spoiler { children, .. } => inlines_text(children, result),
+                    Inline::LineBreak => result.push('\n'),
+                }
+            }
+        }
+        let mut result = String::new();
+        for block in blocks {
+            match block {
+                Block::Paragraph(inlines) => inlines_text(inlines, &mut result),
+                Block::CodeBlock(code) => result.push_str(code),
+                Block::Quote(blocks) => result.push_str(&plain_text(blocks)),
+                Block::List { items, .. } => {
+                    for item in items {
+                        result.push_str(&plain_text(item));
+                    }
+                }
+                Block::Rule => {}
+            }
+        }
+        result
+    }
+
+    fn has_link(blocks: &[Block]) -> bool {
+        fn inlines_have_link(inlines: &[Inline]) -> bool {
+            inlines.iter().any(|inline| match inline {
+                Inline::Link { .. } => true,
+                Inline::Emphasis(children)
+                | Inline::Strong(children)
+                | Inline::Strikethrough(children)
+                | Inline::Spoiler { children, .. } => inlines_have_link(children),
+                _ => false,
+            })
+        }
+        blocks.iter().any(|block| match block {
+            Block::Paragraph(inlines) => inlines_have_link(inlines),
+            Block::Quote(blocks) => has_link(blocks),
+            Block::List { items, .. } => items.iter().any(|item| has_link(item)),
+            _ => false,
+        })
+    }
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(
+            parse("**bold** *italic* `code` [site](https://example.com)"),
+            vec![Block::Paragraph(vec![
+                Inline::Strong(vec![text("bold")]),
+                text(" "),
+                Inline::Emphasis(vec![text("italic")]),
+                text(" "),
+                Inline::Code("code".to_owned()),
+                text(" "),
+                Inline::Link {
+                    url: "https://example.com".to_owned(),
+                    children: vec![text("site")],
+                },
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_tight_list() {
+        assert_eq!(
+            parse("- one\n- two"),
+            vec![Block::List {
+                start: None,
+                items: vec![
+                    vec![Block::Paragraph(vec![text("one")])],
+                    vec![Block::Paragraph(vec![text("two")])],
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_html_kept_as_text() {
+        let message = "<script>alert(1)</script>";
+        assert_eq!(plain_text(&parse(message)), message);
+        let message = "Hi <img src=x onerror=\"alert(1)\"> there";
+        assert_eq!(plain_text(&parse(message)), message);
+        let message = "<div onclick=\"alert(1)\">\n\n**text**\n\n</div>";
+        let blocks = parse(message);
+        assert!(plain_text(&blocks).contains("<div onclick=\"alert(1)\">"));
+        assert!(plain_text(&blocks).contains("</div>"));
+    }
+
+    #[test]
+    fn test_unsafe_links_removed() {
+        for message in [
+            "[click](javascript:alert(1))",
+            "[click](JavaScript:alert(1))",
+            "[click]( vbscript:msgbox )",
+            "[click](data:text/html;base64,PHNjcmlwdD4=)",
+            "[click](file:///etc/passwd)",
+            "**[click](javascript:alert(1))**",
+            "<javascript:alert(1)>",
+        ] {
+            let blocks = parse(message);
+            assert!(!has_link(&blocks), "{message}");
+        }
+        assert_eq!(
+            parse("[click](javascript:alert(1))"),
+            vec![Block::Paragraph(vec![text("click")])]
+        );
+    }
+
+    #[test]
+    fn test_images_not_loaded() {
+        assert_eq!(
+            parse("![tracker](https://example.com/pixel.png)"),
+            vec![Block::Paragraph(vec![text("tracker")])]
+        );
+    }
+
+    #[test]
+    fn test_safe_urls() {
+        assert!(is_safe_url("https://example.com"));
+        assert!(is_safe_url("HTTP://example.com"));
+        assert!(is_safe_url("mailto:user@example.com"));
+        assert!(is_safe_url("peregrine://user/1"));
+        assert!(!is_safe_url("javascript:alert(1)"));
+        assert!(!is_safe_url("peregrine:user"));
+        assert!(!is_safe_url("/relative/path"));
+    }
+
+    #[test]
+    fn test_bare_urls_linked() {
+        let link = |url: &str| Inline::Link {
+            url: url.to_owned(),
+            children: vec![text(url)],
+        };
+        assert_eq!(
+            linkify("Look at https://example.com."),
+            vec![text("Look at "), link("https://example.com"), text(".")]
+        );
+        assert_eq!(
+            parse("**https://example.com**"),
+            vec![Block::Paragraph(vec![Inline::Strong(vec![link(
+                "https://example.com"
+            )])])]
+        );
+        assert_eq!(
+            parse("[site](https://example.com) https://example.com"),
+            vec![Block::Paragraph(vec![
+                Inline::Link {
+                    url: "https://example.com".to_owned(),
+                    children: vec![text("site")],
+                },
+                text(" "),
+                link("https://example.com"),
+            ])]
+        );
+        assert_eq!(
+            parse("```\nhttps://example.com\n```"),
+            vec![Block::CodeBlock("https://example.com\n".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_spoilers() {
+        let spoiler = |id, children| Inline::Spoiler { id, children };
+        assert_eq!(
+            parse("Ending: ||they win|| and ||lose||"),
+            vec![Block::Paragraph(vec![
+                text("Ending: "),
+                spoiler(0, vec![text("they win")]),
+                text(" and "),
+                spoiler(1, vec![text("lose")]),
+            ])]
+        );
+        assert_eq!(
+            parse_plain_text("||**secret**|| https://example.com"),
+            vec![
+                spoiler(0, vec![text("**secret**")]),
+                text(" "),
+                Inline::Link {
+                    url: "https://example.com".to_owned(),
+                    children: vec![text("https://example.com")],
+                },
+            ]
+        );
+        assert_eq!(
+            parse_plain_text("a || b"),
+            vec![text("a || b")],
+            "unpaired delimiters are kept"
+        );
+        assert_eq!(parse_plain_text("||||"), vec![text("||||")]);
+        assert_eq!(
+            parse_plain_text("|| a || b ||"),
+            vec![spoiler(0, vec![text(" a ")]), text(" b ||"),]
+        );
+    }
+
+    #[test]
+    fn test_spoilers_with_markdown() {
+        let spoiler = |id, children| Inline::Spoiler { id, children };
+        assert_eq!(
+            parse("||**bold** `code||`|| *||x||*"),
+            vec![Block::Paragraph(vec![
+                spoiler(
+                    0,
+                    vec![
+                        Inline::Strong(vec![text("bold")]),
+                        text(" "),
+                        Inline::Code("code||".to_owned()),
+                    ]
+                ),
+                text(" "),
+                Inline::Emphasis(vec![spoiler(1, vec![text("x")])]),
+            ])]
+        );
+        assert_eq!(
+            parse("- ||one||\n\n> ||[site](https://example.com)||"),
+            vec![
+                Block::List {
+                    start: None,
+                    items: vec![vec![Block::Paragraph(vec![spoiler(0, vec![text("one")])])]],
+                },
+                Block::Quote(vec![Block::Paragraph(vec![spoiler(
+                    1,
+                    vec![Inline::Link {
+                        url: "https://example.com".to_owned(),
+                        children: vec![text("site")],
+                    }]
+                )])]),
+            ]
+        );
+        assert_eq!(
+            parse("||https://example.com||"),
+            vec![Block::Paragraph(vec![spoiler(
+                0,
+                vec![Inline::Link {
+                    url: "https://example.com".to_owned(),
+                    children: vec![text("https://example.com")],
+                }]
+            )])]
+        );
+        assert_eq!(
+            parse("```\n||code||\n```"),
+            vec![Block::CodeBlock("||code||\n".to_owned())]
+        );
+    }
+
+    #[test]
+    fn test_nesting_depth_capped() {
+        fn depth(blocks: &[Block]) -> usize {
+            blocks
+                .iter()
+                .map(|block| match block {
+                    Block::Quote(blocks) => 1 + depth(blocks),
+                    Block::List { items, .. } => {
+                        1 + items.iter().map(|item| depth(item)).max().unwrap_or(0)
+                    }
+                    _ => 0,
+                })
+                .max()
+                .unwrap_or(0)
+        }
+
+        let blocks = parse(&format!("{}deep", "> ".repeat(1000)));
+        assert_eq!(depth(&blocks), MAX_NESTING_DEPTH);
+        // Deeper markers are kept as text.
+        assert!(plain_text(&blocks).ends_with("> > deep"));
+        let blocks = parse(&format!("{}deep", "- ".repeat(1000)));
+        assert_eq!(depth(&blocks), MAX_NESTING_DEPTH);
+        assert!(plain_text(&blocks).contains("deep"));
+        let blocks = parse(&format!("{}shallow", "> ".repeat(MAX_NESTING_DEPTH)));
+        assert_eq!(depth(&blocks), MAX_NESTING_DEPTH);
+        assert_eq!(plain_text(&blocks), "shallow");
+    }
+
+    #[test]
+    fn test_toggle_spoilers() {
+        let revealed = RevealedSpoilers::default();
+        let message = RevealedSpoilers::message_key("||a|| ||b||");
+        let other_message = RevealedSpoilers::message_key("||c||");
+        assert_ne!(message, other_message);
+        assert!(!revealed.is_revealed(message, 0));
+        assert!(revealed.toggle(message, 0));
+        assert!(revealed.is_revealed(message, 0));
+        assert!(!revealed.is_revealed(message, 1));
+        assert!(!revealed.is_revealed(other_message, 0));
+        assert!(!revealed.toggle(message, 0));
+        assert!(!revealed.is_revealed(message, 0));
+    }
+}
@@ -0,0 +1,82 @@
+use dioxus::prelude::ServerFnError;
+use server::{AccountCredentials, DmMessage, GroupMessage, ServerError, page::Page};
+
+use crate::server_info;
+
+/// Fetches new DM messages using the compact encoding if the server supports it.
+pub async fn fetch_new_dm_messages(
+    group_id: u64,
+    last_received_message_id: u64,
+    batch_size: Option<u32>,
+    credentials: AccountCredentials,
+) -> Result<Page<DmMessage>, ServerFnError<ServerError>> {
+    if server_info::supports(server::CAPABILITY_POSTCARD_BATCHES).await {
+        server::fetch_new_dm_messages_compact(
+            group_id,
+            last_received_message_id,
+            batch_size,
+            credentials,
+        )
+        .await
+        .map(|batch| batch.0)
+    } else {
+        server::fetch_new_dm_messages(group_id, last_received_message_id, batch_size, credentials)
+            .await
+    }
+}
+
+/// Fetches new group messages using the compact encoding if the server supports it.
+pub async fn fetch_new_group_messages(
+    group_id: u64,
+    last_received_message_id: u64,
+    batch_size: Option<u32>,
+    credentials: AccountCredentials,
+) -> Result<Page<GroupMessage>, ServerFnError<ServerError>> {
+    if server_info::supports(server::CAPABILITY_POSTCARD_BATCHES).await {
+        server::fetch_new_group_messages_compact(
+            group_id,
+            last_received_message_id,
+            batch_size,
+            credentials,
+        )
+        .await
+        .map(|batch| batch.0)
+    } else {
+        server::fetch_new_group_messages(
+            group_id,
+            last_received_message_id,
+            batch_size,
+            credentials,
+        )
+        .await
+    }
+}
+
+/// Returns index of the first message that should be shown below the "unread messages" divider:
+/// the first message sent by someone else after the last read one. `messages` are ids of the
+/// loaded messages in ascending order, paired with whether the message was sent by the user.
+pub fn first_unread_index(
+    messages: &[(u64, bool)],
+    last_read_message_id: Option<u64>,
+) -> Option<usize> {
+    messages.iter().position(|&(id, sent_by_self)| {
+        !sent_by_self && last_read_message_id.is_none_or(|last_read| id > last_read)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::first_unread_index;
+
+    #[test]
+    fn test_first_unread_index() {
+        let messages = [(1, false), (2, true), (4, false), (7, true), (9, false)];
+        assert_eq!(first_unread_index(&messages, None), Some(0));
+        assert_eq!(first_unread_index(&messages, Some(1)), Some(2));
+        assert_eq!(first_unread_index(&messages, Some(3)), Some(2));
+        // Own messages are never unread.
+        assert_eq!(first_unread_index(&messages, Some(4)), Some(4));
+        assert_eq!(first_unread_index(&messages, Some(9)), None);
+        assert_eq!(first_unread_index(&[], None), None);
+    }
+}
@@ -0,0 +1,79 @@
+use dioxus::prelude::{GlobalSignal, Signal};
+
+/// Error shown to the user until they dismiss it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toast {
+    pub id: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Toasts {
+    next_id: u64,
+    toasts: Vec<Toast>,
+}
+
+impl Toasts {
+    /// Number of toasts shown at once. The oldest ones are dismissed to show new ones.
+    pub const MAX_SHOWN: usize = 5;
+
+    /// Shows the message, unless it's already shown, so a request retried in a loop doesn't
+    /// fill the screen with the same error. Returns id of the toast showing the message.
+    pub fn push(&mut self, message: String) -> u64 {
+        if let Some(toast) = self.toasts.iter().find(|toast| toast.message == message) {
+            return toast.id;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        self.toasts.push(Toast { id, message });
+        if self.toasts.len() > Self::MAX_SHOWN {
+            self.toasts.remove(0);
+        }
+        id
+    }
+
+    pub fn dismiss(&mut self, id: u64) {
+        self.toasts.retain(|toast| toast.id != id);
+    }
+
+    /// Shown toasts, the oldest first.
+    pub fn toasts(&self) -> &[Toast] {
+        &self.toasts
+    }
+}
+
+pub static TOASTS: GlobalSignal<Toasts> = Signal::global(Toasts::default);
+
+/// Shows the error to the user instead of only logging it.
+pub fn notify_error(message: impl Into<String>) {
+    TOASTS.write().push(message.into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Toasts;
+
+    #[test]
+    fn test_toasts() {
+        let mut toasts = Toasts::default();
+        let first = toasts.push("Request timed out".to_owned());
+        let second = toasts.push("Failed to send the message".to_owned());
+        assert_ne!(first, second);
+        assert_eq!(toasts.push("Request timed out".to_owned()), first);
+        assert_eq!(toasts.toasts().len(), 2);
+        toasts.dismiss(first);
+        assert_eq!(toasts.toasts().len(), 1);
+        assert_eq!(toasts.toasts()[0].id, second);
+        assert_ne!(toasts.push("Request timed out".to_owned()), first);
+    }
+
+    #[test]
+    fn test_oldest_toasts_dismissed() {
+        let mut toasts = Toasts::default();
+        for index in 0..Toasts::MAX_SHOWN + 2 {
+            toasts.push(format!("Error {index}"));
+        }
+        assert_eq!(toasts.toasts().len(), Toasts::MAX_SHOWN);
+        assert_eq!(toasts.toasts()[0].message, "Error 2");
+    }
+}
@@ -0,0 +1,332 @@
+use dioxus::prelude::{Readable, ServerFnError, Signal, Writable};
+use serde::{Deserialize, Serialize};
+use server::{AccountCredentials, MessageReferences, ServerError};
+use shared::crypto;
+
+use crate::{
+    connectivity::{self, CONNECTIVITY},
+    notifications::notify_error,
+    packet_sender::{PacketSender, PacketState},
+    storage::STORAGE,
+    timeouts,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Recipient {
+    Dm(u64),
+    Group(u64),
+}
+
+/// Message as it's sent to the server, with the content already encrypted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutgoingMessage {
+    pub recipient: Recipient,
+    pub encryption_method: String,
+    pub content: Box<[u8]>,
+    pub references: MessageReferences,
+    /// Sent with every attempt, so that the server stores the message once even if a response
+    /// was lost and the message is sent again.
+    pub idempotency_key: [u8; 16],
+}
+
+impl OutgoingMessage {
+    pub fn new(
+        recipient: Recipient,
+        encryption_method: String,
+        content: Box<[u8]>,
+        references: MessageReferences,
+    ) -> Self {
+        let mut idempotency_key = [0u8; 16];
+        // The preferred suite is always supported by the build.
+        crypto::rng_fill(&crypto::preferred_alogirthm(), &mut idempotency_key).unwrap();
+        Self {
+            recipient,
+            encryption_method,
+            content,
+            references,
+            idempotency_key,
+        }
+    }
+}
+
+/// Message which couldn't be sent yet.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueuedMessage {
+    /// Local id, only unique within the outbox.
+    pub id: u64,
+    pub message: OutgoingMessage,
+}
+
+/// Messages sent while the server was offline, kept in the storage until they are sent.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outbox {
+    next_id: u64,
+    messages: Vec<QueuedMessage>,
+}
+
+impl Outbox {
+    pub fn enqueue(&mut self, message: OutgoingMessage) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.messages.push(QueuedMessage { id, message });
+        id
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Queued messages, the oldest first.
+    pub fn messages(&self) -> &[QueuedMessage] {
+        &self.messages
+    }
+
+    pub fn pending_for(&self, recipient: Recipient) -> impl Iterator<Item = &QueuedMessage> {
+        self.messages
+            .iter()
+            .filter(move |queued| queued.message.recipient == recipient)
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.messages.retain(|message| message.id != id);
+    }
+}
+
+/// Sends the messages in the order they were queued, calling `on_sent` after each one which
+/// the server has received. Sending stops at the first message which doesn't reach the server,
+/// so that no message reaches it before the ones queued earlier, in any conversation. A message
+/// is only removed after a response, so one whose response was lost is sent again. Returns the
+/// number of sent messages.
+pub async fn flush<F>(
+    messages: Vec<QueuedMessage>,
+    mut send: impl FnMut(QueuedMessage) -> F,
+    mut on_sent: impl FnMut(u64),
+) -> usize
+where
+    F: Future<Output = bool>,
+{
+    let mut sent = 0;
+    for message in messages {
+        let id = message.id;
+        if !send(message).await {
+            break;
+        }
+        on_sent(id);
+        sent += 1;
+    }
+    sent
+}
+
+pub async fn send(
+    message: OutgoingMessage,
+    credentials: AccountCredentials,
+) -> Result<u64, ServerFnError<ServerError>> {
+    match message.recipient {
+        Recipient::Dm(group_id) => {
+            server::send_dm_message(
+                group_id,
+                message.encryption_method,
+                message.content,
+                message.references,
+                message.idempotency_key,
+                credentials,
+            )
+            .await
+        }
+        Recipient::Group(group_id) => {
+            server::send_group_message(
+                group_id,
+                message.encryption_method,
+                message.content,
+                message.references,
+                message.idempotency_key,
+                credentials,
+            )
+            .await
+        }
+    }
+}
+
+/// Whether the message should be kept to be sent later after the error: the server wasn't
+/// reached, or the session has to be renewed by logging in again.
+fn keeps_queued(err: &ServerFnError<ServerError>) -> bool {
+    connectivity::is_network_error(err)
+        || matches!(
+            err,
+            ServerFnError::WrappedServerError(
+                ServerError::InvalidSessionToken | ServerError::SessionExpired
+            )
+        )
+}
+
+fn enqueue_stored(outbox: &mut Signal<Outbox>, message: OutgoingMessage, user_id: u64) {
+    outbox.write().enqueue(message);
+    let _ = STORAGE.store_outbox(user_id, outbox.peek().clone());
+}
+
+/// Sends the message right away, unless the server is offline or older messages are still
+/// queued, so that it would overtake them. Then, or if the message doesn't reach the server, it's
+/// queued instead of being lost. Only errors returned by the server are returned.
+pub async fn send_or_enqueue(
+    mut outbox: Signal<Outbox>,
+    message: OutgoingMessage,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    if CONNECTIVITY.peek().is_offline() || !outbox.peek().is_empty() {
+        enqueue_stored(&mut outbox, message, credentials.id);
+        return Ok(());
+    }
+    let result = send(message.clone(), credentials).await;
+    connectivity::track(&result);
+    match result {
+        Ok(_) => Ok(()),
+        Err(err) if keeps_queued(&err) => {
+            enqueue_stored(&mut outbox, message, credentials.id);
+            Ok(())
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Keeps sending the queued messages of the account while it's open.
+pub async fn flush_loop(credentials: AccountCredentials, mut outbox: Signal<Outbox>) {
+    loop {
+        let messages = outbox.peek().messages().to_vec();
+        flush(
+            messages,
+            |queued| async move {
+                match PacketSender::default()
+                    .retry(send(queued.message, credentials))
+                    .await
+                {
+                    PacketState::Response(_) => true,
+                    // The server won't accept it later either, like after leaving the group.
+                    PacketState::ServerError(err) if !keeps_queued(&err) => {
+                        notify_error(format!("Failed to send a queued message: {err}"));
+                        true
+                    }
+                    _ => false,
+                }
+            },
+            |id| {
+                outbox.write().remove(id);
+                let _ = STORAGE.store_outbox(credentials.id, outbox.peek().clone());
+            },
+        )
+        .await;
+        tokio::time::sleep(timeouts::timeouts().retry_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use server::MessageReferences;
+    use shared::crypto::PLAINTEXT_METHOD;
+
+    use super::{Outbox, OutgoingMessage, Recipient, flush};
+
+    /// Runs a future which doesn't wait for anything.
+    fn run<F: Future>(future: F) -> F::Output {
+        match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(output) => output,
+            Poll::Pending => panic!("future is waiting"),
+        }
+    }
+
+    fn message(recipient: Recipient, content: u8) -> OutgoingMessage {
+        OutgoingMessage::new(
+            recipient,
+            PLAINTEXT_METHOD.to_owned(),
+            Box::new([content]),
+            MessageReferences::default(),
+        )
+    }
+
+    fn outbox(recipients: &[Recipient]) -> Outbox {
+        let mut outbox = Outbox::default();
+        for (index, recipient) in recipients.iter().enumerate() {
+            outbox.enqueue(message(*recipient, index as u8));
+        }
+        outbox
+    }
+
+    #[test]
+    fn test_enqueue() {
+        let outbox = outbox(&[Recipient::Dm(1), Recipient::Group(1), Recipient::Dm(1)]);
+        let ids: Vec<u64> = outbox.messages().iter().map(|message| message.id).collect();
+        assert_eq!(ids, vec![0, 1, 2]);
+        let pending: Vec<u64> = outbox
+            .pending_for(Recipient::Dm(1))
+            .map(|message| message.id)
+            .collect();
+        assert_eq!(pending, vec![0, 2]);
+        assert_eq!(outbox.pending_for(Recipient::Group(2)).count(), 0);
+        let stored = postcard::to_allocvec(&outbox).unwrap();
+        assert_eq!(postcard::from_bytes::<Outbox>(&stored).unwrap(), outbox);
+    }
+
+    #[test]
+    fn test_idempotency_keys() {
+        let first = message(Recipient::Dm(1), 0);
+        let second = message(Recipient::Dm(1), 0);
+        // Even identical messages are different messages.
+        assert_ne!(first.idempotency_key, second.idempotency_key);
+    }
+
+    #[test]
+    fn test_ids_not_reused() {
+        let mut outbox = outbox(&[Recipient::Dm(1)]);
+        outbox.remove(0);
+        assert!(outbox.is_empty());
+        assert_eq!(outbox.enqueue(message(Recipient::Dm(1), 0)), 1);
+    }
+
+    #[test]
+    fn test_flush_in_order() {
+        let mut outbox = outbox(&[Recipient::Dm(1), Recipient::Group(2), Recipient::Dm(1)]);
+        let mut sent = vec![];
+        let count = run(flush(
+            outbox.messages().to_vec(),
+            |queued| {
+                sent.push(queued.message.content[0]);
+                async { true }
+            },
+            |id| outbox.remove(id),
+        ));
+        assert_eq!(count, 3);
+        assert_eq!(sent, vec![0, 1, 2]);
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn test_flush_stops_at_failure() {
+        let mut outbox = outbox(&[Recipient::Dm(1), Recipient::Group(2), Recipient::Dm(1)]);
+        let mut attempted = vec![];
+        let count = run(flush(
+            outbox.messages().to_vec(),
+            |queued| {
+                attempted.push(queued.id);
+                let succeeded = queued.id != 1;
+                async move { succeeded }
+            },
+            |id| outbox.remove(id),
+        ));
+        assert_eq!(count, 1);
+        assert_eq!(attempted, vec![0, 1], "later messages must not overtake");
+        let left: Vec<u64> = outbox.messages().iter().map(|message| message.id).collect();
+        assert_eq!(left, vec![1, 2]);
+
+        let count = run(flush(
+            outbox.messages().to_vec(),
+            |_| async { true },
+            |id| outbox.remove(id),
+        ));
+        assert_eq!(count, 2);
+        assert!(outbox.is_empty());
+    }
+}
@@ -1,11 +1,10 @@
 use std::time::Duration;
 
-use dioxus::{
-    prelude::ServerFnError,
-    signals::{Signal, Writable},
-};
+use dioxus::prelude::*;
 use server::ServerError;
 
+use crate::{connectivity, notifications::notify_error, timeouts};
+
 #[derive(Debug, PartialEq)]
 pub enum PacketState<T> {
     Response(T),
@@ -27,38 +26,97 @@ impl<T: Clone> Clone for PacketState<T> {
     }
 }
 
+/// Renders the response of a request with `render`, and the same messages for the other states in
+/// every view. Nothing is rendered before the request is started.
+pub fn render_packet_state<T>(state: PacketState<T>, render: impl FnOnce(T) -> Element) -> Element {
+    match state {
+        PacketState::Response(value) => render(value),
+        PacketState::Waiting => rsx!(p { "Loading..." }),
+        PacketState::ServerError(err) => rsx!(p { "Server error: {err}" }),
+        PacketState::RequestTimeout => rsx!(p { "Request timed out" }),
+        PacketState::NotStarted => rsx!(),
+    }
+}
+
 pub struct PacketSender {
     pub wait_timeout: Duration,
     pub retry_interval: Duration,
+    /// Number of the failed attempts of the retry loops, so that views can show the request is
+    /// being retried. It's reset to 0 once a request succeeds.
+    pub failures: Option<Signal<u32>>,
+    /// Ends the retry loops once it's set, before the next attempt. The state of the last attempt
+    /// is kept.
+    pub stop: Option<Signal<bool>>,
 }
 
 impl Default for PacketSender {
     fn default() -> Self {
+        let timeouts = timeouts::timeouts();
         Self {
-            wait_timeout: DEFAULT_WAIT_TIMEOUT,
-            retry_interval: DEFAULT_RETRY_INTERVAL,
+            wait_timeout: timeouts.wait_timeout,
+            retry_interval: timeouts.retry_interval,
+            failures: None,
+            stop: None,
         }
     }
 }
 
-pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(10);
-pub const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(3);
-
 impl PacketSender {
+    /// Uses the timeout configured for the server function, which is longer for the slow ones.
+    pub fn for_endpoint(endpoint: &str) -> Self {
+        let timeouts = timeouts::timeouts();
+        Self {
+            wait_timeout: timeouts.for_endpoint(endpoint),
+            retry_interval: timeouts.retry_interval,
+            failures: None,
+            stop: None,
+        }
+    }
+
+    pub fn with_failure_counter(mut self, failures: Signal<u32>) -> Self {
+        self.failures = Some(failures);
+        self
+    }
+
+    pub fn with_stop_signal(mut self, stop: Signal<bool>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stop.is_some_and(|stop| *stop.peek())
+    }
+
+    fn count_attempt<T>(&mut self, state: &PacketState<T>) {
+        let Some(failures) = &mut self.failures else {
+            return;
+        };
+        if matches!(state, PacketState::Response(_)) {
+            failures.set(0);
+        } else {
+            *failures.write() += 1;
+        }
+    }
+
     pub async fn retry<T, F>(&mut self, func: F) -> PacketState<T>
     where
         F: Future<Output = Result<T, ServerFnError<ServerError>>>,
     {
         let value = match tokio::time::timeout(self.wait_timeout, func).await {
             Ok(value) => value,
-            Err(elapsed) => {
-                eprintln!("Request timed out: {elapsed:?}");
+            Err(_) => {
+                connectivity::track_timeout();
+                notify_error("Request timed out, check your connection");
                 return PacketState::RequestTimeout;
             }
         };
+        connectivity::track(&value);
         match value {
             Ok(value) => PacketState::Response(value),
-            Err(err) => PacketState::ServerError(err),
+            Err(err) => {
+                notify_error(format!("Server error: {err}"));
+                PacketState::ServerError(err)
+            }
         }
     }
 
@@ -70,13 +128,14 @@ impl PacketSender {
         F: Future<Output = Result<T, ServerFnError<ServerError>>>,
     {
         let mut retry_after: bool = true;
-        while retry_after {
+        while retry_after && !self.is_stopped() {
             signal.set(PacketState::Waiting);
 
             let state = self.retry(func()).await;
             if matches!(state, PacketState::Response(_)) {
                 retry_after = false;
             }
+            self.count_attempt(&state);
 
             signal.set(state);
             tokio::time::sleep(self.retry_interval).await;
@@ -92,13 +151,14 @@ impl PacketSender {
         F: Future<Output = Result<T, ServerFnError<ServerError>>>,
     {
         let mut retry_after: bool = true;
-        while retry_after {
+        while retry_after && !self.is_stopped() {
             signal.write()[index] = PacketState::Waiting;
 
             let retry_state = self.retry(func()).await;
             if matches!(retry_state, PacketState::Response(_)) {
                 retry_after = false;
             }
+            self.count_attempt(&retry_state);
 
             signal.write()[index] = retry_state;
             tokio::time::sleep(self.retry_interval).await;
@@ -106,6 +166,12 @@ impl PacketSender {
     }
 }
 
+/// Loads the result of `$future` into a signal, retrying until there is a response.
+///
+/// The loops run in tasks of the component calling the macro, so Dioxus cancels them when it's
+/// unmounted. A component which is rendered again with other props keeps its tasks though, along
+/// with the request they were started with. Such components should have a key, so they are
+/// mounted again instead, or pass a `stop` signal which ends the loop before its next attempt.
 #[macro_export]
 macro_rules! future_retry_loop {
     ($future:expr) => {{
@@ -128,4 +194,97 @@ macro_rules! future_retry_loop {
                 .await;
         });
     };
+    ($signal:ident, $resource:ident, $failures:ident, $future:expr) => {
+        let mut $signal =
+            dioxus::prelude::use_signal(|| $crate::packet_sender::PacketState::Waiting);
+        let mut $failures = dioxus::prelude::use_signal(|| 0);
+        let $resource = dioxus::prelude::use_resource(move || async move {
+            $failures.set(0);
+            $crate::packet_sender::PacketSender::default()
+                .with_failure_counter($failures)
+                .retry_loop(|| $future, &mut $signal)
+                .await;
+        });
+    };
+    ($signal:ident, $resource:ident, $failures:ident, stop: $stop:expr, $future:expr) => {
+        let mut $signal =
+            dioxus::prelude::use_signal(|| $crate::packet_sender::PacketState::Waiting);
+        let mut $failures = dioxus::prelude::use_signal(|| 0);
+        let $resource = dioxus::prelude::use_resource(move || async move {
+            $failures.set(0);
+            $crate::packet_sender::PacketSender::default()
+                .with_failure_counter($failures)
+                .with_stop_signal($stop)
+                .retry_loop(|| $future, &mut $signal)
+                .await;
+        });
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use dioxus::prelude::*;
+
+    use super::{PacketSender, PacketState};
+
+    fn sender(stop: Signal<bool>) -> PacketSender {
+        PacketSender {
+            wait_timeout: Duration::from_secs(1),
+            retry_interval: Duration::ZERO,
+            failures: None,
+            stop: Some(stop),
+        }
+    }
+
+    /// Runs the test in a component, which signals need.
+    fn in_component(test: impl FnOnce()) {
+        let dom = VirtualDom::new(|| rsx!());
+        dom.in_runtime(|| ScopeId::ROOT.in_runtime(test));
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_stopped_retry_loop() {
+        in_component(|| {
+            let mut state: Signal<PacketState<()>> = Signal::new(PacketState::NotStarted);
+            let mut attempts = 0;
+            block_on(sender(Signal::new(true)).retry_loop(
+                || {
+                    attempts += 1;
+                    async { Ok(()) }
+                },
+                &mut state,
+            ));
+            assert_eq!(attempts, 0);
+            assert_eq!(*state.peek(), PacketState::NotStarted);
+        });
+    }
+
+    #[test]
+    fn test_retry_loop_stops_after_attempt() {
+        in_component(|| {
+            let mut state: Signal<PacketState<()>> = Signal::new(PacketState::NotStarted);
+            let mut stop = Signal::new(false);
+            let mut attempts = 0;
+            block_on(sender(stop).retry_loop(
+                || {
+                    attempts += 1;
+                    stop.set(true);
+                    async { Err(ServerFnError::Request("offline".to_owned())) }
+                },
+                &mut state,
+            ));
+            assert_eq!(attempts, 1, "the failed request must not be retried");
+            assert!(matches!(*state.peek(), PacketState::ServerError(_)));
+        });
+    }
 }
@@ -0,0 +1,65 @@
+use dioxus::logger::tracing::{error, info};
+use server::AccountCredentials;
+use shared::crypto::x3dh;
+
+use crate::{private_info, storage::STORAGE};
+
+/// New one-time prekeys are uploaded once fewer than this number of them is left on the server.
+pub const OPK_REPLENISH_THRESHOLD: u32 = 3;
+
+/// Uploads a new batch of one-time prekeys if the server is running out of them. Private keys
+/// are stored locally only after the server has accepted the public ones, so that the indexes on
+/// both sides stay the same. Until then the batch is kept aside and uploaded again next time, as
+/// the server may have accepted it even if the response was lost.
+pub async fn replenish_one_time_prekeys(credentials: AccountCredentials) {
+    let algorithms = STORAGE.algorithms();
+    let (mut private_keys, mut public_keys) = STORAGE.x3dh_data(&algorithms);
+    let pending = STORAGE
+        .load_pending_opks(&algorithms)
+        .filter(|(base_index, _, _)| *base_index as usize == public_keys.opks.len());
+    let (base_index, opks_priv, opks_pub) = match pending {
+        Some(pending) => pending,
+        None => {
+            let unused = match server::get_unused_one_time_prekey_count(credentials).await {
+                Ok(unused) => unused,
+                Err(err) => {
+                    error!("Failed to get unused one-time prekey count: {err:?}");
+                    return;
+                }
+            };
+            if unused >= OPK_REPLENISH_THRESHOLD {
+                return;
+            }
+            let Some((opks_priv, opks_pub)) =
+                x3dh::generate_opks(&algorithms, x3dh::OPK_BATCH_SIZE)
+            else {
+                error!("Failed to generate one-time prekeys");
+                return;
+            };
+            let pending = (public_keys.opks.len() as u32, opks_priv, opks_pub);
+            if let Err(err) = STORAGE.store_pending_opks(&algorithms, pending.clone()) {
+                error!("Failed to store one-time prekeys before uploading them: {err}");
+                return;
+            }
+            pending
+        }
+    };
+
+    if let Err(err) = server::add_one_time_prekeys(base_index, opks_pub.clone(), credentials).await
+    {
+        error!("Failed to upload one-time prekeys: {err:?}");
+        return;
+    }
+    private_keys.opks.extend(opks_priv);
+    public_keys.opks.extend(opks_pub);
+    match STORAGE.store_x3dh_data(&algorithms, (private_keys, public_keys)) {
+        Ok(()) => {
+            let _ = STORAGE.remove_pending_opks(&algorithms);
+            info!("Uploaded {} new one-time prekeys", x3dh::OPK_BATCH_SIZE);
+            private_info::upload_private_info(credentials).await;
+        }
+        Err(err) => error!(
+            "Failed to store new one-time prekeys, messages encrypted with them can't be decrypted: {err}"
+        ),
+    }
+}
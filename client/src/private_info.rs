@@ -0,0 +1,346 @@
+use dioxus::{
+    logger::tracing::{error, info},
+    prelude::ServerFnError,
+};
+use postcard::{from_bytes, to_allocvec};
+use serde::{Deserialize, Serialize};
+use server::{AccountCredentials, ServerError};
+use shared::crypto::{
+    self, CryptoAlgorithms, PublicKey,
+    private_info::{decrypt_private_info, encrypt_private_info, private_info_key},
+    recovery,
+    x3dh::{X3DhReceiverKeysPrivate, X3DhReceiverKeysPublic},
+};
+
+use crate::storage::STORAGE;
+
+type SharedKey = (CryptoAlgorithms, Box<[u8]>);
+
+/// Private data synchronized between devices of the same account.
+#[derive(Serialize, Deserialize)]
+struct PrivateInfo {
+    algorithms: CryptoAlgorithms,
+    x3dh_data: (X3DhReceiverKeysPrivate, X3DhReceiverKeysPublic),
+    dm_keys: Vec<(u64, SharedKey)>,
+    group_keys: Vec<(u64, SharedKey)>,
+}
+
+/// Derives the key protecting private info of account `account_id` from the password and stores
+/// it locally, so that the private info can be updated later without asking for the password
+/// again.
+pub fn remember_password(account_id: u64, password: &str) -> bool {
+    let Some(key) = private_info_key(&STORAGE.algorithms(), account_id, password) else {
+        error!("Failed to derive private info key");
+        return false;
+    };
+    STORAGE.store_private_info_key(key).is_ok()
+}
+
+async fn collect_private_info(credentials: AccountCredentials) -> Option<PrivateInfo> {
+    let algorithms = STORAGE.algorithms();
+    let dm_groups = match server::get_joined_dm_groups(credentials).await {
+        Ok(groups) => groups,
+        Err(err) => {
+            error!("Failed to get joined DM groups: {err:?}");
+            return None;
+        }
+    };
+    let groups = match server::get_joined_groups(credentials).await {
+        Ok(groups) => groups,
+        Err(err) => {
+            error!("Failed to get joined groups: {err:?}");
+            return None;
+        }
+    };
+    let dm_keys = dm_groups
+        .items
+        .into_iter()
+        .map(|group| {
+            if group.initiator_id == credentials.id {
+                group.other_id
+            } else {
+                group.initiator_id
+            }
+        })
+        .filter_map(|other_id| Some((other_id, STORAGE.load_dm_key(other_id)?)))
+        .collect();
+    let group_keys = groups
+        .items
+        .into_iter()
+        .filter_map(|group| Some((group.id, STORAGE.load_group_key(group.id)?)))
+        .collect();
+    Some(PrivateInfo {
+        x3dh_data: STORAGE.x3dh_data(&algorithms),
+        algorithms,
+        dm_keys,
+        group_keys,
+    })
+}
+
+fn encrypt(private_info: &PrivateInfo, key: &[u8]) -> Option<Box<[u8]>> {
+    let data = to_allocvec(private_info)
+        .ok()
+        .and_then(|plaintext| encrypt_private_info(&private_info.algorithms, key, &plaintext));
+    if data.is_none() {
+        error!("Failed to encrypt private info");
+    }
+    data
+}
+
+async fn encrypted_private_info(credentials: AccountCredentials, key: &[u8]) -> Option<Box<[u8]>> {
+    encrypt(&collect_private_info(credentials).await?, key)
+}
+
+fn store_private_info(private_info: PrivateInfo) -> bool {
+    let mut success = STORAGE
+        .store_x3dh_data(&private_info.algorithms, private_info.x3dh_data)
+        .is_ok();
+    for (other_id, shared_key) in private_info.dm_keys {
+        success &= STORAGE.store_dm_key_box(other_id, shared_key).is_ok();
+    }
+    for (group_id, shared_key) in private_info.group_keys {
+        success &= STORAGE.store_group_key_box(group_id, shared_key).is_ok();
+    }
+    success
+}
+
+/// Encrypts local private keys and uploads them to the server. Does nothing if the private info
+/// key is unknown (e.g. the session was created before it was introduced). The copy which can be
+/// recovered with the recovery code is updated too, if it is enabled.
+pub async fn upload_private_info(credentials: AccountCredentials) -> bool {
+    let Some(key) = STORAGE.load_private_info_key() else {
+        return false;
+    };
+    let Some(private_info) = collect_private_info(credentials).await else {
+        return false;
+    };
+    let Some(data) = encrypt(&private_info, &key) else {
+        return false;
+    };
+    if let Err(err) = server::set_encrypted_private_info(data, credentials).await {
+        error!("Failed to upload private info: {err:?}");
+        return false;
+    }
+
+    let Some(recovery_key) = STORAGE.load_recovery_key() else {
+        return true;
+    };
+    let Some(data) = encrypt(&private_info, &recovery_key) else {
+        return false;
+    };
+    match server::set_recovery_private_info(data, credentials).await {
+        Ok(()) => true,
+        Err(ServerFnError::WrappedServerError(ServerError::Forbidden)) => {
+            info!("Recovery was disabled on another device");
+            let _ = STORAGE.remove_recovery_key();
+            true
+        }
+        Err(err) => {
+            error!("Failed to upload recovery private info: {err:?}");
+            false
+        }
+    }
+}
+
+/// Downloads private keys from the server and stores them locally. If nothing was uploaded yet,
+/// uploads local keys instead.
+pub async fn restore_private_info(credentials: AccountCredentials) -> bool {
+    let Some(key) = STORAGE.load_private_info_key() else {
+        return false;
+    };
+    let data = match server::get_encrypted_private_info(credentials).await {
+        Ok(data) => data,
+        Err(err) => {
+            error!("Failed to download private info: {err:?}");
+            return false;
+        }
+    };
+    if data.is_empty() {
+        return upload_private_info(credentials).await;
+    }
+    let Some(private_info) = decrypt_private_info(&key, &data)
+        .and_then(|plaintext| from_bytes::<PrivateInfo>(&plaintext).ok())
+    else {
+        error!("Failed to decrypt private info, it was probably encrypted with another password");
+        return false;
+    };
+
+    let success = store_private_info(private_info);
+    info!("Restored private info from server");
+    success
+}
+
+/// Derives the login keys from both passwords and signs the new public key with the old key at
+/// `signed_at`.
+fn sign_password_change(
+    algorithms: &CryptoAlgorithms,
+    account_id: u64,
+    old_password: &str,
+    new_password: &str,
+    signed_at: u64,
+) -> Option<(PublicKey, PublicKey, Box<[u8]>)> {
+    let (old_private_key, old_public_key) =
+        crypto::kdf_keypair(algorithms, old_password.as_bytes())?;
+    let (_, new_public_key) = crypto::kdf_keypair(algorithms, new_password.as_bytes())?;
+    let signature = crypto::sign(
+        algorithms,
+        old_private_key,
+        old_public_key.clone(),
+        &server::password_change_message(
+            account_id,
+            &old_public_key.pk,
+            &new_public_key.pk,
+            signed_at,
+        ),
+    )?;
+    Some((old_public_key, new_public_key, signature))
+}
+
+/// Changes the password of the account. The login key derived from `old_password` signs the one
+/// derived from `new_password`, and the private info is uploaded again encrypted with the latter.
+/// Other sessions of the account are ended, so the returned credentials replace `credentials`.
+pub async fn change_password(
+    credentials: AccountCredentials,
+    old_password: &str,
+    new_password: &str,
+) -> Result<AccountCredentials, ServerFnError<ServerError>> {
+    let algorithms = STORAGE.algorithms();
+    let signed_at = chrono::Utc::now().timestamp().cast_unsigned();
+    let (Some((old_public_key, new_public_key, signature)), Some(key)) = (
+        sign_password_change(
+            &algorithms,
+            credentials.id,
+            old_password,
+            new_password,
+            signed_at,
+        ),
+        private_info_key(&algorithms, credentials.id, new_password),
+    ) else {
+        error!("Failed to derive keys from the passwords");
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::UnsupportedCryptographicAlgorithm,
+        ));
+    };
+    let Some(data) = encrypted_private_info(credentials, &key).await else {
+        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
+    };
+
+    let session_token = server::change_password(
+        algorithms,
+        old_public_key.pk,
+        new_public_key.pk,
+        signed_at,
+        signature,
+        data,
+        credentials,
+    )
+    .await?;
+    let credentials = AccountCredentials {
+        id: credentials.id,
+        session_token,
+    };
+    info!("Changed password");
+    let _ = STORAGE.store_session_credentials(credentials);
+    if let Err(err) = STORAGE.store_private_info_key(key) {
+        // The private info can't be updated until the next login with the new password.
+        error!("Failed to store new private info key: {err}");
+    }
+    Ok(credentials)
+}
+
+/// Enables recovery of the account with a new recovery code, which replaces the previous one, and
+/// returns the code to be shown to the user.
+pub async fn enable_recovery(
+    credentials: AccountCredentials,
+) -> Result<String, ServerFnError<ServerError>> {
+    let algorithms = STORAGE.algorithms();
+    let keys = recovery::generate_recovery_code(&algorithms).and_then(|code| {
+        let (_, public_key) = recovery::recovery_keypair(&algorithms, &code)?;
+        let key = recovery::recovery_info_key(&algorithms, credentials.id, &code)?;
+        Some((code, public_key, key))
+    });
+    let Some((code, public_key, key)) = keys else {
+        error!("Failed to generate recovery code");
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::UnsupportedCryptographicAlgorithm,
+        ));
+    };
+
+    server::set_account_recovery(Some(public_key.pk), credentials).await?;
+    if let Err(err) = STORAGE.store_recovery_key(key) {
+        // The code still recovers the account, but without the private keys.
+        error!("Failed to store recovery key: {err}");
+    }
+    upload_private_info(credentials).await;
+    Ok(code)
+}
+
+pub async fn disable_recovery(
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    server::set_account_recovery(None, credentials).await?;
+    let _ = STORAGE.remove_recovery_key();
+    Ok(())
+}
+
+/// Derives the recovery keys from the code and signs the login key derived from the new password.
+fn sign_recovery(
+    algorithms: &CryptoAlgorithms,
+    code: &str,
+    new_password: &str,
+) -> Option<(PublicKey, PublicKey, Box<[u8]>)> {
+    let (recovery_private_key, recovery_public_key) = recovery::recovery_keypair(algorithms, code)?;
+    let (_, new_public_key) = crypto::kdf_keypair(algorithms, new_password.as_bytes())?;
+    let signature = crypto::sign(
+        algorithms,
+        recovery_private_key,
+        recovery_public_key.clone(),
+        &server::recovery_message(&new_public_key.pk),
+    )?;
+    Some((recovery_public_key, new_public_key, signature))
+}
+
+/// Logs into the account whose recovery `code` was generated by [`enable_recovery`], setting
+/// `new_password` as its password. The code is used up. It must be used with the same crypto suite
+/// it was generated with.
+pub async fn recover_account(
+    code: &str,
+    new_password: &str,
+) -> Result<AccountCredentials, ServerFnError<ServerError>> {
+    let algorithms = STORAGE.algorithms();
+    let Some((recovery_public_key, new_public_key, signature)) =
+        sign_recovery(&algorithms, code, new_password)
+    else {
+        error!("Failed to derive keys from the recovery code");
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::UnsupportedCryptographicAlgorithm,
+        ));
+    };
+
+    let (id, session_token, data) = server::recover_account(
+        algorithms.clone(),
+        recovery_public_key.pk,
+        new_public_key.pk,
+        signature,
+    )
+    .await?;
+    let credentials = AccountCredentials { id, session_token };
+    info!("Recovered account {id}");
+    let _ = STORAGE.store_session_credentials(credentials);
+    let _ = STORAGE.remove_recovery_key();
+    remember_password(id, new_password);
+
+    let private_info = recovery::recovery_info_key(&algorithms, id, code)
+        .and_then(|key| decrypt_private_info(&key, &data))
+        .and_then(|plaintext| from_bytes::<PrivateInfo>(&plaintext).ok());
+    match private_info {
+        Some(private_info) => {
+            store_private_info(private_info);
+        }
+        None if data.is_empty() => {}
+        None => error!("Failed to decrypt recovered private info"),
+    }
+    // The private info on the server was encrypted with the forgotten password.
+    upload_private_info(credentials).await;
+    Ok(credentials)
+}
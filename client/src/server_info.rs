@@ -0,0 +1,50 @@
+use std::sync::Mutex;
+
+use dioxus::logger::tracing::warn;
+use server::ServerInfo;
+use shared::crypto::{CryptoAlgorithms, SupportedMethods};
+
+/// Information about the server, fetched once per run of the application.
+static SERVER_INFO: Mutex<Option<ServerInfo>> = Mutex::new(None);
+
+async fn server_info() -> Option<ServerInfo> {
+    if let Some(info) = SERVER_INFO.lock().unwrap().as_ref() {
+        return Some(info.clone());
+    }
+    match server::get_server_info().await {
+        Ok(info) => {
+            *SERVER_INFO.lock().unwrap() = Some(info.clone());
+            Some(info)
+        }
+        Err(err) => {
+            warn!("Failed to get server info: {err:?}");
+            None
+        }
+    }
+}
+
+/// Returns whether the server supports the capability. Servers which don't report their
+/// capabilities, or can't be reached, are treated as supporting none of them.
+pub async fn supports(capability: &str) -> bool {
+    server_info()
+        .await
+        .is_some_and(|info| info.supports(capability))
+}
+
+/// Returns crypto suites supported by the server. Empty if the server doesn't report them or
+/// can't be reached.
+pub async fn algorithms() -> Vec<CryptoAlgorithms> {
+    server_info()
+        .await
+        .map(|info| info.algorithms)
+        .unwrap_or_default()
+}
+
+/// Returns algorithms of each kind supported by the server, or `None` if the server doesn't
+/// report them or can't be reached.
+pub async fn methods() -> Option<SupportedMethods> {
+    server_info()
+        .await
+        .map(|info| info.methods)
+        .filter(|methods| *methods != SupportedMethods::default())
+}
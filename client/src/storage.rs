@@ -5,12 +5,14 @@ use server::AccountCredentials;
 
 use shared::{
     crypto::{
-        CryptoAlgorithms,
+        self, CryptoAlgorithms, PrivateKey, PublicKey,
         x3dh::{self, X3DhReceiverKeysPrivate, X3DhReceiverKeysPublic},
     },
-    storage::{GeneralStorage, RawStorage},
+    storage::{GeneralStorage, MemoryFiles, RawStorage, StorageError},
 };
 
+use crate::{emoji, markdown::MessageFormat, outbox::Outbox, theme::Theme, timeouts::Timeouts};
+
 pub static FALLBACK_DATA_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
     let mut path = PathBuf::new();
     path.push("peregrine");
@@ -19,6 +21,8 @@ pub static FALLBACK_DATA_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
 
 pub struct Storage {
     base_path: PathBuf,
+    /// `None` if the data is kept on the disk.
+    memory: Option<MemoryFiles>,
 }
 
 impl Default for Storage {
@@ -27,13 +31,14 @@ impl Default for Storage {
             .map_or(FALLBACK_DATA_PATH.to_path_buf(), |dirs| dirs.data_dir);
         Self {
             base_path: data_dir,
+            memory: None,
         }
     }
 }
 
 macro_rules! storage_file {
     ($vis:vis [ $store_fn:ident, $load_fn:ident, $remove_fn:ident $(,)? ], $file_path:expr, $type:ty, [ $($arg_name:ident : $arg_type:ty),* ] $(,)?) => {
-        $vis fn $store_fn(&self, $($arg_name: $arg_type,)* data: $type) -> bool {
+        $vis fn $store_fn(&self, $($arg_name: $arg_type,)* data: $type) -> Result<(), StorageError> {
             self.store(&$file_path, &data)
         }
 
@@ -41,7 +46,7 @@ macro_rules! storage_file {
             self.load(&$file_path)
         }
 
-        $vis fn $remove_fn(&self, $($arg_name: $arg_type),*) -> bool {
+        $vis fn $remove_fn(&self, $($arg_name: $arg_type),*) -> Result<(), StorageError> {
             self.remove(&$file_path)
         }
     };
@@ -51,11 +56,24 @@ impl RawStorage for Storage {
     fn get_base_path(&self) -> &PathBuf {
         &self.base_path
     }
+
+    fn memory_files(&self) -> Option<&MemoryFiles> {
+        self.memory.as_ref()
+    }
 }
 
 impl GeneralStorage for Storage {}
 
 impl Storage {
+    /// Storage which keeps everything in memory, so that nothing is left on the device once the
+    /// app is closed.
+    pub fn in_memory() -> Self {
+        Self {
+            base_path: PathBuf::new(),
+            memory: Some(MemoryFiles::default()),
+        }
+    }
+
     storage_file!(
         pub [
             store_session_credentials,
@@ -76,6 +94,19 @@ impl Storage {
         (X3DhReceiverKeysPrivate, X3DhReceiverKeysPublic),
         [algorithms: &CryptoAlgorithms],
     );
+    // One-time prekeys which are being uploaded, along with the index of the first of them. They are
+    // kept until the server has accepted them, so that a failed upload is retried with the same
+    // keys at the same index.
+    storage_file!(
+        pub [
+            store_pending_opks,
+            load_pending_opks,
+            remove_pending_opks,
+        ],
+        format!("pending_opks_{algorithms}.bin"),
+        (u32, Vec<PrivateKey>, Vec<PublicKey>),
+        [algorithms: &CryptoAlgorithms],
+    );
     storage_file!(
         pub [
             store_dm_key_box,
@@ -96,6 +127,150 @@ impl Storage {
         (CryptoAlgorithms, Box<[u8]>),
         [group_id: u64],
     );
+    storage_file!(
+        pub [
+            store_private_info_key,
+            load_private_info_key,
+            remove_private_info_key,
+        ],
+        "private_info_key.bin",
+        Box<[u8]>,
+        [],
+    );
+    storage_file!(
+        pub [
+            store_recovery_key,
+            load_recovery_key,
+            remove_recovery_key,
+        ],
+        "recovery_key.bin",
+        Box<[u8]>,
+        [],
+    );
+    storage_file!(
+        pub [
+            store_verified_identity,
+            load_verified_identity,
+            remove_verified_identity,
+        ],
+        format!("verified_user{user_id}.bin"),
+        Box<[u8]>,
+        [user_id: u64],
+    );
+    storage_file!(
+        [
+            store_contact_nickname_string,
+            load_contact_nickname,
+            remove_contact_nickname,
+        ],
+        format!("nickname_user{user_id}.bin"),
+        String,
+        [user_id: u64],
+    );
+    storage_file!(
+        [
+            store_group_nickname_string,
+            load_group_nickname,
+            remove_group_nickname,
+        ],
+        format!("nickname_group{group_id}.bin"),
+        String,
+        [group_id: u64],
+    );
+    storage_file!(
+        pub [
+            store_algorithms,
+            load_algorithms,
+            remove_algorithms,
+        ],
+        "algorithms.bin",
+        CryptoAlgorithms,
+        [],
+    );
+    storage_file!(
+        pub [
+            store_theme,
+            load_theme,
+            remove_theme,
+        ],
+        "theme.bin",
+        Theme,
+        [],
+    );
+    storage_file!(
+        pub [
+            store_message_format,
+            load_message_format,
+            remove_message_format,
+        ],
+        "message_format.bin",
+        MessageFormat,
+        [],
+    );
+    storage_file!(
+        pub [
+            store_recent_emojis,
+            load_recent_emojis,
+            remove_recent_emojis,
+        ],
+        "recent_emojis.bin",
+        Vec<String>,
+        [],
+    );
+    storage_file!(
+        pub [
+            store_timeouts,
+            load_timeouts,
+            remove_timeouts,
+        ],
+        "timeouts.bin",
+        Timeouts,
+        [],
+    );
+    storage_file!(
+        pub [
+            store_outbox,
+            load_outbox,
+            remove_outbox,
+        ],
+        format!("outbox_user{user_id}.bin"),
+        Outbox,
+        [user_id: u64],
+    );
+
+    /// Returns the crypto suite chosen in the settings, or the preferred one if none was chosen
+    /// or the chosen one isn't supported anymore.
+    pub fn algorithms(&self) -> CryptoAlgorithms {
+        self.load_algorithms()
+            .filter(crypto::is_algorithm_supported)
+            .unwrap_or_else(crypto::preferred_alogirthm)
+    }
+
+    pub fn theme(&self) -> Theme {
+        self.load_theme().unwrap_or_default()
+    }
+
+    pub fn message_format(&self) -> MessageFormat {
+        self.load_message_format().unwrap_or_default()
+    }
+
+    /// Messages of the account which are waiting to be sent.
+    pub fn outbox(&self, user_id: u64) -> Outbox {
+        self.load_outbox(user_id).unwrap_or_default()
+    }
+
+    /// Emojis picked recently, the latest first.
+    pub fn recent_emojis(&self) -> Vec<String> {
+        self.load_recent_emojis().unwrap_or_default()
+    }
+
+    pub fn add_recent_emoji(&self, emoji: &str) -> Vec<String> {
+        let mut recents = self.recent_emojis();
+        emoji::push_recent(&mut recents, emoji);
+        // Already logged. The emoji just isn't remembered after a restart.
+        let _ = self.store_recent_emojis(recents.clone());
+        recents
+    }
 
     pub fn x3dh_data(
         &self,
@@ -105,18 +280,191 @@ impl Storage {
             data
         } else {
             let data = x3dh::generate_receiver_keys(algorithms).unwrap();
-            self.store_x3dh_data(algorithms, data.clone());
+            // Already logged. The keys are generated again next time, so messages encrypted to
+            // these ones become unreadable.
+            let _ = self.store_x3dh_data(algorithms, data.clone());
             data
         }
     }
 
-    pub fn store_dm_key(&self, other_contact_id: u64, data: (CryptoAlgorithms, &[u8])) -> bool {
+    pub fn store_dm_key(
+        &self,
+        other_contact_id: u64,
+        data: (CryptoAlgorithms, &[u8]),
+    ) -> Result<(), StorageError> {
         self.store_dm_key_box(other_contact_id, (data.0, Box::from(data.1)))
     }
 
-    pub fn store_group_key(&self, group_id: u64, data: (CryptoAlgorithms, &[u8])) -> bool {
+    pub fn store_group_key(
+        &self,
+        group_id: u64,
+        data: (CryptoAlgorithms, &[u8]),
+    ) -> Result<(), StorageError> {
         self.store_group_key_box(group_id, (data.0, Box::from(data.1)))
     }
+
+    /// Overrides the displayed name of a contact locally. An empty `name` clears the override.
+    pub fn store_contact_nickname(&self, user_id: u64, name: &str) -> Result<(), StorageError> {
+        if name.is_empty() {
+            self.remove_contact_nickname(user_id)
+        } else {
+            self.store_contact_nickname_string(user_id, name.to_owned())
+        }
+    }
+
+    pub fn contact_nickname(&self, user_id: u64) -> Option<String> {
+        self.load_contact_nickname(user_id)
+    }
+
+    /// Overrides the displayed name of a group locally. An empty `name` clears the override.
+    pub fn store_group_nickname(&self, group_id: u64, name: &str) -> Result<(), StorageError> {
+        if name.is_empty() {
+            self.remove_group_nickname(group_id)
+        } else {
+            self.store_group_nickname_string(group_id, name.to_owned())
+        }
+    }
+
+    pub fn group_nickname(&self, group_id: u64) -> Option<String> {
+        self.load_group_nickname(group_id)
+    }
 }
 
 pub static STORAGE: LazyLock<Storage> = LazyLock::new(Default::default);
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use server::AccountCredentials;
+    use shared::{
+        crypto::{self, CryptoAlgorithms},
+        storage::RawStorage,
+    };
+
+    use super::Storage;
+    use crate::decryption::{self, DecryptionError};
+
+    fn test_storage(name: &str) -> Storage {
+        Storage {
+            base_path: std::env::temp_dir().join(format!(
+                "peregrine-client-test-{}-{name}",
+                std::process::id()
+            )),
+            memory: None,
+        }
+    }
+
+    /// Valid encoding of `data` with each byte replaced in turn, and every prefix of it.
+    fn corruptions(data: &impl serde::Serialize) -> Vec<Vec<u8>> {
+        let bytes = postcard::to_allocvec(data).unwrap();
+        let mut result: Vec<Vec<u8>> = (0..bytes.len())
+            .map(|length| bytes[..length].to_vec())
+            .collect();
+        for index in 0..bytes.len() {
+            for value in [0x00, 0x01, 0x7f, 0x80, 0xff] {
+                let mut corrupted = bytes.clone();
+                corrupted[index] = value;
+                result.push(corrupted);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn test_load_corrupted_keys() {
+        let storage = test_storage("corrupted-keys");
+        let write = |file_path: &str, bytes: &[u8]| {
+            fs::write(storage.get_path(file_path).unwrap(), bytes).unwrap();
+        };
+
+        for bytes in corruptions(&AccountCredentials::default()) {
+            write("session.bin", &bytes);
+            let _ = storage.load_session_credentials();
+        }
+
+        for algorithms in crypto::supported_algorithms() {
+            let key = crypto::symmetric_genkey(&algorithms, crypto::KeyStrength::ExtremelyHigh)
+                .unwrap_or_else(|| [0x42; 32].into());
+            let ciphertext = crypto::symmetric_encrypt(&algorithms, b"Hello", &key).unwrap();
+            let method = algorithms.encryption_method();
+            for bytes in corruptions(&(algorithms.clone(), key)) {
+                write("dm1.bin", &bytes);
+                write("group1.bin", &bytes);
+                // Whatever is loaded is used as a key, which mustn't panic either.
+                for key in [storage.load_dm_key(1), storage.load_group_key(1)] {
+                    let _ = decryption::decrypt(key.as_ref(), &method, &ciphertext);
+                }
+            }
+        }
+
+        let _ = fs::remove_dir_all(storage.get_base_path());
+    }
+
+    #[test]
+    fn test_corrupted_key_fails_decryption() {
+        let storage = test_storage("wrong-key-length");
+        for algorithms in crypto::supported_algorithms() {
+            let method = algorithms.encryption_method();
+            storage
+                .store_dm_key_box(1, (algorithms, [0x42; 5].into()))
+                .unwrap();
+            let key = storage.load_dm_key(1);
+            assert_eq!(
+                decryption::decrypt(key.as_ref(), &method, &[0; 64]),
+                Err(DecryptionError::Failed)
+            );
+            assert_eq!(
+                decryption::decrypt(key.as_ref(), &method, &[]),
+                Err(DecryptionError::Failed)
+            );
+        }
+        let _ = fs::remove_dir_all(storage.get_base_path());
+    }
+
+    #[test]
+    fn test_in_memory_storage() {
+        let storage = Storage::in_memory();
+        let credentials = AccountCredentials {
+            id: 7,
+            session_token: [1; 32],
+        };
+        assert_eq!(storage.load_session_credentials(), None);
+        storage.store_session_credentials(credentials).unwrap();
+        assert_eq!(storage.load_session_credentials(), Some(credentials));
+        storage.remove_session_credentials().unwrap();
+        assert_eq!(storage.load_session_credentials(), None);
+        assert!(storage.remove_session_credentials().is_err());
+
+        storage.store_contact_nickname(1, "Alice").unwrap();
+        storage.store_group_nickname(1, "Team").unwrap();
+        assert_eq!(storage.contact_nickname(1).as_deref(), Some("Alice"));
+        assert_eq!(storage.group_nickname(1).as_deref(), Some("Team"));
+        storage.store_contact_nickname(1, "").unwrap();
+        assert_eq!(storage.contact_nickname(1), None);
+        assert_eq!(storage.group_nickname(1).as_deref(), Some("Team"));
+
+        let algorithms = crypto::preferred_alogirthm();
+        assert_eq!(storage.algorithms(), algorithms);
+        storage
+            .store_algorithms(CryptoAlgorithms::from_string("unknown".to_owned()))
+            .unwrap();
+        assert_eq!(storage.algorithms(), algorithms);
+        // Every algorithm is compiled in, but they can't be used together.
+        let diffie_hellman = if algorithms.diffie_hellman == "dalek::x25519" {
+            "bee2-rs::bignb3"
+        } else {
+            "dalek::x25519"
+        };
+        let mixed = CryptoAlgorithms {
+            diffie_hellman: diffie_hellman.to_owned(),
+            ..algorithms.clone()
+        };
+        storage.store_algorithms(mixed).unwrap();
+        assert_eq!(storage.algorithms(), algorithms);
+
+        // Keys are generated once and then reused.
+        let keys = storage.x3dh_data(&algorithms);
+        assert!(storage.x3dh_data(&algorithms).1 == keys.1);
+    }
+}
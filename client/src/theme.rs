@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+/// Color scheme of the application, chosen in the settings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Theme {
+    pub const ALL: [Self; 2] = [Self::Dark, Self::Light];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Dark => "Dark",
+            Self::Light => "Light",
+        }
+    }
+
+    /// Class of the root element which sets the colors of the theme in the stylesheet.
+    pub fn class_name(self) -> &'static str {
+        match self {
+            Self::Dark => "theme-dark",
+            Self::Light => "theme-light",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Theme;
+
+    #[test]
+    fn test_dark_by_default() {
+        assert_eq!(Theme::default(), Theme::Dark);
+    }
+
+    #[test]
+    fn test_stored_theme_round_trip() {
+        for theme in Theme::ALL {
+            let data = postcard::to_allocvec(&theme).unwrap();
+            assert_eq!(postcard::from_bytes::<Theme>(&data).unwrap(), theme);
+        }
+    }
+}
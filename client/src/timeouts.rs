@@ -0,0 +1,101 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, RwLock},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use shared::storage::StorageError;
+
+use crate::storage::STORAGE;
+
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(3);
+/// Timeout of the endpoints sending a whole file in one request.
+pub const DEFAULT_FILE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long requests are waited for, chosen in the settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timeouts {
+    pub wait_timeout: Duration,
+    pub retry_interval: Duration,
+    /// Timeouts of the slow endpoints, by the name of the server function.
+    pub overrides: HashMap<String, Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        let overrides = [
+            "send_dm_file",
+            "send_group_file",
+            "get_dm_file",
+            "get_group_file",
+        ]
+        .into_iter()
+        .map(|endpoint| (endpoint.to_owned(), DEFAULT_FILE_TIMEOUT))
+        .collect();
+        Self {
+            wait_timeout: DEFAULT_WAIT_TIMEOUT,
+            retry_interval: DEFAULT_RETRY_INTERVAL,
+            overrides,
+        }
+    }
+}
+
+impl Timeouts {
+    pub fn for_endpoint(&self, endpoint: &str) -> Duration {
+        self.overrides
+            .get(endpoint)
+            .copied()
+            .unwrap_or(self.wait_timeout)
+    }
+}
+
+static TIMEOUTS: LazyLock<RwLock<Timeouts>> =
+    LazyLock::new(|| RwLock::new(STORAGE.load_timeouts().unwrap_or_default()));
+
+pub fn timeouts() -> Timeouts {
+    TIMEOUTS.read().unwrap().clone()
+}
+
+/// Applies the timeouts to the following requests and stores them. They are applied even if
+/// storing them fails.
+pub fn set_timeouts(timeouts: Timeouts) -> Result<(), StorageError> {
+    *TIMEOUTS.write().unwrap() = timeouts.clone();
+    STORAGE.store_timeouts(timeouts)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{DEFAULT_FILE_TIMEOUT, Timeouts};
+
+    #[test]
+    fn test_endpoint_timeouts() {
+        let mut timeouts = Timeouts::default();
+        assert_eq!(timeouts.for_endpoint("get_contacts"), timeouts.wait_timeout);
+        assert_eq!(timeouts.for_endpoint("send_dm_file"), DEFAULT_FILE_TIMEOUT);
+
+        timeouts.wait_timeout = Duration::from_secs(30);
+        timeouts
+            .overrides
+            .insert("get_contacts".to_owned(), Duration::from_secs(1));
+        assert_eq!(
+            timeouts.for_endpoint("get_contacts"),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            timeouts.for_endpoint("are_session_credentials_valid"),
+            Duration::from_secs(30)
+        );
+        assert_eq!(timeouts.for_endpoint("get_dm_file"), DEFAULT_FILE_TIMEOUT);
+    }
+
+    #[test]
+    fn test_stored_timeouts_round_trip() {
+        let timeouts = Timeouts::default();
+        let data = postcard::to_allocvec(&timeouts).unwrap();
+        assert_eq!(postcard::from_bytes::<Timeouts>(&data).unwrap(), timeouts);
+    }
+}
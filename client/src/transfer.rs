@@ -0,0 +1,58 @@
+use std::{
+    fs::File,
+    io::{Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use dioxus::{logger::tracing::error, prelude::ServerFnError};
+use server::{AccountCredentials, ServerError};
+use shared::limits::LIMITS;
+
+/// Downloads the uploaded file to `path` part by part, so that only one part is kept in memory.
+/// If the file at `path` already exists, the download is resumed after its last byte.
+pub async fn download_file(
+    file_id: u64,
+    path: &Path,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    let mut file = match File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+    {
+        Ok(file) => file,
+        Err(err) => {
+            error!("Failed to open {path:?} for download: {err:?}");
+            return Err(ServerFnError::Request(err.to_string()));
+        }
+    };
+    let mut offset = match file.seek(SeekFrom::End(0)) {
+        Ok(offset) => offset,
+        Err(err) => {
+            error!("Failed to seek to the end of {path:?}: {err:?}");
+            return Err(ServerFnError::Request(err.to_string()));
+        }
+    };
+
+    loop {
+        let range = server::download_file_range(
+            file_id,
+            offset,
+            LIMITS.max_download_range_size,
+            credentials,
+        )
+        .await?;
+        if range.bytes.is_empty() {
+            return Ok(());
+        }
+        if let Err(err) = file.write_all(&range.bytes) {
+            error!("Failed to write downloaded part to {path:?}: {err:?}");
+            return Err(ServerFnError::Request(err.to_string()));
+        }
+        offset += range.bytes.len() as u64;
+        if offset >= range.total_size {
+            return Ok(());
+        }
+    }
+}
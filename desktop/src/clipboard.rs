@@ -0,0 +1,11 @@
+use dioxus::{logger::tracing::error, prelude::*};
+
+pub async fn copy_text(text: String) {
+    let eval = document::eval("await navigator.clipboard.writeText(await dioxus.recv());");
+    if let Err(err) = eval.send(text) {
+        error!("Failed to copy text to clipboard: {err:?}");
+    }
+    if let Err(err) = eval.await {
+        error!("Failed to copy text to clipboard: {err:?}");
+    }
+}
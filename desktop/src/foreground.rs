@@ -0,0 +1,16 @@
+use dioxus::{logger::tracing::error, prelude::*};
+
+/// Calls `on_foreground` right away and then each time the window is brought to the foreground.
+pub async fn on_foreground<F: Future>(mut on_foreground: impl FnMut() -> F) {
+    let mut eval = document::eval(
+        r#"window.addEventListener("focus", () => dioxus.send(true));
+        dioxus.send(true);"#,
+    );
+    loop {
+        if let Err(err) = eval.recv::<bool>().await {
+            error!("Stopped waiting for the window to be focused: {err:?}");
+            return;
+        }
+        on_foreground().await;
+    }
+}
@@ -1,15 +1,26 @@
 #![windows_subsystem = "windows"]
 
+use client::{
+    links::{self, DeepLink},
+    prekeys,
+    storage::STORAGE,
+};
 use dioxus::{logger::tracing::Level, prelude::*};
 
 use server::AccountCredentials;
+use session::{AccountLayout, Session};
 #[cfg(debug_assertions)]
 use views::ChangeCredentials;
 use views::{
-    Contacts, CreateGroup, GroupMenu, Home, Invites, LoginAccount, OtherUserAccount,
-    RegisterAccount, SessionValidityChecker,
+    AddContact, Contacts, CreateGroup, ErrorScreen, GroupMenu, Home, Invites, LoginAccount,
+    OfflineBanner, OtherUserAccount, RecoverAccount, RegisterAccount, Settings, ToastList,
 };
 
+mod clipboard;
+mod foreground;
+mod session;
+#[cfg(feature = "desktop")]
+mod url_scheme;
 mod views;
 
 #[derive(Debug, Clone, Routable, PartialEq)]
@@ -18,28 +29,57 @@ pub enum Route {
     #[layout(DesktopNavbar)]
     #[route("/")]
     Home {},
-    #[route("/contacts/:credentials")]
-    Contacts { credentials: AccountCredentials },
+    #[layout(AccountLayout)]
+        #[route("/contacts")]
+        Contacts {},
+    #[end_layout]
     #[end_layout]
     #[nest("/account")]
         #[route("/")]
         RegisterAccount {},
         #[route("/signup")]
         LoginAccount {},
+        #[route("/recover")]
+        RecoverAccount {},
     #[end_nest]
-    #[route("/check_session/:credentials")]
-    SessionValidityChecker { credentials: AccountCredentials },
-    #[route("/invites/:credentials")]
-    Invites { credentials: AccountCredentials },
-    #[route("/user?:user_id&:credentials")]
-    OtherUserAccount { user_id: u64, credentials: AccountCredentials },
-    #[route("/create_group/:credentials")]
-    CreateGroup { credentials: AccountCredentials },
-    #[cfg(debug_assertions)]
-    #[route("/debug/change_credentials/:credentials")]
-    ChangeCredentials { credentials: AccountCredentials },
-    #[route("/group?:group_id&:credentials")]
-    GroupMenu { group_id: u64, credentials: AccountCredentials },
+    #[layout(AccountLayout)]
+        #[route("/invites")]
+        Invites {},
+        #[route("/user?:user_id")]
+        OtherUserAccount { user_id: u64 },
+        #[route("/create_group")]
+        CreateGroup {},
+        #[cfg(debug_assertions)]
+        #[route("/debug/change_credentials")]
+        ChangeCredentials {},
+        #[route("/group?:group_id")]
+        GroupMenu { group_id: u64 },
+        #[route("/add_contact")]
+        AddContact {},
+    #[end_layout]
+    #[route("/settings")]
+    Settings {},
+}
+
+impl Route {
+    pub fn from_link(link: DeepLink) -> Self {
+        match link {
+            DeepLink::User(user_id) => Self::OtherUserAccount { user_id },
+            DeepLink::Group(group_id) => Self::GroupMenu { group_id },
+        }
+    }
+}
+
+/// Opens the main screen after the user has been authenticated. If the application was opened
+/// with a deep link, its target is opened on top of it.
+pub fn enter_app(credentials: AccountCredentials) {
+    spawn(prekeys::replenish_one_time_prekeys(credentials));
+    session::open_session(credentials);
+    let nav = navigator();
+    nav.replace(Route::Contacts {});
+    if let Some(link) = links::take_pending_link() {
+        nav.push(Route::from_link(link));
+    }
 }
 
 const MAIN_CSS: Asset = asset!("/assets/main.css");
@@ -57,6 +97,20 @@ fn main() {
     {
         server::init_server();
     }
+    #[cfg(feature = "desktop")]
+    {
+        let link = links::find_link_in_args(std::env::args().skip(1));
+        if let Some(link) = link
+            && url_scheme::forward_link(link)
+        {
+            return;
+        }
+        url_scheme::register_url_scheme();
+        if let Some(link) = link {
+            links::set_pending_link(link);
+        }
+        url_scheme::listen_for_links();
+    }
 
     #[cfg(all(not(feature = "server"), not(debug_assertions)))]
     {
@@ -77,17 +131,71 @@ fn main() {
             )
             .launch(App);
     }
-    #[cfg(not(all(feature = "desktop", not(debug_assertions))))]
+    #[cfg(all(not(feature = "desktop"), feature = "server"))]
+    serve();
+    #[cfg(not(any(
+        all(feature = "desktop", not(debug_assertions)),
+        all(not(feature = "desktop"), feature = "server")
+    )))]
     dioxus::launch(App);
 }
 
+/// Runs the fullstack server until it receives a shutdown signal. Unlike `dioxus::launch`, it
+/// lets the requests in flight finish and closes the database afterwards.
+#[cfg(all(not(feature = "desktop"), feature = "server"))]
+fn serve() {
+    use dioxus::logger::tracing::error;
+
+    let listening = tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(async move {
+            let address = dioxus::cli_config::fullstack_address_or_localhost();
+            // Responses are compressed if the client accepts it. Message lists compress well: a
+            // fetch of 30 messages with 40-300 bytes of encrypted content each shrinks from about
+            // 25 KB of JSON to about 9 KB with gzip.
+            let router = axum::Router::new()
+                .serve_dioxus_application(ServeConfigBuilder::default(), App)
+                .layer(tower_http::compression::CompressionLayer::new())
+                .into_make_service();
+            let listener = match tokio::net::TcpListener::bind(address).await {
+                Ok(listener) => listener,
+                Err(err) => {
+                    error!("Failed to listen on {address}: {err}");
+                    return false;
+                }
+            };
+            if let Err(err) = axum::serve(listener, router)
+                .with_graceful_shutdown(server::shutdown::shutdown_signal())
+                .await
+            {
+                error!("Server error: {err:?}");
+            }
+            true
+        });
+    server::shutdown::finish_shutdown();
+    if !listening {
+        std::process::exit(1);
+    }
+}
+
 #[component]
 fn App() -> Element {
     #[cfg(feature = "server")]
     server::init_server();
+    let theme = use_context_provider(|| Signal::new(STORAGE.theme()));
+    use_context_provider(|| Signal::new(STORAGE.message_format()));
+    use_context_provider(|| Signal::new(Session::default()));
     rsx! {
         document::Link { rel: "stylesheet", href: MAIN_CSS }
-        Router::<Route> {}
+        div {
+            class: "app-root {theme().class_name()}",
+            OfflineBanner {}
+            ErrorBoundary {
+                handle_error: ErrorScreen,
+                Router::<Route> {}
+            }
+            ToastList {}
+        }
     }
 }
 
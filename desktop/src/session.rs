@@ -0,0 +1,37 @@
+use dioxus::prelude::*;
+use server::AccountCredentials;
+
+use crate::Route;
+
+/// Credentials of the open account. They are kept here instead of in the routes, which would put
+/// the session token into the navigation history and into logged URLs.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Session(pub Option<AccountCredentials>);
+
+pub fn open_session(credentials: AccountCredentials) {
+    consume_context::<Signal<Session>>().set(Session(Some(credentials)));
+}
+
+/// Layout of the screens of an open account, which provides its credentials to them. Without an
+/// open account it goes back to the home screen, which opens the stored one or asks to log in.
+#[component]
+pub fn AccountLayout() -> Element {
+    let session = use_context::<Signal<Session>>();
+    match session().0 {
+        Some(credentials) => rsx! {
+            CredentialsProvider { key: "{credentials}", credentials, Outlet::<Route> {} }
+        },
+        None => {
+            navigator().replace(Route::Home {});
+            rsx!()
+        }
+    }
+}
+
+#[component]
+fn CredentialsProvider(credentials: AccountCredentials, children: Element) -> Element {
+    use_context_provider(|| credentials);
+    #[cfg(feature = "desktop")]
+    use_future(crate::url_scheme::open_received_links);
+    children
+}
@@ -0,0 +1,183 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    process::Command,
+    time::Duration,
+};
+
+use client::links::{self, DeepLink, URL_SCHEME};
+use dioxus::{
+    logger::tracing::{debug, error, info},
+    prelude::*,
+};
+
+use crate::Route;
+
+/// Port on localhost where the running instance receives deep links from the instances started
+/// by the system to open them.
+const LINK_PORT: u16 = 47_613;
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(1);
+/// Longer lines can't be valid deep links, so the rest is not read.
+const MAX_LINK_LENGTH: u64 = 256;
+const LINK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+fn link_address() -> SocketAddr {
+    (Ipv4Addr::LOCALHOST, LINK_PORT).into()
+}
+
+/// Runs `command`, treating a non-zero exit code as an error.
+fn run(command: &mut Command) -> std::io::Result<()> {
+    let status = command.status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(std::io::Error::other(format!(
+            "{command:?} exited with {status}"
+        )))
+    }
+}
+
+/// Returns whether anything had to be registered.
+#[cfg(target_os = "linux")]
+fn register(executable: &str) -> std::io::Result<bool> {
+    use std::{fs, path::PathBuf};
+
+    let applications_dir = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+        .ok_or(std::io::ErrorKind::NotFound)?
+        .join("applications");
+    let desktop_file = format!("{URL_SCHEME}-url-handler.desktop");
+    let desktop_path = applications_dir.join(&desktop_file);
+    let mime_type = format!("x-scheme-handler/{URL_SCHEME}");
+    let entry = format!(
+        "[Desktop Entry]\n\
+        Type=Application\n\
+        Name=Peregrine\n\
+        Exec=\"{executable}\" %u\n\
+        NoDisplay=true\n\
+        MimeType={mime_type};\n"
+    );
+
+    let is_default = || {
+        Command::new("xdg-mime")
+            .args(["query", "default", &mime_type])
+            .output()
+            .is_ok_and(|output| String::from_utf8_lossy(&output.stdout).trim() == desktop_file)
+    };
+    if fs::read_to_string(&desktop_path).is_ok_and(|existing| existing == entry) && is_default() {
+        return Ok(false);
+    }
+
+    fs::create_dir_all(&applications_dir)?;
+    fs::write(&desktop_path, entry)?;
+    run(Command::new("xdg-mime").args(["default", &desktop_file, &mime_type]))?;
+    Ok(true)
+}
+
+/// Returns whether anything had to be registered.
+#[cfg(target_os = "windows")]
+fn register(executable: &str) -> std::io::Result<bool> {
+    let key = format!(r"HKCU\Software\Classes\{URL_SCHEME}");
+    let command_key = format!(r"{key}\shell\open\command");
+    let open_command = format!("\"{executable}\" \"%1\"");
+
+    let registered = Command::new("reg")
+        .args(["query", &command_key, "/ve"])
+        .output()
+        .is_ok_and(|output| {
+            output.status.success()
+                && String::from_utf8_lossy(&output.stdout).contains(&open_command)
+        });
+    if registered {
+        return Ok(false);
+    }
+
+    run(Command::new("reg").args(["add", &key, "/ve", "/d", "URL:Peregrine", "/f"]))?;
+    run(Command::new("reg").args(["add", &key, "/v", "URL Protocol", "/d", "", "/f"]))?;
+    run(Command::new("reg").args(["add", &command_key, "/ve", "/d", &open_command, "/f"]))?;
+    Ok(true)
+}
+
+// On Mac OS the scheme is declared in the bundle's `Info.plist` instead.
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+fn register(_executable: &str) -> std::io::Result<bool> {
+    Ok(false)
+}
+
+/// Registers this executable as the handler of `peregrine://` links for the current user, unless
+/// it already is.
+pub fn register_url_scheme() {
+    let executable = match std::env::current_exe() {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(err) => {
+            error!("Failed to get current executable path: {err:?}");
+            return;
+        }
+    };
+    match register(&executable) {
+        Ok(true) => info!("Registered {URL_SCHEME}:// URL scheme handler"),
+        Ok(false) => debug!("{URL_SCHEME}:// URL scheme handler is already registered"),
+        Err(err) => error!("Failed to register {URL_SCHEME}:// URL scheme handler: {err:?}"),
+    }
+}
+
+/// Passes `link` to an already running instance. Returns `false` if there is none, in which case
+/// this instance has to open the link itself.
+pub fn forward_link(link: DeepLink) -> bool {
+    let Ok(mut stream) = TcpStream::connect_timeout(&link_address(), FORWARD_TIMEOUT) else {
+        return false;
+    };
+    match writeln!(stream, "{link}") {
+        Ok(()) => {
+            info!("Opened {link} in the running instance");
+            true
+        }
+        Err(err) => {
+            error!("Failed to pass {link} to the running instance: {err:?}");
+            false
+        }
+    }
+}
+
+fn receive_link(stream: TcpStream) -> Option<DeepLink> {
+    stream.set_read_timeout(Some(FORWARD_TIMEOUT)).ok()?;
+    let mut line = String::new();
+    BufReader::new(stream.take(MAX_LINK_LENGTH))
+        .read_line(&mut line)
+        .ok()?;
+    line.trim_end().parse().ok()
+}
+
+/// Starts receiving links passed by [`forward_link`] from instances started later. They are
+/// opened the same way as the link this instance was started with.
+pub fn listen_for_links() {
+    let listener = match TcpListener::bind(link_address()) {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("Failed to listen for deep links from other instances: {err:?}");
+            return;
+        }
+    };
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if let Some(link) = receive_link(stream) {
+                links::set_pending_link(link);
+            }
+        }
+    });
+}
+
+/// Opens the links received while an account is open and brings the window to the front.
+pub async fn open_received_links() {
+    loop {
+        tokio::time::sleep(LINK_POLL_INTERVAL).await;
+        if let Some(link) = links::take_pending_link() {
+            dioxus::desktop::window().set_focus();
+            navigator().push(Route::from_link(link));
+        }
+    }
+}
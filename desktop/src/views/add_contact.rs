@@ -0,0 +1,225 @@
+use client::{display, links::ContactCode, packet_sender::PacketState, storage::STORAGE};
+use dioxus::{logger::tracing::error, prelude::*};
+use qrcode::{QrCode, render::svg};
+use rfd::AsyncFileDialog;
+use server::{AccountCredentials, UserAccount};
+use shared::crypto::fingerprint;
+
+use super::other_user_account::generate_encrypted_shared_key;
+use crate::Route;
+
+#[derive(Debug, Clone, PartialEq)]
+struct ResolvedContact {
+    user_id: u64,
+    account: UserAccount,
+    /// `None` if the contact was found by its handle, so there was no fingerprint to compare with.
+    verified: Option<bool>,
+}
+
+fn own_contact_code(user_id: u64) -> Option<String> {
+    let (_, identity) = STORAGE.x3dh_data(&STORAGE.algorithms());
+    let code = ContactCode {
+        user_id,
+        fingerprint: fingerprint::identity_fingerprint(&identity)?,
+    };
+    Some(code.to_string())
+}
+
+fn render_qr_code(payload: &str) -> Option<String> {
+    let code = match QrCode::new(payload.as_bytes()) {
+        Ok(code) => code,
+        Err(err) => {
+            error!("Failed to generate QR code: {err:?}");
+            return None;
+        }
+    };
+    Some(
+        code.render::<svg::Color>()
+            .min_dimensions(240, 240)
+            .dark_color(svg::Color("#000000"))
+            .light_color(svg::Color("#ffffff"))
+            .build(),
+    )
+}
+
+fn scan_qr_code(image_bytes: &[u8]) -> Option<String> {
+    let image = match image::load_from_memory(image_bytes) {
+        Ok(image) => image.to_luma8(),
+        Err(err) => {
+            error!("Failed to load image with QR code: {err:?}");
+            return None;
+        }
+    };
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(
+        image.width() as usize,
+        image.height() as usize,
+        |x, y| image.get_pixel(x as u32, y as u32).0[0],
+    );
+    prepared
+        .detect_grids()
+        .into_iter()
+        .find_map(|grid| grid.decode().ok())
+        .map(|(_, content)| content)
+}
+
+async fn resolve_contact(
+    query: String,
+    credentials: AccountCredentials,
+) -> Result<Option<ResolvedContact>, ServerFnError<server::ServerError>> {
+    if let Ok(code) = query.parse::<ContactCode>() {
+        let Some(account) = server::get_user_data(code.user_id, credentials).await? else {
+            return Ok(None);
+        };
+        let verified =
+            fingerprint::identity_fingerprint(&account.cryptoidentity) == Some(code.fingerprint);
+        return Ok(Some(ResolvedContact {
+            user_id: code.user_id,
+            account,
+            verified: Some(verified),
+        }));
+    }
+
+    Ok(server::lookup_account(query, credentials)
+        .await?
+        .map(|account| ResolvedContact {
+            user_id: account.id,
+            account: account.into(),
+            verified: None,
+        }))
+}
+
+#[component]
+#[allow(non_snake_case)]
+pub fn AddContact() -> Element {
+    let credentials = use_context::<AccountCredentials>();
+    let mut query: Signal<String> = use_signal(String::new);
+    let mut resolved: Signal<PacketState<Option<ResolvedContact>>> =
+        use_signal(|| PacketState::NotStarted);
+    let mut invite_status: Signal<Option<String>> = use_signal(|| None);
+
+    let own_code = own_contact_code(credentials.id);
+    let own_fingerprint = own_code
+        .as_ref()
+        .and_then(|code| code.parse::<ContactCode>().ok())
+        .map(|code| fingerprint::format_fingerprint(&code.fingerprint));
+    let qr_svg = own_code.as_deref().and_then(render_qr_code);
+
+    let resolve = move |text: String| async move {
+        invite_status.set(None);
+        resolved.set(PacketState::Waiting);
+        match resolve_contact(text.trim().to_owned(), credentials).await {
+            Ok(contact) => resolved.set(PacketState::Response(contact)),
+            Err(err) => resolved.set(PacketState::ServerError(err)),
+        }
+    };
+
+    let result = match resolved() {
+        PacketState::Response(Some(contact)) => {
+            let name = display::contact_display_name(Some(&contact.account), contact.user_id);
+            let user_id = contact.user_id;
+            let verification = match contact.verified {
+                Some(true) => rsx!(p { style: "color:var(--success-text)", "Identity key fingerprint matches." }),
+                Some(false) => rsx!(p {
+                    style: "color:var(--error-text)",
+                    "Identity key fingerprint does NOT match the scanned code. Do not trust this account."
+                }),
+                None => rsx!(p { "Identity key was not verified." }),
+            };
+            rsx! {
+                h3 { "{name}" }
+                {verification}
+                if contact.verified != Some(false) {
+                    button {
+                        onclick: move |_| async move {
+                            let Some(encryption_data) =
+                                generate_encrypted_shared_key(user_id, None, credentials).await
+                            else {
+                                invite_status.set(Some("Identity key of this user has changed. Verify it in their profile first.".to_owned()));
+                                return;
+                            };
+                            match server::send_dm_invite(user_id, Some(encryption_data), credentials).await {
+                                Ok(_) => invite_status.set(Some("Invite sent".to_owned())),
+                                Err(err) => invite_status.set(Some(format!("Server error: {err}"))),
+                            }
+                        },
+                        "Send DM invite"
+                    }
+                }
+                button {
+                    onclick: move |_| {
+                        let nav = navigator();
+                        nav.push(Route::OtherUserAccount { user_id });
+                    },
+                    "Open profile"
+                }
+                if let Some(status) = invite_status() {
+                    p { "{status}" }
+                }
+            }
+        }
+        PacketState::Response(None) => rsx!(p { "No account found." }),
+        PacketState::Waiting => rsx!(p { "Looking up account..." }),
+        PacketState::ServerError(err) => rsx!(p { "Server error: {err}" }),
+        PacketState::RequestTimeout => rsx!(p { "Request timeout" }),
+        PacketState::NotStarted => rsx!(),
+    };
+
+    rsx! {
+        div {
+            height: "100%",
+            margin: "12px 24px",
+
+            button {
+                onclick: |_| {
+                    let nav = navigator();
+                    nav.go_back();
+                },
+                "Back"
+            }
+            h1 { "Add contact" }
+            h2 { "Your code" }
+            if let Some(qr_svg) = qr_svg {
+                div {
+                    width: "240px",
+                    dangerous_inner_html: qr_svg,
+                }
+            } else {
+                p { "Failed to generate your contact code." }
+            }
+            if let Some(own_fingerprint) = own_fingerprint {
+                p { "Fingerprint: {own_fingerprint}" }
+            }
+            h2 { "Find a contact" }
+            input {
+                width: "400px",
+                placeholder: "Contact code, username or email",
+                value: "{query}",
+                oninput: move |event| query.set(event.value()),
+            }
+            button {
+                onclick: move |_| resolve(query()),
+                "Find"
+            }
+            button {
+                onclick: move |_| async move {
+                    let Some(file) = AsyncFileDialog::new()
+                        .add_filter("Image", &["png", "jpg", "jpeg"])
+                        .pick_file()
+                        .await
+                    else {
+                        return;
+                    };
+                    match scan_qr_code(&file.read().await) {
+                        Some(content) => {
+                            query.set(content.clone());
+                            resolve(content).await;
+                        }
+                        None => invite_status.set(Some("No QR code found in the image".to_owned())),
+                    }
+                },
+                "Scan from image"
+            }
+            {result}
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use client::display;
+use dioxus::prelude::*;
+use shared::types::UserIcon;
+
+/// Icon of a user or a group shown in the lists. Falls back to the placeholder if there is no
+/// icon or it can't be displayed.
+#[component]
+#[allow(non_snake_case)]
+pub fn Avatar(icon: UserIcon) -> Element {
+    const ICON_TRANSPARENT: Asset = asset!(
+        "/assets/icon_transparent.png",
+        ImageAssetOptions::new()
+            .with_size(ImageSize::Manual {
+                width: 97,
+                height: 111,
+            })
+            .with_format(ImageFormat::Avif)
+    );
+
+    let src = icon
+        .as_deref()
+        .and_then(display::icon_data_url)
+        .unwrap_or_else(|| ICON_TRANSPARENT.to_string());
+    rsx! {
+        img {
+            src,
+            margin_right: "24px",
+            width: "46px",
+            max_height: "46px",
+        }
+    }
+}
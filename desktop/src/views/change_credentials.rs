@@ -6,7 +6,8 @@ use client::storage::STORAGE;
 use server::AccountCredentials;
 
 #[component]
-pub fn ChangeCredentials(credentials: AccountCredentials) -> Element {
+pub fn ChangeCredentials() -> Element {
+    let credentials = use_context::<AccountCredentials>();
     let mut session_token = use_signal(|| {
         let mut bytes = vec![];
         bytes.extend(credentials.id.to_le_bytes());
@@ -37,7 +38,7 @@ pub fn ChangeCredentials(credentials: AccountCredentials) -> Element {
                 onclick: move |_| async move {
                     let session_token = session_token();
                     if session_token.is_empty() {
-                        STORAGE.remove_session_credentials();
+                        let _ = STORAGE.remove_session_credentials();
                     } else {
                         let Ok(bytes) = STANDARD.decode(session_token) else {
                             return;
@@ -49,7 +50,7 @@ pub fn ChangeCredentials(credentials: AccountCredentials) -> Element {
                             id: u64::from_le_bytes(bytes[..8].try_into().unwrap()),
                             session_token: bytes[8..].try_into().unwrap(),
                         };
-                        STORAGE.store_session_credentials(credentials);
+                        let _ = STORAGE.store_session_credentials(credentials);
                     }
                     let nav = navigator();
                     nav.replace(Route::Home {});
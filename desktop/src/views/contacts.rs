@@ -1,27 +1,51 @@
 use std::{rc::Rc, time::Duration};
 
 use chrono::Local;
-use client::{cache::CACHE, future_retry_loop, packet_sender::PacketState, storage::STORAGE};
+use client::{
+    cache::CACHE,
+    decryption::{self, DecryptionError},
+    display, emoji, future_retry_loop,
+    notifications::notify_error,
+    outbox::{self, Outbox, OutgoingMessage, Recipient},
+    packet_sender::{PacketSender, PacketState, render_packet_state},
+    storage::STORAGE,
+};
 use dioxus::{logger::tracing::error, prelude::*};
-use dioxus_markdown::Markdown;
 use rfd::AsyncFileDialog;
 use server::{
-    AccountCredentials, DmGroup, DmMessage, FoundAccount, GroupMessage, MessageStatus,
-    MultiUserGroup,
+    AccountCredentials, DmGroup, DmMessage, FoundAccount, GroupMessage, MessageReferences,
+    MessageStatus, MultiUserGroup,
 };
-use shared::crypto::{self, CryptoAlgorithms};
+use shared::crypto::{self, CryptoAlgorithms, PLAINTEXT_METHOD};
 
-use crate::Route;
+use super::{
+    avatar::Avatar, emoji_picker::EmojiPicker, message_text::MessageText, retry_status::RetryStatus,
+};
+use crate::{Route, foreground};
 
 #[component]
 #[allow(non_snake_case)]
-pub fn Contacts(credentials: AccountCredentials) -> Element {
+pub fn Contacts() -> Element {
+    let credentials = use_context::<AccountCredentials>();
     let mut found_users: Signal<Option<Vec<FoundAccount>>> = use_signal(|| None);
-    let joined_dm_groups = future_retry_loop!(server::get_joined_dm_groups(credentials));
-    let joined_groups = future_retry_loop!(server::get_joined_groups(credentials));
+    future_retry_loop! { joined_dm_groups_signal, joined_dm_groups_resource, joined_dm_groups_failures, server::get_joined_dm_groups(credentials) };
+    future_retry_loop! { joined_groups_signal, joined_groups_resource, joined_groups_failures, server::get_joined_groups(credentials) };
+    let joined_dm_groups = joined_dm_groups_signal();
+    let joined_groups = joined_groups_signal();
+    let saved_contacts = future_retry_loop!(server::get_contacts(credentials));
     let selected_dm_group: Signal<Option<DmGroup>> = use_signal(|| None);
     let selected_group: Signal<Option<MultiUserGroup>> = use_signal(|| None);
     let force_refresh_messages: Signal<bool> = use_signal(|| false);
+    let outbox = use_signal(|| STORAGE.outbox(credentials.id));
+    use_future(move || outbox::flush_loop(credentials, outbox));
+    // Senders get delivery receipts once the app is open, not only when a conversation is.
+    use_future(move || {
+        foreground::on_foreground(move || async move {
+            if let Err(err) = server::mark_all_delivered(credentials).await {
+                error!("Failed to mark received messages as delivered: {err:?}");
+            }
+        })
+    });
     let item_list = if let Some(users) = found_users() {
         if users.is_empty() {
             rsx!(h3 {
@@ -31,51 +55,71 @@ pub fn Contacts(credentials: AccountCredentials) -> Element {
         } else {
             rsx! {
                 for user in users {
-                    User { key: user.id, account: user.clone(), credentials }
+                    User { key: user.id, account: user.clone() }
                 }
             }
         }
     } else {
-        match joined_dm_groups {
-            PacketState::Response(dm_groups) => match joined_groups {
-                PacketState::Response(groups) => {
-                    if dm_groups.is_empty() && groups.is_empty() {
-                        rsx!(h3 {
-                            margin: "20px",
-                            "You are not a member of any groups or conversations."
-                        })
-                    } else {
-                        rsx! {
-                            for group in dm_groups {
-                                DmGroupPanel { key: (group.id + u64::MAX / 2), group, user_id: credentials.id, selected_dm_group, selected_group, force_refresh_messages, credentials }
-                            }
-                            for group in groups {
-                                GroupPanel { key: group.id, group: group.clone(), user_id: credentials.id, selected_dm_group, selected_group, force_refresh_messages, credentials }
-                            }
-                        }
-                    }
-                }
-                PacketState::Waiting => {
-                    rsx!(h3 { "Loading..." })
+        let contact_list = match saved_contacts {
+            PacketState::Response(contact_ids) if !contact_ids.is_empty() => rsx! {
+                h4 {
+                    margin: "8px 20px",
+                    "Contacts"
                 }
-                PacketState::ServerError(err) => {
-                    rsx!(h3 { "Server error: {err:?}" })
+                for contact_id in contact_ids {
+                    ContactPanel { key: contact_id, user_id: contact_id }
                 }
-                PacketState::RequestTimeout => {
-                    rsx!(h3 { "Request timeout" })
+                h4 {
+                    margin: "8px 20px",
+                    "Conversations"
                 }
-                PacketState::NotStarted => unreachable!(),
             },
-            PacketState::Waiting => {
-                rsx!(h3 { "Loading..." })
+            _ => rsx!(),
+        };
+        // Both lists are loaded at the same time and shown once both are there.
+        let group_list = match (joined_dm_groups, joined_groups) {
+            (PacketState::Response(dm_groups), PacketState::Response(groups)) => {
+                if dm_groups.items.is_empty() && groups.items.is_empty() {
+                    rsx!(h3 {
+                        margin: "20px",
+                        "You are not a member of any groups or conversations."
+                    })
+                } else {
+                    rsx! {
+                        for group in dm_groups.items {
+                            DmGroupPanel { key: (group.id + u64::MAX / 2), group, user_id: credentials.id, selected_dm_group, selected_group, force_refresh_messages }
+                        }
+                        for group in groups.items {
+                            GroupPanel { key: group.id, group: group.clone(), user_id: credentials.id, selected_dm_group, selected_group, force_refresh_messages }
+                        }
+                    }
+                }
+            }
+            (PacketState::ServerError(err), _) => {
+                rsx!(h3 { "Failed to load conversations: {err}" })
+            }
+            (_, PacketState::ServerError(err)) => {
+                rsx!(h3 { "Failed to load groups: {err}" })
             }
-            PacketState::ServerError(err) => {
-                rsx!(h3 { "Server error: {err:?}" })
+            (PacketState::RequestTimeout, _) => {
+                rsx!(h3 { "Loading conversations timed out" })
             }
-            PacketState::RequestTimeout => {
-                rsx!(h3 { "Request timeout" })
+            (_, PacketState::RequestTimeout) => {
+                rsx!(h3 { "Loading groups timed out" })
             }
-            PacketState::NotStarted => unreachable!(),
+            (dm_groups, groups) => {
+                debug_assert!(
+                    dm_groups != PacketState::NotStarted && groups != PacketState::NotStarted,
+                    "the retry loops start waiting"
+                );
+                rsx!(h3 { "Loading..." })
+            }
+        };
+        rsx! {
+            {contact_list}
+            {group_list}
+            RetryStatus { failures: joined_dm_groups_failures, resource: joined_dm_groups_resource }
+            RetryStatus { failures: joined_groups_failures, resource: joined_groups_resource }
         }
     };
     #[cfg(debug_assertions)]
@@ -85,7 +129,7 @@ pub fn Contacts(credentials: AccountCredentials) -> Element {
             a {
                 onclick: move |_| {
                     let nav = navigator();
-                    nav.push(Route::ChangeCredentials { credentials });
+                    nav.push(Route::ChangeCredentials {});
                 },
                 "Change credentials (debug-only)"
             }
@@ -109,7 +153,7 @@ pub fn Contacts(credentials: AccountCredentials) -> Element {
                     width: "100%",
                     height: "32px",
                     border: "none",
-                    background_color: "#202427",
+                    background_color: "var(--search)",
                     placeholder: "Search",
                     oninput: move |event| async move {
                         let query = event.value();
@@ -140,7 +184,7 @@ pub fn Contacts(credentials: AccountCredentials) -> Element {
                     a {
                         onclick: move |_| {
                             let nav = navigator();
-                            nav.push(Route::Invites { credentials });
+                            nav.push(Route::Invites {});
                         },
                         "Invites",
                     }
@@ -150,18 +194,39 @@ pub fn Contacts(credentials: AccountCredentials) -> Element {
                     a {
                         onclick: move |_| {
                             let nav = navigator();
-                            nav.push(Route::CreateGroup { credentials });
+                            nav.push(Route::AddContact {});
+                        },
+                        "Add contact",
+                    }
+                }
+                div {
+                    height: "30px",
+                    a {
+                        onclick: move |_| {
+                            let nav = navigator();
+                            nav.push(Route::CreateGroup {});
                         },
                         "Create a new group",
                     }
                 }
+                div {
+                    height: "30px",
+                    a {
+                        onclick: move |_| {
+                            let nav = navigator();
+                            nav.push(Route::Settings {});
+                        },
+                        "Settings",
+                    }
+                }
             }
             div {
                 class: "twopanel twopanel-right",
                 if let Some(dm_group) = selected_dm_group() {
-                    DmMessagesPanel { selected_dm_group: dm_group, force_refresh_messages, credentials }
+                    // Keyed, so the loops of the previous conversation end when another one is selected.
+                    DmMessagesPanel { key: "{dm_group.id}", selected_dm_group: dm_group, force_refresh_messages, outbox }
                 } else if let Some(group) = selected_group() {
-                    GroupMessagesPanel { selected_group: group, force_refresh_messages, credentials }
+                    GroupMessagesPanel { key: "{group.id}", selected_group: group, force_refresh_messages, outbox }
                 } else {
                     h2 {
                         margin: "20px",
@@ -175,27 +240,16 @@ pub fn Contacts(credentials: AccountCredentials) -> Element {
 
 #[component]
 #[allow(non_snake_case)]
-pub fn User(account: FoundAccount, credentials: AccountCredentials) -> Element {
-    const ICON_TRANSPARENT: Asset = asset!(
-        "/assets/icon_transparent.png",
-        ImageAssetOptions::new()
-            .with_size(ImageSize::Manual {
-                width: 97,
-                height: 111,
-            })
-            .with_format(ImageFormat::Avif)
-    );
-
-    let title = account
-        .username
-        .unwrap_or(account.email.clone().unwrap_or("Anonymous".to_owned()));
-    let email = account.email.unwrap_or("Hidden email".to_owned());
+pub fn User(account: FoundAccount) -> Element {
+    let account_id = account.id;
+    let email = account.email.clone().unwrap_or("Hidden email".to_owned());
+    let title = display::contact_display_name(Some(&account.into()), account_id);
     rsx! {
         div {
             class: "item-panel",
             onclick: move |_| async move {
                 let nav = navigator();
-                nav.push(Route::OtherUserAccount { user_id: account.id, credentials });
+                nav.push(Route::OtherUserAccount { user_id: account_id });
             },
 
             div {
@@ -203,12 +257,7 @@ pub fn User(account: FoundAccount, credentials: AccountCredentials) -> Element {
                 flex: "0 3 48px",
                 max_height: "46px",
 
-                img {
-                    src: ICON_TRANSPARENT,
-                    margin_right: "24px",
-                    width: "46px",
-                    max_height: "46px",
-                }
+                Avatar { icon: None }
             }
             div {
                 flex: "1 0 auto",
@@ -231,7 +280,166 @@ pub fn User(account: FoundAccount, credentials: AccountCredentials) -> Element {
 
 #[component]
 #[allow(non_snake_case)]
-fn DmMessagesPanel(selected_dm_group: DmGroup, force_refresh_messages: Signal<bool>, credentials: AccountCredentials) -> Element {
+fn ContactPanel(user_id: u64) -> Element {
+    let credentials = use_context::<AccountCredentials>();
+    let mut contact_data = use_signal(|| PacketState::NotStarted);
+    use_future(move || async move {
+        CACHE
+            .user_data(user_id, credentials, &mut contact_data)
+            .await;
+    });
+    let contact_icon = match contact_data() {
+        PacketState::Response(Some(data)) => data.icon,
+        _ => None,
+    };
+    let (title, subtitle) = match contact_data() {
+        PacketState::Response(data) => (
+            display::contact_display_name(data.as_ref(), user_id),
+            data.and_then(|data| data.email)
+                .unwrap_or("Hidden email".to_owned()),
+        ),
+        _ => (format!("[Account {user_id}]"), "Loading...".to_owned()),
+    };
+    rsx! {
+        div {
+            class: "item-panel",
+            onclick: move |_| async move {
+                let nav = navigator();
+                nav.push(Route::OtherUserAccount { user_id });
+            },
+
+            div {
+                margin: "0",
+                flex: "0 3 48px",
+                max_height: "46px",
+
+                Avatar { icon: contact_icon }
+            }
+            div {
+                flex: "1 0 auto",
+
+                h3 {
+                    padding: 0,
+                    margin: 0,
+                    {title}
+                }
+                p {
+                    padding: 0,
+                    margin: 0,
+                    margin_top: "6px",
+                    {subtitle}
+                }
+            }
+        }
+    }
+}
+
+/// Scrolls the messages list to the "unread messages" divider, or to the newest message if all
+/// messages are read.
+async fn scroll_to_first_unread() {
+    _ = document::eval(
+        r#"let divider = document.getElementById("unread-divider");
+        if (divider) {
+            divider.scrollIntoView({ block: "center" });
+        } else {
+            let list = document.getElementById("messages-list");
+            if (list) {
+                list.scrollTop = list.scrollHeight;
+            }
+        }"#,
+    )
+    .await;
+}
+
+#[component]
+#[allow(non_snake_case)]
+fn UnreadDivider() -> Element {
+    rsx! {
+        div {
+            id: "unread-divider",
+            display: "flex",
+            align_items: "center",
+            margin: "12px 0",
+            color: "var(--unread)",
+
+            div { flex_grow: 1, height: "1px", background_color: "var(--unread)" }
+            span { margin: "0 12px", "Unread messages" }
+            div { flex_grow: 1, height: "1px", background_color: "var(--unread)" }
+        }
+    }
+}
+
+/// Reloads the messages when a queued message has been sent, so that it's shown from the server
+/// right after disappearing from the pending ones.
+fn use_refresh_on_sent(outbox: Signal<Outbox>, recipient: Recipient, mut messages: Resource<()>) {
+    let pending_count = use_memo(move || outbox.read().pending_for(recipient).count());
+    let mut last_pending_count = use_signal(|| *pending_count.peek());
+    use_effect(move || {
+        let count = pending_count();
+        if count < *last_pending_count.peek() {
+            messages.restart();
+        }
+        last_pending_count.set(count);
+    });
+}
+
+/// Shows the decrypted text of a message, or why it can't be decrypted in the given color.
+fn decrypted_text(
+    key: Option<&(CryptoAlgorithms, Box<[u8]>)>,
+    encryption_method: &str,
+    ciphertext: &[u8],
+    error_color: &str,
+) -> Element {
+    match decryption::decrypt(key, encryption_method, ciphertext) {
+        Ok(plaintext) => rsx!(MessageText {
+            text: String::from_utf8_lossy(&plaintext).into_owned(),
+        }),
+        Err(err) => rsx!(p { style: "color:var({error_color})", "{err}" }),
+    }
+}
+
+/// Messages queued while the server was offline, shown until they are sent.
+fn pending_messages(
+    outbox: Signal<Outbox>,
+    recipient: Recipient,
+    key: Option<(CryptoAlgorithms, Box<[u8]>)>,
+) -> Element {
+    let queued: Vec<_> = outbox.read().pending_for(recipient).cloned().collect();
+    rsx! {
+        for queued in queued {
+            div {
+                key: "pending-{queued.id}",
+                class: "message msg-me msg-pending",
+
+                if queued.message.encryption_method == PLAINTEXT_METHOD {
+                    MessageText {
+                        text: String::from_utf8_lossy(&queued.message.content).into_owned(),
+                    }
+                } else {
+                    {decrypted_text(key.as_ref(), &queued.message.encryption_method, &queued.message.content, "--error-text")}
+                }
+                div {
+                    class: "msg-info",
+
+                    p {
+                        class: "time-text time-text-me",
+                        "Waiting to be sent…"
+                    }
+                }
+            }
+            br {}
+        }
+    }
+}
+
+#[component]
+#[allow(non_snake_case)]
+fn DmMessagesPanel(
+    selected_dm_group: DmGroup,
+    force_refresh_messages: Signal<bool>,
+    outbox: Signal<Outbox>,
+) -> Element {
+    let credentials = use_context::<AccountCredentials>();
     let mut msg_input: Signal<Option<Rc<MountedData>>> = use_signal(|| None);
     let mut message: Signal<String> = use_signal(String::new);
     let sending_message: Signal<PacketState<u64>> = use_signal(|| PacketState::NotStarted);
@@ -249,23 +457,17 @@ fn DmMessagesPanel(selected_dm_group: DmGroup, force_refresh_messages: Signal<bo
             .await;
     });
     let subtitle = match contact_data() {
-        PacketState::Response(data) => {
-            data.map_or(format!("[Deleted account {contact_id}]"), |data| {
-                data.username.unwrap_or(
-                    data.email
-                        .unwrap_or(format!("[Anonymous user {contact_id}]")),
-                )
-            })
-        }
+        PacketState::Response(data) => display::optional_display_name(data.as_ref(), contact_id),
         _ => format!("[Account {contact_id}]"),
     };
-    // TODO: Store the title in `Storage` and then load it.
-    // let title = format!("[Group {}]", group.id);
-    let title = subtitle.clone();
+    let title = STORAGE
+        .contact_nickname(contact_id)
+        .unwrap_or(subtitle.clone());
 
-    future_retry_loop! { dm_messages_signal, dm_messages_resource, server::fetch_new_dm_messages(selected_dm_group.id, 0, credentials) };
+    future_retry_loop! { dm_messages_signal, dm_messages_resource, client::messages::fetch_new_dm_messages(selected_dm_group.id, 0, None, credentials) };
     use_effect(move || {
-        if let PacketState::Response(mut messages) = dm_messages_signal() {
+        if let PacketState::Response(page) = dm_messages_signal() {
+            let mut messages = page.items;
             messages.reverse();
             cached_messages.set(Some(messages.clone()));
         }
@@ -285,35 +487,81 @@ fn DmMessagesPanel(selected_dm_group: DmGroup, force_refresh_messages: Signal<bo
             dm_messages_resource.restart();
         }
     });
+    use_refresh_on_sent(
+        outbox,
+        Recipient::Dm(selected_dm_group.id),
+        dm_messages_resource,
+    );
+
+    // Loaded once, so the divider stays in place while the messages are being marked as read.
+    let last_read_message = use_resource(move || async move {
+        server::get_last_read_message(selected_dm_group.id, true, credentials).await
+    });
+    let mut scrolled_to_unread = use_signal(|| false);
+    let mut marked_message_id: Signal<Option<u64>> = use_signal(|| None);
+    use_effect(move || {
+        let Some(messages) = cached_messages() else {
+            return;
+        };
+        if last_read_message.read().is_none() {
+            return;
+        }
+        if !scrolled_to_unread() {
+            scrolled_to_unread.set(true);
+            spawn(scroll_to_first_unread());
+        }
+        let Some(last_message_id) = messages.iter().map(|message| message.id).max() else {
+            return;
+        };
+        if marked_message_id().is_some_and(|marked| marked >= last_message_id) {
+            return;
+        }
+        marked_message_id.set(Some(last_message_id));
+        spawn(async move {
+            if let Err(err) =
+                server::mark_messages_read(selected_dm_group.id, true, last_message_id, credentials)
+                    .await
+            {
+                error!("Failed to mark DM messages as read: {err:?}");
+            }
+        });
+    });
+    let unread_index = |messages: &[DmMessage]| {
+        let Some(Ok(last_read_message_id)) = *last_read_message.read() else {
+            return None;
+        };
+        let messages: Vec<(u64, bool)> = messages
+            .iter()
+            .map(|message| (message.id, message.status != MessageStatus::SentByOther))
+            .collect();
+        client::messages::first_unread_index(&messages, last_read_message_id)
+    };
 
     // TODO: Store `last_received_message_id` and received messages in `Storage`.
     let messages = if let Some(messages) = cached_messages() {
+        let unread_index = unread_index(&messages);
         rsx! {
-            for message in messages {
-                DmMessageComponent { contact_id, message, credentials }
+            for (index, message) in messages.into_iter().enumerate() {
+                if unread_index == Some(index) {
+                    UnreadDivider {}
+                }
+                DmMessageComponent { contact_id, message }
             }
         }
     } else {
-        match dm_messages_signal() {
-            PacketState::Response(mut messages) => {
-                messages.reverse();
-                rsx! {
-                    for message in messages {
-                        DmMessageComponent { contact_id, message, credentials }
+        render_packet_state(dm_messages_signal(), |page| {
+            let mut messages = page.items;
+            messages.reverse();
+            let unread_index = unread_index(&messages);
+            rsx! {
+                for (index, message) in messages.into_iter().enumerate() {
+                    if unread_index == Some(index) {
+                        UnreadDivider {}
                     }
+                    DmMessageComponent { contact_id, message }
                 }
             }
-            PacketState::Waiting => {
-                rsx!(h1 { "Loading messages..." })
-            }
-            PacketState::ServerError(err) => {
-                rsx!(h1 { "Server error: {err}" })
-            }
-            PacketState::RequestTimeout => {
-                rsx!(h1 { "Request timeout" })
-            }
-            PacketState::NotStarted => unreachable!(),
-        }
+        })
     };
     let sending_messages = match sending_message() {
         PacketState::Response(_) | PacketState::NotStarted => {
@@ -346,7 +594,7 @@ fn DmMessagesPanel(selected_dm_group: DmGroup, force_refresh_messages: Signal<bo
                 padding: "16px",
                 onclick: move |_| async move {
                     let nav = navigator();
-                    nav.push(Route::OtherUserAccount { user_id: contact_id, credentials });
+                    nav.push(Route::OtherUserAccount { user_id: contact_id });
                 },
 
                 h1 {
@@ -358,26 +606,28 @@ fn DmMessagesPanel(selected_dm_group: DmGroup, force_refresh_messages: Signal<bo
                 }
             }
             div {
+                class: "separator",
                 width: "100%",
                 height: "1px",
-                background_image: "linear-gradient(#2b2b2b00, #2b2b2bff, #2b2b2b00)",
 
                 br {}
             }
             div {
                 width: "100%",
                 max_width: "calc(100% - 32px)",
+                id: "messages-list",
                 flex_grow: 1,
                 overflow: "auto",
                 padding: "16px",
 
                 {messages}
+                {pending_messages(outbox, Recipient::Dm(selected_dm_group.id), STORAGE.load_dm_key(contact_id))}
                 {sending_messages}
             }
             div {
+                class: "separator",
                 width: "100%",
                 height: "1px",
-                background_image: "linear-gradient(#2b2b2b00, #2b2b2bff, #2b2b2b00)",
 
                 br {}
             }
@@ -386,7 +636,7 @@ fn DmMessagesPanel(selected_dm_group: DmGroup, force_refresh_messages: Signal<bo
                 max_width: "calc(100% - 32px)",
                 height: "auto",
                 padding: "16px",
-                background_color: "#121519",
+                background_color: "var(--input)",
                 onclick: move |_| async move {
                     let Some(msg_input) = msg_input() else {
                         return;
@@ -402,7 +652,7 @@ fn DmMessagesPanel(selected_dm_group: DmGroup, force_refresh_messages: Signal<bo
                     value: "{message}",
                     onmounted: move |cx| msg_input.set(Some(cx.data())),
                     oninput: move |event| async move {
-                        message.set(event.value());
+                        message.set(emoji::expand_shortcodes(&event.value()));
                         document::eval(r#"let input = document.getElementById("main-msg-input");
                             let height = input.scrollHeight;
                             if (height > 300) {
@@ -429,19 +679,23 @@ fn DmMessagesPanel(selected_dm_group: DmGroup, force_refresh_messages: Signal<bo
                                 algorithm_name.encryption_method(),
                             )
                         } else {
-                            eprintln!("Failed to load encryption data for DM group {selected_dm_group:?}");
-                            (Box::from(content.clone().as_bytes()), "plain".to_owned())
+                            notify_error("The encryption key of this conversation is missing, so the message is sent unencrypted");
+                            (Box::from(content.clone().as_bytes()), PLAINTEXT_METHOD.to_owned())
                         };
-                        println!("Send result: {:?}", server::send_dm_message(
-                            selected_dm_group.id,
+                        let outgoing = OutgoingMessage::new(
+                            Recipient::Dm(selected_dm_group.id),
                             encryption_method,
                             msg_bytes,
-                            credentials,
-                        ).await);
+                            MessageReferences::default(),
+                        );
+                        if let Err(err) = outbox::send_or_enqueue(outbox, outgoing, credentials).await {
+                            notify_error(format!("Failed to send the message: {err}"));
+                            return;
+                        }
                         // PacketSender::default()
                         //     .retry_loop(move || server::send_dm_message(
                         //         selected_dm_group.id,
-                        //         "plain".to_owned(),
+                        //         PLAINTEXT_METHOD.to_owned(),
                         //         msg_bytes.clone(),
                         //         credentials,
                         //     ), &mut sending_message).await;
@@ -469,19 +723,23 @@ fn DmMessagesPanel(selected_dm_group: DmGroup, force_refresh_messages: Signal<bo
                                 algorithm_name.encryption_method(),
                             )
                         } else {
-                            (Box::from(file.file_name().as_bytes()), file.read().await.into_boxed_slice(), "plain".to_owned())
+                            (Box::from(file.file_name().as_bytes()), file.read().await.into_boxed_slice(), PLAINTEXT_METHOD.to_owned())
                         };
-                        println!("Send file result: {:?}", server::send_dm_file(
-                            selected_dm_group.id,
-                            encryption_method,
-                            encrypted_file_name,
-                            encrypted_content,
-                            credentials,
-                        ).await);
+                        // Errors are shown by the sender.
+                        PacketSender::for_endpoint("send_dm_file")
+                            .retry(server::send_dm_file(
+                                selected_dm_group.id,
+                                encryption_method,
+                                encrypted_file_name,
+                                encrypted_content,
+                                credentials,
+                            ))
+                            .await;
                         dm_messages_resource.restart();
                     },
                     "F"
                 }
+                EmojiPicker { message }
             }
         }
     }
@@ -489,15 +747,21 @@ fn DmMessagesPanel(selected_dm_group: DmGroup, force_refresh_messages: Signal<bo
 
 #[component]
 #[allow(non_snake_case)]
-fn GroupMessagesPanel(selected_group: MultiUserGroup, force_refresh_messages: Signal<bool>, credentials: AccountCredentials) -> Element {
+fn GroupMessagesPanel(
+    selected_group: MultiUserGroup,
+    force_refresh_messages: Signal<bool>,
+    outbox: Signal<Outbox>,
+) -> Element {
+    let credentials = use_context::<AccountCredentials>();
     let mut msg_input: Signal<Option<Rc<MountedData>>> = use_signal(|| None);
     let mut message: Signal<String> = use_signal(String::new);
     let sending_message: Signal<PacketState<u64>> = use_signal(|| PacketState::NotStarted);
     let mut cached_messages: Signal<Option<Vec<GroupMessage>>> = use_signal(|| None);
 
-    future_retry_loop! { group_messages_signal, group_messages_resource, server::fetch_new_group_messages(selected_group.id, 0, credentials) };
+    future_retry_loop! { group_messages_signal, group_messages_resource, client::messages::fetch_new_group_messages(selected_group.id, 0, None, credentials) };
     use_effect(move || {
-        if let PacketState::Response(mut messages) = group_messages_signal() {
+        if let PacketState::Response(page) = group_messages_signal() {
+            let mut messages = page.items;
             messages.reverse();
             cached_messages.set(Some(messages));
         }
@@ -517,35 +781,81 @@ fn GroupMessagesPanel(selected_group: MultiUserGroup, force_refresh_messages: Si
             group_messages_resource.restart();
         }
     });
+    use_refresh_on_sent(
+        outbox,
+        Recipient::Group(selected_group.id),
+        group_messages_resource,
+    );
+
+    // Loaded once, so the divider stays in place while the messages are being marked as read.
+    let last_read_message = use_resource(move || async move {
+        server::get_last_read_message(selected_group.id, false, credentials).await
+    });
+    let mut scrolled_to_unread = use_signal(|| false);
+    let mut marked_message_id: Signal<Option<u64>> = use_signal(|| None);
+    use_effect(move || {
+        let Some(messages) = cached_messages() else {
+            return;
+        };
+        if last_read_message.read().is_none() {
+            return;
+        }
+        if !scrolled_to_unread() {
+            scrolled_to_unread.set(true);
+            spawn(scroll_to_first_unread());
+        }
+        let Some(last_message_id) = messages.iter().map(|message| message.id).max() else {
+            return;
+        };
+        if marked_message_id().is_some_and(|marked| marked >= last_message_id) {
+            return;
+        }
+        marked_message_id.set(Some(last_message_id));
+        spawn(async move {
+            if let Err(err) =
+                server::mark_messages_read(selected_group.id, false, last_message_id, credentials)
+                    .await
+            {
+                error!("Failed to mark group messages as read: {err:?}");
+            }
+        });
+    });
+    let unread_index = |messages: &[GroupMessage]| {
+        let Some(Ok(last_read_message_id)) = *last_read_message.read() else {
+            return None;
+        };
+        let messages: Vec<(u64, bool)> = messages
+            .iter()
+            .map(|message| (message.id, message.sender_id == credentials.id))
+            .collect();
+        client::messages::first_unread_index(&messages, last_read_message_id)
+    };
 
     // TODO: Store `last_received_message_id` and received messages in `Storage`.
     let messages = if let Some(messages) = cached_messages() {
+        let unread_index = unread_index(&messages);
         rsx! {
-            for message in messages {
-                GroupMessageComponent { message, self_id: credentials.id, credentials, group_id: selected_group.id }
+            for (index, message) in messages.into_iter().enumerate() {
+                if unread_index == Some(index) {
+                    UnreadDivider {}
+                }
+                GroupMessageComponent { message, self_id: credentials.id, group_id: selected_group.id }
             }
         }
     } else {
-        match group_messages_signal() {
-            PacketState::Response(mut messages) => {
-                messages.reverse();
-                rsx! {
-                    for message in messages {
-                        GroupMessageComponent { message, self_id: credentials.id, credentials, group_id: selected_group.id }
+        render_packet_state(group_messages_signal(), |page| {
+            let mut messages = page.items;
+            messages.reverse();
+            let unread_index = unread_index(&messages);
+            rsx! {
+                for (index, message) in messages.into_iter().enumerate() {
+                    if unread_index == Some(index) {
+                        UnreadDivider {}
                     }
+                    GroupMessageComponent { message, self_id: credentials.id, group_id: selected_group.id }
                 }
             }
-            PacketState::Waiting => {
-                rsx!(h1 { "Loading messages..." })
-            }
-            PacketState::ServerError(err) => {
-                rsx!(h1 { "Server error: {err}" })
-            }
-            PacketState::RequestTimeout => {
-                rsx!(h1 { "Request timeout" })
-            }
-            PacketState::NotStarted => unreachable!(),
-        }
+        })
     };
     let sending_messages = match sending_message() {
         PacketState::Response(_) | PacketState::NotStarted => {
@@ -578,7 +888,7 @@ fn GroupMessagesPanel(selected_group: MultiUserGroup, force_refresh_messages: Si
                 padding: "16px",
                 onclick: move |_| async move {
                     let nav = navigator();
-                    nav.push(Route::GroupMenu { group_id: selected_group.id, credentials });
+                    nav.push(Route::GroupMenu { group_id: selected_group.id });
                 },
 
                 h1 {
@@ -586,19 +896,20 @@ fn GroupMessagesPanel(selected_group: MultiUserGroup, force_refresh_messages: Si
                     margin_bottom: 0,
                     margin_left: "16px",
 
-                    {selected_group.name}
+                    {STORAGE.group_nickname(selected_group.id).unwrap_or(selected_group.name)}
                 }
             }
             div {
+                class: "separator",
                 width: "100%",
                 height: "1px",
-                background_image: "linear-gradient(#2b2b2b00, #2b2b2bff, #2b2b2b00)",
 
                 br {}
             }
             div {
                 width: "100%",
                 max_width: "calc(100% - 32px)",
+                id: "messages-list",
                 flex_grow: 1,
                 overflow: "auto",
                 padding: "16px",
@@ -608,12 +919,13 @@ fn GroupMessagesPanel(selected_group: MultiUserGroup, force_refresh_messages: Si
                 //     h4 { {format!("Message {i}!")} }
                 // }
                 {messages}
+                {pending_messages(outbox, Recipient::Group(selected_group.id), STORAGE.load_group_key(selected_group.id))}
                 {sending_messages}
             }
             div {
+                class: "separator",
                 width: "100%",
                 height: "1px",
-                background_image: "linear-gradient(#2b2b2b00, #2b2b2bff, #2b2b2b00)",
 
                 br {}
             }
@@ -623,7 +935,7 @@ fn GroupMessagesPanel(selected_group: MultiUserGroup, force_refresh_messages: Si
                 height: "auto",
                 // height: "34px",
                 padding: "16px",
-                background_color: "#121519",
+                background_color: "var(--input)",
                 onclick: move |_| async move {
                     let Some(msg_input) = msg_input() else {
                         return;
@@ -638,7 +950,7 @@ fn GroupMessagesPanel(selected_group: MultiUserGroup, force_refresh_messages: Si
                     value: "{message}",
                     onmounted: move |cx| msg_input.set(Some(cx.data())),
                     oninput: move |event| async move {
-                        message.set(event.value());
+                        message.set(emoji::expand_shortcodes(&event.value()));
                         document::eval(r#"let input = document.getElementById("main-msg-input");
                             let height = input.scrollHeight;
                             if (height > 300) {
@@ -659,22 +971,27 @@ fn GroupMessagesPanel(selected_group: MultiUserGroup, force_refresh_messages: Si
                                 algorithm_name.encryption_method(),
                             )
                         } else {
-                            eprintln!("Failed to load encryption data for group {}", selected_group.id);
-                            (Box::from(content.clone().as_bytes()), "plain".to_owned())
+                            notify_error("The encryption key of this group is missing, so the message is sent unencrypted");
+                            (Box::from(content.clone().as_bytes()), PLAINTEXT_METHOD.to_owned())
                         };
-                        println!("Send result: {:?}", server::send_group_message(
-                            selected_group.id,
+                        let outgoing = OutgoingMessage::new(
+                            Recipient::Group(selected_group.id),
                             encryption_method,
                             msg_bytes,
-                            credentials,
-                        ).await);
-                        println!("Sending group message: {content:?}");
+                            MessageReferences::default(),
+                        );
+                        if let Err(err) = outbox::send_or_enqueue(outbox, outgoing, credentials).await {
+                            notify_error(format!("Failed to send the message: {err}"));
+                            return;
+                        }
                         message.set(String::new());
                         group_messages_resource.restart();
                         document::eval(r#"let input = document.getElementById("main-msg-input");
                             input.style = "height: 36px";"#).await.unwrap();
                     }
                 }
+
+                EmojiPicker { message }
             }
         }
     }
@@ -688,18 +1005,8 @@ pub fn DmGroupPanel(
     selected_dm_group: Signal<Option<DmGroup>>,
     selected_group: Signal<Option<MultiUserGroup>>,
     force_refresh_messages: Signal<bool>,
-    credentials: AccountCredentials,
 ) -> Element {
-    const ICON_TRANSPARENT: Asset = asset!(
-        "/assets/icon_transparent.png",
-        ImageAssetOptions::new()
-            .with_size(ImageSize::Manual {
-                width: 97,
-                height: 111,
-            })
-            .with_format(ImageFormat::Avif)
-    );
-
+    let credentials = use_context::<AccountCredentials>();
     let mut contact_data = use_signal(|| PacketState::NotStarted);
     let contact_id = if group.initiator_id == user_id {
         group.other_id
@@ -711,20 +1018,17 @@ pub fn DmGroupPanel(
             .user_data(contact_id, credentials, &mut contact_data)
             .await;
     });
+    let contact_icon = match contact_data() {
+        PacketState::Response(Some(data)) => data.icon,
+        _ => None,
+    };
     let subtitle = match contact_data() {
-        PacketState::Response(data) => {
-            data.map_or(format!("[Deleted account {contact_id}]"), |data| {
-                data.username.unwrap_or(
-                    data.email
-                        .unwrap_or(format!("[Anonymous user {contact_id}]")),
-                )
-            })
-        }
+        PacketState::Response(data) => display::optional_display_name(data.as_ref(), contact_id),
         _ => format!("[Account {contact_id}]"),
     };
-    // TODO: Store the title in `Storage` and then load it.
-    // let title = format!("[Group {}]", group.id);
-    let title = subtitle.clone();
+    let title = STORAGE
+        .contact_nickname(contact_id)
+        .unwrap_or(subtitle.clone());
     rsx! {
         div {
             class: "item-panel",
@@ -739,12 +1043,7 @@ pub fn DmGroupPanel(
                 flex: "0 3 48px",
                 max_height: "46px",
 
-                img {
-                    src: ICON_TRANSPARENT,
-                    margin_right: "24px",
-                    width: "46px",
-                    max_height: "46px",
-                }
+                Avatar { icon: contact_icon }
             }
             div {
                 flex: "1 0 auto",
@@ -767,11 +1066,8 @@ pub fn DmGroupPanel(
 
 #[component]
 #[allow(non_snake_case)]
-fn DmMessageComponent(
-    contact_id: u64,
-    message: DmMessage,
-    credentials: AccountCredentials,
-) -> Element {
+fn DmMessageComponent(contact_id: u64, message: DmMessage) -> Element {
+    let credentials = use_context::<AccountCredentials>();
     const ICON_MSG_STATUS_SENT: Asset = asset!(
         "/assets/msg_status_sent_icon.png",
         ImageAssetOptions::new()
@@ -790,26 +1086,24 @@ fn DmMessageComponent(
             })
             .with_format(ImageFormat::Avif)
     );
-    let message_content = if message.encryption_method != "plain" {
+    let message_content = if message.encryption_method != PLAINTEXT_METHOD {
         if let Some(key) = STORAGE.load_dm_key(contact_id) {
             if let Some(file_name) = message.file_name {
-                match crypto::symmetric_decrypt(&key.0, &file_name, &key.1) {
-                    Some(Some(file_name)) => {
+                match decryption::decrypt(Some(&key), &message.encryption_method, &file_name) {
+                    Ok(file_name) => {
                         let file_name = String::from_utf8_lossy(&file_name);
                         rsx!(button {
                             onclick: move |_| {
                                 let key = key.clone();
                                 async move {
-                                    let file_data = match server::get_dm_file(message.id, credentials).await {
-                                        Ok(data) => data,
-                                        Err(err) => {
-                                            println!("Failed to get file from server: {err}");
-                                            return;
-                                        },
+                                    let PacketState::Response(file_data) = PacketSender::for_endpoint("get_dm_file")
+                                        .retry(server::get_dm_file(message.id, credentials))
+                                        .await
+                                    else {
+                                        return;
                                     };
-                                    // TODO: Use `file_data.encryption_method` instead of `key.0`.
-                                    match crypto::symmetric_decrypt(&key.0, &file_data.content, &key.1) {
-                                        Some(Some(content)) => {
+                                    match decryption::decrypt(Some(&key), &file_data.encryption_method, &file_data.content) {
+                                        Ok(content) => {
                                             let Some(file) = AsyncFileDialog::new()
                                                 .save_file()
                                                 .await
@@ -818,8 +1112,9 @@ fn DmMessageComponent(
                                             };
                                             file.write(&content).await.unwrap();
                                         }
-                                        status => {
-                                            println!("File content decryption failed: {status:?}");
+                                        Err(err) => {
+                                            error!("File content decryption failed: {err:?}");
+                                            notify_error(format!("Failed to decrypt the file: {err}"));
                                         }
                                     }
                                 }
@@ -827,37 +1122,36 @@ fn DmMessageComponent(
                             {file_name}
                         })
                     }
-                    status => {
-                        println!("Decryption failed: {status:?}");
-                        rsx!(p { style: "color:#faa", "Failed to decrypt message" })
+                    Err(err) => {
+                        error!("Decryption failed: {err:?}");
+                        rsx!(p { style: "color:var(--error-text)", "{err}" })
                     }
                 }
             } else {
-                match crypto::symmetric_decrypt(&key.0, &message.content.unwrap(), &key.1) {
-                    Some(Some(plaintext)) => {
-                        let plain_string = String::from_utf8_lossy(&plaintext);
-                        rsx!(Markdown { src: plain_string })
-                    }
-                    status => {
-                        println!("Decryption failed: {status:?}");
-                        rsx!(p { style: "color:#faa", "Failed to decrypt message" })
-                    }
-                }
+                decrypted_text(
+                    Some(&key),
+                    &message.encryption_method,
+                    &message.content.unwrap(),
+                    "--error-text",
+                )
             }
         } else {
-            rsx!(p { style: "color:#faa", "Failed to decrypt message" })
+            let err = match CryptoAlgorithms::from_encryption_method(&message.encryption_method) {
+                Some(_) => DecryptionError::MissingKey,
+                None => DecryptionError::UnsupportedAlgorithm,
+            };
+            rsx!(p { style: "color:var(--error-text)", "{err}" })
         }
     } else if let Some(file_name) = message.file_name {
         let file_name = String::from_utf8_lossy(&file_name);
         rsx!(button {
             onclick: move |_| {
                 async move {
-                    let file_data = match server::get_dm_file(message.id, credentials).await {
-                        Ok(data) => data,
-                        Err(err) => {
-                            println!("Failed to get file from server: {err}");
-                            return;
-                        },
+                    let PacketState::Response(file_data) = PacketSender::for_endpoint("get_dm_file")
+                        .retry(server::get_dm_file(message.id, credentials))
+                        .await
+                    else {
+                        return;
                     };
                     let Some(file) = AsyncFileDialog::new()
                             .save_file()
@@ -871,8 +1165,8 @@ fn DmMessageComponent(
             {file_name}
         })
     } else {
-        let plain_string = String::from_utf8_lossy(message.content.as_ref().unwrap());
-        rsx!(Markdown { src: plain_string })
+        let text = String::from_utf8_lossy(message.content.as_ref().unwrap()).into_owned();
+        rsx!(MessageText { text })
     };
     let sent_by_me = message.status != MessageStatus::SentByOther;
     let time = if let Some(time) = message.sent_time {
@@ -932,20 +1226,10 @@ pub fn GroupPanel(
     selected_dm_group: Signal<Option<DmGroup>>,
     selected_group: Signal<Option<MultiUserGroup>>,
     force_refresh_messages: Signal<bool>,
-    credentials: AccountCredentials,
 ) -> Element {
-    const ICON_TRANSPARENT: Asset = asset!(
-        "/assets/icon_transparent.png",
-        ImageAssetOptions::new()
-            .with_size(ImageSize::Manual {
-                width: 97,
-                height: 111,
-            })
-            .with_format(ImageFormat::Avif)
-    );
-
-    // TODO: Store the title in `Storage` and then load it (if overriden).
-    let title = group.name.clone();
+    let credentials = use_context::<AccountCredentials>();
+    let icon = group.icon.clone();
+    let title = STORAGE.group_nickname(group.id).unwrap_or(group.name.clone());
     let members_data = future_retry_loop!(server::get_group_member_count(group.id, credentials));
     let subtitle = match members_data {
         PacketState::Response(members) => {
@@ -974,12 +1258,7 @@ pub fn GroupPanel(
                 flex: "0 3 48px",
                 max_height: "46px",
 
-                img {
-                    src: ICON_TRANSPARENT,
-                    margin_right: "24px",
-                    width: "46px",
-                    max_height: "46px",
-                }
+                Avatar { icon }
             }
             div {
                 flex: "1 0 auto",
@@ -1002,12 +1281,8 @@ pub fn GroupPanel(
 
 #[component]
 #[allow(non_snake_case)]
-fn GroupMessageComponent(
-    message: GroupMessage,
-    self_id: u64,
-    credentials: AccountCredentials,
-    group_id: u64,
-) -> Element {
+fn GroupMessageComponent(message: GroupMessage, self_id: u64, group_id: u64) -> Element {
+    let credentials = use_context::<AccountCredentials>();
     let mut author_data = use_signal(|| PacketState::NotStarted);
     let author_id = message.sender_id;
     use_future(move || async move {
@@ -1021,7 +1296,7 @@ fn GroupMessageComponent(
                 h3 {
                     margin_top: "12px",
                     margin_bottom: "4px",
-                    {data.map_or(format!("[Deleted account {author_id}]"), |data| data.username.unwrap_or(data.email.unwrap_or(format!("[Anonymous user {author_id}]"))))}
+                    {display::contact_display_name(data.as_ref(), author_id)}
                 }
             }
         }
@@ -1040,23 +1315,16 @@ fn GroupMessageComponent(
     } else {
         "??:??".to_owned()
     };
-    let message_content = if message.encryption_method != "plain" {
-        if let Some(key) = STORAGE.load_group_key(group_id) {
-            if let Some(Some(plaintext)) =
-                crypto::symmetric_decrypt(&key.0, &message.content.unwrap(), &key.1)
-            {
-                rsx!(Markdown {
-                    src: String::from_utf8_lossy(&plaintext)
-                })
-            } else {
-                rsx!(p { style: "color:#f00", "Failed to decrypt message" })
-            }
-        } else {
-            rsx!(p { style: "color:#f00", "Failed to decrypt message" })
-        }
+    let message_content = if message.encryption_method != PLAINTEXT_METHOD {
+        decrypted_text(
+            STORAGE.load_group_key(group_id).as_ref(),
+            &message.encryption_method,
+            &message.content.unwrap(),
+            "--strong-error-text",
+        )
     } else {
-        rsx!(Markdown {
-            src: String::from_utf8_lossy(message.content.as_ref().unwrap())
+        rsx!(MessageText {
+            text: String::from_utf8_lossy(message.content.as_ref().unwrap()).into_owned(),
         })
     };
     rsx! {
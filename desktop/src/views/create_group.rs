@@ -1,8 +1,10 @@
-use dioxus::prelude::*;
+use client::notifications::notify_error;
+use dioxus::{logger::tracing::info, prelude::*};
 use server::AccountCredentials;
 
 #[component]
-pub fn CreateGroup(credentials: AccountCredentials) -> Element {
+pub fn CreateGroup() -> Element {
+    let credentials = use_context::<AccountCredentials>();
     let mut group_name = use_signal(String::new);
     let mut encrypted = use_signal(|| true);
     let mut public = use_signal(|| false);
@@ -46,13 +48,13 @@ pub fn CreateGroup(credentials: AccountCredentials) -> Element {
             }
             button {
                 onclick: move |_| async move {
-                    println!("Creating a group with name {group_name:?}");
                     match server::create_group(group_name(), None, encrypted(), public(), channel(), credentials).await {
                         Ok(group_id) => {
-                            println!("Created a new group with id {group_id}");
+                            info!("Created a new group with id {group_id}");
                         }
                         Err(err) => {
-                            eprintln!("Error while trying to create a new group: {err:?}");
+                            notify_error(format!("Failed to create the group: {err}"));
+                            return;
                         }
                     };
                     let nav = navigator();
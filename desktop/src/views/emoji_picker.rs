@@ -0,0 +1,77 @@
+use client::{emoji, storage::STORAGE};
+use dioxus::prelude::*;
+
+#[component]
+#[allow(non_snake_case)]
+fn EmojiOption(
+    emoji: String,
+    title: String,
+    message: Signal<String>,
+    recents: Signal<Vec<String>>,
+) -> Element {
+    rsx! {
+        button {
+            class: "emoji-option",
+            title: "{title}",
+            onclick: move |event| {
+                event.stop_propagation();
+                message.write().push_str(&emoji);
+                recents.set(STORAGE.add_recent_emoji(&emoji));
+            },
+            "{emoji}"
+        }
+    }
+}
+
+/// Button opening a list of emojis, the recently picked ones first, which appends the chosen
+/// emoji to the message.
+#[component]
+#[allow(non_snake_case)]
+pub fn EmojiPicker(message: Signal<String>) -> Element {
+    let mut opened = use_signal(|| false);
+    let recents = use_signal(|| STORAGE.recent_emojis());
+
+    rsx! {
+        div {
+            position: "relative",
+
+            button {
+                width: "29px",
+                height: "29px",
+                title: "Emoji",
+                onclick: move |event| {
+                    event.stop_propagation();
+                    opened.toggle();
+                },
+                "☺"
+            }
+            if opened() {
+                div {
+                    class: "panel emoji-picker",
+                    onclick: move |event| event.stop_propagation(),
+
+                    if !recents().is_empty() {
+                        p { margin: "0 0 4px 0", "Recent" }
+                        for recent in recents() {
+                            EmojiOption {
+                                emoji: recent.clone(),
+                                title: recent,
+                                message,
+                                recents,
+                            }
+                        }
+                        p { margin: "8px 0 4px 0", "All" }
+                    }
+                    for (code, emoji) in emoji::EMOJIS {
+                        EmojiOption {
+                            emoji: emoji.to_string(),
+                            title: format!(":{code}:"),
+                            message,
+                            recents,
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,40 +1,45 @@
+use std::rc::Rc;
+
 use client::{
     cache::CACHE,
-    future_retry_loop,
-    packet_sender::{PacketSender, PacketState},
+    display, future_retry_loop,
+    links::DeepLink,
+    notifications::notify_error,
+    packet_sender::{PacketSender, PacketState, render_packet_state},
+    storage::STORAGE,
 };
 use dioxus::prelude::*;
 
 use server::{AccountCredentials, GroupMember, MultiUserGroup, UserAccount};
+use shared::types::GroupPermissions;
+
+use super::avatar::Avatar;
+use crate::clipboard::copy_text;
+
+/// Number of members loaded at once.
+const MEMBERS_PAGE_SIZE: u32 = 50;
+/// Distance in pixels from the end of the member list at which the next page starts loading.
+const MEMBERS_LOAD_DISTANCE: f64 = 200.0;
 
 #[component]
 fn User(
     account: UserAccount,
     is_admin: bool,
+    permissions: GroupPermissions,
     self_is_admin: bool,
     group_id: u64,
     user_id: u64,
-    credentials: AccountCredentials,
 ) -> Element {
-    const ICON_TRANSPARENT: Asset = asset!(
-        "/assets/icon_transparent.png",
-        ImageAssetOptions::new()
-            .with_size(ImageSize::Manual {
-                width: 97,
-                height: 111,
-            })
-            .with_format(ImageFormat::Avif)
-    );
-
+    let credentials = use_context::<AccountCredentials>();
     let mut action_result = use_signal(|| PacketState::NotStarted);
 
-    let mut title = account
-        .username
-        .unwrap_or(account.email.clone().unwrap_or("Anonymous".to_owned()));
+    let icon = account.icon.clone();
+    let mut title = display::contact_display_name(Some(&account), user_id);
     if is_admin {
         title += " [Administrator]";
     }
     let email = account.email.unwrap_or("Hidden email".to_owned());
+    let role = display::member_role(&permissions);
     let action_result_rsx = match action_result() {
         PacketState::Response(()) | PacketState::NotStarted => rsx!(),
         PacketState::Waiting => rsx!("Waiting..."),
@@ -50,12 +55,7 @@ fn User(
                 flex: "0 3 48px",
                 max_height: "46px",
 
-                img {
-                    src: ICON_TRANSPARENT,
-                    margin_right: "24px",
-                    width: "46px",
-                    max_height: "46px",
-                }
+                Avatar { icon }
             }
             div {
                 flex: "1 0 auto",
@@ -71,6 +71,12 @@ fn User(
                     margin_top: "6px",
                     {email}
                 }
+                p {
+                    padding: 0,
+                    margin: 0,
+                    margin_top: "6px",
+                    {role}
+                }
             }
             if self_is_admin {
                 if action_result() == PacketState::NotStarted {
@@ -128,7 +134,6 @@ pub fn Member(
     group_id: u64,
     group_member: GroupMember,
     self_is_admin: bool,
-    credentials: AccountCredentials,
 ) -> Element {
     match member {
         PacketState::Response(Some(user)) => {
@@ -138,10 +143,10 @@ pub fn Member(
                     key: group_member.user_id,
                     account: user,
                     is_admin: group_member.is_admin,
+                    permissions: group_member.permissions.clone(),
                     self_is_admin,
                     group_id,
                     user_id: group_member.user_id,
-                    credentials,
                 }
                 // button {
                 //     key: group.id,
@@ -168,67 +173,127 @@ pub fn Member(
 }
 
 #[component]
-pub fn GroupMenu(group_id: u64, credentials: AccountCredentials) -> Element {
+pub fn GroupMenu(group_id: u64) -> Element {
+    let credentials = use_context::<AccountCredentials>();
     let group_data = future_retry_loop!(server::get_group_data(group_id, credentials));
-    let group_info = match group_data {
-        PacketState::Response(info) => match info {
-            Some(info) => {
-                let _: MultiUserGroup = info;
-                rsx! {
-                    h3 { margin: 0, "Group name: {info.name}" },
-                    h3 { margin: 0, if info.encrypted { "Encrypted" } else { "Not encrypted" } },
-                    h3 { margin: 0, if info.public { "Public" } else { "Private" } },
-                    h3 { margin: 0, if info.channel { "Channel" } else { "Not a channel" } },
-                }
+    let group_info = render_packet_state(group_data, |info| match info {
+        Some(info) => {
+            let _: MultiUserGroup = info;
+            rsx! {
+                h3 { margin: 0, "Group name: {info.name}" },
+                h3 { margin: 0, if info.encrypted { "Encrypted" } else { "Not encrypted" } },
+                h3 { margin: 0, if info.public { "Public" } else { "Private" } },
+                h3 { margin: 0, if info.channel { "Channel" } else { "Not a channel" } },
+            }
+        }
+        None => rsx!("Removed group"),
+    });
+    let mut members: Signal<Vec<GroupMember>> = use_signal(Vec::new);
+    let mut members_data: Signal<Vec<PacketState<Option<UserAccount>>>> = use_signal(Vec::new);
+    // `Response` tells whether there are more members to load.
+    let mut members_page: Signal<PacketState<bool>> = use_signal(|| PacketState::NotStarted);
+    let mut members_list: Signal<Option<Rc<MountedData>>> = use_signal(|| None);
+    let mut member_task: Signal<Option<Task>> = use_signal(|| None);
+    let load_members = move |group_id: u64| async move {
+        let before_user_id = members.peek().last().map(|member| member.user_id);
+        let page = match server::get_group_members(
+            group_id,
+            before_user_id,
+            Some(MEMBERS_PAGE_SIZE),
+            credentials,
+        )
+        .await
+        {
+            Ok(page) => page,
+            Err(err) => {
+                members_page.set(PacketState::ServerError(err));
+                return;
+            }
+        };
+        let start = members.peek().len();
+        members_page.set(PacketState::Response(page.has_more));
+        members_data
+            .write()
+            .extend(vec![PacketState::NotStarted; page.items.len()]);
+        members.write().extend(page.items.iter().cloned());
+        for (i, member) in page.items.iter().enumerate() {
+            CACHE
+                .user_data_vec(member.user_id, credentials, &mut members_data, start + i)
+                .await;
+        }
+    };
+    // Dioxus cancels the tasks when the menu is closed. The menu of another group reuses this
+    // component though, so loading for the previous group has to be stopped by hand, or it would
+    // keep writing its members into the new list. Only one page is loaded at a time, so there is
+    // at most one task to stop.
+    let mut start_loading = move |group_id: u64| {
+        if matches!(
+            *members_page.peek(),
+            PacketState::Waiting | PacketState::Response(false)
+        ) {
+            return;
+        }
+        members_page.set(PacketState::Waiting);
+        member_task.set(Some(spawn(load_members(group_id))));
+    };
+    use_effect(use_reactive!(|group_id| {
+        if let Some(task) = member_task.write().take() {
+            task.cancel();
+        }
+        members.set(vec![]);
+        members_data.set(vec![]);
+        members_page.set(PacketState::NotStarted);
+        start_loading(group_id);
+    }));
+    let self_permissions =
+        future_retry_loop!(server::get_own_group_permissions(group_id, credentials));
+    let self_is_admin = match self_permissions {
+        PacketState::Response(ref permissions) => permissions.is_admin(),
+        _ => false,
+    };
+    let members_status = match members_page() {
+        PacketState::Response(true) => rsx! {
+            button {
+                onclick: move |_| start_loading(group_id),
+                "Load more members"
             }
-            None => rsx!("Removed group"),
         },
-        PacketState::Waiting => rsx!("Loading group information..."),
+        PacketState::Response(false) | PacketState::NotStarted => rsx!(),
+        PacketState::Waiting => rsx!("Loading members..."),
         PacketState::ServerError(err) => rsx!("Server error: {err:?}"),
         PacketState::RequestTimeout => rsx!("Request timeout"),
-        PacketState::NotStarted => unreachable!(),
     };
-    let mut cached_members = use_signal(Vec::new);
-    let mut cached_members_data = use_signal(Vec::new);
-    let group_members = future_retry_loop!(server::get_group_members(group_id, credentials));
-    let group_members_element = match group_members {
-        PacketState::Response(members) => {
-            use_effect(move || {
-                cached_members.set(members.clone());
-                cached_members_data.set(vec![PacketState::NotStarted; members.len()]);
-            });
-            let data = cached_members_data();
-            if data.len() == cached_members().len() && !data.is_empty() {
-                use_future(move || async move {
-                    for (i, member) in cached_members().iter().enumerate() {
-                        println!("LOADING MEMBER {i}");
-                        CACHE
-                            .user_data_vec(member.user_id, credentials, &mut cached_members_data, i)
-                            .await;
-                        println!("RESULT: {:?}", cached_members_data()[i]);
-                    }
-                });
-
-                let mut self_is_admin: bool = false;
-                for member in cached_members() {
-                    if member.user_id == credentials.id {
-                        self_is_admin = member.is_admin;
-                    }
+    let group_members_element = rsx! {
+        div {
+            max_height: "60vh",
+            overflow_y: "auto",
+            onmounted: move |cx| members_list.set(Some(cx.data())),
+            onscroll: move |_| async move {
+                let Some(list) = members_list() else {
+                    return;
+                };
+                let (Ok(offset), Ok(size), Ok(rect)) = (
+                    list.get_scroll_offset().await,
+                    list.get_scroll_size().await,
+                    list.get_client_rect().await,
+                ) else {
+                    return;
+                };
+                if offset.y + rect.height() >= size.height - MEMBERS_LOAD_DISTANCE {
+                    start_loading(group_id);
                 }
+            },
 
-                rsx! {
-                    for (i, member) in data.iter().enumerate() {
-                        Member { member: member.clone(), group_id, group_member: cached_members()[i].clone(), self_is_admin, credentials }
-                    }
+            for (i, member) in members_data().into_iter().enumerate() {
+                Member {
+                    member,
+                    group_id,
+                    group_member: members()[i].clone(),
+                    self_is_admin,
                 }
-            } else {
-                rsx!("Loading members...")
             }
+            {members_status}
         }
-        PacketState::Waiting => rsx!("Loading members..."),
-        PacketState::ServerError(err) => rsx!("Server error: {err:?}"),
-        PacketState::RequestTimeout => rsx!("Request timeout"),
-        PacketState::NotStarted => unreachable!(),
     };
     rsx! {
         div {
@@ -244,6 +309,18 @@ pub fn GroupMenu(group_id: u64, credentials: AccountCredentials) -> Element {
             }
             h1 { "Group" }
             {group_info}
+            input {
+                margin_top: "6px",
+                placeholder: "Nickname (only visible to you)",
+                value: STORAGE.group_nickname(group_id).unwrap_or_default(),
+                onchange: move |event| {
+                    let _ = STORAGE.store_group_nickname(group_id, &event.value());
+                },
+            }
+            button {
+                onclick: move |_| copy_text(DeepLink::Group(group_id).to_string()),
+                "Copy link",
+            }
             h2 {
                 margin_bottom: 0,
                 "Members:"
@@ -269,7 +346,8 @@ pub fn GroupMenu(group_id: u64, credentials: AccountCredentials) -> Element {
                     match server::leave_group(group_id, credentials).await {
                         Ok(()) => {}
                         Err(err) => {
-                            eprintln!("Unexpected error occurred while trying to leave a group: {err:?}");
+                            notify_error(format!("Failed to leave the group: {err}"));
+                            return;
                         }
                     }
                     let nav = navigator();
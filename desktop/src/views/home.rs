@@ -2,19 +2,20 @@ use client::storage::STORAGE;
 use dioxus::prelude::*;
 use ui::{Echo, Hero};
 
+use super::session_validity_checker::SessionValidityChecker;
 use crate::Route;
 
 #[component]
 pub fn Home() -> Element {
     let credentials = STORAGE.load_session_credentials();
 
-    let nav = navigator();
-
     if let Some(credentials) = credentials {
-        nav.replace(Route::SessionValidityChecker { credentials });
-    } else {
-        nav.replace(Route::RegisterAccount {});
+        return rsx! {
+            SessionValidityChecker { credentials }
+        };
     }
+    let nav = navigator();
+    nav.replace(Route::RegisterAccount {});
 
     rsx! {
         Hero {}
@@ -1,19 +1,26 @@
+use chrono::Utc;
 use client::{
     cache::CACHE,
-    future_retry_loop,
-    packet_sender::{PacketSender, PacketState},
+    display, future_retry_loop,
+    packet_sender::{PacketSender, PacketState, render_packet_state},
+    notifications::notify_error,
+    private_info,
     storage::STORAGE,
 };
-use dioxus::prelude::*;
+use dioxus::{
+    logger::tracing::{debug, error},
+    prelude::*,
+};
 use dioxus_free_icons::icons::go_icons::{
     GoAlert, GoCircleSlash, GoLock, GoPeople, GoSync, GoUnlock,
 };
 use postcard::from_bytes;
-use server::{AccountCredentials, DmInvite, GroupInvite, UserAccount};
-use shared::crypto::{
-    self,
-    x3dh::{self, X3DhData},
+use server::{
+    AccountCredentials, DmInvite, GroupInvite, MultiUserGroup, ServerError, UserAccount,
 };
+use shared::crypto::x3dh::{self, X3DhData};
+
+use super::{avatar::Avatar, retry_status::RetryStatus};
 
 #[derive(Clone, Copy)]
 enum Tab {
@@ -23,11 +30,11 @@ enum Tab {
 
 #[component]
 #[allow(non_snake_case)]
-pub fn Invites(credentials: AccountCredentials) -> Element {
+pub fn Invites() -> Element {
     let mut current_tab = use_signal(|| Tab::ReceivedInvites);
     let tab = match *current_tab.read() {
-        Tab::SentInvites => rsx!(SentInvitesTab { credentials }),
-        Tab::ReceivedInvites => rsx!(ReceivedInvitesTab { credentials }),
+        Tab::SentInvites => rsx!(SentInvitesTab {}),
+        Tab::ReceivedInvites => rsx!(ReceivedInvitesTab {}),
     };
     rsx! {
         div {
@@ -70,90 +77,81 @@ enum Invite {
     Group(GroupInvite),
 }
 
+impl Invite {
+    fn expiry(&self) -> Option<String> {
+        let expires_at = match self {
+            Self::Conversation(invite) => invite.expires_at,
+            Self::Group(invite) => invite.expires_at,
+        };
+        display::invite_expiry(expires_at, Utc::now().naive_utc())
+    }
+}
+
 #[component]
 #[allow(non_snake_case)]
-pub fn SentInvitesTab(credentials: AccountCredentials) -> Element {
+pub fn SentInvitesTab() -> Element {
+    let credentials = use_context::<AccountCredentials>();
     // TODO: Add invite caching so "Loading invites..." won't be shown every time user switches
     // tab. But still make a request each time.
     // The following feature is being called every time the tab is switched on purpose.
-    let sent_dm_invites = future_retry_loop!(server::get_sent_dm_invites(credentials));
-    let sent_group_invites = future_retry_loop!(server::get_sent_group_invites(credentials));
-    let invites = match sent_dm_invites {
-        PacketState::Response(dm_invites) => match sent_group_invites {
-            PacketState::Response(group_invites) => {
-                rsx! {
-                    for invite in dm_invites {
-                        SentInvite { key: {invite.id * 2}, invite: Invite::Conversation(invite.clone()), credentials }
-                    }
-                    for invite in group_invites {
-                        SentInvite { key: {invite.id * 2 + 1}, invite: Invite::Group(invite.clone()), credentials }
-                    }
-                }
+    future_retry_loop! { invites_signal, invites_resource, invites_failures, server::get_all_invites(credentials) };
+    let invites = render_packet_state(invites_signal(), |invites| {
+        rsx! {
+            for invite in invites.sent_dm_invites.items {
+                SentInvite { key: {invite.id * 2}, invite: Invite::Conversation(invite.clone()) }
             }
-            PacketState::Waiting => rsx!(p { "Loading invites..." }),
-            PacketState::ServerError(err) => rsx!(p { "Server error: {err:?}" }),
-            PacketState::RequestTimeout => rsx!(p { "Request timeout" }),
-            PacketState::NotStarted => unreachable!(),
-        },
-        PacketState::Waiting => rsx!(p { "Loading invites..." }),
-        PacketState::ServerError(err) => rsx!(p { "Server error: {err:?}" }),
-        PacketState::RequestTimeout => rsx!(p { "Request timeout" }),
-        PacketState::NotStarted => unreachable!(),
-    };
+            for invite in invites.sent_group_invites.items {
+                SentInvite { key: {invite.id * 2 + 1}, invite: Invite::Group(invite.clone()) }
+            }
+        }
+    });
     rsx! {
         h3 { "Sent invites" }
         {invites}
+        RetryStatus { failures: invites_failures, resource: invites_resource }
     }
 }
 
 #[component]
 #[allow(non_snake_case)]
-pub fn ReceivedInvitesTab(credentials: AccountCredentials) -> Element {
+pub fn ReceivedInvitesTab() -> Element {
+    let credentials = use_context::<AccountCredentials>();
     // The following feature is being called every time the tab is switched on purpose.
-    let received_dm_invites = future_retry_loop!(server::get_received_dm_invites(credentials));
-    let received_group_invites =
-        future_retry_loop!(server::get_received_group_invites(credentials));
-    let invites = match received_dm_invites {
-        PacketState::Response(dm_invites) => match received_group_invites {
-            PacketState::Response(group_invites) => {
-                rsx! {
-                    for invite in dm_invites {
-                        ReceivedInvite { key: {invite.id * 2}, invite: Invite::Conversation(invite.clone()), credentials }
-                    }
-                    for invite in group_invites {
-                        ReceivedInvite { key: {invite.id * 2 + 1}, invite: Invite::Group(invite.clone()), credentials }
-                    }
+    future_retry_loop! { received_invites_signal, received_invites_resource, received_invites_failures, server::get_received_invites_detailed(credentials) };
+    let received_invites = received_invites_signal();
+    let invites = render_packet_state(received_invites, |invites| {
+        rsx! {
+            for detailed in invites.dm_invites {
+                ReceivedInvite {
+                    key: {detailed.invite.id * 2},
+                    invite: Invite::Conversation(detailed.invite.clone()),
+                    inviter: detailed.initiator.clone(),
+                    inviter_identity_corrupt: detailed.initiator_identity_corrupt,
+                    group: None,
                 }
             }
-            PacketState::Waiting => rsx!(p { "Loading invites..." }),
-            PacketState::ServerError(err) => rsx!(p { "Server error: {err:?}" }),
-            PacketState::RequestTimeout => rsx!(p { "Request timeout" }),
-            PacketState::NotStarted => unreachable!(),
-        },
-        PacketState::Waiting => rsx!(p { "Loading invites..." }),
-        PacketState::ServerError(err) => rsx!(p { "Server error: {err:?}" }),
-        PacketState::RequestTimeout => rsx!(p { "Request timeout" }),
-        PacketState::NotStarted => unreachable!(),
-    };
+            for detailed in invites.group_invites {
+                ReceivedInvite {
+                    key: {detailed.invite.id * 2 + 1},
+                    invite: Invite::Group(detailed.invite.clone()),
+                    inviter: detailed.inviter.clone(),
+                    inviter_identity_corrupt: detailed.inviter_identity_corrupt,
+                    group: detailed.group.clone(),
+                }
+            }
+        }
+    });
     rsx! {
         h3 { "Received invites" }
         {invites}
+        RetryStatus { failures: received_invites_failures, resource: received_invites_resource }
     }
 }
 
 #[component]
 #[allow(non_snake_case)]
-fn SentInvite(invite: Invite, credentials: AccountCredentials) -> Element {
-    const ICON_TRANSPARENT: Asset = asset!(
-        "/assets/icon_transparent.png",
-        ImageAssetOptions::new()
-            .with_size(ImageSize::Manual {
-                width: 97,
-                height: 111,
-            })
-            .with_format(ImageFormat::Avif)
-    );
-
+fn SentInvite(invite: Invite) -> Element {
+    let credentials = use_context::<AccountCredentials>();
     let mut cancel_result = use_signal(|| PacketState::NotStarted);
     let mut user_data = use_signal(|| PacketState::NotStarted);
     let mut group_data = use_signal(|| PacketState::NotStarted);
@@ -173,7 +171,7 @@ fn SentInvite(invite: Invite, credentials: AccountCredentials) -> Element {
                 dioxus_free_icons::Icon {
                     width: 16,
                     height: 16,
-                    fill: "white",
+                    fill: "currentColor",
                     icon: $icon,
                 }
             }
@@ -206,9 +204,13 @@ fn SentInvite(invite: Invite, credentials: AccountCredentials) -> Element {
         PacketState::RequestTimeout => (Some("Timeout".to_owned()), Some(icon!(GoAlert))),
         PacketState::NotStarted => (None, None),
     };
-    let (username, email, icon) = match user_data() {
+    let user_icon = match user_data() {
+        PacketState::Response(Some(account)) => account.icon,
+        _ => None,
+    };
+    let (title, email, icon) = match user_data() {
         PacketState::Response(Some(account)) => (
-            account.username,
+            display::contact_display_name(Some(&account), invited_id),
             account.email,
             match invite {
                 Invite::Conversation(ref invite) => {
@@ -222,23 +224,22 @@ fn SentInvite(invite: Invite, credentials: AccountCredentials) -> Element {
             },
         ),
         PacketState::Response(None) => (
-            Some("Deleted account".to_owned()),
+            "Deleted account".to_owned(),
             None,
             icon!(GoCircleSlash),
         ),
         PacketState::NotStarted | PacketState::Waiting => {
-            (Some("Loading user data...".to_owned()), None, icon!(GoSync))
+            ("Loading user data...".to_owned(), None, icon!(GoSync))
         }
         PacketState::ServerError(err) => (
-            Some("Server error".to_string()),
+            "Server error".to_string(),
             Some(err.to_string()),
             icon!(GoAlert),
         ),
         PacketState::RequestTimeout => {
-            (Some("Request timed out".to_string()), None, icon!(GoAlert))
+            ("Request timed out".to_string(), None, icon!(GoAlert))
         }
     };
-    let title = username.unwrap_or_else(|| email.clone().unwrap_or("Anonymous".to_owned()));
 
     rsx! {
         div {
@@ -250,12 +251,7 @@ fn SentInvite(invite: Invite, credentials: AccountCredentials) -> Element {
                 flex: "0 3 48px",
                 max_height: "46px",
 
-                img {
-                    src: ICON_TRANSPARENT,
-                    margin_right: "24px",
-                    width: "46px",
-                    max_height: "46px",
-                }
+                Avatar { icon: user_icon }
             }
             div {
                 flex: "1 0 auto",
@@ -293,6 +289,15 @@ fn SentInvite(invite: Invite, credentials: AccountCredentials) -> Element {
                     margin_top: "6px",
                     {email}
                 }
+                if let Some(expiry) = invite.expiry() {
+                    p {
+                        padding: 0,
+                        margin: 0,
+                        margin_top: "4px",
+                        color: "var(--secondary-text)",
+                        {expiry}
+                    }
+                }
             }
             if matches!(user_data(), PacketState::Response(_)) && *cancel_result.read() == PacketState::NotStarted {
                 button {
@@ -334,44 +339,65 @@ fn get_shared_key(
         return None;
     };
     let encryption_data = encryption_data?;
-    println!("Get shared key: found encryption data");
     let x3dh_data: X3DhData = from_bytes(&encryption_data).ok()?;
-    println!("Get shared key: found valid X3DH data");
     // TODO: Get `crypto_alg` from `encryption_data`.
-    let crypto_alg = crypto::preferred_alogirthm();
+    let crypto_alg = STORAGE.algorithms();
     let (private_keys, public_keys) = STORAGE.x3dh_data(&crypto_alg);
     let shared_key = match x3dh::decode_x3dh(x3dh_data, user.cryptoidentity.ik, public_keys, private_keys) {
         Ok(key) => key,
         Err(err) => {
-            eprintln!("Failed to decode X3DH data (shared key): {err:?}");
+            error!("Failed to decode X3DH data (shared key): {err:?}");
             return None;
         }
     };
     if for_dm {
-        STORAGE.store_dm_key(id, (crypto_alg, &shared_key));
+        STORAGE.store_dm_key(id, (crypto_alg, &shared_key)).ok()?;
     } else {
-        STORAGE.store_group_key(id, (crypto_alg, &shared_key));
+        STORAGE.store_group_key(id, (crypto_alg, &shared_key)).ok()?;
     }
     Some(shared_key)
 }
 
 #[component]
 #[allow(non_snake_case)]
-fn ReceivedInvite(invite: Invite, credentials: AccountCredentials) -> Element {
-    const ICON_TRANSPARENT: Asset = asset!(
-        "/assets/icon_transparent.png",
-        ImageAssetOptions::new()
-            .with_size(ImageSize::Manual {
-                width: 97,
-                height: 111,
-            })
-            .with_format(ImageFormat::Avif)
-    );
-
+fn ReceivedInvite(
+    invite: Invite,
+    inviter: Option<UserAccount>,
+    inviter_identity_corrupt: bool,
+    group: Option<MultiUserGroup>,
+) -> Element {
+    let credentials = use_context::<AccountCredentials>();
     let mut accept_result = use_signal(|| PacketState::NotStarted);
     let mut reject_result = use_signal(|| PacketState::NotStarted);
-    let mut user_data = use_signal(|| PacketState::NotStarted);
-    let mut group_data = use_signal(|| PacketState::NotStarted);
+    let inviter_id = match invite {
+        Invite::Conversation(ref invite) => invite.initiator_id,
+        Invite::Group(ref invite) => invite.inviter_id,
+    };
+    // Invite data arrives along with the invite, so nothing has to be fetched here. A cached
+    // account is still preferred, as its identity key is the one the user already trusts.
+    let user_data = use_signal(|| {
+        if let Some(account) = CACHE.load_user_data(inviter_id) {
+            return PacketState::Response(Some(account));
+        }
+        if inviter_identity_corrupt {
+            return PacketState::ServerError(ServerFnError::WrappedServerError(
+                ServerError::CorruptIdentity,
+            ));
+        }
+        if let Some(ref account) = inviter {
+            CACHE.update_user_data(inviter_id, account);
+        }
+        PacketState::Response(inviter)
+    });
+    let group_data = use_signal(|| match invite {
+        Invite::Conversation(_) => PacketState::NotStarted,
+        Invite::Group(_) => {
+            if let Some(ref group) = group {
+                CACHE.store_group_data(group.id, group);
+            }
+            PacketState::Response(group)
+        }
+    });
     let status = match (*accept_result.read()).clone() {
         PacketState::Response(Some(group_id)) => {
             let (valid_shared_key, id) = match invite {
@@ -390,14 +416,19 @@ fn ReceivedInvite(invite: Invite, credentials: AccountCredentials) -> Element {
                     )
                 }
             };
-            if !valid_shared_key {
-                eprintln!("Failed to decrypt shared key for invite {id}");
+            if valid_shared_key {
+                spawn(async move {
+                    private_info::upload_private_info(credentials).await;
+                });
+            } else {
+                error!("Failed to decrypt shared key for invite {id}");
+                notify_error("Failed to decrypt the encryption key of the invite, so its messages can't be read");
             }
-            println!("Created DM group: {group_id}");
+            debug!("Accepted invite into group {group_id}");
             return rsx!();
         }
         PacketState::Response(None) => {
-            println!("Joined DM group");
+            debug!("Accepted invite");
             return rsx!();
         }
         PacketState::Waiting => rsx!(p { "Accepting..." }),
@@ -420,26 +451,12 @@ fn ReceivedInvite(invite: Invite, credentials: AccountCredentials) -> Element {
                 dioxus_free_icons::Icon {
                     width: 16,
                     height: 16,
-                    fill: "white",
+                    fill: "currentColor",
                     icon: $icon,
                 }
             }
         };
     }
-    let (inviter_id, group_id) = match invite {
-        Invite::Conversation(ref invite) => (invite.initiator_id, None),
-        Invite::Group(ref invite) => (invite.inviter_id, Some(invite.group_id)),
-    };
-    use_future(move || async move {
-        CACHE
-            .user_data(inviter_id, credentials, &mut user_data)
-            .await;
-    });
-    if let Some(id) = group_id {
-        use_future(move || async move {
-            CACHE.group_data(id, credentials, &mut group_data).await;
-        });
-    }
     let (group_name, group_icon) = match group_data() {
         PacketState::Response(Some(group)) => (Some(group.name), Some(icon!(GoPeople))),
         PacketState::Response(None) => {
@@ -453,9 +470,13 @@ fn ReceivedInvite(invite: Invite, credentials: AccountCredentials) -> Element {
         PacketState::RequestTimeout => (Some("Timeout".to_owned()), Some(icon!(GoAlert))),
         PacketState::NotStarted => (None, None),
     };
-    let (username, email, icon) = match user_data() {
+    let user_icon = match user_data() {
+        PacketState::Response(Some(account)) => account.icon,
+        _ => None,
+    };
+    let (title, email, icon) = match user_data() {
         PacketState::Response(Some(account)) => (
-            account.username,
+            display::contact_display_name(Some(&account), inviter_id),
             account.email,
             match invite {
                 Invite::Conversation(ref invite) => {
@@ -469,23 +490,22 @@ fn ReceivedInvite(invite: Invite, credentials: AccountCredentials) -> Element {
             },
         ),
         PacketState::Response(None) => (
-            Some("Deleted account".to_owned()),
+            "Deleted account".to_owned(),
             None,
             icon!(GoCircleSlash),
         ),
         PacketState::NotStarted | PacketState::Waiting => {
-            (Some("Loading user data...".to_owned()), None, icon!(GoSync))
+            ("Loading user data...".to_owned(), None, icon!(GoSync))
         }
         PacketState::ServerError(err) => (
-            Some("Server error".to_string()),
+            "Server error".to_string(),
             Some(err.to_string()),
             icon!(GoAlert),
         ),
         PacketState::RequestTimeout => {
-            (Some("Request timed out".to_string()), None, icon!(GoAlert))
+            ("Request timed out".to_string(), None, icon!(GoAlert))
         }
     };
-    let title = username.unwrap_or_else(|| email.clone().unwrap_or("Anonymous".to_owned()));
     let invite1 = invite.clone();
     let invite2 = invite.clone();
 
@@ -499,12 +519,7 @@ fn ReceivedInvite(invite: Invite, credentials: AccountCredentials) -> Element {
                 flex: "0 3 48px",
                 max_height: "46px",
 
-                img {
-                    src: ICON_TRANSPARENT,
-                    margin_right: "24px",
-                    width: "46px",
-                    max_height: "46px",
-                }
+                Avatar { icon: user_icon }
             }
             div {
                 flex: "1 0 auto",
@@ -542,6 +557,15 @@ fn ReceivedInvite(invite: Invite, credentials: AccountCredentials) -> Element {
                     margin_top: "6px",
                     {email}
                 }
+                if let Some(expiry) = invite.expiry() {
+                    p {
+                        padding: 0,
+                        margin: 0,
+                        margin_top: "4px",
+                        color: "var(--secondary-text)",
+                        {expiry}
+                    }
+                }
             }
             if matches!(user_data(), PacketState::Response(Some(_))) && *accept_result.read() == PacketState::NotStarted {
                 button {
@@ -0,0 +1,255 @@
+use client::{
+    links::DeepLink,
+    markdown::{self, Block, Inline, MessageFormat, REVEALED_SPOILERS, RevealedSpoilers},
+};
+use dioxus::{logger::tracing::error, prelude::*};
+
+use crate::{Route, clipboard::copy_text};
+
+/// What the elements of a message need to handle clicks.
+#[derive(Clone, Copy)]
+struct MessageContext {
+    open_link: Callback<String>,
+    message_key: u64,
+    /// Link the context menu was opened on.
+    menu_link: Signal<Option<String>>,
+}
+
+/// Text hidden until clicked. Clicking it again hides it back.
+#[component]
+#[allow(non_snake_case)]
+fn Spoiler(message_key: u64, id: usize, children: Element) -> Element {
+    let mut revealed = use_signal(|| REVEALED_SPOILERS.is_revealed(message_key, id));
+    rsx! {
+        span {
+            class: if revealed() { "spoiler spoiler-revealed" } else { "spoiler" },
+            title: if !revealed() { "Show spoiler" },
+            onclick: move |event| {
+                event.stop_propagation();
+                revealed.set(REVEALED_SPOILERS.toggle(message_key, id));
+            },
+            {children}
+        }
+    }
+}
+
+fn inlines_element(inlines: Vec<Inline>, context: MessageContext) -> Element {
+    rsx! {
+        for inline in inlines {
+            {inline_element(inline, context)}
+        }
+    }
+}
+
+fn inline_element(inline: Inline, context: MessageContext) -> Element {
+    match inline {
+        Inline::Text(text) => rsx!("{text}"),
+        Inline::Code(code) => rsx!(code { "{code}" }),
+        Inline::Emphasis(children) => rsx!(em { {inlines_element(children, context)} }),
+        Inline::Strong(children) => rsx!(strong { {inlines_element(children, context)} }),
+        Inline::Strikethrough(children) => rsx!(s { {inlines_element(children, context)} }),
+        // Without `href` the webview doesn't open the link on its own, so it can be confirmed.
+        Inline::Link { url, children } => {
+            let menu_url = url.clone();
+            let mut menu_link = context.menu_link;
+            rsx! {
+                a {
+                    title: "{url}",
+                    onclick: move |event| {
+                        event.stop_propagation();
+                        context.open_link.call(url.clone());
+                    },
+                    // The menu itself is opened by the message.
+                    oncontextmenu: move |_| menu_link.set(Some(menu_url.clone())),
+                    {inlines_element(children, context)}
+                }
+            }
+        }
+        Inline::LineBreak => rsx!(br {}),
+        Inline::Spoiler { id, children } => rsx! {
+            Spoiler { message_key: context.message_key, id, {inlines_element(children, context)} }
+        },
+    }
+}
+
+fn blocks_element(blocks: Vec<Block>, context: MessageContext) -> Element {
+    rsx! {
+        for block in blocks {
+            {block_element(block, context)}
+        }
+    }
+}
+
+fn block_element(block: Block, context: MessageContext) -> Element {
+    match block {
+        Block::Paragraph(inlines) => rsx!(p { {inlines_element(inlines, context)} }),
+        Block::CodeBlock(code) => rsx!(pre { code { "{code}" } }),
+        Block::Quote(blocks) => rsx!(blockquote { {blocks_element(blocks, context)} }),
+        Block::List {
+            start: Some(start),
+            items,
+        } => rsx! {
+            ol {
+                start: start.to_string(),
+                for item in items {
+                    li { {blocks_element(item, context)} }
+                }
+            }
+        },
+        Block::List { start: None, items } => rsx! {
+            ul {
+                for item in items {
+                    li { {blocks_element(item, context)} }
+                }
+            }
+        },
+        Block::Rule => rsx!(hr {}),
+    }
+}
+
+/// Asks whether to open a link leading outside of the application, showing where it leads to
+/// even if the text of the link says otherwise.
+#[component]
+#[allow(non_snake_case)]
+fn LinkConfirmation(url: String, opened_url: Signal<Option<String>>) -> Element {
+    let open_url = url.clone();
+    rsx! {
+        div {
+            class: "dialog-backdrop",
+            onclick: move |event| {
+                event.stop_propagation();
+                opened_url.set(None);
+            },
+
+            div {
+                class: "panel dialog",
+                onclick: move |event| event.stop_propagation(),
+
+                h3 { "Open link?" }
+                p { "This link leads to a site outside of Peregrine. Only open it if you trust it:" }
+                p { class: "dialog-url", "{url}" }
+                button {
+                    onclick: move |_| {
+                        if let Err(err) = webbrowser::open(&open_url) {
+                            error!("Failed to open {open_url}: {err:?}");
+                        }
+                        opened_url.set(None);
+                    },
+                    "Open"
+                }
+                " "
+                button { onclick: move |_| opened_url.set(None), "Cancel" }
+            }
+        }
+    }
+}
+
+/// Actions for the message opened with a right click or a long press. Copies the text as it was
+/// typed, so the decrypted text of encrypted messages.
+#[component]
+#[allow(non_snake_case)]
+fn MessageMenu(
+    text: String,
+    position: (f64, f64),
+    menu_position: Signal<Option<(f64, f64)>>,
+    menu_link: Signal<Option<String>>,
+) -> Element {
+    let mut close = move || {
+        menu_position.set(None);
+        menu_link.set(None);
+    };
+    rsx! {
+        div {
+            class: "context-menu-backdrop",
+            onclick: move |event| {
+                event.stop_propagation();
+                close();
+            },
+            oncontextmenu: move |event| {
+                event.prevent_default();
+                event.stop_propagation();
+                close();
+            },
+
+            div {
+                class: "panel context-menu",
+                left: "{position.0}px",
+                top: "{position.1}px",
+
+                button {
+                    onclick: move |event| {
+                        event.stop_propagation();
+                        let text = text.clone();
+                        close();
+                        copy_text(text)
+                    },
+                    "Copy text"
+                }
+                if let Some(link) = menu_link() {
+                    button {
+                        onclick: move |event| {
+                            event.stop_propagation();
+                            close();
+                            copy_text(link.clone())
+                        },
+                        "Copy link"
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Text of a message, formatted as chosen in the settings. Formatting is built from RSX
+/// elements only, so that no part of the message is interpreted as HTML.
+#[component]
+#[allow(non_snake_case)]
+pub fn MessageText(text: String) -> Element {
+    let format = use_context::<Signal<MessageFormat>>();
+    let mut opened_url = use_signal(|| None);
+    let mut menu_position = use_signal(|| None);
+    let menu_link = use_signal(|| None);
+    // Links to the application's own screens are safe to open right away.
+    let open_link = use_callback(move |url: String| match url.parse::<DeepLink>() {
+        Ok(link) => {
+            navigator().push(Route::from_link(link));
+        }
+        Err(()) => opened_url.set(Some(url)),
+    });
+    let context = MessageContext {
+        open_link,
+        message_key: RevealedSpoilers::message_key(&text),
+        menu_link,
+    };
+    let content = match format() {
+        MessageFormat::Markdown => blocks_element(markdown::parse(&text), context),
+        MessageFormat::PlainText => rsx! {
+            p {
+                white_space: "pre-wrap",
+                {inlines_element(markdown::parse_plain_text(&text), context)}
+            }
+        },
+    };
+    rsx! {
+        div {
+            oncontextmenu: move |event| {
+                event.prevent_default();
+                let point = event.client_coordinates();
+                menu_position.set(Some((point.x, point.y)));
+            },
+
+            {content}
+        }
+        if let Some(position) = menu_position() {
+            MessageMenu {
+                text,
+                position,
+                menu_position,
+                menu_link,
+            }
+        }
+        if let Some(url) = opened_url() {
+            LinkConfirmation { url, opened_url }
+        }
+    }
+}
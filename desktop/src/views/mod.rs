@@ -1,14 +1,23 @@
+mod add_contact;
+mod avatar;
 #[cfg(debug_assertions)]
 mod change_credentials;
 mod contacts;
 mod create_group;
+mod emoji_picker;
 mod group_menu;
 mod home;
 mod invites;
+mod message_text;
 mod other_user_account;
+mod recover_account;
 mod register_account;
+mod retry_status;
 mod session_validity_checker;
+mod settings;
+mod toasts;
 
+pub use add_contact::AddContact;
 #[cfg(debug_assertions)]
 pub use change_credentials::ChangeCredentials;
 pub use contacts::Contacts;
@@ -17,5 +26,7 @@ pub use group_menu::GroupMenu;
 pub use home::Home;
 pub use invites::Invites;
 pub use other_user_account::OtherUserAccount;
+pub use recover_account::RecoverAccount;
 pub use register_account::{LoginAccount, RegisterAccount};
-pub use session_validity_checker::SessionValidityChecker;
+pub use settings::Settings;
+pub use toasts::{ErrorScreen, OfflineBanner, ToastList};
@@ -1,53 +1,88 @@
-use client::{future_retry_loop, packet_sender::PacketState, storage::STORAGE};
-use dioxus::prelude::*;
+use client::{
+    cache::{self, CACHE, IdentityCheck},
+    display, future_retry_loop,
+    links::DeepLink,
+    notifications::notify_error,
+    packet_sender::{PacketState, render_packet_state},
+    private_info,
+    storage::STORAGE,
+};
+use dioxus::{
+    logger::tracing::{error, info, warn},
+    prelude::*,
+};
 use postcard::to_allocvec;
-use server::{AccountCredentials, UserAccount};
+use server::{AccountCredentials, ServerError};
 use shared::{
-    crypto::{self, x3dh},
+    crypto::{self, fingerprint, x3dh},
     types::GroupPermissions,
 };
 
-fn generate_encrypted_shared_key(
-    id: u64,
-    user_data: PacketState<Option<UserAccount>>,
-    for_dm: bool,
+use crate::clipboard::copy_text;
+
+/// Generates a new shared key for a DM with the user or, if `group_id` is set, for the group and
+/// encrypts it to the user's identity. Returns `None` if the identity key of the user has changed
+/// and the change wasn't accepted yet.
+pub(super) async fn generate_encrypted_shared_key(
+    user_id: u64,
+    group_id: Option<u64>,
+    credentials: AccountCredentials,
 ) -> Option<Box<[u8]>> {
-    let PacketState::Response(Some(user)) = user_data else {
-        return None;
+    let bundle = match server::get_prekey_bundle(user_id, true, credentials).await {
+        Ok(Some(bundle)) => bundle,
+        Ok(None) => return None,
+        Err(err) => {
+            error!("Failed to get prekey bundle of user {user_id}: {err:?}");
+            return None;
+        }
     };
-    let crypto_alg = crypto::preferred_alogirthm();
+    if CACHE.update_identity(user_id, &bundle.cryptoidentity) == IdentityCheck::Changed {
+        return None;
+    }
+    if bundle.opk.is_none() {
+        warn!(
+            "User {user_id} has no one-time prekeys left, forward secrecy of the shared key is reduced"
+        );
+    }
+    let crypto_alg = STORAGE.algorithms();
     let (private_keys, public_keys) = STORAGE.x3dh_data(&crypto_alg);
     let shared_key = crypto::symmetric_genkey(&crypto_alg, crypto::KeyStrength::ExtremelyHigh)?;
-    let Ok(encrypted_shared_key) = x3dh::encode_x3dh(
+    let Ok(encrypted_shared_key) = x3dh::encode_x3dh_with_claimed_opk(
         &shared_key,
         private_keys.ik,
         public_keys.ik,
-        user.cryptoidentity.clone(),
+        bundle.cryptoidentity,
+        bundle.opk,
     ) else {
         return None;
     };
     let encrypted_shared_key = to_allocvec(&encrypted_shared_key)
         .unwrap()
         .into_boxed_slice();
-    if for_dm {
-        STORAGE.store_dm_key(id, (crypto_alg, &shared_key));
+    // Nothing could be decrypted without the key, so the invite isn't sent.
+    if let Some(group_id) = group_id {
+        STORAGE.store_group_key(group_id, (crypto_alg, &shared_key)).ok()?;
     } else {
-        STORAGE.store_group_key(id, (crypto_alg, &shared_key));
+        STORAGE.store_dm_key(user_id, (crypto_alg, &shared_key)).ok()?;
     }
+    private_info::upload_private_info(credentials).await;
     Some(encrypted_shared_key)
 }
 
 #[component]
-pub fn OtherUserAccount(user_id: u64, credentials: AccountCredentials) -> Element {
+pub fn OtherUserAccount(user_id: u64) -> Element {
+    let credentials = use_context::<AccountCredentials>();
     let user_data = future_retry_loop!(server::get_user_data(user_id, credentials));
-    let user_info = match user_data {
-        PacketState::Response(ref info) => match info {
+    let user_info = match user_data.clone() {
+        PacketState::ServerError(ServerFnError::WrappedServerError(
+            ServerError::CorruptIdentity,
+        )) => rsx!(
+            "The server has a corrupt identity key for this user, so messages to them can't be encrypted. Ask the server operator to repair the account."
+        ),
+        state => render_packet_state(state, |info| match info {
             Some(info) => {
-                let email = info.email.clone().unwrap_or("Hidden email".to_owned());
-                let username = info
-                    .username
-                    .clone()
-                    .unwrap_or("Hidden username".to_owned());
+                let email = info.email.unwrap_or("Hidden email".to_owned());
+                let username = info.username.unwrap_or("Hidden username".to_owned());
                 rsx! {
                     h4 { margin: 0, "Email: {email}" }
                     h4 { margin: 0, "Username: {username}" }
@@ -55,51 +90,154 @@ pub fn OtherUserAccount(user_id: u64, credentials: AccountCredentials) -> Elemen
                 }
             }
             None => rsx!("Removed account"),
-        },
-        PacketState::Waiting => rsx!("Loading user information..."),
-        PacketState::ServerError(ref err) => rsx!("Server error: {err:?}"),
-        PacketState::RequestTimeout => rsx!("Request timeout"),
-        PacketState::NotStarted => unreachable!(),
+        }),
     };
-    let user_data1 = user_data.clone();
-    let user_data2 = user_data.clone();
-    let joined_groups = future_retry_loop!(server::get_joined_groups(credentials));
-    let joined_groups_element = match joined_groups {
-        PacketState::Response(groups) => {
-            let mut result = rsx!();
-            let user_data = &user_data1.clone();
-            for group in groups {
-                let user_data = user_data.clone();
-                result = rsx! {
-                    {result}
-                    br {}
-                    button {
-                        key: group.id,
-                        margin_top: "6px",
-                        onclick: move |_| {
-                            let user_data = user_data.clone();
-                            async move {
-                                match server::send_group_invite(user_id, group.id, GroupPermissions::default().to_bytes(), credentials, generate_encrypted_shared_key(group.id, user_data.clone(), false)).await {
-                                    Ok(invite_id) => {
-                                        println!("Sent group invite: {invite_id:?} (for group {} to user {user_id})", group.id);
-                                    }
-                                    Err(err) => {
-                                        eprintln!("Error from server: {err:?}");
-                                    }
+    let mut identity_accepted = use_signal(|| false);
+    let identity_changed = match user_data {
+        PacketState::Response(Some(ref info)) => {
+            // Subscribes to the signal so that the warning disappears once the key is accepted.
+            identity_accepted();
+            cache::check_identity(CACHE.known_identity(user_id).as_ref(), &info.cryptoidentity)
+                == IdentityCheck::Changed
+        }
+        _ => false,
+    };
+    let user_data3 = user_data.clone();
+    let identity_warning = rsx! {
+        div {
+            style: "border:2px solid var(--warning-border);padding:8px;margin:8px 0",
+            h2 { style: "color:var(--error-text);margin:0", "Identity key has changed!" }
+            p {
+                "The server returned a different identity key for this user than before. This may mean that they have reinstalled the application, or that someone is trying to intercept your messages. Verify the safety number with them before continuing."
+            }
+            button {
+                onclick: move |_| {
+                    if let PacketState::Response(Some(ref info)) = user_data3 {
+                        CACHE.accept_identity_change(user_id, &info.cryptoidentity);
+                        identity_accepted.set(true);
+                    }
+                },
+                "I have verified the new key",
+            }
+        }
+    };
+    let mut verified_identity: Signal<Option<Box<[u8]>>> =
+        use_signal(|| STORAGE.load_verified_identity(user_id));
+    let safety_number_element = match user_data {
+        PacketState::Response(Some(ref info)) => {
+            let (_, own_identity) = STORAGE.x3dh_data(&STORAGE.algorithms());
+            let safety_number = fingerprint::safety_number(
+                credentials.id,
+                &own_identity,
+                user_id,
+                &info.cryptoidentity,
+            )
+            .map_or("Unavailable".to_owned(), |number| {
+                fingerprint::format_safety_number(&number)
+            });
+            let current_identity = fingerprint::identity_fingerprint(&info.cryptoidentity);
+            let is_verified = verified_identity()
+                .is_some_and(|verified| Some(&verified) == current_identity.as_ref());
+            let status = match verified_identity() {
+                Some(_) if is_verified => rsx!(p { style: "color:var(--success-text)", "Verified" }),
+                Some(_) => rsx!(p {
+                    style: "color:var(--error-text)",
+                    "Warning: identity key of this user has changed since you verified it. Compare the safety number again before trusting this account."
+                }),
+                None => rsx!(p { "Not verified" }),
+            };
+            rsx! {
+                h2 { "Safety number" }
+                p {
+                    font_family: "monospace",
+                    "{safety_number}"
+                }
+                {status}
+                if !is_verified {
+                    if let Some(current_identity) = current_identity {
+                        button {
+                            onclick: move |_| {
+                                if STORAGE.store_verified_identity(user_id, current_identity.clone()).is_ok() {
+                                    verified_identity.set(Some(current_identity.clone()));
                                 }
-                            }
-                        },
-                        {group.name},
+                            },
+                            "Mark as verified",
+                        }
                     }
-                };
+                }
             }
-            result
         }
-        PacketState::Waiting => rsx!("Loading groups..."),
-        PacketState::ServerError(err) => rsx!("Server error: {err:?}"),
-        PacketState::RequestTimeout => rsx!("Request timeout"),
-        PacketState::NotStarted => unreachable!(),
+        _ => rsx!(),
+    };
+    let mut is_contact: Signal<Option<bool>> = use_signal(|| None);
+    use_future(move || async move {
+        match server::get_contacts(credentials).await {
+            Ok(contacts) => is_contact.set(Some(contacts.contains(&user_id))),
+            Err(err) => notify_error(format!("Failed to load contacts: {err}")),
+        }
+    });
+    let contact_button = match is_contact() {
+        Some(true) => rsx! {
+            button {
+                onclick: move |_| async move {
+                    match server::remove_contact(user_id, credentials).await {
+                        Ok(()) => is_contact.set(Some(false)),
+                        Err(err) => notify_error(format!("Failed to remove the contact: {err}")),
+                    }
+                },
+                "Remove from contacts",
+            }
+        },
+        Some(false) => rsx! {
+            button {
+                onclick: move |_| async move {
+                    match server::add_contact(user_id, credentials).await {
+                        Ok(()) => is_contact.set(Some(true)),
+                        Err(err) => notify_error(format!("Failed to add the contact: {err}")),
+                    }
+                },
+                "Add to contacts",
+            }
+        },
+        None => rsx!(),
+    };
+    let display_name = match user_data {
+        PacketState::Response(ref info) => display::contact_display_name(info.as_ref(), user_id),
+        _ => "User".to_owned(),
     };
+    let joined_groups = future_retry_loop!(server::get_joined_groups(credentials));
+    let joined_groups_element = render_packet_state(joined_groups, |groups| {
+        let mut result = rsx!();
+        for group in groups.items {
+            result = rsx! {
+                {result}
+                br {}
+                button {
+                    key: group.id,
+                    margin_top: "6px",
+                    onclick: move |_| async move {
+                        let Some(encryption_data) = generate_encrypted_shared_key(user_id, Some(group.id), credentials).await else {
+                            notify_error("Failed to create the encryption key for the invite. Check the user's identity key and try again.");
+                            return;
+                        };
+                        match server::send_group_invite(user_id, group.id, GroupPermissions::default().to_bytes(), credentials, Some(encryption_data)).await {
+                            Ok(invite_id) => {
+                                info!("Sent group invite {invite_id} for group {} to user {user_id}", group.id);
+                            }
+                            Err(ServerFnError::WrappedServerError(ServerError::InviteAlreadyExists)) => {
+                                notify_error("The user has already been invited to this group");
+                            }
+                            Err(err) => {
+                                notify_error(format!("Failed to send the invite: {err}"));
+                            }
+                        }
+                    },
+                    {group.name},
+                }
+            };
+        }
+        result
+    });
     rsx! {
         div {
             height: "100%",
@@ -113,27 +251,46 @@ pub fn OtherUserAccount(user_id: u64, credentials: AccountCredentials) -> Elemen
                 "Back"
             }
             if matches!(user_data, PacketState::Response(_)) {
-                h1 { "User" }
+                h1 { {display_name} }
                 {user_info}
-                h2 { "Invite to:" }
+                input {
+                    margin_top: "6px",
+                    placeholder: "Nickname (only visible to you)",
+                    value: STORAGE.contact_nickname(user_id).unwrap_or_default(),
+                    onchange: move |event| {
+                        let _ = STORAGE.store_contact_nickname(user_id, &event.value());
+                    },
+                }
+                br {}
+                {contact_button}
                 button {
-                    onclick: move |_| {
-                        let user_data = user_data2.clone();
-                        async move {
-                            match server::send_dm_invite(user_id, generate_encrypted_shared_key(user_id, user_data.clone(), true), credentials).await {
+                    onclick: move |_| copy_text(DeepLink::User(user_id).to_string()),
+                    "Copy link",
+                }
+                {safety_number_element}
+                if identity_changed {
+                    {identity_warning}
+                } else {
+                    h2 { "Invite to:" }
+                    button {
+                        onclick: move |_| async move {
+                            let Some(encryption_data) = generate_encrypted_shared_key(user_id, None, credentials).await else {
+                                notify_error("Failed to create the encryption key for the invite. Check the user's identity key and try again.");
+                                return;
+                            };
+                            match server::send_dm_invite(user_id, Some(encryption_data), credentials).await {
                                 Ok(invite_id) => {
-                                    println!("Sent invite: {invite_id:?}");
+                                    info!("Sent DM invite {invite_id} to user {user_id}");
                                 }
                                 Err(err) => {
-                                    eprintln!("Error from server: {err:?}");
+                                    notify_error(format!("Failed to send the invite: {err}"));
                                 }
                             }
-                            println!("User {user_id:?} clicked");
-                        }
-                    },
-                    "Direct conversation",
+                        },
+                        "Direct conversation",
+                    }
+                    {joined_groups_element}
                 }
-                {joined_groups_element}
             } else {
                 {user_info}
             }
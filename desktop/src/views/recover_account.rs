@@ -0,0 +1,91 @@
+use client::private_info;
+use dioxus::{logger::tracing::error, prelude::*};
+use server::ServerError;
+use shared::crypto::recovery;
+
+use super::register_account::check_password;
+use crate::{Route, enter_app};
+
+async fn recover_account(event: Event<FormData>, mut error_sig: Signal<Option<String>>) {
+    let values = event.values();
+    let code: &str = &values["code"].as_value();
+    let password: &str = &values["password"].as_value();
+
+    if !recovery::is_recovery_code(code) {
+        error_sig.set(Some("This is not a recovery code".to_owned()));
+        return;
+    }
+    if let Some(error) = check_password(password) {
+        error_sig.set(Some(error));
+        return;
+    }
+    error_sig.set(None);
+
+    match private_info::recover_account(code, password).await {
+        Ok(credentials) => enter_app(credentials),
+        Err(ServerFnError::WrappedServerError(
+            ServerError::AccountNotFound | ServerError::InvalidSignature,
+        )) => {
+            error_sig.set(Some(
+                "The recovery code is wrong or has already been used".to_owned(),
+            ));
+        }
+        Err(ServerFnError::WrappedServerError(ServerError::AccountBanned)) => {
+            error_sig.set(Some("This account is banned".to_owned()));
+        }
+        Err(err) => {
+            error!("Error while trying to recover account: {err:?}");
+            error_sig.set(Some(format!("Failed to recover account: {err}")));
+        }
+    }
+}
+
+/// Sets a new password for an account with the recovery code generated in the settings.
+#[component]
+pub fn RecoverAccount() -> Element {
+    let error: Signal<Option<String>> = use_signal(|| None);
+
+    rsx! {
+        div {
+            id: "center-container",
+
+            div {
+                id: "main-panel",
+
+                h2 { margin_top: 0, "Recover account" }
+                p { "Enter the recovery code you have saved and a new password. The code can only be used once." }
+
+                if let Some(error_message) = error() {
+                    div {
+                        class: "error-container",
+                        text_align: "center",
+                        margin_top: "8px",
+                        margin_bottom: "12px",
+                        p { "{error_message}" }
+                    }
+                }
+
+                form {
+                    onsubmit: move |event| recover_account(event, error),
+                    p { margin: 0, margin_bottom: "8px", "Recovery code" }
+                    input { name: "code", placeholder: "XXXX-XXXX-XXXX-XXXX-XXXX-XXXX-XXXX-XXXX" }
+                    br {}
+                    br {}
+                    p { margin: 0, margin_bottom: "8px", "New password" }
+                    input { name: "password", r#type: "password" }
+                    br {}
+                    br {}
+                    button {
+                        padding: "8px",
+                        width: "100%",
+                        "Recover account",
+                    }
+                }
+                p {
+                    text_align: "center",
+                    Link { to: Route::LoginAccount {}, "Back to login" }
+                }
+            }
+        }
+    }
+}
@@ -1,15 +1,33 @@
-use client::storage::STORAGE;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use client::{display, private_info, storage::STORAGE};
 use dioxus::{
     logger::tracing::{error, info},
     prelude::*,
 };
-use server::{AccountCredentials, SessionParams};
-use shared::{crypto, limits::LIMITS};
+use server::{AccountCredentials, ServerError, SessionParams};
+use shared::{
+    crypto::{self, proof_of_work},
+    limits::LIMITS,
+};
 
-use crate::Route;
+use crate::{Route, enter_app};
 
 const DEFAULT_SERVER: &str = "peregrine.werryxgames.com";
 
+/// Stops solving the proof of work when the registration is abandoned, as the blocking task keeps
+/// running after the future awaiting it is dropped.
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
 fn check_email(email: &str) -> Option<String> {
     // TODO: Use some crate for email-checking.
     // It is way harder than I expected.
@@ -84,7 +102,7 @@ fn check_username(_username: &str) -> Option<String> {
     None
 }
 
-fn check_password(password: &str) -> Option<String> {
+pub(super) fn check_password(password: &str) -> Option<String> {
     // TODO: Use some crate for password security checking
 
     if password.len() >= 32 {
@@ -139,6 +157,10 @@ pub fn RegisterAccount() -> Element {
         let email: &str = &values["email"].as_value();
         let username: &str = &values["username"].as_value();
         let password: &str = &values["password"].as_value();
+        let signup_code = values
+            .get("signup_code")
+            .map(|value| value.as_value())
+            .filter(|value| !value.is_empty());
         let server: String = if values.contains_key("server") {
             let value = values["server"].as_value();
             if value.is_empty() {
@@ -169,29 +191,88 @@ pub fn RegisterAccount() -> Element {
         }
 
         let (_private_key, public_key) =
-            crypto::kdf_keypair(&crypto::preferred_alogirthm(), password.as_bytes()).unwrap();
+            crypto::kdf_keypair(&STORAGE.algorithms(), password.as_bytes()).unwrap();
         info!(
             "Submitting form: email='{email}', username='{username}', server='{server}', public_key={public_key:?}"
         );
         error_sig.set(None);
-        let (_, x3dh_public) = STORAGE.x3dh_data(&crypto::preferred_alogirthm());
-        let (account_id, session_token) = server::create_account(
+        let proof = match server::get_registration_challenge().await {
+            Ok(Some(challenge)) if challenge.difficulty > proof_of_work::MAX_DIFFICULTY => {
+                error_sig.set(Some(
+                    "This server requires too much proof of work to register".to_owned(),
+                ));
+                return;
+            }
+            Ok(Some(challenge)) => {
+                error_sig.set(Some(
+                    "Verifying this device, it may take a while...".to_owned(),
+                ));
+                let cancelled = Arc::new(AtomicBool::new(false));
+                let _cancel = CancelOnDrop(cancelled.clone());
+                let proof = tokio::task::spawn_blocking(move || {
+                    proof_of_work::solve(&challenge, &cancelled)
+                })
+                .await;
+                error_sig.set(None);
+                match proof {
+                    Ok(Some(proof)) => Some(proof),
+                    Ok(None) => {
+                        error_sig.set(Some(
+                            "This server requires an unsupported proof of work".to_owned(),
+                        ));
+                        return;
+                    }
+                    Err(err) => {
+                        error!("Failed to solve proof of work: {err:?}");
+                        error_sig.set(Some("Failed to solve proof of work".to_owned()));
+                        return;
+                    }
+                }
+            }
+            Ok(None) => None,
+            Err(err) => {
+                error_sig.set(Some(format!("Server error: {err}")));
+                return;
+            }
+        };
+        let (_, x3dh_public) = STORAGE.x3dh_data(&STORAGE.algorithms());
+        let (account_id, session_token) = match server::create_account(
             email.to_owned(),
             username.to_owned(),
             public_key.pk,
             x3dh_public,
+            signup_code,
+            proof,
         )
         .await
-        .unwrap();
+        {
+            Ok(account) => account,
+            Err(ServerFnError::WrappedServerError(ServerError::RegistrationClosed)) => {
+                error_sig.set(Some("Registration on this server is closed".to_owned()));
+                return;
+            }
+            Err(ServerFnError::WrappedServerError(ServerError::InvalidSignupCode)) => {
+                error_sig.set(Some("Signup code is missing or invalid".to_owned()));
+                return;
+            }
+            Err(ServerFnError::WrappedServerError(ServerError::InvalidProofOfWork)) => {
+                error_sig.set(Some("Proof of work was rejected, try again".to_owned()));
+                return;
+            }
+            Err(err) => {
+                error_sig.set(Some(format!("Server error: {err}")));
+                return;
+            }
+        };
         let login_credentials = AccountCredentials {
             id: account_id,
             session_token,
         };
-        STORAGE.store_session_credentials(login_credentials);
-        let nav = navigator();
-        nav.replace(Route::Contacts {
-            credentials: login_credentials,
-        });
+        // The session still works if it isn't stored, it just has to be logged into again.
+        let _ = STORAGE.store_session_credentials(login_credentials);
+        private_info::remember_password(account_id, password);
+        private_info::upload_private_info(login_credentials).await;
+        enter_app(login_credentials);
         info!("Form submitted, session token: {session_token:?}");
     }
 
@@ -246,7 +327,7 @@ pub fn RegisterAccount() -> Element {
                             margin_bottom: "8px",
                             "Email "
                             b {
-                                color: "#b67de9",
+                                color: "var(--accent)",
                                 padding: 0,
                                 margin: 0,
                                 "*"
@@ -264,13 +345,21 @@ pub fn RegisterAccount() -> Element {
                             margin_bottom: "8px",
                             "Password "
                             b {
-                                color: "#b67de9",
+                                color: "var(--accent)",
                                 padding: 0,
                                 margin: 0,
                                 "*"
                             }
                         }
                         input { name: "password", margin_top: "8px", r#type: "password" }
+                        br {}
+                        br {}
+                        p { margin: 0, "Signup code" }
+                        input {
+                            name: "signup_code",
+                            margin_top: "8px",
+                            placeholder: "Only needed on invite-only servers",
+                        }
                         if advanced_mode() {
                             br {}
                             br {}
@@ -375,7 +464,7 @@ pub fn LoginAccount() -> Element {
         }
 
         let (private_key, public_key) =
-            crypto::kdf_keypair(&crypto::preferred_alogirthm(), password.as_bytes()).unwrap();
+            crypto::kdf_keypair(&STORAGE.algorithms(), password.as_bytes()).unwrap();
         let session_params = SessionParams {
             current_timestamp: chrono::Utc::now().timestamp().cast_unsigned(),
             authorize_before_seconds: LIMITS.max_session_before_period,
@@ -384,14 +473,14 @@ pub fn LoginAccount() -> Element {
         };
         let session_params_bytes = session_params.to_boxed_slice();
         let signature = crypto::sign(
-            &crypto::preferred_alogirthm(),
+            &STORAGE.algorithms(),
             private_key,
             public_key.clone(),
             &session_params_bytes,
         )
         .unwrap();
         if !crypto::verify(
-            &crypto::preferred_alogirthm(),
+            &STORAGE.algorithms(),
             public_key.clone(),
             &session_params_bytes,
             &signature,
@@ -407,16 +496,38 @@ pub fn LoginAccount() -> Element {
         );
         error_sig.set(None);
 
+        let client_time = session_params.current_timestamp;
+        // Keys on this device are only known to belong to the account if it was logged in before.
+        let identity_key = STORAGE.load_session_credentials().and_then(|_| {
+            STORAGE
+                .load_x3dh_data(&STORAGE.algorithms())
+                .map(|(_, cryptoidentity)| cryptoidentity.ik.pk)
+        });
         let (account_id, session_token) = match server::login_account(
             login.to_owned(),
-            crypto::preferred_alogirthm().signature,
+            STORAGE.algorithms().signature,
             public_key.pk,
             session_params,
             signature,
+            identity_key,
         )
         .await
         {
             Ok(value) => value,
+            Err(ServerFnError::WrappedServerError(ServerError::AccountBanned)) => {
+                error_sig.set(Some("This account is banned".to_owned()));
+                return;
+            }
+            Err(ServerFnError::WrappedServerError(ServerError::ClockSkew { server_time })) => {
+                error_sig.set(Some(display::clock_skew(client_time, server_time)));
+                return;
+            }
+            Err(ServerFnError::WrappedServerError(ServerError::IdentityMismatch)) => {
+                error_sig.set(Some(
+                    "The identity keys of this account have changed since it was last used on this device. Verify them with your contacts".to_owned(),
+                ));
+                return;
+            }
             Err(err) => {
                 eprintln!("Error while trying to log into account: {err:?}");
                 error_sig.set(Some("Invalid login or password".to_owned()));
@@ -427,11 +538,10 @@ pub fn LoginAccount() -> Element {
             id: account_id,
             session_token,
         };
-        STORAGE.store_session_credentials(login_credentials);
-        let nav = navigator();
-        nav.replace(Route::Contacts {
-            credentials: login_credentials,
-        });
+        let _ = STORAGE.store_session_credentials(login_credentials);
+        private_info::remember_password(account_id, password);
+        private_info::restore_private_info(login_credentials).await;
+        enter_app(login_credentials);
         info!("Form submitted, session token: {session_token:?}");
     }
 
@@ -486,7 +596,7 @@ pub fn LoginAccount() -> Element {
                             margin_bottom: "8px",
                             "Username or email "
                             b {
-                                color: "#b67de9",
+                                color: "var(--accent)",
                                 padding: 0,
                                 margin: 0,
                                 "*"
@@ -500,7 +610,7 @@ pub fn LoginAccount() -> Element {
                             margin_bottom: "8px",
                             "Password "
                             b {
-                                color: "#b67de9",
+                                color: "var(--accent)",
                                 padding: 0,
                                 margin: 0,
                                 "*"
@@ -544,6 +654,12 @@ pub fn LoginAccount() -> Element {
                         "Don't have an account? "
                         Link { to: Route::RegisterAccount {}, "Sign up" }
                     }
+                    p {
+                        text_align: "center",
+                        margin_bottom: "8px",
+                        "Forgot your password? "
+                        Link { to: Route::RecoverAccount {}, "Recover account" }
+                    }
                     if !advanced_mode() {
                         p {
                             text_align: "center",
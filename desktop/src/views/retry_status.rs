@@ -0,0 +1,24 @@
+use dioxus::prelude::*;
+
+/// Shows that a request made with `future_retry_loop!` keeps failing and is being retried, with
+/// a button to retry it right away instead of waiting.
+#[component]
+#[allow(non_snake_case)]
+pub fn RetryStatus(failures: Signal<u32>, resource: Resource<()>) -> Element {
+    if failures() == 0 {
+        return rsx!();
+    }
+    let attempt = failures() + 1;
+    rsx! {
+        p {
+            color: "var(--secondary-text)",
+            margin: "8px 20px",
+
+            "Retrying… (attempt {attempt}) "
+            button {
+                onclick: move |_| resource.restart(),
+                "Retry now"
+            }
+        }
+    }
+}
@@ -1,31 +1,45 @@
-use client::{future_retry_loop, packet_sender::PacketState};
+use client::{future_retry_loop, packet_sender::PacketState, timeouts};
 use dioxus::prelude::*;
-use server::AccountCredentials;
+use server::{AccountCredentials, ServerError};
 
-use crate::Route;
+use crate::{Route, enter_app};
 
 #[component]
 pub fn SessionValidityChecker(credentials: AccountCredentials) -> Element {
     let nav = navigator();
     let state_data = match future_retry_loop!(server::are_session_credentials_valid(credentials)) {
         PacketState::Response(true) => {
-            nav.replace(Route::Contacts { credentials });
+            enter_app(credentials);
             rsx! { h3 { "Loading resources" } }
         }
         PacketState::Response(false) => {
             nav.replace(Route::LoginAccount {});
             rsx! { h3 { "Invalid credentials" } }
         }
-        PacketState::Waiting => {
+        state @ (PacketState::Waiting | PacketState::NotStarted) => {
+            debug_assert_ne!(
+                state,
+                PacketState::NotStarted,
+                "the retry loop starts waiting"
+            );
             rsx! { h3 { "Checking credentials" } }
         }
+        PacketState::ServerError(ServerFnError::WrappedServerError(
+            ServerError::SessionExpired,
+        )) => {
+            nav.replace(Route::LoginAccount {});
+            rsx! { h3 { "Your session has expired, log in again" } }
+        }
+        PacketState::ServerError(ServerFnError::WrappedServerError(ServerError::AccountBanned)) => {
+            rsx! { h3 { "This account is banned" } }
+        }
         PacketState::ServerError(err) => {
             rsx! { h3 { "Server error: {err:?}" } }
         }
         PacketState::RequestTimeout => {
-            rsx! { h3 { "Request timeout" } }
+            let timeout = timeouts::timeouts().wait_timeout.as_secs();
+            rsx! { h3 { "No response in {timeout} seconds, retrying" } }
         }
-        PacketState::NotStarted => unreachable!(),
     };
     rsx! {
         h1 { "Loading..." },
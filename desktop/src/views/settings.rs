@@ -0,0 +1,314 @@
+use std::time::Duration;
+
+use client::{
+    display, markdown::MessageFormat, private_info, server_info, storage::STORAGE, theme::Theme,
+    timeouts,
+};
+use dioxus::prelude::*;
+use server::{AccountCredentials, ServerError};
+use shared::crypto::{self, CryptoAlgorithms, SupportedMethods};
+
+use super::register_account::check_password;
+use crate::session::Session;
+
+#[component]
+#[allow(non_snake_case)]
+fn SuiteOption(
+    algorithms: CryptoAlgorithms,
+    selected: Signal<CryptoAlgorithms>,
+    server_algorithms: Option<Vec<CryptoAlgorithms>>,
+    server_methods: Option<SupportedMethods>,
+    locked: bool,
+    save_error: Signal<Option<String>>,
+) -> Element {
+    let name = display::suite_name(&algorithms);
+    // Servers which don't report their suites may still support this one.
+    let unsupported = match server_methods {
+        Some(methods) => !methods.supports(&algorithms),
+        None => server_algorithms
+            .is_some_and(|supported| !supported.is_empty() && !supported.contains(&algorithms)),
+    };
+    let checked = selected() == algorithms;
+    let details = [
+        ("Hash", algorithms.hash.clone()),
+        ("Key derivation", algorithms.kdf.clone()),
+        ("Key exchange", algorithms.diffie_hellman.clone()),
+        ("Signature", algorithms.signature.clone()),
+        ("Encryption", algorithms.symmetric_encryption.clone()),
+        ("Authenticated encryption", algorithms.aead.clone()),
+        ("Random numbers", algorithms.rng.clone()),
+    ];
+    rsx! {
+        div {
+            margin_bottom: "12px",
+
+            label {
+                input {
+                    r#type: "radio",
+                    name: "crypto-suite",
+                    checked,
+                    disabled: locked,
+                    oninput: move |_| {
+                        if locked {
+                            return;
+                        }
+                        let algorithms = algorithms.clone();
+                        save_error.set(STORAGE.store_algorithms(algorithms.clone()).err().map(|err| err.to_string()));
+                        selected.set(algorithms);
+                    },
+                }
+                b { "{name}" }
+                if unsupported {
+                    " (not supported by the server)"
+                }
+            }
+            ul {
+                margin: "4px 0",
+
+                for (kind, algorithm) in details {
+                    li { "{kind}: {algorithm}" }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+#[allow(non_snake_case)]
+fn ChangePassword(credentials: AccountCredentials) -> Element {
+    let mut status = use_signal(|| None::<String>);
+    let mut session = use_context::<Signal<Session>>();
+
+    rsx! {
+        form {
+            onsubmit: move |event: Event<FormData>| async move {
+                let values = event.values();
+                let old_password = values["old_password"].as_value();
+                let new_password = values["new_password"].as_value();
+                if let Some(error) = check_password(&new_password) {
+                    status.set(Some(error));
+                    return;
+                }
+                status.set(Some("Changing password...".to_owned()));
+                let result = private_info::change_password(credentials, &old_password, &new_password).await;
+                status.set(Some(match result {
+                    Ok(credentials) => {
+                        // Other sessions were ended and this one got a new token.
+                        session.set(Session(Some(credentials)));
+                        "Password changed".to_owned()
+                    }
+                    Err(ServerFnError::WrappedServerError(ServerError::Forbidden | ServerError::InvalidSignature)) => {
+                        "The current password is wrong".to_owned()
+                    }
+                    Err(err) => format!("Failed to change password: {err}"),
+                }));
+            },
+            input {
+                name: "old_password",
+                r#type: "password",
+                placeholder: "Current password",
+                margin_right: "8px",
+            }
+            input {
+                name: "new_password",
+                r#type: "password",
+                placeholder: "New password",
+                margin_right: "8px",
+            }
+            button { "Change password" }
+        }
+        if let Some(status) = status() {
+            p { "{status}" }
+        }
+    }
+}
+
+#[component]
+#[allow(non_snake_case)]
+fn AccountRecovery(credentials: AccountCredentials) -> Element {
+    let mut code = use_signal(|| None::<String>);
+    let mut status = use_signal(|| None::<String>);
+
+    rsx! {
+        p { "A recovery code lets you set a new password if you forget the current one. Anyone who has it can take over your account." }
+        button {
+            margin_right: "8px",
+            onclick: move |_| async move {
+                match private_info::enable_recovery(credentials).await {
+                    Ok(new_code) => {
+                        code.set(Some(new_code));
+                        status.set(None);
+                    }
+                    Err(err) => status.set(Some(format!("Failed to enable recovery: {err}"))),
+                }
+            },
+            "Generate recovery code"
+        }
+        button {
+            onclick: move |_| async move {
+                code.set(None);
+                status.set(Some(match private_info::disable_recovery(credentials).await {
+                    Ok(()) => "Recovery disabled".to_owned(),
+                    Err(err) => format!("Failed to disable recovery: {err}"),
+                }));
+            },
+            "Disable recovery"
+        }
+        if let Some(code) = code() {
+            p { "Write this code down and keep it safe. It replaces the previous one and is shown only once:" }
+            p { font_family: "monospace", font_size: "1.2em", user_select: "all", "{code}" }
+        }
+        if let Some(status) = status() {
+            p { "{status}" }
+        }
+    }
+}
+
+#[component]
+#[allow(non_snake_case)]
+fn ReadStatePrivacy(credentials: AccountCredentials) -> Element {
+    let mut hidden = use_resource(move || server::is_read_state_hidden(credentials));
+    let mut status = use_signal(|| None::<String>);
+
+    rsx! {
+        label {
+            input {
+                r#type: "checkbox",
+                disabled: !matches!(hidden(), Some(Ok(_))),
+                checked: matches!(hidden(), Some(Ok(true))),
+                oninput: move |_| async move {
+                    let hide = !matches!(hidden(), Some(Ok(true)));
+                    match server::set_read_state_hidden(hide, credentials).await {
+                        Ok(()) => status.set(None),
+                        Err(err) => status.set(Some(format!("Failed to save the setting: {err}"))),
+                    }
+                    hidden.restart();
+                },
+            }
+            "Hide which group messages I have read"
+        }
+        p { "Senders and group admins won't see you among the readers of their messages." }
+        if let Some(Err(err)) = hidden() {
+            p { "Failed to load the setting: {err}" }
+        }
+        if let Some(status) = status() {
+            p { "{status}" }
+        }
+    }
+}
+
+#[component]
+pub fn Settings() -> Element {
+    let session = use_context::<Signal<Session>>();
+    let selected = use_signal(|| STORAGE.algorithms());
+    let mut save_error = use_signal(|| None::<String>);
+    let mut theme = use_context::<Signal<Theme>>();
+    let mut message_format = use_context::<Signal<MessageFormat>>();
+    let server_algorithms = use_resource(server_info::algorithms);
+    let server_methods = use_resource(server_info::methods);
+    let mut timeout_secs = use_signal(|| timeouts::timeouts().wait_timeout.as_secs());
+    // Keys of a registered account were generated with the stored suite. Switching it would
+    // generate an identity the server has never seen, so it can only be chosen before registering.
+    let suite_locked =
+        session().0.is_some() || STORAGE.load_x3dh_data(&STORAGE.algorithms()).is_some();
+
+    rsx! {
+        div {
+            height: "100%",
+            margin: "12px 24px",
+
+            button {
+                onclick: |_| {
+                    let nav = navigator();
+                    nav.go_back();
+                },
+                "Back"
+            }
+            h1 { "Settings" }
+            h2 { "Appearance" }
+            for option in Theme::ALL {
+                label {
+                    margin_right: "16px",
+
+                    input {
+                        r#type: "radio",
+                        name: "theme",
+                        checked: theme() == option,
+                        oninput: move |_| {
+                            save_error.set(STORAGE.store_theme(option).err().map(|err| err.to_string()));
+                            theme.set(option);
+                        },
+                    }
+                    "{option.name()}"
+                }
+            }
+            h2 { "Messages" }
+            for option in MessageFormat::ALL {
+                label {
+                    margin_right: "16px",
+
+                    input {
+                        r#type: "radio",
+                        name: "message-format",
+                        checked: message_format() == option,
+                        oninput: move |_| {
+                            save_error.set(STORAGE.store_message_format(option).err().map(|err| err.to_string()));
+                            message_format.set(option);
+                        },
+                    }
+                    "{option.name()}"
+                }
+            }
+            h2 { "Network" }
+            label {
+                "Request timeout, seconds: "
+                input {
+                    r#type: "number",
+                    min: "1",
+                    max: "300",
+                    value: "{timeout_secs}",
+                    onchange: move |event| {
+                        let Ok(secs) = event.value().parse::<u64>() else {
+                            return;
+                        };
+                        let secs = secs.clamp(1, 300);
+                        let mut config = timeouts::timeouts();
+                        config.wait_timeout = Duration::from_secs(secs);
+                        save_error.set(timeouts::set_timeouts(config).err().map(|err| err.to_string()));
+                        timeout_secs.set(secs);
+                    },
+                }
+            }
+            p { "Increase it on slow networks. File transfers are awaited longer." }
+            if let Some(credentials) = session().0 {
+                h2 { "Account" }
+                ChangePassword { credentials }
+                h3 { "Recovery" }
+                AccountRecovery { credentials }
+                h3 { "Privacy" }
+                ReadStatePrivacy { credentials }
+            }
+            h2 { "Crypto suite" }
+            p { "Suite used to generate your identity and encryption keys on this device." }
+            for algorithms in crypto::supported_algorithms().into_iter().filter(crypto::is_algorithm_supported) {
+                SuiteOption {
+                    algorithms,
+                    selected,
+                    server_algorithms: server_algorithms(),
+                    server_methods: server_methods().flatten(),
+                    locked: suite_locked,
+                    save_error,
+                }
+            }
+            if suite_locked {
+                p {
+                    color: "var(--warning-text)",
+                    "The suite can only be chosen before registering, since your account key, identity and the keys of existing conversations were generated with it."
+                }
+            }
+            if let Some(err) = save_error() {
+                p { "Failed to save the setting: {err}" }
+            }
+        }
+    }
+}
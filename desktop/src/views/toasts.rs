@@ -0,0 +1,73 @@
+use client::{connectivity::CONNECTIVITY, notifications::TOASTS};
+use dioxus::prelude::*;
+
+/// Errors reported with `notify_error`, shown on top of every screen.
+#[component]
+#[allow(non_snake_case)]
+pub fn ToastList() -> Element {
+    rsx! {
+        div {
+            class: "toast-list",
+
+            for toast in TOASTS.read().toasts().iter().cloned() {
+                div {
+                    key: "{toast.id}",
+                    class: "panel toast",
+
+                    p { "{toast.message}" }
+                    button {
+                        title: "Dismiss",
+                        onclick: move |_| TOASTS.write().dismiss(toast.id),
+                        "✕"
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Shown by the error boundary in place of a screen which failed to render.
+#[allow(non_snake_case)]
+pub fn ErrorScreen(errors: ErrorContext) -> Element {
+    let messages: Vec<String> = errors.errors().iter().map(ToString::to_string).collect();
+    rsx! {
+        div {
+            margin: "12px 24px",
+
+            h1 { "Something went wrong" }
+            for message in messages {
+                p { color: "var(--error-text)", "{message}" }
+            }
+            button {
+                onclick: {
+                    let errors = errors.clone();
+                    move |_| errors.clear_errors()
+                },
+                "Try again"
+            }
+            " "
+            button {
+                onclick: move |_| {
+                    errors.clear_errors();
+                    navigator().go_back();
+                },
+                "Back"
+            }
+        }
+    }
+}
+
+/// Shown while requests keep failing to reach the server.
+#[component]
+#[allow(non_snake_case)]
+pub fn OfflineBanner() -> Element {
+    if !CONNECTIVITY().is_offline() {
+        return rsx!();
+    }
+    rsx! {
+        div {
+            class: "offline-banner",
+            "You are offline. Messages you send are queued and will be sent once the connection is back."
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use bytes::Bytes;
+use dioxus::prelude::{
+    ServerFnError,
+    server_fn::{
+        codec::{Encoding, FromRes, IntoRes},
+        response::{ClientRes, Res},
+    },
+};
+use http::Method;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::page::Page;
+
+/// Server function output encoding which serializes the response with `postcard`. It is much
+/// more compact than the default JSON encoding, especially for byte arrays, which JSON encodes
+/// as arrays of numbers.
+///
+/// A server function using it as its output encoding should return [`PostcardBatch`].
+pub struct Postcard;
+
+impl Encoding for Postcard {
+    const CONTENT_TYPE: &'static str = "application/x-postcard";
+    const METHOD: Method = Method::POST;
+}
+
+/// Page of values sent with the [`Postcard`] encoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostcardBatch<T>(pub Page<T>);
+
+impl<CustErr, T, Response> IntoRes<Postcard, Response, CustErr> for PostcardBatch<T>
+where
+    Response: Res<CustErr>,
+    T: Serialize + Send,
+{
+    async fn into_res(self) -> Result<Response, ServerFnError<CustErr>> {
+        let data = postcard::to_allocvec(&self.0)
+            .map_err(|err| ServerFnError::Serialization(err.to_string()))?;
+        Response::try_from_bytes(Postcard::CONTENT_TYPE, Bytes::from(data))
+    }
+}
+
+impl<CustErr, T, Response> FromRes<Postcard, Response, CustErr> for PostcardBatch<T>
+where
+    Response: ClientRes<CustErr> + Send,
+    T: DeserializeOwned + Send,
+{
+    async fn from_res(res: Response) -> Result<Self, ServerFnError<CustErr>> {
+        let data = res.try_into_bytes().await?;
+        postcard::from_bytes(&data)
+            .map(Self)
+            .map_err(|err| ServerFnError::Deserialization(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{DmMessage, MessageStatus};
+
+    fn messages() -> Vec<DmMessage> {
+        (0..30)
+            .map(|id| DmMessage {
+                id,
+                encryption_method: "aes".to_owned(),
+                content: Some(vec![0xA5; 200].into_boxed_slice()),
+                reply_to: None,
+                edit_for: None,
+                sent_time: chrono::DateTime::from_timestamp(1_700_000_000 + id as i64, 0)
+                    .map(|time| time.naive_utc()),
+                sender_id: 2,
+                status: MessageStatus::SentByOther,
+                file_name: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_postcard_round_trip() {
+        let messages = messages();
+        let data = postcard::to_allocvec(&messages).unwrap();
+        assert_eq!(
+            postcard::from_bytes::<Vec<DmMessage>>(&data).unwrap(),
+            messages
+        );
+    }
+
+    #[test]
+    fn test_postcard_smaller_than_json() {
+        let messages = messages();
+        let postcard_size = postcard::to_allocvec(&messages).unwrap().len();
+        let json_size = serde_json::to_vec(&messages).unwrap().len();
+        assert!(
+            postcard_size * 2 < json_size,
+            "{postcard_size} vs {json_size}"
+        );
+    }
+}
@@ -1,5 +1,11 @@
+pub mod codec;
+#[cfg(feature = "server")]
+pub mod metrics;
+pub mod page;
 #[cfg(feature = "server")]
 pub mod secret;
+#[cfg(feature = "server")]
+pub mod shutdown;
 
 use std::{fmt::Display, str::FromStr};
 
@@ -8,22 +14,27 @@ use chrono::NaiveDateTime;
 #[cfg(feature = "server")]
 use chrono::{DateTime, TimeDelta, Utc};
 #[cfg(feature = "server")]
-use dioxus::logger::tracing::{debug, error, info};
+use dioxus::logger::tracing::{debug, error, info, warn};
 use dioxus::prelude::*;
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "server")]
-use shared::crypto::PublicKey;
-#[cfg(feature = "server")]
 use shared::limits::LIMITS;
-#[cfg(feature = "server")]
-use shared::types::GroupPermissions;
 use shared::{
-    crypto::{CryptoAlgorithms, x3dh::X3DhReceiverKeysPublic},
-    types::{File, UserIcon},
+    crypto::{
+        CryptoAlgorithms, PublicKey, SupportedMethods,
+        proof_of_work::{Challenge, Proof},
+        x3dh::X3DhReceiverKeysPublic,
+    },
+    types::{File, GroupPermissions, UserIcon},
 };
 
 #[cfg(feature = "server")]
-use crate::secret::db::DB;
+use crate::metrics::METRICS;
+#[cfg(feature = "server")]
+use crate::page::LIST_PAGE_SIZE;
+use crate::page::Page;
+#[cfg(feature = "server")]
+use crate::secret::db::{AccountRecovery, ChunkUpload, DB, Db, NewReport, OpkUpload, UploadFinish};
 #[cfg(feature = "server")]
 use crate::secret::storage::STORAGE;
 #[cfg(feature = "server")]
@@ -33,14 +44,23 @@ use shared::storage::{GeneralStorage, RawStorage};
 pub enum ServerError {
     InternalDatabaseError,
     InvalidSessionToken,
+    /// The session was valid, but has ended, so the user has to log in again.
+    SessionExpired,
     Forbidden,
     GroupPartiallyCreated(u64),
     InvalidArgumentSize,
     InvalidValue,
     InvalidUserId,
     LimitExceeded,
+    /// Not sent anymore, see [`Self::ClockSkew`].
     SignatureEarly,
+    /// Not sent anymore, see [`Self::ClockSkew`].
     SignatureExpired,
+    /// The login was signed too long before or after the current time of the server, which is
+    /// `server_time` (a Unix timestamp). Usually the clock of the client is wrong.
+    ClockSkew {
+        server_time: u64,
+    },
     InvalidSignature,
     UnsupportedCryptographicAlgorithm,
     AccountNotFound,
@@ -49,6 +69,18 @@ pub enum ServerError {
     InvalidGroupId,
     ActionOnSelfIsForbidden,
     FileNotFound,
+    UploadIncomplete,
+    InviteExpired,
+    /// The user already has a pending invite to the group.
+    InviteAlreadyExists,
+    RegistrationClosed,
+    InvalidSignupCode,
+    InvalidProofOfWork,
+    AccountBanned,
+    CorruptIdentity,
+    /// The identity key stored on the server isn't the one the client remembers for the account,
+    /// so contacts need to verify it again.
+    IdentityMismatch,
 }
 
 impl FromStr for ServerError {
@@ -58,6 +90,7 @@ impl FromStr for ServerError {
         match s {
             "InternalDatabaseError" => Ok(Self::InternalDatabaseError),
             "InvalidSessionToken" => Ok(Self::InvalidSessionToken),
+            "SessionExpired" => Ok(Self::SessionExpired),
             "Forbidden" => Ok(Self::Forbidden),
             "InvalidArgumentSize" => Ok(Self::InvalidArgumentSize),
             "InvalidValue" => Ok(Self::InvalidValue),
@@ -73,17 +106,26 @@ impl FromStr for ServerError {
             "InvalidGroupId" => Ok(Self::InvalidGroupId),
             "ActionOnSelfIsForbidden" => Ok(Self::ActionOnSelfIsForbidden),
             "FileNotFound" => Ok(Self::FileNotFound),
+            "UploadIncomplete" => Ok(Self::UploadIncomplete),
+            "InviteExpired" => Ok(Self::InviteExpired),
+            "InviteAlreadyExists" => Ok(Self::InviteAlreadyExists),
+            "RegistrationClosed" => Ok(Self::RegistrationClosed),
+            "InvalidSignupCode" => Ok(Self::InvalidSignupCode),
+            "InvalidProofOfWork" => Ok(Self::InvalidProofOfWork),
+            "AccountBanned" => Ok(Self::AccountBanned),
+            "CorruptIdentity" => Ok(Self::CorruptIdentity),
+            "IdentityMismatch" => Ok(Self::IdentityMismatch),
             _ => {
                 let Some(s_split) = s.split_once(':') else {
                     return Err(());
                 };
-                if s_split.0 == "GroupPartiallyCreated" {
-                    let Ok(id) = s_split.1.parse::<u64>() else {
-                        return Err(());
-                    };
-                    Ok(Self::GroupPartiallyCreated(id))
-                } else {
-                    Err(())
+                let Ok(value) = s_split.1.parse::<u64>() else {
+                    return Err(());
+                };
+                match s_split.0 {
+                    "GroupPartiallyCreated" => Ok(Self::GroupPartiallyCreated(value)),
+                    "ClockSkew" => Ok(Self::ClockSkew { server_time: value }),
+                    _ => Err(()),
                 }
             }
         }
@@ -95,6 +137,7 @@ impl Display for ServerError {
         f.write_str(&match *self {
             Self::InternalDatabaseError => "InternalDatabaseError".to_owned(),
             Self::InvalidSessionToken => "InvalidSessionToken".to_owned(),
+            Self::SessionExpired => "SessionExpired".to_owned(),
             Self::Forbidden => "Forbidden".to_owned(),
             Self::GroupPartiallyCreated(id) => format!("GroupPartiallyCreated:{id}"),
             Self::InvalidArgumentSize => "InvalidArgumentSize".to_owned(),
@@ -103,6 +146,7 @@ impl Display for ServerError {
             Self::LimitExceeded => "LimitExceeded".to_owned(),
             Self::SignatureEarly => "SignatureEarly".to_owned(),
             Self::SignatureExpired => "SignatureExpired".to_owned(),
+            Self::ClockSkew { server_time } => format!("ClockSkew:{server_time}"),
             Self::InvalidSignature => "InvalidSignature".to_owned(),
             Self::UnsupportedCryptographicAlgorithm => {
                 "UnsupportedCryptographicAlgorithm".to_owned()
@@ -113,11 +157,24 @@ impl Display for ServerError {
             Self::InvalidGroupId => "InvalidGroupId".to_owned(),
             Self::ActionOnSelfIsForbidden => "ActionOnSelfIsForbidden".to_owned(),
             Self::FileNotFound => "FileNotFound".to_owned(),
+            Self::UploadIncomplete => "UploadIncomplete".to_owned(),
+            Self::InviteExpired => "InviteExpired".to_owned(),
+            Self::InviteAlreadyExists => "InviteAlreadyExists".to_owned(),
+            Self::RegistrationClosed => "RegistrationClosed".to_owned(),
+            Self::InvalidSignupCode => "InvalidSignupCode".to_owned(),
+            Self::InvalidProofOfWork => "InvalidProofOfWork".to_owned(),
+            Self::AccountBanned => "AccountBanned".to_owned(),
+            Self::CorruptIdentity => "CorruptIdentity".to_owned(),
+            Self::IdentityMismatch => "IdentityMismatch".to_owned(),
         })?;
         Ok(())
     }
 }
 
+/// Server functions still have to return it as `ServerFnError::WrappedServerError`. Converting it
+/// with `?` makes a `ServerFnError::ServerError` with just the message, which clients don't parse.
+impl std::error::Error for ServerError {}
+
 #[cfg(feature = "server")]
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Account {
@@ -138,6 +195,34 @@ pub struct UserAccount {
     pub icon: UserIcon,
 }
 
+/// Profile of the account which made the request, returned by [`get_self`]. Unlike [`Account`],
+/// it doesn't contain the encrypted private info.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnAccount {
+    pub id: u64,
+    pub email: Option<String>,
+    pub username: Option<String>,
+    pub icon: UserIcon,
+}
+
+/// Keys needed to start an encrypted conversation with a user, without any profile data.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrekeyBundle {
+    /// Identity of the user without the one-time prekeys, `opks` is always empty.
+    pub cryptoidentity: X3DhReceiverKeysPublic,
+    /// Index and public key of the one-time prekey claimed for the caller. `None` if it wasn't
+    /// requested or the user has run out of them.
+    pub opk: Option<(u32, PublicKey)>,
+}
+
+/// Part of an uploaded file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRange {
+    /// Size of the whole file, so that the client knows when to stop downloading.
+    pub total_size: u64,
+    pub bytes: Box<[u8]>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FoundAccount {
     pub id: u64,
@@ -147,6 +232,102 @@ pub struct FoundAccount {
     pub email: Option<String>,
 }
 
+/// Counters of the server load since it was started, returned by [`get_metrics`]. All of them
+/// only grow, so rates like messages per second are computed from the difference between two
+/// snapshots.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerMetrics {
+    pub uptime_seconds: u64,
+    /// Counters of each endpoint which was called at least once.
+    pub endpoints: Vec<EndpointMetrics>,
+    pub messages_sent: u64,
+    pub active_sessions: u64,
+    /// Number of times an account was left out of search results because its stored identity
+    /// is corrupt. Anything other than 0 means the database needs to be repaired.
+    pub corrupt_identities: u64,
+}
+
+/// Upper bounds in milliseconds of the buckets of [`EndpointMetrics::latency_buckets`].
+pub const LATENCY_BUCKETS_MS: [u64; 11] = [1, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EndpointMetrics {
+    pub endpoint: String,
+    pub requests: u64,
+    /// Number of calls which took longer than the previous bound of [`LATENCY_BUCKETS_MS`], but
+    /// not longer than the matching one. The last bucket counts calls slower than all bounds.
+    pub latency_buckets: Vec<u64>,
+    pub total_latency_micros: u64,
+}
+
+/// Account as seen by server admins.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountSummary {
+    pub id: u64,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub banned: bool,
+}
+
+#[cfg(feature = "server")]
+impl Account {
+    pub fn into_user_account(self, icon: UserIcon) -> UserAccount {
+        UserAccount {
+            cryptoidentity: self.cryptoidentity,
+            public_key: self.public_key,
+            email: self.email,
+            username: self.username,
+            icon,
+        }
+    }
+
+    pub fn into_own_account(self, icon: UserIcon) -> OwnAccount {
+        OwnAccount {
+            id: self.id,
+            email: self.email,
+            username: self.username,
+            icon,
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl From<Account> for FoundAccount {
+    fn from(account: Account) -> Self {
+        Self {
+            id: account.id,
+            cryptoidentity: account.cryptoidentity,
+            public_key: account.public_key,
+            username: account.username,
+            email: account.email,
+        }
+    }
+}
+
+impl From<FoundAccount> for UserAccount {
+    fn from(account: FoundAccount) -> Self {
+        Self {
+            cryptoidentity: account.cryptoidentity,
+            public_key: account.public_key,
+            email: account.email,
+            username: account.username,
+            icon: None,
+        }
+    }
+}
+
+impl FoundAccount {
+    pub fn from_user_account(id: u64, account: UserAccount) -> Self {
+        Self {
+            id,
+            cryptoidentity: account.cryptoidentity,
+            public_key: account.public_key,
+            username: account.username,
+            email: account.email,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MessageStatus {
     SentByOther,
@@ -162,6 +343,7 @@ pub struct DmMessage {
     pub reply_to: Option<u64>,
     pub edit_for: Option<u64>,
     pub sent_time: Option<NaiveDateTime>,
+    pub sender_id: u64,
     pub status: MessageStatus,
     pub file_name: Option<Box<[u8]>>,
 }
@@ -178,6 +360,14 @@ pub struct GroupMessage {
     pub file_name: Option<Box<[u8]>>,
 }
 
+/// Messages which a new message replies to or edits. Both must belong to the same group as the
+/// new message, and only own messages can be edited.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageReferences {
+    pub reply_to: Option<u64>,
+    pub edit_for: Option<u64>,
+}
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AccountCredentials {
     pub id: u64,
@@ -190,6 +380,8 @@ pub struct DmInvite {
     pub initiator_id: u64,
     pub other_id: u64,
     pub encryption_data: Option<Box<[u8]>>,
+    /// Time (in UTC) after which the invite can't be accepted. `None` if it never expires.
+    pub expires_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -200,6 +392,65 @@ pub struct GroupInvite {
     pub group_id: u64,
     pub permissions: Box<[u8]>,
     pub encryption_data: Option<Box<[u8]>>,
+    /// Time (in UTC) after which the invite can't be accepted. `None` if it never expires.
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+/// Message which a report refers to. `dm` tells whether `group_id` is a DM group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportedMessage {
+    pub group_id: u64,
+    pub dm: bool,
+    pub message_id: u64,
+}
+
+/// Abuse report waiting for admin review.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    pub id: u64,
+    pub reporter_id: u64,
+    pub reported_user_id: u64,
+    /// `None` if the user was reported as a whole.
+    pub message: Option<ReportedMessage>,
+    pub reason: String,
+    pub created_at: NaiveDateTime,
+}
+
+/// Received DM invite together with the account of the user who sent it. `initiator` is `None`
+/// if that account has been deleted or its stored identity is corrupt, which
+/// `initiator_identity_corrupt` tells apart.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetailedDmInvite {
+    pub invite: DmInvite,
+    pub initiator: Option<UserAccount>,
+    pub initiator_identity_corrupt: bool,
+}
+
+/// Received group invite together with the account of the inviter and the group. The group is
+/// included even if it's private, as the invited user can't fetch it before joining. `inviter` is
+/// `None` the same way as `initiator` of [`DetailedDmInvite`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DetailedGroupInvite {
+    pub invite: GroupInvite,
+    pub inviter: Option<UserAccount>,
+    pub inviter_identity_corrupt: bool,
+    pub group: Option<MultiUserGroup>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceivedInvites {
+    pub dm_invites: Vec<DetailedDmInvite>,
+    pub group_invites: Vec<DetailedGroupInvite>,
+}
+
+/// Sent and received DM and group invites of a user. Unlike [`ReceivedInvites`], the received ones
+/// come without the accounts of their senders.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllInvites {
+    pub sent_dm_invites: Page<DmInvite>,
+    pub sent_group_invites: Page<GroupInvite>,
+    pub received_dm_invites: Page<DmInvite>,
+    pub received_group_invites: Page<GroupInvite>,
 }
 
 /// Describes parameters of a requested session.
@@ -237,16 +488,65 @@ pub struct MultiUserGroup {
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GroupMember {
     pub user_id: u64,
+    /// Same as `permissions.is_admin()`. Kept for older clients.
     pub is_admin: bool,
+    pub permissions: GroupPermissions,
+}
+
+/// Optional feature of the server which clients may use if it's listed in [`ServerInfo`].
+pub const CAPABILITY_POSTCARD_BATCHES: &str = "postcard_batches";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub capabilities: Vec<String>,
+    /// Crypto suites the server can verify, in the order of preference. Servers before these were
+    /// reported don't send it.
+    #[serde(default)]
+    pub algorithms: Vec<CryptoAlgorithms>,
+    /// Algorithms of each kind the server can dispatch. Empty for servers before these were
+    /// reported.
+    #[serde(default)]
+    pub methods: SupportedMethods,
+}
+
+impl ServerInfo {
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities
+            .iter()
+            .any(|supported| supported == capability)
+    }
+}
+
+/// Returned when a string isn't [`AccountCredentials`] formatted with [`Display`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CredentialsParseError {
+    InvalidBase64,
+    /// The decoded bytes have the given length instead of 40.
+    InvalidLength(usize),
+}
+
+impl Display for CredentialsParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::InvalidBase64 => f.write_str("Credentials are not valid base64"),
+            Self::InvalidLength(length) => {
+                write!(f, "Credentials are {length} bytes long instead of 40")
+            }
+        }
+    }
 }
 
+impl std::error::Error for CredentialsParseError {}
+
 impl FromStr for AccountCredentials {
-    type Err = usize;
+    type Err = CredentialsParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let bytes = BASE64_URL_SAFE_NO_PAD.decode(s).unwrap_or_default();
+        let bytes = BASE64_URL_SAFE_NO_PAD
+            .decode(s)
+            .map_err(|_| CredentialsParseError::InvalidBase64)?;
         if bytes.len() != 40 {
-            return Err(bytes.len());
+            return Err(CredentialsParseError::InvalidLength(bytes.len()));
         }
         let id = u64::from_le_bytes(bytes[..8].try_into().unwrap());
         let session_token: [u8; 32] = bytes[8..].try_into().unwrap();
@@ -276,13 +576,42 @@ impl SessionParams {
     }
 }
 
+/// Data signed by the key derived from the recovery code of the account to hand the account over
+/// to the `new_public_key` derived from a new password, see [`recover_account`].
+pub fn recovery_message(new_public_key: &[u8]) -> Box<[u8]> {
+    let mut result: Vec<u8> = b"peregrine-account-recovery".to_vec();
+    result.extend(new_public_key);
+    result.into_boxed_slice()
+}
+
+/// Data signed by the current login key `old_public_key` of the account at `signed_at` seconds
+/// since Unix epoch to hand the account over to the `new_public_key` derived from a new password,
+/// see [`change_password`].
+pub fn password_change_message(
+    account_id: u64,
+    old_public_key: &[u8],
+    new_public_key: &[u8],
+    signed_at: u64,
+) -> Box<[u8]> {
+    let mut result: Vec<u8> = b"peregrine-password-change".to_vec();
+    result.extend(account_id.to_le_bytes());
+    result.extend(signed_at.to_le_bytes());
+    result.extend((old_public_key.len() as u64).to_le_bytes());
+    result.extend(old_public_key);
+    result.extend(new_public_key);
+    result.into_boxed_slice()
+}
+
 #[server(endpoint = "create_account")]
 pub async fn create_account(
     email: String,
     username: String,
     public_key: Box<[u8]>,
     cryptoidentity: X3DhReceiverKeysPublic,
+    signup_code: Option<String>,
+    proof: Option<Proof>,
 ) -> Result<(u64, [u8; 32]), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("create_account");
     if email.len() > LIMITS.max_email_length
         || public_key.len() > LIMITS.max_public_key_length
         || username.len() > LIMITS.max_username_length
@@ -292,18 +621,55 @@ pub async fn create_account(
         ));
     }
 
-    match DB.create_account(
-        &public_key,
-        cryptoidentity,
-        &[],
-        Some(&email),
-        if username.is_empty() {
-            None
-        } else {
-            Some(&username)
-        },
+    let signup_code = match secret::registration::check_registration(
+        *secret::config::REGISTRATION_MODE,
+        signup_code.as_deref(),
+    ) {
+        Ok(signup_code) => signup_code,
+        Err(err) => return Err(ServerFnError::WrappedServerError(err)),
+    };
+
+    match secret::registration::check_proof_of_work(
+        &*secret::registration::CHALLENGE_KEY,
+        &shared::crypto::preferred_alogirthm(),
+        *secret::config::POW_DIFFICULTY,
+        proof.as_ref(),
+        chrono::Utc::now().naive_utc(),
+        |challenge, issued_at| DB.use_pow_challenge(challenge, issued_at),
     ) {
-        Ok(account_id) => {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => return Err(ServerFnError::WrappedServerError(err)),
+        Err(err) => {
+            error!("Failed to consume proof of work challenge: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    }
+
+    let username = if username.is_empty() {
+        None
+    } else {
+        Some(username.as_str())
+    };
+    let account_id = match signup_code {
+        Some(code) => DB.create_account_with_signup_code(
+            &public_key,
+            cryptoidentity,
+            &[],
+            Some(&email),
+            username,
+            code,
+        ),
+        None => DB
+            .create_account(&public_key, cryptoidentity, &[], Some(&email), username)
+            .map(Some),
+    };
+    match account_id {
+        Ok(None) => Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidSignupCode,
+        )),
+        Ok(Some(account_id)) => {
             info!("New account created: {account_id}");
             match DB.create_session(account_id, None, None) {
                 Ok(session_id) => {
@@ -327,14 +693,258 @@ pub async fn create_account(
     }
 }
 
+/// Issues a proof of work challenge which has to be solved to create an account. Returns `None`
+/// if the server doesn't require proof of work.
+#[server(endpoint = "get_registration_challenge")]
+pub async fn get_registration_challenge() -> Result<Option<Challenge>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_registration_challenge");
+    let difficulty = *secret::config::POW_DIFFICULTY;
+    if difficulty == 0 {
+        return Ok(None);
+    }
+
+    // Challenges are signed instead of stored, so requesting them doesn't grow the database.
+    match secret::registration::issue_challenge(
+        &*secret::registration::CHALLENGE_KEY,
+        &shared::crypto::preferred_alogirthm(),
+        difficulty,
+        chrono::Utc::now().naive_utc(),
+    ) {
+        Some(challenge) => Ok(Some(challenge)),
+        None => {
+            error!("Failed to issue proof of work challenge: hash algorithm is not supported");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+/// Returns `Forbidden` unless the user is a server admin.
+#[cfg(feature = "server")]
+fn check_is_admin(user_id: u64) -> Result<(), ServerFnError<ServerError>> {
+    if secret::config::ADMIN_IDS.contains(&user_id) {
+        Ok(())
+    } else {
+        Err(ServerFnError::WrappedServerError(ServerError::Forbidden))
+    }
+}
+
+/// Mints a single-use code which allows creating an account while registration is invite-only.
+/// Only server admins may call it.
+#[server(endpoint = "create_signup_code")]
+pub async fn create_signup_code(
+    credentials: AccountCredentials,
+) -> Result<String, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("create_signup_code");
+    check_session(credentials)?;
+    check_is_admin(credentials.id)?;
+
+    let mut bytes = [0u8; 16];
+    secret::db::rng::fill_bytes(&mut bytes);
+    let code = BASE64_URL_SAFE_NO_PAD.encode(bytes);
+
+    match DB.add_signup_code(&code, credentials.id) {
+        Ok(()) => Ok(code),
+        Err(err) => {
+            error!("Failed to add signup code: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+/// Returns load counters of the server. Only server admins may call it.
+#[server(endpoint = "get_metrics")]
+pub async fn get_metrics(
+    credentials: AccountCredentials,
+) -> Result<ServerMetrics, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_metrics");
+    check_session(credentials)?;
+    check_is_admin(credentials.id)?;
+
+    let active_sessions = match DB.count_active_sessions() {
+        Ok(count) => count,
+        Err(err) => {
+            error!("Failed to count active sessions: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+    Ok(ServerMetrics {
+        uptime_seconds: METRICS.uptime_seconds(),
+        endpoints: METRICS.endpoint_metrics(),
+        messages_sent: METRICS.messages_sent(),
+        active_sessions,
+        corrupt_identities: METRICS.corrupt_identities(),
+    })
+}
+
+/// Returns accounts with ids greater than `after_id`, ordered by id. Only server admins may
+/// call it.
+#[server(endpoint = "list_accounts")]
+pub async fn list_accounts(
+    after_id: Option<u64>,
+    batch_size: Option<u32>,
+    credentials: AccountCredentials,
+) -> Result<Vec<AccountSummary>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("list_accounts");
+    check_session(credentials)?;
+    check_is_admin(credentials.id)?;
+    let batch_size = fetch_batch_size(batch_size)?;
+
+    match DB.list_accounts(after_id.unwrap_or(0), batch_size) {
+        Ok(accounts) => Ok(accounts),
+        Err(err) => {
+            error!("Failed to list accounts: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+fn set_account_banned(
+    user_id: u64,
+    banned: bool,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    check_session(credentials)?;
+    check_is_admin(credentials.id)?;
+    if user_id == credentials.id {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::ActionOnSelfIsForbidden,
+        ));
+    }
+    // Admins are configured outside of the database, so banning them would have no lasting effect.
+    if secret::config::ADMIN_IDS.contains(&user_id) {
+        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+    }
+    check_user(user_id)?;
+
+    match DB.set_account_banned(user_id, banned) {
+        Ok(()) => {
+            let admin_id = credentials.id;
+            info!("Account {user_id} banned: {banned}, by admin {admin_id}");
+            Ok(())
+        }
+        Err(err) => {
+            error!("Failed to set account ban: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+/// Prevents the user from logging in or using any of their sessions. Only server admins may call
+/// it.
+#[server(endpoint = "ban_account")]
+pub async fn ban_account(
+    user_id: u64,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("ban_account");
+    set_account_banned(user_id, true, credentials)
+}
+
+/// Lifts the ban set with [`ban_account`]. Only server admins may call it.
+#[server(endpoint = "unban_account")]
+pub async fn unban_account(
+    user_id: u64,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("unban_account");
+    set_account_banned(user_id, false, credentials)
+}
+
+/// Returns `ClockSkew` unless the server's time `unix_secs_now` is within the window around the
+/// time at which the client has signed the login.
+#[cfg(feature = "server")]
+fn check_login_time(session_params: &SessionParams, unix_secs_now: u64) -> Result<(), ServerError> {
+    let earliest = session_params
+        .current_timestamp
+        .saturating_sub(session_params.authorize_before_seconds as u64);
+    let latest = session_params
+        .current_timestamp
+        .saturating_add(session_params.authorize_after_seconds as u64);
+    if (earliest..=latest).contains(&unix_secs_now) {
+        Ok(())
+    } else {
+        Err(ServerError::ClockSkew {
+            server_time: unix_secs_now,
+        })
+    }
+}
+
+/// Resolves the account logging in by its username or email `handle`, which must be registered
+/// with `public_key`. If the client remembers the account's `identity_key`, it must still be the
+/// stored one.
+#[cfg(feature = "server")]
+fn find_login_account_in(
+    db: &impl Db,
+    handle: String,
+    public_key: &[u8],
+    identity_key: Option<&[u8]>,
+) -> Result<u64, ServerFnError<ServerError>> {
+    let id = match db.find_user_with_pubkey(handle, public_key) {
+        Ok(Some(id)) => id,
+        Ok(None) => {
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::AccountNotFound,
+            ));
+        }
+        Err(err) => {
+            error!("Failed to check if user has pubkey while loggin into account: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    match db.is_account_banned(id) {
+        Ok(false) => {}
+        Ok(true) => {
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::AccountBanned,
+            ));
+        }
+        Err(err) => {
+            error!("Failed to check if account is banned: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    }
+
+    let Some(identity_key) = identity_key else {
+        return Ok(id);
+    };
+    match db.identity_key(id) {
+        Ok(Some(stored)) if *stored == *identity_key => Ok(id),
+        Ok(_) => Err(ServerFnError::WrappedServerError(
+            ServerError::IdentityMismatch,
+        )),
+        Err(err) => {
+            error!("Failed to load identity key while logging into account: {err:?}");
+            Err(ServerFnError::WrappedServerError(account_error(&*err)))
+        }
+    }
+}
+
 #[server(endpoint = "login_account")]
 pub async fn login_account(
-    username: String,
+    handle: String,
     login_algorithm: String,
     public_key: Box<[u8]>,
     session_params: SessionParams,
     signature: Box<[u8]>,
+    identity_key: Option<Box<[u8]>>,
 ) -> Result<(u64, [u8; 32]), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("login_account");
     if session_params.authorize_before_seconds > LIMITS.max_session_before_period
         || session_params.authorize_after_seconds > LIMITS.max_session_after_period
         || session_params.session_validity_seconds > LIMITS.max_session_validity_period
@@ -356,7 +966,11 @@ pub async fn login_account(
             ServerError::LimitExceeded,
         ));
     };
-    if public_key.len() > LIMITS.max_public_key_length {
+    if public_key.len() > LIMITS.max_public_key_length
+        || identity_key
+            .as_ref()
+            .is_some_and(|key| key.len() > LIMITS.max_public_key_length)
+    {
         return Err(ServerFnError::WrappedServerError(
             ServerError::LimitExceeded,
         ));
@@ -365,21 +979,7 @@ pub async fn login_account(
         .signed_duration_since(DateTime::UNIX_EPOCH)
         .num_seconds()
         .cast_unsigned();
-
-    if unix_secs_now
-        < session_params.current_timestamp - session_params.authorize_before_seconds as u64
-    {
-        return Err(ServerFnError::WrappedServerError(
-            ServerError::SignatureEarly,
-        ));
-    }
-    if unix_secs_now
-        > session_params.current_timestamp + session_params.authorize_after_seconds as u64
-    {
-        return Err(ServerFnError::WrappedServerError(
-            ServerError::SignatureExpired,
-        ));
-    }
+    check_login_time(&session_params, unix_secs_now).map_err(ServerFnError::WrappedServerError)?;
 
     let data = &session_params.to_boxed_slice();
 
@@ -402,23 +1002,7 @@ pub async fn login_account(
         ));
     }
 
-    let id = match DB.find_user_with_pubkey(username, &public_key) {
-        Ok(result) => {
-            if let Some(result) = result {
-                result
-            } else {
-                return Err(ServerFnError::WrappedServerError(
-                    ServerError::AccountNotFound,
-                ));
-            }
-        }
-        Err(err) => {
-            error!("Failed to check if user has pubkey while loggin into account: {err:?}");
-            return Err(ServerFnError::WrappedServerError(
-                ServerError::InternalDatabaseError,
-            ));
-        }
-    };
+    let id = find_login_account_in(&*DB, handle, &public_key, identity_key.as_deref())?;
 
     match DB.create_session(
         id,
@@ -440,16 +1024,40 @@ pub async fn login_account(
 
 #[cfg(feature = "server")]
 fn check_session(credentials: AccountCredentials) -> Result<(), ServerFnError<ServerError>> {
-    match secret::db::DB.is_session_valid(credentials.id, credentials.session_token) {
-        Ok(is_valid) => {
-            if is_valid {
-                Ok(())
-            } else {
-                Err(ServerFnError::WrappedServerError(
-                    ServerError::InvalidSessionToken,
-                ))
+    check_session_in(&*DB, credentials)
+}
+
+#[cfg(feature = "server")]
+fn check_session_in(
+    db: &impl Db,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    use secret::db::SessionStatus;
+
+    match db.session_status(credentials.id, credentials.session_token) {
+        Ok(SessionStatus::Active { banned: false }) => {
+            if LIMITS.session_extension_period > 0
+                && let Err(err) = db.touch_session(
+                    credentials.id,
+                    credentials.session_token,
+                    LIMITS.session_extension_period,
+                    LIMITS.max_session_lifetime,
+                )
+            {
+                // The session is still valid until its current end, so the request goes on.
+                error!("Failed to extend session: {err:?}");
             }
+            Ok(())
         }
+        Ok(SessionStatus::Active { banned: true }) => Err(ServerFnError::WrappedServerError(
+            ServerError::AccountBanned,
+        )),
+        Ok(SessionStatus::Expired) => Err(ServerFnError::WrappedServerError(
+            ServerError::SessionExpired,
+        )),
+        Ok(SessionStatus::Unknown) => Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidSessionToken,
+        )),
         Err(err) => {
             error!("Failed to check if session is valid: {err:?}");
             Err(ServerFnError::WrappedServerError(
@@ -459,11 +1067,34 @@ fn check_session(credentials: AccountCredentials) -> Result<(), ServerFnError<Se
     }
 }
 
+/// Returns optional features supported by this server, so that newer clients can use them while
+/// still working with older servers.
+#[server(endpoint = "server_info")]
+pub async fn get_server_info() -> Result<ServerInfo, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("server_info");
+    Ok(ServerInfo {
+        capabilities: vec![CAPABILITY_POSTCARD_BATCHES.to_owned()],
+        algorithms: shared::crypto::supported_algorithms(),
+        methods: shared::crypto::supported_methods(),
+    })
+}
+
+/// Returns `false` for credentials which never were valid. Expired ones are reported with
+/// [`ServerError::SessionExpired`] instead.
 #[server(endpoint = "are_session_credentials_valid")]
 pub async fn are_session_credentials_valid(
     credentials: AccountCredentials,
 ) -> Result<bool, ServerFnError<ServerError>> {
-    match check_session(credentials) {
+    let _timer = METRICS.track_request("are_session_credentials_valid");
+    session_credentials_valid(&*DB, credentials)
+}
+
+#[cfg(feature = "server")]
+fn session_credentials_valid(
+    db: &impl Db,
+    credentials: AccountCredentials,
+) -> Result<bool, ServerFnError<ServerError>> {
+    match check_session_in(db, credentials) {
         Ok(()) => Ok(true),
         Err(err) => {
             if err == ServerFnError::WrappedServerError(ServerError::InvalidSessionToken) {
@@ -501,6 +1132,7 @@ pub async fn find_user(
     query: String,
     credentials: AccountCredentials,
 ) -> Result<Vec<FoundAccount>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("find_user");
     if query.is_empty() {
         return Err(ServerFnError::WrappedServerError(
             ServerError::InvalidArgumentSize,
@@ -516,22 +1148,7 @@ pub async fn find_user(
     check_session(credentials)?;
 
     match DB.find_user(&query, credentials.id) {
-        Ok(result) => {
-            let mut found_accounts = vec![];
-            found_accounts.reserve_exact(result.len());
-
-            for account in result {
-                found_accounts.push(FoundAccount {
-                    id: account.id,
-                    cryptoidentity: account.cryptoidentity,
-                    public_key: account.public_key,
-                    username: account.username,
-                    email: account.email,
-                });
-            }
-
-            Ok(found_accounts)
-        }
+        Ok(result) => Ok(result.into_iter().map(FoundAccount::from).collect()),
         Err(err) => {
             error!("Failed to find user: {err:?}");
             Err(ServerFnError::WrappedServerError(
@@ -541,6 +1158,39 @@ pub async fn find_user(
     }
 }
 
+#[server(endpoint = "lookup_account")]
+pub async fn lookup_account(
+    handle: String,
+    credentials: AccountCredentials,
+) -> Result<Option<FoundAccount>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("lookup_account");
+    if handle.is_empty() || handle.len() > LIMITS.max_email_length.max(LIMITS.max_username_length) {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        ));
+    }
+
+    check_session(credentials)?;
+
+    match DB.get_account_by_handle(&handle) {
+        Ok(account) => Ok(account.map(FoundAccount::from)),
+        Err(err) => {
+            error!("Failed to look up account by handle: {err:?}");
+            Err(ServerFnError::WrappedServerError(account_error(&*err)))
+        }
+    }
+}
+
+/// Converts an error of loading an account, which may have a corrupt identity.
+#[cfg(feature = "server")]
+fn account_error(err: &(dyn std::error::Error + 'static)) -> ServerError {
+    if err.is::<secret::db::CorruptIdentity>() {
+        ServerError::CorruptIdentity
+    } else {
+        ServerError::InternalDatabaseError
+    }
+}
+
 #[cfg(feature = "server")]
 pub fn check_is_in_dm_group(user_id: u64, group_id: u64) -> Result<(), ServerFnError<ServerError>> {
     match DB.is_in_dm_group(user_id, group_id) {
@@ -560,13 +1210,49 @@ pub fn check_is_in_dm_group(user_id: u64, group_id: u64) -> Result<(), ServerFnE
     }
 }
 
+/// Returns `InvalidValue` unless every referenced message exists in the group and the edited
+/// message was sent by `sender_id`. `get_sender` looks up the sender of a message in the group.
+#[cfg(feature = "server")]
+fn check_message_references(
+    references: MessageReferences,
+    sender_id: u64,
+    get_sender: impl Fn(u64) -> Result<Option<u64>, Box<dyn std::error::Error>>,
+) -> Result<(), ServerFnError<ServerError>> {
+    let lookup = |message_id| match get_sender(message_id) {
+        Ok(sender) => Ok(sender),
+        Err(err) => {
+            error!("Failed to get sender of referenced message {message_id}: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    };
+
+    if let Some(reply_to) = references.reply_to
+        && lookup(reply_to)?.is_none()
+    {
+        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
+    }
+    if let Some(edit_for) = references.edit_for
+        && lookup(edit_for)? != Some(sender_id)
+    {
+        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
+    }
+    Ok(())
+}
+
+/// Sends the message and returns its id. The client generates `idempotency_key` once per
+/// message, so that a retried message whose response was lost isn't stored twice.
 #[server(endpoint = "send_dm_message")]
 pub async fn send_dm_message(
     group_id: u64,
     encryption_method: String,
     message: Box<[u8]>,
+    references: MessageReferences,
+    idempotency_key: [u8; 16],
     credentials: AccountCredentials,
 ) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("send_dm_message");
     check_session(credentials)?;
     check_is_in_dm_group(credentials.id, group_id)?;
 
@@ -576,14 +1262,33 @@ pub async fn send_dm_message(
         ));
     }
 
+    if !secret::validation::is_valid_encryption_method(&encryption_method) {
+        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
+    }
+
     if message.len() > LIMITS.max_message_length {
         return Err(ServerFnError::WrappedServerError(
             ServerError::InvalidArgumentSize,
         ));
     }
 
-    match DB.send_dm_message(credentials.id, group_id, &encryption_method, &message, None) {
-        Ok(id) => Ok(id),
+    check_message_references(references, credentials.id, |message_id| {
+        DB.get_dm_message_sender(group_id, message_id)
+    })?;
+
+    match DB.send_dm_message(
+        credentials.id,
+        group_id,
+        &encryption_method,
+        &message,
+        references,
+        None,
+        Some(idempotency_key),
+    ) {
+        Ok(id) => {
+            METRICS.record_message();
+            Ok(id)
+        }
         Err(err) => {
             error!("Failed to send DM message: {err:?}");
             Err(ServerFnError::WrappedServerError(
@@ -593,17 +1298,40 @@ pub async fn send_dm_message(
     }
 }
 
-#[server(endpoint = "fetch_new_dm_messages")]
-pub async fn fetch_new_dm_messages(
+/// Number of messages returned by a fetch if the client doesn't ask for a different amount.
+pub const DEFAULT_FETCH_BATCH: u32 = 30;
+
+#[cfg(feature = "server")]
+fn fetch_batch_size(batch_size: Option<u32>) -> Result<u32, ServerFnError<ServerError>> {
+    match batch_size {
+        None => Ok(DEFAULT_FETCH_BATCH),
+        Some(0) => Err(ServerFnError::WrappedServerError(ServerError::InvalidValue)),
+        Some(size) if size > LIMITS.max_fetch_batch => Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        )),
+        Some(size) => Ok(size),
+    }
+}
+
+#[cfg(feature = "server")]
+fn load_new_dm_messages(
     group_id: u64,
     last_received_message_id: u64,
+    batch_size: Option<u32>,
     credentials: AccountCredentials,
-) -> Result<Vec<DmMessage>, ServerFnError<ServerError>> {
+) -> Result<Page<DmMessage>, ServerFnError<ServerError>> {
     check_session(credentials)?;
     check_is_in_dm_group(credentials.id, group_id)?;
+    let limit = fetch_batch_size(batch_size)?;
 
-    let result = match DB.get_dm_messages(last_received_message_id, group_id, credentials.id) {
-        Ok(messages) => messages,
+    let messages = DB.get_dm_messages(
+        last_received_message_id,
+        group_id,
+        credentials.id,
+        limit + 1,
+    );
+    let result = match messages {
+        Ok(messages) => Page::from_overfetched(messages, limit),
         Err(err) => {
             error!("Failed to fetch new DM messages: {err:?}");
             return Err(ServerFnError::WrappedServerError(
@@ -612,38 +1340,67 @@ pub async fn fetch_new_dm_messages(
         }
     };
 
-    for message in result.iter() {
-        if message.status == MessageStatus::SentByOther {
-            let db_result = DB.mark_dm_message_delivered(group_id, message.id);
-            if let Err(err) = db_result {
-                error!(
-                    "Failed to mark DM message {} as delivered: {err:?}",
-                    message.id
-                );
-            }
-        }
+    let received_ids: Vec<u64> = result
+        .items
+        .iter()
+        .filter(|message| message.status == MessageStatus::SentByOther)
+        .map(|message| message.id)
+        .collect();
+    if let Err(err) = DB.mark_dm_messages_delivered(group_id, credentials.id, &received_ids) {
+        error!("Failed to mark DM messages as delivered: {err:?}");
     }
 
     Ok(result)
 }
 
-#[server(endpoint = "send_dm_invite")]
-pub async fn send_dm_invite(
-    other_id: u64,
-    encryption_data: Option<Box<[u8]>>,
+#[server(endpoint = "fetch_new_dm_messages")]
+pub async fn fetch_new_dm_messages(
+    group_id: u64,
+    last_received_message_id: u64,
+    batch_size: Option<u32>,
     credentials: AccountCredentials,
-) -> Result<u64, ServerFnError<ServerError>> {
-    check_session(credentials)?;
-    check_user(other_id)?;
+) -> Result<Page<DmMessage>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("fetch_new_dm_messages");
+    load_new_dm_messages(group_id, last_received_message_id, batch_size, credentials)
+}
 
-    if credentials.id == other_id {
-        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
-    }
+/// Same as [`fetch_new_dm_messages`], but the response is encoded with `postcard`. Available
+/// if the server supports [`CAPABILITY_POSTCARD_BATCHES`].
+#[server(endpoint = "fetch_new_dm_messages_compact", output = codec::Postcard)]
+pub async fn fetch_new_dm_messages_compact(
+    group_id: u64,
+    last_received_message_id: u64,
+    batch_size: Option<u32>,
+    credentials: AccountCredentials,
+) -> Result<codec::PostcardBatch<DmMessage>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("fetch_new_dm_messages_compact");
+    load_new_dm_messages(group_id, last_received_message_id, batch_size, credentials)
+        .map(codec::PostcardBatch)
+}
 
-    match DB.add_dm_invite(credentials.id, other_id, encryption_data.as_deref()) {
-        Ok(id) => Ok(id),
+/// Returns `Forbidden` unless the user is a participant of the conversation: a member of the DM
+/// group if `is_dm` is set, or of the multi-user group otherwise.
+#[cfg(feature = "server")]
+pub fn check_is_participant(
+    user_id: u64,
+    conversation_id: u64,
+    is_dm: bool,
+) -> Result<(), ServerFnError<ServerError>> {
+    check_is_participant_in(&*DB, user_id, conversation_id, is_dm)
+}
+
+#[cfg(feature = "server")]
+fn check_is_participant_in(
+    db: &impl Db,
+    user_id: u64,
+    conversation_id: u64,
+    is_dm: bool,
+) -> Result<(), ServerFnError<ServerError>> {
+    match db.is_participant(user_id, conversation_id, is_dm) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ServerFnError::WrappedServerError(ServerError::Forbidden)),
         Err(err) => {
-            error!("Failed to send DM invite: {err:?}");
+            error!("Failed to check whether the user participates in a conversation: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -651,77 +1408,43 @@ pub async fn send_dm_invite(
     }
 }
 
-#[server(endpoint = "accept_dm_invite")]
-pub async fn accept_dm_invite(
-    invite_id: u64,
+/// Marks the messages received by the user in all of their conversations as delivered, so that
+/// senders get delivery receipts without the conversations being opened. Returns the number of
+/// newly delivered messages.
+#[server(endpoint = "mark_all_delivered")]
+pub async fn mark_all_delivered(
     credentials: AccountCredentials,
 ) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("mark_all_delivered");
     check_session(credentials)?;
 
-    let invite = match DB.get_dm_invite(invite_id) {
-        Ok(invite) => invite,
-        Err(err) => {
-            error!("Failed to get DM invite while trying to accept: {err:?}");
-            return Err(ServerFnError::WrappedServerError(
-                ServerError::InternalDatabaseError,
-            ));
-        }
-    };
-
-    if invite.other_id != credentials.id {
-        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
-    }
-
-    let group_id = match DB.create_dm_group(
-        invite.initiator_id,
-        invite.other_id,
-        invite.encryption_data.is_some(),
-    ) {
-        Ok(id) => id,
-        Err(err) => {
-            error!("Failed to create DM group while trying to accept invite: {err:?}");
-            return Err(ServerFnError::WrappedServerError(
-                ServerError::InternalDatabaseError,
-            ));
-        }
-    };
-
-    match DB.remove_dm_invite(invite_id) {
-        Ok(()) => Ok(group_id),
+    match DB.mark_all_dm_messages_delivered(credentials.id) {
+        Ok(delivered) => Ok(delivered),
         Err(err) => {
-            error!("Failed to accept DM invite (after creating group): {err:?}");
+            error!("Failed to mark all DM messages as delivered: {err:?}");
             Err(ServerFnError::WrappedServerError(
-                ServerError::GroupPartiallyCreated(group_id),
+                ServerError::InternalDatabaseError,
             ))
         }
     }
 }
 
-#[server(endpoint = "reject_dm_invite")]
-pub async fn reject_dm_invite(
-    invite_id: u64,
+/// Marks all messages of the group up to `message_id` as read by the user.
+#[server(endpoint = "mark_messages_read")]
+pub async fn mark_messages_read(
+    group_id: u64,
+    dm: bool,
+    message_id: u64,
     credentials: AccountCredentials,
 ) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("mark_messages_read");
     check_session(credentials)?;
+    check_is_participant(credentials.id, group_id, dm)?;
 
-    let invite = match DB.get_dm_invite(invite_id) {
-        Ok(invite) => invite,
-        Err(err) => {
-            error!("Failed to get DM invite while trying to reject: {err:?}");
-            return Err(ServerFnError::WrappedServerError(
-                ServerError::InternalDatabaseError,
-            ));
-        }
-    };
-
-    if invite.other_id != credentials.id {
-        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
-    }
-
-    match DB.remove_dm_invite(invite_id) {
+    match DB.set_last_read_message(credentials.id, group_id, dm, message_id) {
         Ok(()) => Ok(()),
         Err(err) => {
-            error!("Failed to reject DM invite: {err:?}");
+            error!("Failed to mark messages of group {group_id} as read: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -729,16 +1452,22 @@ pub async fn reject_dm_invite(
     }
 }
 
-#[server(endpoint = "get_sent_dm_invites")]
-pub async fn get_sent_dm_invites(
+/// Returns id of the last message of the group read by the user, or `None` if the user hasn't
+/// read any.
+#[server(endpoint = "get_last_read_message")]
+pub async fn get_last_read_message(
+    group_id: u64,
+    dm: bool,
     credentials: AccountCredentials,
-) -> Result<Vec<DmInvite>, ServerFnError<ServerError>> {
+) -> Result<Option<u64>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_last_read_message");
     check_session(credentials)?;
+    check_is_participant(credentials.id, group_id, dm)?;
 
-    match DB.get_sent_dm_invites(credentials.id) {
-        Ok(invites) => Ok(invites),
+    match DB.get_last_read_message(credentials.id, group_id, dm) {
+        Ok(message_id) => Ok(message_id),
         Err(err) => {
-            error!("Failed to get sent DM invites: {err:?}");
+            error!("Failed to get last read message of group {group_id}: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -746,16 +1475,42 @@ pub async fn get_sent_dm_invites(
     }
 }
 
-#[server(endpoint = "get_received_dm_invites")]
-pub async fn get_received_dm_invites(
+/// Returns the members of the group who have read the group message, not including its sender or
+/// the members who hide their read state. Only the sender and the admins of the group may see
+/// them.
+#[server(endpoint = "get_message_readers")]
+pub async fn get_message_readers(
+    group_id: u64,
+    message_id: u64,
     credentials: AccountCredentials,
-) -> Result<Vec<DmInvite>, ServerFnError<ServerError>> {
+) -> Result<Vec<u64>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_message_readers");
     check_session(credentials)?;
+    check_is_participant(credentials.id, group_id, false)?;
 
-    match DB.get_received_dm_invites(credentials.id) {
-        Ok(invites) => Ok(invites),
+    let sender_id = match DB.get_group_message_sender(group_id, message_id) {
+        Ok(Some(sender_id)) => sender_id,
+        Ok(None) => {
+            return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
+        }
         Err(err) => {
-            error!("Failed to get received DM invites: {err:?}");
+            error!("Failed to get sender of group message {message_id}: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+    if sender_id != credentials.id {
+        check_is_group_admin(group_id, credentials.id)?;
+    }
+
+    match DB.get_group_message_readers(group_id, message_id) {
+        Ok(mut readers) => {
+            readers.retain(|&reader_id| reader_id != sender_id);
+            Ok(readers)
+        }
+        Err(err) => {
+            error!("Failed to get readers of group message {message_id}: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -763,31 +1518,22 @@ pub async fn get_received_dm_invites(
     }
 }
 
-#[server(endpoint = "cancel_dm_invite")]
-pub async fn cancel_dm_invite(
-    invite_id: u64,
+/// Sets whether the other members of the user's groups may see which messages the user has read.
+#[server(endpoint = "set_read_state_hidden")]
+pub async fn set_read_state_hidden(
+    hidden: bool,
     credentials: AccountCredentials,
 ) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("set_read_state_hidden");
     check_session(credentials)?;
 
-    let invite = match DB.get_dm_invite(invite_id) {
-        Ok(invite) => invite,
-        Err(err) => {
-            error!("Failed to get DM invite while trying to reject: {err:?}");
-            return Err(ServerFnError::WrappedServerError(
-                ServerError::InternalDatabaseError,
-            ));
-        }
-    };
-
-    if invite.initiator_id != credentials.id {
-        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
-    }
-
-    match DB.remove_dm_invite(invite_id) {
+    match DB.set_read_state_hidden(credentials.id, hidden) {
         Ok(()) => Ok(()),
         Err(err) => {
-            error!("Failed to cancel DM invite: {err:?}");
+            error!(
+                "Failed to hide read state of user {}: {err:?}",
+                credentials.id
+            );
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -795,18 +1541,20 @@ pub async fn cancel_dm_invite(
     }
 }
 
-#[server(endpoint = "leave_dm_group")]
-pub async fn leave_dm_group(
-    group_id: u64,
+#[server(endpoint = "is_read_state_hidden")]
+pub async fn is_read_state_hidden(
     credentials: AccountCredentials,
-) -> Result<(), ServerFnError<ServerError>> {
+) -> Result<bool, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("is_read_state_hidden");
     check_session(credentials)?;
-    check_is_in_dm_group(credentials.id, group_id)?;
 
-    match DB.remove_dm_group(group_id) {
-        Ok(()) => Ok(()),
+    match DB.is_read_state_hidden(credentials.id) {
+        Ok(hidden) => Ok(hidden),
         Err(err) => {
-            error!("Failed to leave DM group: {err:?}");
+            error!(
+                "Failed to get read state privacy of user {}: {err:?}",
+                credentials.id
+            );
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -815,35 +1563,858 @@ pub async fn leave_dm_group(
 }
 
 #[cfg(feature = "server")]
-fn store_icon(prefix: &str, id: u64, icon: Box<[u8]>) {
-    STORAGE.store(&format!("{prefix}{id}.bin"), &icon);
+fn check_report_reason(reason: &str) -> Result<(), ServerFnError<ServerError>> {
+    if reason.len() > LIMITS.max_report_reason_length {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        ));
+    }
+    Ok(())
 }
 
 #[cfg(feature = "server")]
-fn load_icon(prefix: &str, id: u64) -> UserIcon {
-    STORAGE.raw_load(format!("{prefix}{id}.bin")).ok()
-}
-
+fn add_report(
+    reporter_id: u64,
+    reported_user_id: u64,
+    message: Option<ReportedMessage>,
+    reason: &str,
+) -> Result<u64, ServerFnError<ServerError>> {
+    match DB.add_report(
+        reporter_id,
+        reported_user_id,
+        message,
+        reason,
+        LIMITS.max_daily_reports,
+    ) {
+        Ok(NewReport::Added(id)) => Ok(id),
+        Ok(NewReport::LimitExceeded) => Err(ServerFnError::WrappedServerError(
+            ServerError::LimitExceeded,
+        )),
+        Err(err) => {
+            error!("Failed to add report: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+/// Reports the user to the server admins. Returns id of the report, which is the earlier one if
+/// the user was reported by the caller already.
+#[server(endpoint = "report_user")]
+pub async fn report_user(
+    user_id: u64,
+    reason: String,
+    credentials: AccountCredentials,
+) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("report_user");
+    check_session(credentials)?;
+    check_report_reason(&reason)?;
+    check_user(user_id)?;
+
+    if user_id == credentials.id {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::ActionOnSelfIsForbidden,
+        ));
+    }
+
+    add_report(credentials.id, user_id, None, &reason)
+}
+
+/// Reports a message of the group (the DM group if `dm` is set) to the server admins. Only a
+/// reference to the message is stored, so the admins see as much as the reporter shares with
+/// them. Returns id of the report, which is the earlier one if the message was reported by the
+/// caller already.
+#[server(endpoint = "report_message")]
+pub async fn report_message(
+    group_id: u64,
+    dm: bool,
+    message_id: u64,
+    reason: String,
+    credentials: AccountCredentials,
+) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("report_message");
+    check_session(credentials)?;
+    check_report_reason(&reason)?;
+    check_is_participant(credentials.id, group_id, dm)?;
+
+    let sender = if dm {
+        DB.get_dm_message_sender(group_id, message_id)
+    } else {
+        DB.get_group_message_sender(group_id, message_id)
+    };
+    let sender_id = match sender {
+        Ok(Some(sender_id)) => sender_id,
+        Ok(None) => return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue)),
+        Err(err) => {
+            error!("Failed to get sender of reported message: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    if sender_id == credentials.id {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::ActionOnSelfIsForbidden,
+        ));
+    }
+
+    let message = ReportedMessage {
+        group_id,
+        dm,
+        message_id,
+    };
+    add_report(credentials.id, sender_id, Some(message), &reason)
+}
+
+/// Returns `LimitExceeded` if the sender already has too many pending invites.
+#[cfg(feature = "server")]
+fn check_pending_invite_limit(sender_id: u64) -> Result<(), ServerFnError<ServerError>> {
+    let sent = match DB.count_pending_sent_invites(sender_id) {
+        Ok(sent) => sent,
+        Err(err) => {
+            error!("Failed to count pending invites: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    if !secret::invites::is_within_pending_invite_limit(sent) {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::LimitExceeded,
+        ));
+    }
+    Ok(())
+}
+
+#[server(endpoint = "send_dm_invite")]
+pub async fn send_dm_invite(
+    other_id: u64,
+    encryption_data: Option<Box<[u8]>>,
+    credentials: AccountCredentials,
+) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("send_dm_invite");
+    check_session(credentials)?;
+    check_user(other_id)?;
+
+    if credentials.id == other_id {
+        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
+    }
+
+    check_pending_invite_limit(credentials.id)?;
+
+    let expires_at = secret::invites::default_invite_expiry(Utc::now().naive_utc());
+    match DB.add_dm_invite(
+        credentials.id,
+        other_id,
+        encryption_data.as_deref(),
+        Some(expires_at),
+    ) {
+        Ok(id) => Ok(id),
+        Err(err) => {
+            error!("Failed to send DM invite: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "accept_dm_invite")]
+pub async fn accept_dm_invite(
+    invite_id: u64,
+    credentials: AccountCredentials,
+) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("accept_dm_invite");
+    check_session(credentials)?;
+
+    let invite = match DB.get_dm_invite(invite_id) {
+        Ok(invite) => invite,
+        Err(err) => {
+            error!("Failed to get DM invite while trying to accept: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    if invite.other_id != credentials.id {
+        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+    }
+
+    if secret::invites::is_invite_expired(invite.expires_at, Utc::now().naive_utc()) {
+        if let Err(err) = DB.remove_dm_invite(invite_id) {
+            error!("Failed to remove expired DM invite: {err:?}");
+        }
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InviteExpired,
+        ));
+    }
+
+    let group_id = match DB.create_dm_group(
+        invite.initiator_id,
+        invite.other_id,
+        invite.encryption_data.is_some(),
+    ) {
+        Ok(id) => id,
+        Err(err) => {
+            error!("Failed to create DM group while trying to accept invite: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    match DB.remove_dm_invite(invite_id) {
+        Ok(()) => Ok(group_id),
+        Err(err) => {
+            error!("Failed to accept DM invite (after creating group): {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::GroupPartiallyCreated(group_id),
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "reject_dm_invite")]
+pub async fn reject_dm_invite(
+    invite_id: u64,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("reject_dm_invite");
+    check_session(credentials)?;
+
+    let invite = match DB.get_dm_invite(invite_id) {
+        Ok(invite) => invite,
+        Err(err) => {
+            error!("Failed to get DM invite while trying to reject: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    if invite.other_id != credentials.id {
+        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+    }
+
+    match DB.remove_dm_invite(invite_id) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            error!("Failed to reject DM invite: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "get_sent_dm_invites")]
+pub async fn get_sent_dm_invites(
+    credentials: AccountCredentials,
+) -> Result<Page<DmInvite>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_sent_dm_invites");
+    check_session(credentials)?;
+
+    match DB.get_sent_dm_invites(credentials.id, LIST_PAGE_SIZE + 1) {
+        Ok(invites) => Ok(Page::from_overfetched(invites, LIST_PAGE_SIZE)),
+        Err(err) => {
+            error!("Failed to get sent DM invites: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "get_received_dm_invites")]
+pub async fn get_received_dm_invites(
+    credentials: AccountCredentials,
+) -> Result<Page<DmInvite>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_received_dm_invites");
+    check_session(credentials)?;
+
+    match DB.get_received_dm_invites(credentials.id, LIST_PAGE_SIZE + 1) {
+        Ok(invites) => Ok(Page::from_overfetched(invites, LIST_PAGE_SIZE)),
+        Err(err) => {
+            error!("Failed to get received DM invites: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "cancel_dm_invite")]
+pub async fn cancel_dm_invite(
+    invite_id: u64,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("cancel_dm_invite");
+    check_session(credentials)?;
+
+    let invite = match DB.get_dm_invite(invite_id) {
+        Ok(invite) => invite,
+        Err(err) => {
+            error!("Failed to get DM invite while trying to reject: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    if invite.initiator_id != credentials.id {
+        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+    }
+
+    match DB.remove_dm_invite(invite_id) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            error!("Failed to cancel DM invite: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "leave_dm_group")]
+pub async fn leave_dm_group(
+    group_id: u64,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("leave_dm_group");
+    check_session(credentials)?;
+    check_is_in_dm_group(credentials.id, group_id)?;
+
+    match DB.remove_dm_group(group_id, remove_attachment_content) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            error!("Failed to leave DM group: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+fn store_icon(prefix: &str, id: u64, icon: Box<[u8]>) {
+    // Already logged. The icon is just missing then.
+    let _ = STORAGE.store(&format!("{prefix}{id}.bin"), &icon);
+}
+
+#[cfg(feature = "server")]
+fn load_icon(prefix: &str, id: u64) -> UserIcon {
+    STORAGE.raw_load(format!("{prefix}{id}.bin")).ok()
+}
+
 #[server(endpoint = "get_user_data")]
 pub async fn get_user_data(
     user_id: u64,
     credentials: AccountCredentials,
-) -> Result<Option<UserAccount>, ServerFnError<ServerError>> {
+) -> Result<Option<UserAccount>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_user_data");
+    check_session(credentials)?;
+
+    let icon = load_icon("u", user_id);
+
+    match DB.get_user_by_id(user_id) {
+        Ok(Some(account)) => Ok(Some(account.into_user_account(icon))),
+        Ok(None) => Ok(None),
+        Err(err) => {
+            eprintln!("Failed to get user by id {user_id}: {err:?}");
+            Err(ServerFnError::WrappedServerError(account_error(&*err)))
+        }
+    }
+}
+
+#[server(endpoint = "get_self")]
+pub async fn get_self(
+    credentials: AccountCredentials,
+) -> Result<OwnAccount, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_self");
+    check_session(credentials)?;
+
+    let icon = load_icon("u", credentials.id);
+
+    match DB.get_user_by_id(credentials.id) {
+        Ok(Some(account)) => Ok(account.into_own_account(icon)),
+        // The session was just checked, so the account can only be missing if it was deleted
+        // in between.
+        Ok(None) => Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidSessionToken,
+        )),
+        Err(err) => {
+            error!("Failed to get own account {}: {err:?}", credentials.id);
+            Err(ServerFnError::WrappedServerError(account_error(&*err)))
+        }
+    }
+}
+
+#[server(endpoint = "get_group_data")]
+pub async fn get_group_data(
+    group_id: u64,
+    credentials: AccountCredentials,
+) -> Result<Option<MultiUserGroup>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_group_data");
+    check_session(credentials)?;
+
+    let err = check_is_in_group(credentials.id, group_id);
+
+    match DB.get_group_by_id(group_id) {
+        Ok(Some(mut group)) => {
+            if let Err(err) = err
+                && !group.public
+            {
+                return Err(err);
+            }
+
+            let icon = load_icon("g", group_id);
+            group.icon = icon;
+
+            Ok(Some(group))
+        }
+        Ok(None) => Err(ServerFnError::WrappedServerError(ServerError::Forbidden)),
+        Err(err) => {
+            eprintln!("Failed to get group data by id {group_id}: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "get_joined_dm_groups")]
+pub async fn get_joined_dm_groups(
+    credentials: AccountCredentials,
+) -> Result<Page<DmGroup>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_joined_dm_groups");
+    check_session(credentials)?;
+
+    match DB.get_dm_groups(credentials.id, LIST_PAGE_SIZE + 1) {
+        Ok(groups) => Ok(Page::from_overfetched(groups, LIST_PAGE_SIZE)),
+        Err(err) => {
+            error!(
+                "Failed to get joined DM groups of user {}: {err:?}",
+                credentials.id
+            );
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "get_joined_groups")]
+pub async fn get_joined_groups(
+    credentials: AccountCredentials,
+) -> Result<Page<MultiUserGroup>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_joined_groups");
+    check_session(credentials)?;
+
+    match DB.get_groups(credentials.id, LIST_PAGE_SIZE + 1) {
+        Ok(groups) => Ok(Page::from_overfetched(groups, LIST_PAGE_SIZE)),
+        Err(err) => {
+            error!(
+                "Failed to get joined multi-user groups of user {}: {err:?}",
+                credentials.id
+            );
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+pub fn check_is_in_group(user_id: u64, group_id: u64) -> Result<(), ServerFnError<ServerError>> {
+    match DB.is_in_group(user_id, group_id) {
+        Ok(value) => {
+            if value {
+                Ok(())
+            } else {
+                Err(ServerFnError::WrappedServerError(ServerError::Forbidden))
+            }
+        }
+        Err(err) => {
+            error!("Failed to check whether the user is in group or not: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+pub fn check_is_not_in_group(
+    user_id: u64,
+    group_id: u64,
+) -> Result<(), ServerFnError<ServerError>> {
+    match DB.is_in_group(user_id, group_id) {
+        Ok(value) => {
+            if value {
+                Err(ServerFnError::WrappedServerError(
+                    ServerError::AlreadyInGroup,
+                ))
+            } else {
+                Ok(())
+            }
+        }
+        Err(err) => {
+            error!("Failed to check whether the user is in group or not: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+/// Fails if the user has already been invited to the group and the invite is still pending, so
+/// that the invite isn't listed twice.
+#[cfg(feature = "server")]
+fn check_no_group_invite(user_id: u64, group_id: u64) -> Result<(), ServerFnError<ServerError>> {
+    match DB.group_invite_exists(user_id, group_id) {
+        Ok(false) => Ok(()),
+        Ok(true) => Err(ServerFnError::WrappedServerError(
+            ServerError::InviteAlreadyExists,
+        )),
+        Err(err) => {
+            error!("Failed to check for an existing group invite: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+pub fn check_is_group_admin(group_id: u64, user_id: u64) -> Result<(), ServerFnError<ServerError>> {
+    check_is_group_admin_in(&*DB, group_id, user_id)
+}
+
+#[cfg(feature = "server")]
+fn check_is_group_admin_in(
+    db: &impl Db,
+    group_id: u64,
+    user_id: u64,
+) -> Result<(), ServerFnError<ServerError>> {
+    match db.get_group_member_permissions(group_id, user_id) {
+        Ok(Some(permissions)) => {
+            if permissions.is_admin() {
+                Ok(())
+            } else {
+                Err(ServerFnError::WrappedServerError(ServerError::Forbidden))
+            }
+        }
+        Ok(None) => Err(ServerFnError::WrappedServerError(ServerError::Forbidden)),
+        Err(err) => {
+            error!("Failed to check whether the user is the group admin or not: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+/// Returns the permissions which the user may offer in an invite sent by `inviter_id` to the
+/// group, dropping the ones they can't give. Only the inviter and the admins of the group may
+/// offer permissions in an invite.
+#[cfg(feature = "server")]
+fn offered_invite_permissions_in(
+    db: &impl Db,
+    group_id: u64,
+    user_id: u64,
+    inviter_id: u64,
+    permissions: &[u8],
+) -> Result<Box<[u8]>, ServerFnError<ServerError>> {
+    let member_permissions = match db.get_group_member_permissions(group_id, user_id) {
+        Ok(Some(permissions)) => permissions,
+        Ok(None) => return Err(ServerFnError::WrappedServerError(ServerError::Forbidden)),
+        Err(err) => {
+            error!("Failed to get group member permissions for an invite: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+    if user_id != inviter_id && !member_permissions.is_admin() {
+        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+    }
+
+    let Some(permissions) = GroupPermissions::from_bytes(permissions) else {
+        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
+    };
+    Ok(secret::invites::offered_permissions(permissions, &member_permissions).to_bytes())
+}
+
+#[server(endpoint = "send_group_invite")]
+pub async fn send_group_invite(
+    user_id: u64,
+    group_id: u64,
+    permissions: Box<[u8]>,
+    credentials: AccountCredentials,
+    encryption_data: Option<Box<[u8]>>,
+) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("send_group_invite");
+    check_session(credentials)?;
+    check_is_in_group(credentials.id, group_id)?;
+    check_is_not_in_group(user_id, group_id)?;
+    check_no_group_invite(user_id, group_id)?;
+    check_pending_invite_limit(credentials.id)?;
+
+    // The permissions are given to the user once the invite is accepted.
+    let permissions = offered_invite_permissions_in(
+        &*DB,
+        group_id,
+        credentials.id,
+        credentials.id,
+        &permissions,
+    )?;
+
+    let expires_at = secret::invites::default_invite_expiry(Utc::now().naive_utc());
+    match DB.add_group_invite(
+        credentials.id,
+        user_id,
+        group_id,
+        &permissions,
+        encryption_data.as_deref(),
+        Some(expires_at),
+    ) {
+        Ok(invite_id) => Ok(invite_id),
+        Err(err) => {
+            error!("Failed to send group invite to user {user_id}: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "create_group")]
+pub async fn create_group(
+    name: String,
+    icon: Option<Box<[u8]>>,
+    encrypted: bool,
+    public: bool,
+    channel: bool,
+    credentials: AccountCredentials,
+) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("create_group");
+    check_session(credentials)?;
+
+    if let Some(icon) = icon.as_ref()
+        && icon.len() > LIMITS.max_group_icon_size
+    {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::LimitExceeded,
+        ));
+    }
+
+    let group_id = match DB.create_group(&name, encrypted, public, channel) {
+        Ok(group_id) => group_id,
+        Err(err) => {
+            error!("Failed to create a new group: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    if let Some(icon) = icon {
+        store_icon("g", group_id, icon);
+    }
+
+    match DB.add_group_member(
+        group_id,
+        credentials.id,
+        &GroupPermissions::admin().to_bytes(),
+    ) {
+        Ok(_) => Ok(group_id),
+        Err(err) => {
+            error!("Failed to add user creator to its group: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::GroupPartiallyCreated(group_id),
+            ))
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+fn load_new_group_messages(
+    group_id: u64,
+    last_received_message_id: u64,
+    batch_size: Option<u32>,
+    credentials: AccountCredentials,
+) -> Result<Page<GroupMessage>, ServerFnError<ServerError>> {
+    check_session(credentials)?;
+    check_is_in_group(credentials.id, group_id)?;
+    let limit = fetch_batch_size(batch_size)?;
+
+    match DB.get_group_messages(last_received_message_id, group_id, limit + 1) {
+        Ok(messages) => Ok(Page::from_overfetched(messages, limit)),
+        Err(err) => {
+            error!("Failed to fetch new group messages: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "fetch_new_group_messages")]
+pub async fn fetch_new_group_messages(
+    group_id: u64,
+    last_received_message_id: u64,
+    batch_size: Option<u32>,
+    credentials: AccountCredentials,
+) -> Result<Page<GroupMessage>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("fetch_new_group_messages");
+    load_new_group_messages(group_id, last_received_message_id, batch_size, credentials)
+}
+
+/// Same as [`fetch_new_group_messages`], but the response is encoded with `postcard`. Available
+/// if the server supports [`CAPABILITY_POSTCARD_BATCHES`].
+#[server(endpoint = "fetch_new_group_messages_compact", output = codec::Postcard)]
+pub async fn fetch_new_group_messages_compact(
+    group_id: u64,
+    last_received_message_id: u64,
+    batch_size: Option<u32>,
+    credentials: AccountCredentials,
+) -> Result<codec::PostcardBatch<GroupMessage>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("fetch_new_group_messages_compact");
+    load_new_group_messages(group_id, last_received_message_id, batch_size, credentials)
+        .map(codec::PostcardBatch)
+}
+
+/// Sends the message and returns its id. The client generates `idempotency_key` once per
+/// message, so that a retried message whose response was lost isn't stored twice.
+#[server(endpoint = "send_group_message")]
+pub async fn send_group_message(
+    group_id: u64,
+    encryption_method: String,
+    message: Box<[u8]>,
+    references: MessageReferences,
+    idempotency_key: [u8; 16],
+    credentials: AccountCredentials,
+) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("send_group_message");
+    check_session(credentials)?;
+    check_is_in_group(credentials.id, group_id)?;
+
+    if encryption_method.len() > LIMITS.max_encryption_method_length {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        ));
+    }
+
+    if !secret::validation::is_valid_encryption_method(&encryption_method) {
+        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
+    }
+
+    if message.len() > LIMITS.max_message_length {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        ));
+    }
+
+    let permissions = match DB.get_group_member_permissions(group_id, credentials.id) {
+        Ok(Some(permissions)) => {
+            permissions
+        }
+        Ok(None) => {
+            return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+        }
+        Err(err) => {
+            error!("Failed to get group member permissions before sending message: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    let group = match DB.get_group_by_id(group_id) {
+        Ok(Some(group)) => {
+            group
+        }
+        Ok(None) => {
+            return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+        }
+        Err(err) => {
+            error!("Failed to get group before sending message: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    // TODO: Don't check for admin rights but instead just don't include `send_messages` when
+    // inviting into a channel (by default).
+    if (group.channel && !permissions.is_admin()) || (!group.channel && !permissions.send_messages) {
+        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+    }
+
+    if !secret::validation::encryption_method_matches_group(group.encrypted, &encryption_method) {
+        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
+    }
+
+    check_message_references(references, credentials.id, |message_id| {
+        DB.get_group_message_sender(group_id, message_id)
+    })?;
+
+    match DB.send_group_message(
+        credentials.id,
+        group_id,
+        &encryption_method,
+        &message,
+        references,
+        None,
+        Some(idempotency_key),
+    ) {
+        Ok(id) => {
+            METRICS.record_message();
+            Ok(id)
+        }
+        Err(err) => {
+            error!("Failed to send group message: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "get_sent_group_invites")]
+pub async fn get_sent_group_invites(
+    credentials: AccountCredentials,
+) -> Result<Page<GroupInvite>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_sent_group_invites");
     check_session(credentials)?;
 
-    let icon = load_icon("u", user_id);
+    match DB.get_sent_group_invites(credentials.id, LIST_PAGE_SIZE + 1) {
+        Ok(invites) => Ok(Page::from_overfetched(invites, LIST_PAGE_SIZE)),
+        Err(err) => {
+            error!("Failed to get sent group invites: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
 
-    match DB.get_user_by_id(user_id) {
-        Ok(Some(account)) => Ok(Some(UserAccount {
-            public_key: account.public_key,
-            cryptoidentity: account.cryptoidentity,
-            email: account.email,
-            username: account.username,
-            icon,
-        })),
-        Ok(None) => Ok(None),
+#[server(endpoint = "get_received_group_invites")]
+pub async fn get_received_group_invites(
+    credentials: AccountCredentials,
+) -> Result<Page<GroupInvite>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_received_group_invites");
+    check_session(credentials)?;
+
+    match DB.get_received_group_invites(credentials.id, LIST_PAGE_SIZE + 1) {
+        Ok(invites) => Ok(Page::from_overfetched(invites, LIST_PAGE_SIZE)),
         Err(err) => {
-            eprintln!("Failed to get user by id {user_id}: {err:?}");
+            error!("Failed to get received group invites: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -851,31 +2422,207 @@ pub async fn get_user_data(
     }
 }
 
-#[server(endpoint = "get_group_data")]
-pub async fn get_group_data(
-    group_id: u64,
+/// Returns all received DM and group invites along with the data needed to display them, so that
+/// the client doesn't have to fetch every sender and group separately.
+#[server(endpoint = "get_received_invites_detailed")]
+pub async fn get_received_invites_detailed(
     credentials: AccountCredentials,
-) -> Result<Option<MultiUserGroup>, ServerFnError<ServerError>> {
+) -> Result<ReceivedInvites, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_received_invites_detailed");
     check_session(credentials)?;
 
-    let err = check_is_in_group(credentials.id, group_id);
+    let invites = DB
+        .get_received_dm_invites(credentials.id, LIST_PAGE_SIZE)
+        .and_then(|dm_invites| {
+            Ok((
+                dm_invites,
+                DB.get_received_group_invites(credentials.id, LIST_PAGE_SIZE)?,
+            ))
+        })
+        .and_then(|(dm_invites, group_invites)| {
+            secret::invites::join_received_invites(
+                dm_invites,
+                group_invites,
+                |user_id| {
+                    Ok(DB
+                        .get_user_by_id(user_id)?
+                        .map(|account| account.into_user_account(load_icon("u", user_id))))
+                },
+                |group_id| {
+                    Ok(DB.get_group_by_id(group_id)?.map(|mut group| {
+                        group.icon = load_icon("g", group_id);
+                        group
+                    }))
+                },
+                |err: &Box<dyn std::error::Error>| err.is::<secret::db::CorruptIdentity>(),
+            )
+        });
+
+    match invites {
+        Ok(invites) => Ok(invites),
+        Err(err) => {
+            error!("Failed to get detailed received invites: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
 
-    match DB.get_group_by_id(group_id) {
-        Ok(Some(mut group)) => {
-            if let Err(err) = err
-                && !group.public
-            {
-                return Err(err);
+/// Same as the four endpoints listing sent and received invites, but in a single request.
+#[server(endpoint = "get_all_invites")]
+pub async fn get_all_invites(
+    credentials: AccountCredentials,
+) -> Result<AllInvites, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_all_invites");
+    check_session(credentials)?;
+
+    match DB.get_all_invites(credentials.id, LIST_PAGE_SIZE) {
+        Ok(invites) => Ok(invites),
+        Err(err) => {
+            error!("Failed to get all invites: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "cancel_group_invite")]
+pub async fn cancel_group_invite(
+    invite_id: u64,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("cancel_group_invite");
+    check_session(credentials)?;
+
+    let invite = match DB.get_group_invite(invite_id) {
+        Ok(invite) => invite,
+        Err(err) => {
+            error!("Failed to get group invite while trying to reject: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    if invite.inviter_id != credentials.id {
+        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+    }
+
+    match DB.remove_group_invite(invite_id) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            error!("Failed to cancel group invite: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "accept_group_invite")]
+pub async fn accept_group_invite(
+    invite_id: u64,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("accept_group_invite");
+    check_session(credentials)?;
+
+    let invite = match DB.get_group_invite(invite_id) {
+        Ok(invite) => invite,
+        Err(err) => {
+            error!("Failed to get group invite while trying to accept: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    if invite.invited_id != credentials.id {
+        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+    }
+
+    if secret::invites::is_invite_expired(invite.expires_at, Utc::now().naive_utc()) {
+        if let Err(err) = DB.remove_group_invite(invite_id) {
+            error!("Failed to remove expired group invite: {err:?}");
+        }
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InviteExpired,
+        ));
+    }
+
+    // If the member was added, but removing the invite failed with `GroupPartiallyJoined`, the
+    // client retries and only the invite is left to remove.
+    match check_is_not_in_group(credentials.id, invite.group_id) {
+        Ok(()) => {
+            // Adding fails only on database errors, since a member added by a concurrent
+            // request is kept as it is.
+            if let Err(err) = DB.add_group_member(
+                invite.group_id,
+                invite.invited_id,
+                &secret::invites::accepted_permissions(&invite.permissions),
+            ) {
+                error!("Failed to create group while trying to accept invite: {err:?}");
+                return Err(ServerFnError::WrappedServerError(
+                    ServerError::InternalDatabaseError,
+                ));
             }
+        }
+        Err(ServerFnError::WrappedServerError(ServerError::AlreadyInGroup)) => {}
+        Err(err) => return Err(err),
+    }
 
-            let icon = load_icon("g", group_id);
-            group.icon = icon;
+    match DB.remove_group_invite(invite_id) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            error!("Failed to accept group invite (after creating group): {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::GroupPartiallyJoined,
+            ))
+        }
+    }
+}
 
-            Ok(Some(group))
+/// Changes the permissions offered by a pending group invite. Allowed to the inviter and the
+/// admins of the group, who can't offer more than when sending an invite.
+#[server(endpoint = "update_group_invite_permissions")]
+pub async fn update_group_invite_permissions(
+    invite_id: u64,
+    permissions: Box<[u8]>,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("update_group_invite_permissions");
+    check_session(credentials)?;
+
+    let invite = match DB.get_group_invite(invite_id) {
+        Ok(invite) => invite,
+        Err(err) => {
+            error!("Failed to get group invite while trying to update it: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
         }
-        Ok(None) => Err(ServerFnError::WrappedServerError(ServerError::Forbidden)),
+    };
+
+    let permissions = offered_invite_permissions_in(
+        &*DB,
+        invite.group_id,
+        credentials.id,
+        invite.inviter_id,
+        &permissions,
+    )?;
+
+    if secret::invites::is_invite_expired(invite.expires_at, Utc::now().naive_utc()) {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InviteExpired,
+        ));
+    }
+
+    match DB.update_group_invite_permissions(invite_id, &permissions) {
+        Ok(()) => Ok(()),
         Err(err) => {
-            eprintln!("Failed to get group data by id {group_id}: {err:?}");
+            error!("Failed to update group invite permissions: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -883,19 +2630,32 @@ pub async fn get_group_data(
     }
 }
 
-#[server(endpoint = "get_joined_dm_groups")]
-pub async fn get_joined_dm_groups(
+#[server(endpoint = "reject_group_invite")]
+pub async fn reject_group_invite(
+    invite_id: u64,
     credentials: AccountCredentials,
-) -> Result<Vec<DmGroup>, ServerFnError<ServerError>> {
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("reject_group_invite");
     check_session(credentials)?;
 
-    match DB.get_dm_groups(credentials.id) {
-        Ok(groups) => Ok(groups),
+    let invite = match DB.get_group_invite(invite_id) {
+        Ok(invite) => invite,
         Err(err) => {
-            error!(
-                "Failed to get joined DM groups of user {}: {err:?}",
-                credentials.id
-            );
+            error!("Failed to get group invite while trying to reject: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+
+    if invite.invited_id != credentials.id {
+        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+    }
+
+    match DB.remove_group_invite(invite_id) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            error!("Failed to reject group invite: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -903,19 +2663,24 @@ pub async fn get_joined_dm_groups(
     }
 }
 
-#[server(endpoint = "get_joined_groups")]
-pub async fn get_joined_groups(
+#[server(endpoint = "get_group_member_count")]
+pub async fn get_group_member_count(
+    group_id: u64,
     credentials: AccountCredentials,
-) -> Result<Vec<MultiUserGroup>, ServerFnError<ServerError>> {
+) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_group_member_count");
     check_session(credentials)?;
+    check_is_in_group(credentials.id, group_id)?;
 
-    match DB.get_groups(credentials.id) {
-        Ok(groups) => Ok(groups),
+    match DB.get_group_member_count(group_id) {
+        Ok(Some(member_count)) => Ok(member_count),
+        // In theory it's possible that `check_is_in_group` will return `Ok`-value then the group
+        // will be removed and after that `DB.get_group_member_count` will be called.
+        Ok(None) => Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidGroupId,
+        )),
         Err(err) => {
-            error!(
-                "Failed to get joined multi-user groups of user {}: {err:?}",
-                credentials.id
-            );
+            error!("Failed to get group member count: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -923,18 +2688,85 @@ pub async fn get_joined_groups(
     }
 }
 
-#[cfg(feature = "server")]
-pub fn check_is_in_group(user_id: u64, group_id: u64) -> Result<(), ServerFnError<ServerError>> {
-    match DB.is_in_group(user_id, group_id) {
-        Ok(value) => {
-            if value {
-                Ok(())
-            } else {
-                Err(ServerFnError::WrappedServerError(ServerError::Forbidden))
-            }
+/// Returns the permissions the current user has in the group.
+#[server(endpoint = "get_own_group_permissions")]
+pub async fn get_own_group_permissions(
+    group_id: u64,
+    credentials: AccountCredentials,
+) -> Result<GroupPermissions, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_own_group_permissions");
+    check_session(credentials)?;
+
+    match DB.get_group_member_permissions(group_id, credentials.id) {
+        Ok(Some(permissions)) => Ok(permissions),
+        Ok(None) => Err(ServerFnError::WrappedServerError(ServerError::Forbidden)),
+        Err(err) => {
+            error!("Failed to get own group permissions: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
         }
+    }
+}
+
+/// Number of members returned by [`get_group_members`] if the client doesn't ask for a different
+/// amount.
+pub const DEFAULT_GROUP_MEMBERS_PAGE: u32 = 50;
+
+/// Returns members of the group ordered by their id from the highest. Next pages are requested
+/// by passing the id of the last returned member as `before_user_id`.
+#[server(endpoint = "get_group_members")]
+pub async fn get_group_members(
+    group_id: u64,
+    before_user_id: Option<u64>,
+    limit: Option<u32>,
+    credentials: AccountCredentials,
+) -> Result<Page<GroupMember>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_group_members");
+    let limit = match limit {
+        None => DEFAULT_GROUP_MEMBERS_PAGE,
+        Some(0) => return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue)),
+        Some(limit) if limit > LIMITS.max_group_members_page => {
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InvalidArgumentSize,
+            ));
+        }
+        Some(limit) => limit,
+    };
+    check_session(credentials)?;
+    check_is_in_group(credentials.id, group_id)?;
+
+    match DB.get_group_members(group_id, before_user_id, limit + 1) {
+        Ok(members) => Ok(Page::from_overfetched(members, limit)),
+        Err(err) => {
+            error!("Failed to get group members: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
+    }
+}
+
+#[server(endpoint = "kick_group_member")]
+pub async fn kick_group_member(
+    group_id: u64,
+    user_id: u64,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("kick_group_member");
+    check_session(credentials)?;
+    check_is_group_admin(group_id, credentials.id)?;
+
+    if credentials.id == user_id {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::ActionOnSelfIsForbidden,
+        ));
+    }
+
+    match DB.remove_group_member(group_id, user_id) {
+        Ok(()) => Ok(()),
         Err(err) => {
-            error!("Failed to check whether the user is in group or not: {err:?}");
+            error!("Failed to kick user from a group: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -942,23 +2774,26 @@ pub fn check_is_in_group(user_id: u64, group_id: u64) -> Result<(), ServerFnErro
     }
 }
 
-#[cfg(feature = "server")]
-pub fn check_is_not_in_group(
-    user_id: u64,
+#[server(endpoint = "promote_group_member")]
+pub async fn promote_group_member(
     group_id: u64,
+    user_id: u64,
+    credentials: AccountCredentials,
 ) -> Result<(), ServerFnError<ServerError>> {
-    match DB.is_in_group(user_id, group_id) {
-        Ok(value) => {
-            if value {
-                Err(ServerFnError::WrappedServerError(
-                    ServerError::AlreadyInGroup,
-                ))
-            } else {
-                Ok(())
-            }
-        }
+    let _timer = METRICS.track_request("promote_group_member");
+    check_session(credentials)?;
+    check_is_group_admin(group_id, credentials.id)?;
+
+    if credentials.id == user_id {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::ActionOnSelfIsForbidden,
+        ));
+    }
+
+    match DB.set_group_member_permissions(group_id, user_id, GroupPermissions::admin()) {
+        Ok(()) => Ok(()),
         Err(err) => {
-            error!("Failed to check whether the user is in group or not: {err:?}");
+            error!("Failed to promote user in a group: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -966,19 +2801,26 @@ pub fn check_is_not_in_group(
     }
 }
 
-#[cfg(feature = "server")]
-pub fn check_is_group_admin(group_id: u64, user_id: u64) -> Result<(), ServerFnError<ServerError>> {
-    match DB.get_group_member_permissions(group_id, user_id) {
-        Ok(Some(permissions)) => {
-            if permissions.is_admin() {
-                Ok(())
-            } else {
-                Err(ServerFnError::WrappedServerError(ServerError::Forbidden))
-            }
-        }
-        Ok(None) => Err(ServerFnError::WrappedServerError(ServerError::Forbidden)),
+#[server(endpoint = "demote_group_member")]
+pub async fn demote_group_member(
+    group_id: u64,
+    user_id: u64,
+    credentials: AccountCredentials,
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("demote_group_member");
+    check_session(credentials)?;
+    check_is_group_admin(group_id, credentials.id)?;
+
+    if credentials.id == user_id {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::ActionOnSelfIsForbidden,
+        ));
+    }
+
+    match DB.set_group_member_permissions(group_id, user_id, GroupPermissions::default()) {
+        Ok(()) => Ok(()),
         Err(err) => {
-            error!("Failed to check whether the user is the group admin or not: {err:?}");
+            error!("Failed to demote user in a group: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -986,28 +2828,19 @@ pub fn check_is_group_admin(group_id: u64, user_id: u64) -> Result<(), ServerFnE
     }
 }
 
-#[server(endpoint = "send_group_invite")]
-pub async fn send_group_invite(
-    user_id: u64,
+#[server(endpoint = "leave_group")]
+pub async fn leave_group(
     group_id: u64,
-    permissions: Box<[u8]>,
     credentials: AccountCredentials,
-    encryption_data: Option<Box<[u8]>>,
-) -> Result<u64, ServerFnError<ServerError>> {
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("leave_group");
     check_session(credentials)?;
     check_is_in_group(credentials.id, group_id)?;
-    check_is_not_in_group(user_id, group_id)?;
 
-    match DB.add_group_invite(
-        credentials.id,
-        user_id,
-        group_id,
-        &permissions,
-        encryption_data.as_deref(),
-    ) {
-        Ok(invite_id) => Ok(invite_id),
+    match DB.remove_group_member(group_id, credentials.id) {
+        Ok(()) => Ok(()),
         Err(err) => {
-            error!("Failed to send group invite to user {user_id}: {err:?}");
+            error!("Failed to leave from a group: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1015,81 +2848,138 @@ pub async fn send_group_invite(
     }
 }
 
-#[server(endpoint = "create_group")]
-pub async fn create_group(
-    name: String,
-    icon: Option<Box<[u8]>>,
-    encrypted: bool,
-    public: bool,
-    channel: bool,
-    credentials: AccountCredentials,
-) -> Result<u64, ServerFnError<ServerError>> {
-    check_session(credentials)?;
-
-    if let Some(icon) = icon.as_ref()
-        && icon.len() > LIMITS.max_group_icon_size
-    {
+/// Stores the attachment in the content-addressed store and returns its hash, which the message
+/// should reference. Identical attachments share the stored content.
+#[cfg(feature = "server")]
+fn store_attachment(content: &[u8]) -> Result<Box<[u8]>, ServerFnError<ServerError>> {
+    let Some(hash) = shared::crypto::hash(&shared::crypto::preferred_alogirthm(), content) else {
+        error!("Failed to hash attachment: hash algorithm is not supported");
         return Err(ServerFnError::WrappedServerError(
-            ServerError::LimitExceeded,
+            ServerError::InternalDatabaseError,
         ));
-    }
-
-    let group_id = match DB.create_group(&name, encrypted, public, channel) {
-        Ok(group_id) => group_id,
+    };
+    let is_new = match DB.add_blob_reference(&hash) {
+        Ok(is_new) => is_new,
         Err(err) => {
-            error!("Failed to create a new group: {err:?}");
+            error!("Failed to add attachment reference: {err:?}");
             return Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ));
         }
     };
+    // Releasing the last reference removes the content before the reference is gone, so a blob
+    // which is referenced already has its content unless storing it failed before.
+    if (is_new || !STORAGE.has_blob(&hash)) && STORAGE.store_blob(&hash, content).is_err() {
+        release_attachment(&hash);
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InternalDatabaseError,
+        ));
+    }
+    Ok(hash)
+}
 
-    if let Some(icon) = icon {
-        store_icon("g", group_id, icon);
+/// Removes a reference to the attachment and deletes its content once nothing references it.
+#[cfg(feature = "server")]
+fn release_attachment(hash: &[u8]) {
+    if let Err(err) = DB.release_blob_reference(hash, remove_attachment_content) {
+        error!("Failed to release attachment reference: {err:?}");
     }
+}
 
-    match DB.add_group_member(
-        group_id,
-        credentials.id,
-        &GroupPermissions::admin().to_bytes(),
-    ) {
-        Ok(()) => Ok(group_id),
+#[cfg(feature = "server")]
+fn remove_attachment_content(hash: &[u8]) {
+    // Already logged. An orphaned blob only takes space.
+    let _ = STORAGE.remove_blob(hash);
+}
+
+#[cfg(feature = "server")]
+fn load_attachment(message_id: u64, dm: bool) -> Result<Box<[u8]>, ServerFnError<ServerError>> {
+    let content = match DB.get_message_blob(message_id, dm) {
+        Ok(Some(hash)) => STORAGE.load_blob(&hash),
+        // Files sent before attachments became content-addressed are stored per message.
+        Ok(None) if dm => STORAGE.load_dm_file(message_id),
+        Ok(None) => STORAGE.load_group_file(message_id),
         Err(err) => {
-            error!("Failed to add user creator to its group: {err:?}");
-            Err(ServerFnError::WrappedServerError(
-                ServerError::GroupPartiallyCreated(group_id),
-            ))
+            error!("Failed to get attachment of message {message_id}: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
         }
-    }
+    };
+    content.ok_or(ServerFnError::WrappedServerError(ServerError::FileNotFound))
 }
 
-#[server(endpoint = "fetch_new_group_messages")]
-pub async fn fetch_new_group_messages(
+#[server(endpoint = "send_dm_file")]
+pub async fn send_dm_file(
     group_id: u64,
-    last_received_message_id: u64,
+    encryption_method: String,
+    encrypted_file_name: Box<[u8]>,
+    content: Box<[u8]>,
     credentials: AccountCredentials,
-) -> Result<Vec<GroupMessage>, ServerFnError<ServerError>> {
+) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("send_dm_file");
     check_session(credentials)?;
-    check_is_in_group(credentials.id, group_id)?;
+    check_is_in_dm_group(credentials.id, group_id)?;
+
+    if encryption_method.len() > LIMITS.max_encryption_method_length {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        ));
+    }
+
+    if !secret::validation::is_valid_encryption_method(&encryption_method) {
+        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
+    }
+
+    if encrypted_file_name.len() > LIMITS.max_file_name_length {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        ));
+    }
+
+    if content.len() > LIMITS.max_message_length {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        ));
+    }
 
-    match DB.get_group_messages(last_received_message_id, group_id) {
-        Ok(messages) => Ok(messages),
+    let hash = store_attachment(&content)?;
+    let message_id = match DB.send_dm_file(
+        credentials.id,
+        group_id,
+        &encryption_method,
+        &encrypted_file_name,
+        None,
+    ) {
+        Ok(id) => id,
         Err(err) => {
-            error!("Failed to fetch new group messages: {err:?}");
-            Err(ServerFnError::WrappedServerError(
+            error!("Failed to send DM file: {err:?}");
+            release_attachment(&hash);
+            return Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
-            ))
+            ));
         }
+    };
+    if let Err(err) = DB.set_message_blob(message_id, true, &hash) {
+        error!("Failed to attach file to message {message_id}: {err:?}");
+        release_attachment(&hash);
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InternalDatabaseError,
+        ));
     }
+    METRICS.record_message();
+    Ok(message_id)
 }
 
-#[server(endpoint = "send_group_message")]
-pub async fn send_group_message(
+#[server(endpoint = "send_group_file")]
+pub async fn send_group_file(
     group_id: u64,
     encryption_method: String,
-    message: Box<[u8]>,
+    encrypted_file_name: Box<[u8]>,
+    content: Box<[u8]>,
     credentials: AccountCredentials,
 ) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("send_group_file");
     check_session(credentials)?;
     check_is_in_group(credentials.id, group_id)?;
 
@@ -1099,52 +2989,70 @@ pub async fn send_group_message(
         ));
     }
 
-    if message.len() > LIMITS.max_message_length {
+    if !secret::validation::is_valid_encryption_method(&encryption_method) {
+        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
+    }
+
+    if encrypted_file_name.len() > LIMITS.max_file_name_length {
         return Err(ServerFnError::WrappedServerError(
             ServerError::InvalidArgumentSize,
         ));
     }
 
-    let permissions = match DB.get_group_member_permissions(group_id, credentials.id) {
-        Ok(Some(permissions)) => {
-            permissions
-        }
-        Ok(None) => {
-            return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
-        }
-        Err(err) => {
-            error!("Failed to get group member permissions before sending message: {err:?}");
-            return Err(ServerFnError::WrappedServerError(
-                ServerError::InternalDatabaseError,
-            ));
-        }
-    };
+    if content.len() > LIMITS.max_message_length {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        ));
+    }
 
-    let group = match DB.get_group_by_id(group_id) {
-        Ok(Some(group)) => {
-            group
-        }
-        Ok(None) => {
-            return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
-        }
+    let hash = store_attachment(&content)?;
+    let message_id = match DB.send_group_file(
+        credentials.id,
+        group_id,
+        &encryption_method,
+        &encrypted_file_name,
+        None,
+    ) {
+        Ok(id) => id,
         Err(err) => {
-            error!("Failed to get group before sending message: {err:?}");
+            error!("Failed to send group file: {err:?}");
+            release_attachment(&hash);
             return Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ));
         }
     };
-
-    // TODO: Don't check for admin rights but instead just don't include `send_messages` when
-    // inviting into a channel (by default).
-    if (group.channel && !permissions.is_admin()) || (!group.channel && !permissions.send_messages) {
-        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+    if let Err(err) = DB.set_message_blob(message_id, false, &hash) {
+        error!("Failed to attach file to message {message_id}: {err:?}");
+        release_attachment(&hash);
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InternalDatabaseError,
+        ));
     }
+    METRICS.record_message();
+    Ok(message_id)
+}
 
-    match DB.send_group_message(credentials.id, group_id, &encryption_method, &message, None) {
-        Ok(id) => Ok(id),
+#[server(endpoint = "get_dm_file")]
+pub async fn get_dm_file(
+    message_id: u64,
+    credentials: AccountCredentials,
+) -> Result<File, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_dm_file");
+    check_session(credentials)?;
+    match DB.get_dm_file_data(message_id) {
+        Ok(Some((group_id, encryption_method, file_name))) => {
+            check_is_in_dm_group(credentials.id, group_id)?;
+            let content = load_attachment(message_id, true)?;
+            Ok(File {
+                name: file_name,
+                content,
+                encryption_method,
+            })
+        }
+        Ok(None) => Err(ServerFnError::WrappedServerError(ServerError::FileNotFound)),
         Err(err) => {
-            error!("Failed to send group message: {err:?}");
+            error!("Failed to get DM file: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1152,16 +3060,26 @@ pub async fn send_group_message(
     }
 }
 
-#[server(endpoint = "get_sent_group_invites")]
-pub async fn get_sent_group_invites(
+#[server(endpoint = "get_group_file")]
+pub async fn get_group_file(
+    message_id: u64,
     credentials: AccountCredentials,
-) -> Result<Vec<GroupInvite>, ServerFnError<ServerError>> {
+) -> Result<File, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_group_file");
     check_session(credentials)?;
-
-    match DB.get_sent_group_invites(credentials.id) {
-        Ok(invites) => Ok(invites),
+    match DB.get_group_file_data(message_id) {
+        Ok(Some((group_id, encryption_method, file_name))) => {
+            check_is_in_group(credentials.id, group_id)?;
+            let content = load_attachment(message_id, false)?;
+            Ok(File {
+                name: file_name,
+                content,
+                encryption_method,
+            })
+        }
+        Ok(None) => Err(ServerFnError::WrappedServerError(ServerError::FileNotFound)),
         Err(err) => {
-            error!("Failed to get sent group invites: {err:?}");
+            error!("Failed to get group file: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1169,16 +3087,25 @@ pub async fn get_sent_group_invites(
     }
 }
 
-#[server(endpoint = "get_received_group_invites")]
-pub async fn get_received_group_invites(
+#[server(endpoint = "add_contact")]
+pub async fn add_contact(
+    user_id: u64,
     credentials: AccountCredentials,
-) -> Result<Vec<GroupInvite>, ServerFnError<ServerError>> {
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("add_contact");
     check_session(credentials)?;
+    check_user(user_id)?;
 
-    match DB.get_received_group_invites(credentials.id) {
-        Ok(invites) => Ok(invites),
+    if credentials.id == user_id {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::ActionOnSelfIsForbidden,
+        ));
+    }
+
+    match DB.add_contact(credentials.id, user_id) {
+        Ok(()) => Ok(()),
         Err(err) => {
-            error!("Failed to get received group invites: {err:?}");
+            error!("Failed to add contact {user_id}: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1186,31 +3113,36 @@ pub async fn get_received_group_invites(
     }
 }
 
-#[server(endpoint = "cancel_group_invite")]
-pub async fn cancel_group_invite(
-    invite_id: u64,
+#[server(endpoint = "remove_contact")]
+pub async fn remove_contact(
+    user_id: u64,
     credentials: AccountCredentials,
 ) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("remove_contact");
     check_session(credentials)?;
 
-    let invite = match DB.get_group_invite(invite_id) {
-        Ok(invite) => invite,
+    match DB.remove_contact(credentials.id, user_id) {
+        Ok(()) => Ok(()),
         Err(err) => {
-            error!("Failed to get group invite while trying to reject: {err:?}");
-            return Err(ServerFnError::WrappedServerError(
+            error!("Failed to remove contact {user_id}: {err:?}");
+            Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
-            ));
+            ))
         }
-    };
-
-    if invite.inviter_id != credentials.id {
-        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
     }
+}
 
-    match DB.remove_group_invite(invite_id) {
-        Ok(()) => Ok(()),
+#[server(endpoint = "get_contacts")]
+pub async fn get_contacts(
+    credentials: AccountCredentials,
+) -> Result<Vec<u64>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_contacts");
+    check_session(credentials)?;
+
+    match DB.get_contacts(credentials.id) {
+        Ok(contacts) => Ok(contacts),
         Err(err) => {
-            error!("Failed to cancel group invite: {err:?}");
+            error!("Failed to get contacts of user {}: {err:?}", credentials.id);
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1218,77 +3150,98 @@ pub async fn cancel_group_invite(
     }
 }
 
-#[server(endpoint = "accept_group_invite")]
-pub async fn accept_group_invite(
-    invite_id: u64,
+/// Returns the keys needed to start an encrypted conversation with the user. If `claim_opk` is
+/// set, one of the user's one-time prekeys is claimed for the caller. When all of them are
+/// consumed, `opk` is `None` and the key agreement has weaker forward secrecy until the user
+/// uploads new ones. A user can claim only a few prekeys of another one a day, so that they can't
+/// be drained.
+#[server(endpoint = "get_prekey_bundle")]
+pub async fn get_prekey_bundle(
+    user_id: u64,
+    claim_opk: bool,
     credentials: AccountCredentials,
-) -> Result<(), ServerFnError<ServerError>> {
+) -> Result<Option<PrekeyBundle>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_prekey_bundle");
     check_session(credentials)?;
 
-    let invite = match DB.get_group_invite(invite_id) {
-        Ok(invite) => invite,
+    let mut cryptoidentity = match DB.get_user_by_id(user_id) {
+        Ok(Some(account)) => account.cryptoidentity,
+        Ok(None) => return Ok(None),
         Err(err) => {
-            error!("Failed to get group invite while trying to accept: {err:?}");
-            return Err(ServerFnError::WrappedServerError(
-                ServerError::InternalDatabaseError,
-            ));
+            error!("Failed to get user by id {user_id}: {err:?}");
+            return Err(ServerFnError::WrappedServerError(account_error(&*err)));
         }
     };
-
-    if invite.invited_id != credentials.id {
-        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
+    let opks = std::mem::take(&mut cryptoidentity.opks);
+    if !claim_opk {
+        return Ok(Some(PrekeyBundle {
+            cryptoidentity,
+            opk: None,
+        }));
     }
 
-    match DB.add_group_member(
-        invite.group_id,
-        invite.invited_id,
-        &GroupPermissions::default().to_bytes(),
-    ) {
-        Ok(id) => id,
+    match DB.count_recent_opk_claims(user_id, credentials.id, LIMITS.opk_claim_period) {
+        Ok(claims) if claims >= LIMITS.max_opk_claims => {
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::LimitExceeded,
+            ));
+        }
+        Ok(_) => {}
         Err(err) => {
-            error!("Failed to create group while trying to accept invite: {err:?}");
+            error!("Failed to count claimed one-time prekeys of user {user_id}: {err:?}");
             return Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ));
         }
-    };
+    }
 
-    match DB.remove_group_invite(invite_id) {
-        Ok(()) => Ok(()),
+    match DB.claim_opk(user_id, credentials.id, opks.len() as u32) {
+        Ok(opk_id) => {
+            if opk_id.is_none() {
+                info!("User {user_id} has run out of one-time prekeys");
+            }
+            Ok(Some(PrekeyBundle {
+                cryptoidentity,
+                opk: opk_id.map(|opk_id| (opk_id, opks[opk_id as usize].clone())),
+            }))
+        }
         Err(err) => {
-            error!("Failed to accept group invite (after creating group): {err:?}");
+            error!("Failed to claim one-time prekey of user {user_id}: {err:?}");
             Err(ServerFnError::WrappedServerError(
-                ServerError::GroupPartiallyJoined,
+                ServerError::InternalDatabaseError,
             ))
         }
     }
 }
 
-#[server(endpoint = "reject_group_invite")]
-pub async fn reject_group_invite(
-    invite_id: u64,
+/// Returns the number of own one-time prekeys which are not consumed yet.
+#[server(endpoint = "get_unused_one_time_prekey_count")]
+pub async fn get_unused_one_time_prekey_count(
     credentials: AccountCredentials,
-) -> Result<(), ServerFnError<ServerError>> {
+) -> Result<u32, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_unused_one_time_prekey_count");
     check_session(credentials)?;
 
-    let invite = match DB.get_group_invite(invite_id) {
-        Ok(invite) => invite,
-        Err(err) => {
-            error!("Failed to get group invite while trying to reject: {err:?}");
+    let account = match DB.get_user_by_id(credentials.id) {
+        Ok(Some(account)) => account,
+        Ok(None) => {
             return Err(ServerFnError::WrappedServerError(
-                ServerError::InternalDatabaseError,
+                ServerError::AccountNotFound,
             ));
         }
+        Err(err) => {
+            error!("Failed to get user by id {}: {err:?}", credentials.id);
+            return Err(ServerFnError::WrappedServerError(account_error(&*err)));
+        }
     };
 
-    if invite.invited_id != credentials.id {
-        return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
-    }
-
-    match DB.remove_group_invite(invite_id) {
-        Ok(()) => Ok(()),
+    match DB.get_consumed_opk_count(credentials.id) {
+        Ok(consumed) => Ok((account.cryptoidentity.opks.len() as u32).saturating_sub(consumed)),
         Err(err) => {
-            error!("Failed to reject group invite: {err:?}");
+            error!(
+                "Failed to get consumed one-time prekeys of user {}: {err:?}",
+                credentials.id
+            );
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1296,42 +3249,82 @@ pub async fn reject_group_invite(
     }
 }
 
-#[server(endpoint = "get_group_member_count")]
-pub async fn get_group_member_count(
-    group_id: u64,
+/// Appends new one-time prekeys to the user's cryptoidentity, where they get indexes starting at
+/// `base_index`, which must be the number of prekeys the user has published. Indexes of already
+/// published prekeys don't change. Sending the same prekeys again is accepted, so the upload can be
+/// retried if the response was lost.
+#[server(endpoint = "add_one_time_prekeys")]
+pub async fn add_one_time_prekeys(
+    base_index: u32,
+    opks: Vec<PublicKey>,
     credentials: AccountCredentials,
-) -> Result<u64, ServerFnError<ServerError>> {
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("add_one_time_prekeys");
     check_session(credentials)?;
-    check_is_in_group(credentials.id, group_id)?;
 
-    match DB.get_group_member_count(group_id) {
-        Ok(Some(member_count)) => Ok(member_count),
-        // In theory it's possible that `check_is_in_group` will return `Ok`-value then the group
-        // will be removed and after that `DB.get_group_member_count` will be called.
-        Ok(None) => Err(ServerFnError::WrappedServerError(
-            ServerError::InvalidGroupId,
+    if opks
+        .iter()
+        .any(|opk| opk.pk.len() > LIMITS.max_public_key_length)
+    {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        ));
+    }
+
+    match DB.add_one_time_prekeys(
+        credentials.id,
+        base_index,
+        &opks,
+        LIMITS.max_one_time_prekeys,
+    ) {
+        Ok(OpkUpload::Added) => Ok(()),
+        Ok(OpkUpload::IndexMismatch(published)) => {
+            warn!(
+                "User {} uploaded one-time prekeys at index {base_index}, but has {published}",
+                credentials.id
+            );
+            Err(ServerFnError::WrappedServerError(ServerError::InvalidValue))
+        }
+        Ok(OpkUpload::LimitExceeded) => Err(ServerFnError::WrappedServerError(
+            ServerError::LimitExceeded,
+        )),
+        Ok(OpkUpload::AccountNotFound) => Err(ServerFnError::WrappedServerError(
+            ServerError::AccountNotFound,
         )),
         Err(err) => {
-            error!("Failed to get group member count: {err:?}");
-            Err(ServerFnError::WrappedServerError(
-                ServerError::InternalDatabaseError,
-            ))
+            error!(
+                "Failed to add one-time prekeys of user {}: {err:?}",
+                credentials.id
+            );
+            Err(ServerFnError::WrappedServerError(account_error(&*err)))
         }
     }
 }
 
-#[server(endpoint = "get_group_members")]
-pub async fn get_group_members(
-    group_id: u64,
+/// Stores private account data (keys of the cryptoidentity, DMs and groups) so that it can be
+/// recovered on another device. It must be encrypted by the client with a key derived from the
+/// password, see `shared::crypto::private_info`.
+#[server(endpoint = "set_encrypted_private_info")]
+pub async fn set_encrypted_private_info(
+    data: Box<[u8]>,
     credentials: AccountCredentials,
-) -> Result<Vec<GroupMember>, ServerFnError<ServerError>> {
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("set_encrypted_private_info");
     check_session(credentials)?;
-    check_is_in_group(credentials.id, group_id)?;
 
-    match DB.get_group_members(group_id) {
-        Ok(members) => Ok(members),
+    if data.len() > LIMITS.max_encrypted_private_info_size {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        ));
+    }
+
+    match DB.update_encrypted_private_info(credentials.id, &data) {
+        Ok(()) => Ok(()),
         Err(err) => {
-            error!("Failed to get group members: {err:?}");
+            error!(
+                "Failed to update encrypted private info of user {}: {err:?}",
+                credentials.id
+            );
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1339,25 +3332,130 @@ pub async fn get_group_members(
     }
 }
 
-#[server(endpoint = "kick_group_member")]
-pub async fn kick_group_member(
-    group_id: u64,
-    user_id: u64,
+#[cfg(feature = "server")]
+fn check_signature(
+    algorithms: &CryptoAlgorithms,
+    public_key: &[u8],
+    data: &[u8],
+    signature: &[u8],
+) -> Result<(), ServerError> {
+    match shared::crypto::verify(
+        algorithms,
+        PublicKey {
+            pk: public_key.into(),
+        },
+        data,
+        signature,
+    ) {
+        Some(true) => Ok(()),
+        Some(false) => Err(ServerError::InvalidSignature),
+        None => Err(ServerError::UnsupportedCryptographicAlgorithm),
+    }
+}
+
+/// How many seconds a password change may be signed before or after the server's time.
+#[cfg(feature = "server")]
+const PASSWORD_CHANGE_SIGNATURE_WINDOW: u32 = 5 * 60;
+
+/// Checks that `signature` of the change to `new_public_key` was made with `old_public_key` at
+/// `signed_at`, which must be close to the server's time `unix_secs_now`, so that a captured
+/// request can't be replayed later.
+#[cfg(feature = "server")]
+fn check_password_change(
+    algorithms: &CryptoAlgorithms,
+    account_id: u64,
+    old_public_key: &[u8],
+    new_public_key: &[u8],
+    signed_at: u64,
+    unix_secs_now: u64,
+    signature: &[u8],
+) -> Result<(), ServerError> {
+    let window = SessionParams {
+        current_timestamp: signed_at,
+        authorize_before_seconds: PASSWORD_CHANGE_SIGNATURE_WINDOW,
+        authorize_after_seconds: PASSWORD_CHANGE_SIGNATURE_WINDOW,
+        session_validity_seconds: 0,
+    };
+    check_login_time(&window, unix_secs_now)?;
+    check_signature(
+        algorithms,
+        old_public_key,
+        &password_change_message(account_id, old_public_key, new_public_key, signed_at),
+        signature,
+    )
+}
+
+/// Checks that `signature` of the recovery to `new_public_key` was made with the key derived from
+/// the recovery code.
+#[cfg(feature = "server")]
+fn check_recovery(
+    algorithms: &CryptoAlgorithms,
+    recovery_public_key: &[u8],
+    new_public_key: &[u8],
+    signature: &[u8],
+) -> Result<(), ServerError> {
+    check_signature(
+        algorithms,
+        recovery_public_key,
+        &recovery_message(new_public_key),
+        signature,
+    )
+}
+
+/// Replaces the login key of the account, which is derived from the password, with
+/// `new_public_key`. The change must be signed with the current key at `signed_at` seconds since
+/// Unix epoch, so a stolen session alone can't take the account over. The private info is replaced
+/// too, since it has to be encrypted with the new password. All other sessions of the account are
+/// ended, and the token of the caller's session is replaced with the returned one.
+#[server(endpoint = "change_password")]
+pub async fn change_password(
+    algorithms: CryptoAlgorithms,
+    old_public_key: Box<[u8]>,
+    new_public_key: Box<[u8]>,
+    signed_at: u64,
+    transition_signature: Box<[u8]>,
+    encrypted_private_info: Box<[u8]>,
     credentials: AccountCredentials,
-) -> Result<(), ServerFnError<ServerError>> {
+) -> Result<[u8; 32], ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("change_password");
     check_session(credentials)?;
-    check_is_group_admin(group_id, credentials.id)?;
 
-    if credentials.id == user_id {
+    if old_public_key.len() > LIMITS.max_public_key_length
+        || new_public_key.len() > LIMITS.max_public_key_length
+        || encrypted_private_info.len() > LIMITS.max_encrypted_private_info_size
+    {
         return Err(ServerFnError::WrappedServerError(
-            ServerError::ActionOnSelfIsForbidden,
+            ServerError::InvalidArgumentSize,
         ));
     }
 
-    match DB.remove_group_member(group_id, user_id) {
-        Ok(()) => Ok(()),
+    let unix_secs_now = Utc::now().timestamp().cast_unsigned();
+    check_password_change(
+        &algorithms,
+        credentials.id,
+        &old_public_key,
+        &new_public_key,
+        signed_at,
+        unix_secs_now,
+        &transition_signature,
+    )
+    .map_err(ServerFnError::WrappedServerError)?;
+
+    match DB.update_public_key(
+        credentials.id,
+        credentials.session_token,
+        &old_public_key,
+        &new_public_key,
+        &encrypted_private_info,
+    ) {
+        Ok(Some(session_token)) => Ok(session_token),
+        // The signing key isn't the current login key of the account.
+        Ok(None) => Err(ServerFnError::WrappedServerError(ServerError::Forbidden)),
         Err(err) => {
-            error!("Failed to kick user from a group: {err:?}");
+            error!(
+                "Failed to change public key of user {}: {err:?}",
+                credentials.id
+            );
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1365,25 +3463,30 @@ pub async fn kick_group_member(
     }
 }
 
-#[server(endpoint = "promote_group_member")]
-pub async fn promote_group_member(
-    group_id: u64,
-    user_id: u64,
+/// Enables recovery of the account with a recovery code, see `shared::crypto::recovery`, or
+/// disables it if `recovery_public_key` is `None`. A new code replaces the previous one, whose
+/// private info copy is dropped.
+#[server(endpoint = "set_account_recovery")]
+pub async fn set_account_recovery(
+    recovery_public_key: Option<Box<[u8]>>,
     credentials: AccountCredentials,
 ) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("set_account_recovery");
     check_session(credentials)?;
-    check_is_group_admin(group_id, credentials.id)?;
 
-    if credentials.id == user_id {
+    if recovery_public_key
+        .as_ref()
+        .is_some_and(|key| key.len() > LIMITS.max_public_key_length)
+    {
         return Err(ServerFnError::WrappedServerError(
-            ServerError::ActionOnSelfIsForbidden,
+            ServerError::InvalidArgumentSize,
         ));
     }
 
-    match DB.set_group_member_permissions(group_id, user_id, GroupPermissions::admin()) {
+    match DB.set_account_recovery(credentials.id, recovery_public_key.as_deref()) {
         Ok(()) => Ok(()),
         Err(err) => {
-            error!("Failed to promote user in a group: {err:?}");
+            error!("Failed to set recovery of user {}: {err:?}", credentials.id);
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1391,25 +3494,30 @@ pub async fn promote_group_member(
     }
 }
 
-#[server(endpoint = "demote_group_member")]
-pub async fn demote_group_member(
-    group_id: u64,
-    user_id: u64,
+/// Same as [`set_encrypted_private_info`], but for the copy encrypted with the recovery code.
+/// Returns `Forbidden` if recovery of the account isn't enabled.
+#[server(endpoint = "set_recovery_private_info")]
+pub async fn set_recovery_private_info(
+    data: Box<[u8]>,
     credentials: AccountCredentials,
 ) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("set_recovery_private_info");
     check_session(credentials)?;
-    check_is_group_admin(group_id, credentials.id)?;
 
-    if credentials.id == user_id {
+    if data.len() > LIMITS.max_encrypted_private_info_size {
         return Err(ServerFnError::WrappedServerError(
-            ServerError::ActionOnSelfIsForbidden,
+            ServerError::InvalidArgumentSize,
         ));
     }
 
-    match DB.set_group_member_permissions(group_id, user_id, GroupPermissions::default()) {
-        Ok(()) => Ok(()),
+    match DB.update_recovery_private_info(credentials.id, &data) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ServerFnError::WrappedServerError(ServerError::Forbidden)),
         Err(err) => {
-            error!("Failed to demote user in a group: {err:?}");
+            error!(
+                "Failed to update recovery private info of user {}: {err:?}",
+                credentials.id
+            );
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1417,18 +3525,79 @@ pub async fn demote_group_member(
     }
 }
 
-#[server(endpoint = "leave_group")]
-pub async fn leave_group(
-    group_id: u64,
+/// Logs into the account whose recovery code `recovery_public_key` is derived from, replacing its
+/// login key with `new_public_key`. The code can only be used once. All other sessions of the
+/// account are ended. Returns the new session and the private info encrypted with the code, which
+/// is empty if it wasn't uploaded.
+#[server(endpoint = "recover_account")]
+pub async fn recover_account(
+    algorithms: CryptoAlgorithms,
+    recovery_public_key: Box<[u8]>,
+    new_public_key: Box<[u8]>,
+    signature: Box<[u8]>,
+) -> Result<(u64, [u8; 32], Box<[u8]>), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("recover_account");
+    if recovery_public_key.len() > LIMITS.max_public_key_length
+        || new_public_key.len() > LIMITS.max_public_key_length
+    {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        ));
+    }
+
+    check_recovery(
+        &algorithms,
+        &recovery_public_key,
+        &new_public_key,
+        &signature,
+    )
+    .map_err(ServerFnError::WrappedServerError)?;
+
+    let recovery = DB.recover_account(&recovery_public_key, &new_public_key);
+    let (id, session_token, encrypted_private_info) = match recovery {
+        Ok(AccountRecovery::Recovered(id, session_token, encrypted_private_info)) => {
+            (id, session_token, encrypted_private_info)
+        }
+        Ok(AccountRecovery::Banned) => {
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::AccountBanned,
+            ));
+        }
+        Ok(AccountRecovery::NotFound) => {
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::AccountNotFound,
+            ));
+        }
+        Err(err) => {
+            error!("Failed to recover account: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+    info!("Account recovered: {id}");
+    Ok((id, session_token, encrypted_private_info))
+}
+
+/// Returns the data previously stored with [`set_encrypted_private_info`]. It is empty if
+/// nothing was stored yet.
+#[server(endpoint = "get_encrypted_private_info")]
+pub async fn get_encrypted_private_info(
     credentials: AccountCredentials,
-) -> Result<(), ServerFnError<ServerError>> {
+) -> Result<Box<[u8]>, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("get_encrypted_private_info");
     check_session(credentials)?;
-    check_is_in_group(credentials.id, group_id)?;
 
-    match DB.remove_group_member(group_id, credentials.id) {
-        Ok(()) => Ok(()),
+    match DB.get_user_by_id(credentials.id) {
+        Ok(Some(account)) => Ok(account.encrypted_private_info),
+        Ok(None) => Err(ServerFnError::WrappedServerError(
+            ServerError::AccountNotFound,
+        )),
         Err(err) => {
-            error!("Failed to leave from a group: {err:?}");
+            error!(
+                "Failed to get encrypted private info of user {}: {err:?}",
+                credentials.id
+            );
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1436,123 +3605,138 @@ pub async fn leave_group(
     }
 }
 
-#[server(endpoint = "send_dm_file")]
-pub async fn send_dm_file(
-    group_id: u64,
-    encryption_method: String,
-    encrypted_file_name: Box<[u8]>,
-    content: Box<[u8]>,
+/// Starts uploading a file of `size` bytes in chunks. Returns the upload id.
+#[server(endpoint = "begin_upload")]
+pub async fn begin_upload(
+    size: u64,
     credentials: AccountCredentials,
 ) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("begin_upload");
     check_session(credentials)?;
-    check_is_in_dm_group(credentials.id, group_id)?;
 
-    if encryption_method.len() > LIMITS.max_encryption_method_length {
+    if size > LIMITS.max_upload_size {
         return Err(ServerFnError::WrappedServerError(
             ServerError::InvalidArgumentSize,
         ));
     }
 
-    if encrypted_file_name.len() > LIMITS.max_file_name_length {
-        return Err(ServerFnError::WrappedServerError(
-            ServerError::InvalidArgumentSize,
-        ));
+    match DB.count_pending_uploads(credentials.id) {
+        Ok(pending) if pending >= LIMITS.max_pending_uploads => {
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::LimitExceeded,
+            ));
+        }
+        Ok(_) => {}
+        Err(err) => {
+            error!("Failed to count pending uploads: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
     }
 
-    if content.len() > LIMITS.max_message_length {
-        return Err(ServerFnError::WrappedServerError(
-            ServerError::InvalidArgumentSize,
-        ));
+    match DB.begin_upload(credentials.id, size) {
+        Ok(upload_id) => Ok(upload_id),
+        Err(err) => {
+            error!("Failed to begin upload: {err:?}");
+            Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ))
+        }
     }
+}
 
-    let message_id = match DB.send_dm_file(
-        credentials.id,
-        group_id,
-        &encryption_method,
-        &encrypted_file_name,
-        None,
-    ) {
-        Ok(id) => Ok(id),
+/// Returns the size of the upload if it belongs to the user and is not finished yet.
+#[cfg(feature = "server")]
+fn check_pending_upload(upload_id: u64, user_id: u64) -> Result<u64, ServerFnError<ServerError>> {
+    match DB.get_upload(upload_id) {
+        Ok(Some((owner_id, size, false))) if owner_id == user_id => Ok(size),
+        Ok(Some(_)) => Err(ServerFnError::WrappedServerError(ServerError::Forbidden)),
+        Ok(None) => Err(ServerFnError::WrappedServerError(ServerError::FileNotFound)),
         Err(err) => {
-            error!("Failed to send DM file: {err:?}");
+            error!("Failed to get upload {upload_id}: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
         }
-    }?;
-    STORAGE.store_dm_file(message_id, &content);
-    Ok(message_id)
+    }
 }
 
-#[server(endpoint = "send_group_file")]
-pub async fn send_group_file(
-    group_id: u64,
-    encryption_method: String,
-    encrypted_file_name: Box<[u8]>,
-    content: Box<[u8]>,
+/// Writes `bytes` at `offset` of the upload. Chunks may be sent in any order and sending the
+/// same chunk again is harmless, so failed chunks can simply be retried. A chunk of another
+/// length at an offset which was already received is rejected.
+#[server(endpoint = "upload_chunk")]
+pub async fn upload_chunk(
+    upload_id: u64,
+    offset: u64,
+    bytes: Box<[u8]>,
     credentials: AccountCredentials,
-) -> Result<u64, ServerFnError<ServerError>> {
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("upload_chunk");
     check_session(credentials)?;
-    check_is_in_group(credentials.id, group_id)?;
-
-    if encryption_method.len() > LIMITS.max_encryption_method_length {
-        return Err(ServerFnError::WrappedServerError(
-            ServerError::InvalidArgumentSize,
-        ));
-    }
+    let size = check_pending_upload(upload_id, credentials.id)?;
 
-    if encrypted_file_name.len() > LIMITS.max_file_name_length {
+    if bytes.len() > LIMITS.max_upload_chunk_size {
         return Err(ServerFnError::WrappedServerError(
             ServerError::InvalidArgumentSize,
         ));
     }
-
-    if content.len() > LIMITS.max_message_length {
-        return Err(ServerFnError::WrappedServerError(
-            ServerError::InvalidArgumentSize,
-        ));
+    if bytes.is_empty()
+        || offset
+            .checked_add(bytes.len() as u64)
+            .is_none_or(|end| end > size)
+    {
+        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
     }
 
-    let message_id = match DB.send_group_file(
-        credentials.id,
-        group_id,
-        &encryption_method,
-        &encrypted_file_name,
-        None,
+    match DB.add_upload_chunk(
+        upload_id,
+        offset,
+        bytes.len() as u64,
+        LIMITS.max_upload_chunks,
+        || STORAGE.write_upload_chunk(upload_id, offset, &bytes),
     ) {
-        Ok(id) => Ok(id),
+        Ok(ChunkUpload::Added) => Ok(()),
+        Ok(ChunkUpload::Conflict) => {
+            Err(ServerFnError::WrappedServerError(ServerError::InvalidValue))
+        }
+        Ok(ChunkUpload::LimitExceeded) => Err(ServerFnError::WrappedServerError(
+            ServerError::LimitExceeded,
+        )),
+        // Finished by a concurrent request after the check above.
+        Ok(ChunkUpload::NotPending) => {
+            Err(ServerFnError::WrappedServerError(ServerError::Forbidden))
+        }
         Err(err) => {
-            error!("Failed to send DM file: {err:?}");
+            error!("Failed to add chunk of upload {upload_id}: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
         }
-    }?;
-    STORAGE.store_group_file(message_id, &content);
-    Ok(message_id)
+    }
 }
 
-#[server(endpoint = "get_dm_file")]
-pub async fn get_dm_file(
-    message_id: u64,
+/// Finalizes the upload once all of its bytes are received and returns the id of the file.
+#[server(endpoint = "finish_upload")]
+pub async fn finish_upload(
+    upload_id: u64,
     credentials: AccountCredentials,
-) -> Result<File, ServerFnError<ServerError>> {
+) -> Result<u64, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("finish_upload");
     check_session(credentials)?;
-    match DB.get_dm_file_data(message_id) {
-        Ok(Some((group_id, encryption_method, file_name))) => {
-            check_is_in_dm_group(credentials.id, group_id)?;
-            let Some(content) = STORAGE.load_dm_file(message_id) else {
-                return Err(ServerFnError::WrappedServerError(ServerError::FileNotFound));
-            };
-            Ok(File {
-                name: file_name,
-                content,
-                encryption_method,
-            })
+    check_pending_upload(upload_id, credentials.id)?;
+
+    match DB.finish_upload(upload_id, || STORAGE.finish_upload(upload_id)) {
+        Ok(UploadFinish::Finished) => Ok(upload_id),
+        Ok(UploadFinish::Incomplete) => Err(ServerFnError::WrappedServerError(
+            ServerError::UploadIncomplete,
+        )),
+        // Finished by a concurrent request after the check above.
+        Ok(UploadFinish::NotPending) => {
+            Err(ServerFnError::WrappedServerError(ServerError::Forbidden))
         }
-        Ok(None) => Err(ServerFnError::WrappedServerError(ServerError::FileNotFound)),
         Err(err) => {
-            error!("Failed to get DM file: {err:?}");
+            error!("Failed to finish upload {upload_id}: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1560,27 +3744,36 @@ pub async fn get_dm_file(
     }
 }
 
-#[server(endpoint = "get_group_file")]
-pub async fn get_group_file(
-    message_id: u64,
+/// Allows members of the DM or group to download the uploaded file. Only the owner of the file
+/// can share it.
+#[server(endpoint = "share_file")]
+pub async fn share_file(
+    file_id: u64,
+    group_id: u64,
+    dm: bool,
     credentials: AccountCredentials,
-) -> Result<File, ServerFnError<ServerError>> {
+) -> Result<(), ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("share_file");
     check_session(credentials)?;
-    match DB.get_group_file_data(message_id) {
-        Ok(Some((group_id, encryption_method, file_name))) => {
-            check_is_in_group(credentials.id, group_id)?;
-            let Some(content) = STORAGE.load_group_file(message_id) else {
-                return Err(ServerFnError::WrappedServerError(ServerError::FileNotFound));
-            };
-            Ok(File {
-                name: file_name,
-                content,
-                encryption_method,
-            })
+    match DB.get_upload(file_id) {
+        Ok(Some((owner_id, _, true))) if owner_id == credentials.id => {}
+        Ok(Some((owner_id, _, _))) if owner_id != credentials.id => {
+            return Err(ServerFnError::WrappedServerError(ServerError::Forbidden));
         }
-        Ok(None) => Err(ServerFnError::WrappedServerError(ServerError::FileNotFound)),
+        Ok(_) => return Err(ServerFnError::WrappedServerError(ServerError::FileNotFound)),
         Err(err) => {
-            error!("Failed to get group file: {err:?}");
+            error!("Failed to get file {file_id}: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    }
+    check_is_participant(credentials.id, group_id, dm)?;
+
+    match DB.share_file(file_id, group_id, dm) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            error!("Failed to share file {file_id}: {err:?}");
             Err(ServerFnError::WrappedServerError(
                 ServerError::InternalDatabaseError,
             ))
@@ -1588,6 +3781,69 @@ pub async fn get_group_file(
     }
 }
 
+/// Returns up to `length` bytes of the uploaded file starting at `offset`. The range is
+/// truncated at the end of the file, so large files can be downloaded in parts of the same size
+/// and an interrupted download can be resumed from the last received byte.
+#[server(endpoint = "download_file_range")]
+pub async fn download_file_range(
+    file_id: u64,
+    offset: u64,
+    length: u64,
+    credentials: AccountCredentials,
+) -> Result<FileRange, ServerFnError<ServerError>> {
+    let _timer = METRICS.track_request("download_file_range");
+    check_session(credentials)?;
+
+    if length > LIMITS.max_download_range_size {
+        return Err(ServerFnError::WrappedServerError(
+            ServerError::InvalidArgumentSize,
+        ));
+    }
+
+    let (owner_id, size) = match DB.get_upload(file_id) {
+        Ok(Some((owner_id, size, true))) => (owner_id, size),
+        Ok(_) => return Err(ServerFnError::WrappedServerError(ServerError::FileNotFound)),
+        Err(err) => {
+            error!("Failed to get file {file_id}: {err:?}");
+            return Err(ServerFnError::WrappedServerError(
+                ServerError::InternalDatabaseError,
+            ));
+        }
+    };
+    if owner_id != credentials.id {
+        match DB.is_file_shared_with(file_id, credentials.id) {
+            Ok(true) => {}
+            Ok(false) => return Err(ServerFnError::WrappedServerError(ServerError::Forbidden)),
+            Err(err) => {
+                error!("Failed to check access to file {file_id}: {err:?}");
+                return Err(ServerFnError::WrappedServerError(
+                    ServerError::InternalDatabaseError,
+                ));
+            }
+        }
+    }
+
+    let Some(length) = secret::upload::download_range_length(size, offset, length) else {
+        return Err(ServerFnError::WrappedServerError(ServerError::InvalidValue));
+    };
+    if length == 0 {
+        return Ok(FileRange {
+            total_size: size,
+            bytes: Box::new([]),
+        });
+    }
+    match STORAGE.read_uploaded_file_range(file_id, offset, length) {
+        Ok(bytes) => Ok(FileRange {
+            total_size: size,
+            bytes,
+        }),
+        Err(err) => {
+            error!("Failed to read file {file_id}: {err:?}");
+            Err(ServerFnError::WrappedServerError(ServerError::FileNotFound))
+        }
+    }
+}
+
 #[cfg(feature = "server")]
 pub fn init_server() {
     println!("Initializing server");
@@ -1607,5 +3863,712 @@ pub fn init_server() {
         println!("Database initialized successfully");
     }
 
+    start_session_purge_task();
+
     println!("Server initialized");
 }
+
+/// How often expired sessions, invites and uploads are deleted.
+#[cfg(feature = "server")]
+const SESSION_PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Starts a background thread which periodically deletes expired sessions, invites and uploads.
+/// The thread is started only once, no matter how many times the server is initialized.
+#[cfg(feature = "server")]
+fn start_session_purge_task() {
+    static STARTED: std::sync::Once = std::sync::Once::new();
+    STARTED.call_once(|| {
+        std::thread::spawn(|| {
+            while !shutdown::is_shutting_down() {
+                match DB.purge_expired_sessions() {
+                    Ok(0) => {}
+                    Ok(count) => info!("Removed {count} expired sessions"),
+                    Err(err) => error!("Failed to remove expired sessions: {err:?}"),
+                }
+                match DB.remove_expired_invites() {
+                    Ok(0) => {}
+                    Ok(count) => info!("Removed {count} expired invites"),
+                    Err(err) => error!("Failed to remove expired invites: {err:?}"),
+                }
+                if let Err(err) = DB.remove_expired_pow_challenges(
+                    chrono::Utc::now().naive_utc()
+                        - chrono::Duration::seconds(
+                            secret::registration::CHALLENGE_VALIDITY_SECONDS.into(),
+                        ),
+                ) {
+                    error!("Failed to remove expired proof of work challenges: {err:?}");
+                }
+                match DB.remove_stale_uploads(secret::upload::UPLOAD_VALIDITY_SECONDS) {
+                    Ok(upload_ids) => {
+                        for &upload_id in &upload_ids {
+                            if let Err(err) = STORAGE.remove_upload(upload_id) {
+                                error!("Failed to remove data of upload {upload_id}: {err:?}");
+                            }
+                        }
+                        if !upload_ids.is_empty() {
+                            info!("Removed {} stale uploads", upload_ids.len());
+                        }
+                    }
+                    Err(err) => error!("Failed to remove stale uploads: {err:?}"),
+                }
+                shutdown::wait_for_shutdown(SESSION_PURGE_INTERVAL);
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use std::str::FromStr;
+
+    use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use crate::{AccountCredentials, CredentialsParseError, ServerError};
+
+    fn all_server_errors() -> Vec<ServerError> {
+        // Fails to compile when a variant is added, so that it gets listed below as well.
+        match ServerError::InternalDatabaseError {
+            ServerError::InternalDatabaseError
+            | ServerError::InvalidSessionToken
+            | ServerError::SessionExpired
+            | ServerError::Forbidden
+            | ServerError::GroupPartiallyCreated(_)
+            | ServerError::InvalidArgumentSize
+            | ServerError::InvalidValue
+            | ServerError::InvalidUserId
+            | ServerError::LimitExceeded
+            | ServerError::SignatureEarly
+            | ServerError::SignatureExpired
+            | ServerError::ClockSkew { .. }
+            | ServerError::InvalidSignature
+            | ServerError::UnsupportedCryptographicAlgorithm
+            | ServerError::AccountNotFound
+            | ServerError::AlreadyInGroup
+            | ServerError::GroupPartiallyJoined
+            | ServerError::InvalidGroupId
+            | ServerError::ActionOnSelfIsForbidden
+            | ServerError::FileNotFound
+            | ServerError::UploadIncomplete
+            | ServerError::InviteExpired
+            | ServerError::InviteAlreadyExists
+            | ServerError::RegistrationClosed
+            | ServerError::InvalidSignupCode
+            | ServerError::InvalidProofOfWork
+            | ServerError::AccountBanned
+            | ServerError::CorruptIdentity
+            | ServerError::IdentityMismatch => {}
+        }
+        let mut errors = vec![
+            ServerError::InternalDatabaseError,
+            ServerError::InvalidSessionToken,
+            ServerError::SessionExpired,
+            ServerError::Forbidden,
+            ServerError::InvalidArgumentSize,
+            ServerError::InvalidValue,
+            ServerError::InvalidUserId,
+            ServerError::LimitExceeded,
+            ServerError::SignatureEarly,
+            ServerError::SignatureExpired,
+            ServerError::InvalidSignature,
+            ServerError::UnsupportedCryptographicAlgorithm,
+            ServerError::AccountNotFound,
+            ServerError::AlreadyInGroup,
+            ServerError::GroupPartiallyJoined,
+            ServerError::InvalidGroupId,
+            ServerError::ActionOnSelfIsForbidden,
+            ServerError::FileNotFound,
+            ServerError::UploadIncomplete,
+            ServerError::InviteExpired,
+            ServerError::InviteAlreadyExists,
+            ServerError::RegistrationClosed,
+            ServerError::InvalidSignupCode,
+            ServerError::InvalidProofOfWork,
+            ServerError::AccountBanned,
+            ServerError::CorruptIdentity,
+            ServerError::IdentityMismatch,
+        ];
+        for value in [0, 1, 42, u64::MAX] {
+            errors.push(ServerError::GroupPartiallyCreated(value));
+            errors.push(ServerError::ClockSkew { server_time: value });
+        }
+        errors
+    }
+
+    #[test]
+    fn test_server_error_round_trip() {
+        for error in all_server_errors() {
+            assert_eq!(ServerError::from_str(&error.to_string()), Ok(error));
+        }
+    }
+
+    #[test]
+    fn test_server_error_boxed() {
+        fn fail() -> Result<(), Box<dyn std::error::Error>> {
+            Err(ServerError::GroupPartiallyCreated(7))?
+        }
+
+        let err = fail().unwrap_err();
+        assert_eq!(err.to_string(), "GroupPartiallyCreated:7");
+        assert_eq!(
+            err.downcast_ref::<ServerError>(),
+            Some(&ServerError::GroupPartiallyCreated(7))
+        );
+        assert!(err.source().is_none());
+    }
+
+    #[test]
+    fn test_server_error_parse_errors() {
+        assert_eq!(ServerError::from_str(""), Err(()));
+        assert_eq!(ServerError::from_str("Unknown"), Err(()));
+        assert_eq!(ServerError::from_str("forbidden"), Err(()));
+        assert_eq!(ServerError::from_str("GroupPartiallyCreated"), Err(()));
+        assert_eq!(ServerError::from_str("GroupPartiallyCreated:"), Err(()));
+        assert_eq!(ServerError::from_str("GroupPartiallyCreated:-1"), Err(()));
+        assert_eq!(ServerError::from_str("Forbidden:1"), Err(()));
+    }
+
+    #[test]
+    fn test_credentials_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut all_credentials = vec![
+            AccountCredentials::default(),
+            AccountCredentials {
+                id: u64::MAX,
+                session_token: [0xff; 32],
+            },
+        ];
+        all_credentials.extend((0..1000).map(|_| AccountCredentials {
+            id: rng.random(),
+            session_token: rng.random(),
+        }));
+        for credentials in all_credentials {
+            let encoded = credentials.to_string();
+            // Credentials used to be put into route URLs, so they must not need escaping.
+            assert!(
+                encoded
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+            );
+            assert_eq!(AccountCredentials::from_str(&encoded), Ok(credentials));
+        }
+    }
+
+    #[test]
+    fn test_credentials_parse_errors() {
+        let encoded = AccountCredentials::default().to_string();
+        assert_eq!(
+            AccountCredentials::from_str("not base64!"),
+            Err(CredentialsParseError::InvalidBase64)
+        );
+        // Padding and the standard alphabet are not accepted.
+        assert_eq!(
+            AccountCredentials::from_str(&format!("{encoded}=")),
+            Err(CredentialsParseError::InvalidBase64)
+        );
+        assert_eq!(
+            AccountCredentials::from_str(&"+".repeat(encoded.len())),
+            Err(CredentialsParseError::InvalidBase64)
+        );
+        assert_eq!(
+            AccountCredentials::from_str(""),
+            Err(CredentialsParseError::InvalidLength(0))
+        );
+        assert_eq!(
+            AccountCredentials::from_str(&BASE64_URL_SAFE_NO_PAD.encode([0; 39])),
+            Err(CredentialsParseError::InvalidLength(39))
+        );
+        assert_eq!(
+            AccountCredentials::from_str(&BASE64_URL_SAFE_NO_PAD.encode([0; 41])),
+            Err(CredentialsParseError::InvalidLength(41))
+        );
+    }
+}
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use std::collections::HashMap;
+
+    use shared::{crypto::recovery, types::GroupPermissions};
+
+    use crate::{
+        AccountCredentials, ServerError, ServerFnError, SessionParams, check_is_group_admin_in,
+        check_is_participant_in, check_login_time, check_password_change, check_recovery,
+        check_session_in, find_login_account_in, offered_invite_permissions_in,
+        password_change_message, recovery_message,
+        secret::db::{Db, DbResult, SessionStatus},
+        session_credentials_valid,
+    };
+
+    /// Database with a fixed set of accounts, sessions and group members.
+    #[derive(Default)]
+    struct MockDb {
+        /// `(id, username, email, public_key)` of each account.
+        accounts: Vec<(u64, Option<&'static str>, Option<&'static str>, Vec<u8>)>,
+        banned: Vec<u64>,
+        identity_keys: HashMap<u64, Box<[u8]>>,
+        sessions: HashMap<u64, SessionStatus>,
+        dm_groups: Vec<(u64, u64)>,
+        group_members: HashMap<(u64, u64), GroupPermissions>,
+        unavailable: bool,
+    }
+
+    impl MockDb {
+        fn check_available(&self) -> DbResult<()> {
+            if self.unavailable {
+                Err("database is closed".into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl Db for MockDb {
+        fn session_status(
+            &self,
+            account_id: u64,
+            _session_token: [u8; 32],
+        ) -> DbResult<SessionStatus> {
+            self.check_available()?;
+            Ok(self
+                .sessions
+                .get(&account_id)
+                .copied()
+                .unwrap_or(SessionStatus::Unknown))
+        }
+
+        fn touch_session(
+            &self,
+            _account_id: u64,
+            _session_token: [u8; 32],
+            _extension: u32,
+            _max_lifetime: u32,
+        ) -> DbResult<bool> {
+            Err("sessions can't be extended".into())
+        }
+
+        fn is_participant(
+            &self,
+            user_id: u64,
+            conversation_id: u64,
+            is_dm: bool,
+        ) -> DbResult<bool> {
+            self.check_available()?;
+            Ok(if is_dm {
+                self.dm_groups.contains(&(user_id, conversation_id))
+            } else {
+                self.group_members.contains_key(&(conversation_id, user_id))
+            })
+        }
+
+        fn get_group_member_permissions(
+            &self,
+            group_id: u64,
+            user_id: u64,
+        ) -> DbResult<Option<GroupPermissions>> {
+            self.check_available()?;
+            Ok(self.group_members.get(&(group_id, user_id)).cloned())
+        }
+
+        fn find_user_with_pubkey(
+            &self,
+            account_name: String,
+            public_key: &[u8],
+        ) -> DbResult<Option<u64>> {
+            self.check_available()?;
+            let handle = Some(account_name.as_str());
+            Ok(self
+                .accounts
+                .iter()
+                .find(|(_, username, email, key)| {
+                    (*username == handle || *email == handle) && key.as_slice() == public_key
+                })
+                .map(|(id, ..)| *id))
+        }
+
+        fn is_account_banned(&self, account_id: u64) -> DbResult<bool> {
+            self.check_available()?;
+            Ok(self.banned.contains(&account_id))
+        }
+
+        fn identity_key(&self, account_id: u64) -> DbResult<Option<Box<[u8]>>> {
+            self.check_available()?;
+            Ok(self.identity_keys.get(&account_id).cloned())
+        }
+    }
+
+    fn credentials(id: u64) -> AccountCredentials {
+        AccountCredentials {
+            id,
+            session_token: [0; 32],
+        }
+    }
+
+    fn server_error<T>(error: ServerError) -> Result<T, ServerFnError<ServerError>> {
+        Err(ServerFnError::WrappedServerError(error))
+    }
+
+    #[test]
+    fn test_session_credentials_valid() {
+        let db = MockDb {
+            sessions: HashMap::from([
+                (1, SessionStatus::Active { banned: false }),
+                (2, SessionStatus::Active { banned: true }),
+                (3, SessionStatus::Expired),
+            ]),
+            ..MockDb::default()
+        };
+        // A failure to extend the session doesn't fail the request.
+        assert_eq!(session_credentials_valid(&db, credentials(1)), Ok(true));
+        assert_eq!(
+            session_credentials_valid(&db, credentials(2)),
+            server_error(ServerError::AccountBanned)
+        );
+        assert_eq!(
+            session_credentials_valid(&db, credentials(3)),
+            server_error(ServerError::SessionExpired)
+        );
+        assert_eq!(session_credentials_valid(&db, credentials(4)), Ok(false));
+
+        let db = MockDb {
+            unavailable: true,
+            ..db
+        };
+        assert_eq!(session_credentials_valid(&db, credentials(1)), Ok(false));
+        assert_eq!(
+            check_session_in(&db, credentials(1)),
+            server_error(ServerError::InvalidSessionToken)
+        );
+    }
+
+    #[test]
+    fn test_conversation_checks() {
+        let db = MockDb {
+            dm_groups: vec![(1, 10)],
+            group_members: HashMap::from([
+                ((20, 1), GroupPermissions::admin()),
+                ((20, 2), GroupPermissions::default()),
+            ]),
+            ..MockDb::default()
+        };
+        assert_eq!(check_is_participant_in(&db, 1, 10, true), Ok(()));
+        assert_eq!(
+            check_is_participant_in(&db, 1, 10, false),
+            server_error(ServerError::Forbidden)
+        );
+        assert_eq!(check_is_participant_in(&db, 2, 20, false), Ok(()));
+        assert_eq!(
+            check_is_participant_in(&db, 3, 20, false),
+            server_error(ServerError::Forbidden)
+        );
+
+        assert_eq!(check_is_group_admin_in(&db, 20, 1), Ok(()));
+        assert_eq!(
+            check_is_group_admin_in(&db, 20, 2),
+            server_error(ServerError::Forbidden)
+        );
+        assert_eq!(
+            check_is_group_admin_in(&db, 20, 3),
+            server_error(ServerError::Forbidden)
+        );
+
+        let db = MockDb {
+            unavailable: true,
+            ..db
+        };
+        assert_eq!(
+            check_is_participant_in(&db, 1, 10, true),
+            server_error(ServerError::InternalDatabaseError)
+        );
+        assert_eq!(
+            check_is_group_admin_in(&db, 20, 1),
+            server_error(ServerError::InternalDatabaseError)
+        );
+    }
+
+    #[test]
+    fn test_offered_invite_permissions() {
+        let db = MockDb {
+            group_members: HashMap::from([
+                ((20, 1), GroupPermissions::admin()),
+                ((20, 2), GroupPermissions::default()),
+            ]),
+            ..MockDb::default()
+        };
+        let admin = GroupPermissions::admin().to_bytes();
+        let member = GroupPermissions::default().to_bytes();
+        assert_eq!(
+            offered_invite_permissions_in(&db, 20, 1, 1, &admin),
+            Ok(admin.clone())
+        );
+        // Admins may update the invites of other members.
+        assert_eq!(
+            offered_invite_permissions_in(&db, 20, 1, 2, &admin),
+            Ok(admin.clone())
+        );
+        // Other members may only update their own invites, without offering to be an admin.
+        assert_eq!(
+            offered_invite_permissions_in(&db, 20, 2, 2, &admin),
+            Ok(member.clone())
+        );
+        assert_eq!(
+            offered_invite_permissions_in(&db, 20, 2, 1, &member),
+            server_error(ServerError::Forbidden)
+        );
+        // An inviter who has left the group can't update the invite anymore.
+        assert_eq!(
+            offered_invite_permissions_in(&db, 20, 3, 3, &member),
+            server_error(ServerError::Forbidden)
+        );
+        assert_eq!(
+            offered_invite_permissions_in(&db, 20, 1, 1, &[4]),
+            server_error(ServerError::InvalidValue)
+        );
+
+        let db = MockDb {
+            unavailable: true,
+            ..db
+        };
+        assert_eq!(
+            offered_invite_permissions_in(&db, 20, 1, 1, &admin),
+            server_error(ServerError::InternalDatabaseError)
+        );
+    }
+
+    #[test]
+    fn test_login_by_handle() {
+        let db = MockDb {
+            accounts: vec![
+                (1, Some("user"), Some("user@example.com"), vec![1]),
+                (2, None, Some("other@example.com"), vec![2]),
+                (3, Some("banned"), None, vec![3]),
+            ],
+            banned: vec![3],
+            ..MockDb::default()
+        };
+        assert_eq!(
+            find_login_account_in(&db, "user".to_owned(), &[1], None),
+            Ok(1)
+        );
+        assert_eq!(
+            find_login_account_in(&db, "user@example.com".to_owned(), &[1], None),
+            Ok(1)
+        );
+        assert_eq!(
+            find_login_account_in(&db, "other@example.com".to_owned(), &[2], None),
+            Ok(2)
+        );
+        // The key derived from a wrong password doesn't match.
+        assert_eq!(
+            find_login_account_in(&db, "user".to_owned(), &[2], None),
+            server_error(ServerError::AccountNotFound)
+        );
+        assert_eq!(
+            find_login_account_in(&db, "nobody".to_owned(), &[1], None),
+            server_error(ServerError::AccountNotFound)
+        );
+        assert_eq!(
+            find_login_account_in(&db, "banned".to_owned(), &[3], None),
+            server_error(ServerError::AccountBanned)
+        );
+
+        let db = MockDb {
+            unavailable: true,
+            ..db
+        };
+        assert_eq!(
+            find_login_account_in(&db, "user".to_owned(), &[1], None),
+            server_error(ServerError::InternalDatabaseError)
+        );
+    }
+
+    #[test]
+    fn test_login_identity_mismatch() {
+        let db = MockDb {
+            accounts: vec![(1, Some("user"), None, vec![1])],
+            identity_keys: HashMap::from([(1, [7; 32].into())]),
+            ..MockDb::default()
+        };
+        assert_eq!(
+            find_login_account_in(&db, "user".to_owned(), &[1], Some(&[7; 32][..])),
+            Ok(1)
+        );
+        // The key was replaced on the server since the client has last seen it.
+        assert_eq!(
+            find_login_account_in(&db, "user".to_owned(), &[1], Some(&[8; 32][..])),
+            server_error(ServerError::IdentityMismatch)
+        );
+        // Clients which don't remember the key aren't checked.
+        assert_eq!(
+            find_login_account_in(&db, "user".to_owned(), &[1], None),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn test_check_login_time() {
+        let session_params = SessionParams {
+            current_timestamp: 1_000_000,
+            authorize_before_seconds: 60,
+            authorize_after_seconds: 300,
+            session_validity_seconds: 3600,
+        };
+        for now in [999_940, 1_000_000, 1_000_300] {
+            assert_eq!(check_login_time(&session_params, now), Ok(()));
+        }
+        // The clock of the client is six minutes behind, then an hour ahead.
+        for now in [1_000_360, 996_400] {
+            assert_eq!(
+                check_login_time(&session_params, now),
+                Err(ServerError::ClockSkew { server_time: now })
+            );
+        }
+
+        // Window bounds don't overflow.
+        let session_params = SessionParams {
+            current_timestamp: 10,
+            ..session_params
+        };
+        assert_eq!(check_login_time(&session_params, 0), Ok(()));
+        let session_params = SessionParams {
+            current_timestamp: u64::MAX,
+            ..session_params
+        };
+        assert_eq!(check_login_time(&session_params, u64::MAX), Ok(()));
+    }
+
+    #[test]
+    fn test_check_password_change() {
+        let algorithms = shared::crypto::preferred_alogirthm();
+        let (old_private_key, old_public_key) =
+            shared::crypto::kdf_keypair(&algorithms, b"old password").unwrap();
+        let (new_private_key, new_public_key) =
+            shared::crypto::kdf_keypair(&algorithms, b"new password").unwrap();
+        let sign = |private_key, public_key, account_id| {
+            shared::crypto::sign(
+                &algorithms,
+                private_key,
+                public_key,
+                &password_change_message(
+                    account_id,
+                    &old_public_key.pk,
+                    &new_public_key.pk,
+                    1_000_000,
+                ),
+            )
+            .unwrap()
+        };
+        let check = |account_id, new_public_key: &[u8], signed_at, now, signature: &[u8]| {
+            check_password_change(
+                &algorithms,
+                account_id,
+                &old_public_key.pk,
+                new_public_key,
+                signed_at,
+                now,
+                signature,
+            )
+        };
+
+        let signature = sign(old_private_key.clone(), old_public_key.clone(), 1);
+        for now in [999_700, 1_000_000, 1_000_300] {
+            assert_eq!(
+                check(1, &new_public_key.pk, 1_000_000, now, &signature),
+                Ok(())
+            );
+        }
+        // The signature is bound to the account, the new key and the time.
+        assert_eq!(
+            check(2, &new_public_key.pk, 1_000_000, 1_000_000, &signature),
+            Err(ServerError::InvalidSignature)
+        );
+        assert_eq!(
+            check(1, &old_public_key.pk, 1_000_000, 1_000_000, &signature),
+            Err(ServerError::InvalidSignature)
+        );
+        assert_eq!(
+            check(1, &new_public_key.pk, 1_000_100, 1_000_000, &signature),
+            Err(ServerError::InvalidSignature)
+        );
+        // A captured request can't be replayed later.
+        assert_eq!(
+            check(1, &new_public_key.pk, 1_000_000, 1_000_301, &signature),
+            Err(ServerError::ClockSkew {
+                server_time: 1_000_301
+            })
+        );
+        // Knowing only the new password doesn't prove control of the account.
+        let signature = sign(new_private_key, new_public_key.clone(), 1);
+        assert_eq!(
+            check(1, &new_public_key.pk, 1_000_000, 1_000_000, &signature),
+            Err(ServerError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_check_recovery() {
+        let algorithms = shared::crypto::preferred_alogirthm();
+        let code = "ABCD-EFGH-JKLM-NPQR-STUV-WXYZ-2345-6789";
+        let (recovery_private_key, recovery_public_key) =
+            recovery::recovery_keypair(&algorithms, code).unwrap();
+        let (new_private_key, new_public_key) =
+            shared::crypto::kdf_keypair(&algorithms, b"new password").unwrap();
+
+        let signature = shared::crypto::sign(
+            &algorithms,
+            recovery_private_key,
+            recovery_public_key.clone(),
+            &recovery_message(&new_public_key.pk),
+        )
+        .unwrap();
+        assert_eq!(
+            check_recovery(
+                &algorithms,
+                &recovery_public_key.pk,
+                &new_public_key.pk,
+                &signature
+            ),
+            Ok(())
+        );
+        // The signature is bound to the new key.
+        assert_eq!(
+            check_recovery(
+                &algorithms,
+                &recovery_public_key.pk,
+                &recovery_public_key.pk,
+                &signature
+            ),
+            Err(ServerError::InvalidSignature)
+        );
+        // A mistyped code derives another key.
+        let (_, other_public_key) =
+            recovery::recovery_keypair(&algorithms, "ABCD-EFGH-JKLM-NPQR-STUV-WXYZ-2345-6788")
+                .unwrap();
+        assert_eq!(
+            check_recovery(
+                &algorithms,
+                &other_public_key.pk,
+                &new_public_key.pk,
+                &signature
+            ),
+            Err(ServerError::InvalidSignature)
+        );
+        // Knowing the new password doesn't prove knowledge of the code.
+        let signature = shared::crypto::sign(
+            &algorithms,
+            new_private_key,
+            new_public_key.clone(),
+            &recovery_message(&new_public_key.pk),
+        )
+        .unwrap();
+        assert_eq!(
+            check_recovery(
+                &algorithms,
+                &recovery_public_key.pk,
+                &new_public_key.pk,
+                &signature
+            ),
+            Err(ServerError::InvalidSignature)
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        LazyLock, RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{EndpointMetrics, LATENCY_BUCKETS_MS};
+
+#[derive(Debug, Default)]
+struct EndpointCounters {
+    requests: AtomicU64,
+    /// Has one more bucket than [`LATENCY_BUCKETS_MS`] for the calls slower than all of them.
+    latency_buckets: [AtomicU64; LATENCY_BUCKETS_MS.len() + 1],
+    total_latency_micros: AtomicU64,
+}
+
+/// In-memory counters of the server load. They are reset when the server restarts.
+#[derive(Debug)]
+pub struct Metrics {
+    started: Instant,
+    /// Counters of each endpoint. Endpoints are added on their first call, so that only a read
+    /// lock is needed afterwards.
+    endpoints: RwLock<BTreeMap<&'static str, EndpointCounters>>,
+    messages_sent: AtomicU64,
+    corrupt_identities: AtomicU64,
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Records how long an endpoint took when dropped. Returned by [`Metrics::track_request`].
+#[must_use = "the duration is recorded when the timer is dropped"]
+pub struct RequestTimer<'a> {
+    metrics: &'a Metrics,
+    endpoint: &'static str,
+    started: Instant,
+}
+
+impl Drop for RequestTimer<'_> {
+    fn drop(&mut self) {
+        self.metrics
+            .record_latency(self.endpoint, self.started.elapsed());
+    }
+}
+
+/// Returns the index of the latency bucket `duration` belongs to.
+fn latency_bucket(duration: Duration) -> usize {
+    let micros = duration.as_micros();
+    LATENCY_BUCKETS_MS
+        .iter()
+        .position(|&bound| micros <= bound as u128 * 1000)
+        .unwrap_or(LATENCY_BUCKETS_MS.len())
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            endpoints: RwLock::new(BTreeMap::new()),
+            messages_sent: AtomicU64::new(0),
+            corrupt_identities: AtomicU64::new(0),
+        }
+    }
+
+    fn with_endpoint(&self, endpoint: &'static str, f: impl Fn(&EndpointCounters)) {
+        if let Some(counters) = self.endpoints.read().unwrap().get(endpoint) {
+            f(counters);
+            return;
+        }
+        f(self.endpoints.write().unwrap().entry(endpoint).or_default());
+    }
+
+    /// Must be called at the start of every endpoint, with the result kept until it returns.
+    pub fn track_request(&self, endpoint: &'static str) -> RequestTimer<'_> {
+        self.record_request(endpoint);
+        RequestTimer {
+            metrics: self,
+            endpoint,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn record_request(&self, endpoint: &'static str) {
+        self.with_endpoint(endpoint, |counters| {
+            counters.requests.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+
+    pub fn record_latency(&self, endpoint: &'static str, duration: Duration) {
+        self.with_endpoint(endpoint, |counters| {
+            counters.latency_buckets[latency_bucket(duration)].fetch_add(1, Ordering::Relaxed);
+            counters.total_latency_micros.fetch_add(
+                duration.as_micros().try_into().unwrap_or(u64::MAX),
+                Ordering::Relaxed,
+            );
+        });
+    }
+
+    /// Counts a message or a file which was sent to a DM or multi-user group.
+    pub fn record_message(&self) {
+        self.messages_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts an account which was skipped because its stored identity couldn't be decoded.
+    pub fn record_corrupt_identity(&self) {
+        self.corrupt_identities.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn request_count(&self, endpoint: &str) -> u64 {
+        self.endpoints
+            .read()
+            .unwrap()
+            .get(endpoint)
+            .map_or(0, |counters| counters.requests.load(Ordering::Relaxed))
+    }
+
+    /// Returns counters of each endpoint which was called at least once, ordered by the endpoint
+    /// name.
+    pub fn endpoint_metrics(&self) -> Vec<EndpointMetrics> {
+        self.endpoints
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(endpoint, counters)| EndpointMetrics {
+                endpoint: (*endpoint).to_owned(),
+                requests: counters.requests.load(Ordering::Relaxed),
+                latency_buckets: counters
+                    .latency_buckets
+                    .iter()
+                    .map(|count| count.load(Ordering::Relaxed))
+                    .collect(),
+                total_latency_micros: counters.total_latency_micros.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    pub fn messages_sent(&self) -> u64 {
+        self.messages_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn corrupt_identities(&self) -> u64 {
+        self.corrupt_identities.load(Ordering::Relaxed)
+    }
+
+    pub fn uptime_seconds(&self) -> u64 {
+        self.started.elapsed().as_secs()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+        time::Duration,
+    };
+
+    use super::{METRICS, Metrics, latency_bucket};
+    use crate::LATENCY_BUCKETS_MS;
+
+    #[test]
+    fn test_request_counters() {
+        let metrics = Metrics::new();
+        assert_eq!(metrics.request_count("find_user"), 0);
+        metrics.record_request("find_user");
+        metrics.record_request("login_account");
+        metrics.record_request("find_user");
+        metrics.record_message();
+        assert_eq!(metrics.request_count("find_user"), 2);
+        let endpoints = metrics.endpoint_metrics();
+        assert_eq!(
+            endpoints
+                .iter()
+                .map(|endpoint| (endpoint.endpoint.as_str(), endpoint.requests))
+                .collect::<Vec<_>>(),
+            vec![("find_user", 2), ("login_account", 1)]
+        );
+        assert_eq!(metrics.messages_sent(), 1);
+    }
+
+    #[test]
+    fn test_endpoint_call_increments_counter() {
+        let before = METRICS.request_count("server_info");
+        // `get_server_info` doesn't wait for anything, so a single poll completes it.
+        let result = pin!(crate::get_server_info()).poll(&mut Context::from_waker(Waker::noop()));
+        assert!(matches!(result, Poll::Ready(Ok(_))));
+        // Other tests may call it at the same time, so the counter can grow by more than 1.
+        assert!(METRICS.request_count("server_info") > before);
+    }
+
+    #[test]
+    fn test_latency_bucket() {
+        assert_eq!(latency_bucket(Duration::ZERO), 0);
+        assert_eq!(latency_bucket(Duration::from_micros(1500)), 1);
+        assert_eq!(latency_bucket(Duration::from_millis(100)), 5);
+        assert_eq!(
+            latency_bucket(Duration::from_secs(60)),
+            LATENCY_BUCKETS_MS.len()
+        );
+    }
+
+    #[test]
+    fn test_timer_records_duration() {
+        let metrics = Metrics::new();
+        {
+            let _timer = metrics.track_request("upload_chunk");
+            std::thread::sleep(Duration::from_millis(3));
+        }
+        let endpoints = metrics.endpoint_metrics();
+        assert_eq!(endpoints.len(), 1);
+        let endpoint = &endpoints[0];
+        assert_eq!(endpoint.requests, 1);
+        assert_eq!(endpoint.latency_buckets.len(), LATENCY_BUCKETS_MS.len() + 1);
+        assert_eq!(endpoint.latency_buckets.iter().sum::<u64>(), 1);
+        // The call took at least 3 ms, so it can't be in the first bucket.
+        assert_eq!(endpoint.latency_buckets[0], 0);
+        assert!(endpoint.total_latency_micros >= 3000);
+    }
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+/// Number of items returned by the list endpoints which don't let the client choose it.
+pub const LIST_PAGE_SIZE: u32 = 30;
+
+/// Items returned by a list endpoint, which returns at most a limited number of them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Whether there are more items than returned. Endpoints taking a cursor return the next
+    /// ones when it is taken from the last returned item.
+    pub has_more: bool,
+}
+
+impl<T> Page<T> {
+    /// Makes a page of at most `limit` items. `items` should be fetched with a limit one higher,
+    /// so that the extra item tells that there are more of them.
+    pub fn from_overfetched(mut items: Vec<T>, limit: u32) -> Self {
+        let has_more = items.len() > limit as usize;
+        items.truncate(limit as usize);
+        Self { items, has_more }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Page;
+
+    #[test]
+    fn test_page_has_more() {
+        let page = Page::from_overfetched(vec![5, 4, 3, 2], 3);
+        assert_eq!(page.items, [5, 4, 3]);
+        assert!(page.has_more);
+
+        let page = Page::from_overfetched(vec![5, 4, 3], 3);
+        assert_eq!(page.items, [5, 4, 3]);
+        assert!(!page.has_more);
+
+        let page = Page::from_overfetched(vec![5], 3);
+        assert_eq!(page.items, [5]);
+        assert!(!page.has_more);
+
+        let page = Page::<u64>::from_overfetched(vec![], 3);
+        assert!(page.items.is_empty());
+        assert!(!page.has_more);
+    }
+}
@@ -0,0 +1,97 @@
+use std::{str::FromStr, sync::LazyLock};
+
+use dioxus::logger::tracing::warn;
+
+/// Who may create new accounts, set with the `PEREGRINE_REGISTRATION` environment variable.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationMode {
+    /// Anyone may register. Used if the variable isn't set.
+    #[default]
+    Open,
+    /// Registration requires a signup code minted by an admin (`invite-only`).
+    InviteOnly,
+    /// No new accounts can be created.
+    Closed,
+}
+
+impl FromStr for RegistrationMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(Self::Open),
+            "invite-only" => Ok(Self::InviteOnly),
+            "closed" => Ok(Self::Closed),
+            _ => Err(()),
+        }
+    }
+}
+
+pub static REGISTRATION_MODE: LazyLock<RegistrationMode> =
+    LazyLock::new(|| match std::env::var("PEREGRINE_REGISTRATION") {
+        Ok(value) => value.parse().unwrap_or_else(|()| {
+            // Falling back to open registration would expose a private instance.
+            warn!("Unknown registration mode {value:?}, registration is closed");
+            RegistrationMode::Closed
+        }),
+        Err(_) => RegistrationMode::default(),
+    });
+
+/// Number of leading zero bits required from the proof of work sent with `create_account`, set
+/// with the `PEREGRINE_POW_DIFFICULTY` environment variable. Proof of work is disabled if it's 0,
+/// which is the default.
+pub static POW_DIFFICULTY: LazyLock<u32> =
+    LazyLock::new(|| match std::env::var("PEREGRINE_POW_DIFFICULTY") {
+        Ok(value) => value.parse().unwrap_or_else(|_| {
+            warn!("Invalid proof of work difficulty {value:?}, proof of work is disabled");
+            0
+        }),
+        Err(_) => 0,
+    });
+
+/// Parses a comma-separated list of account ids. Invalid entries are skipped.
+pub fn parse_id_list(value: &str) -> Vec<u64> {
+    value
+        .split(',')
+        .filter_map(|id| {
+            let id = id.trim();
+            if id.is_empty() {
+                return None;
+            }
+            let parsed = id.parse().ok();
+            if parsed.is_none() {
+                warn!("Skipping invalid account id {id:?}");
+            }
+            parsed
+        })
+        .collect()
+}
+
+/// Ids of server admins, set with the `PEREGRINE_ADMIN_IDS` environment variable as a
+/// comma-separated list. There are no admins if it isn't set.
+pub static ADMIN_IDS: LazyLock<Vec<u64>> = LazyLock::new(|| {
+    std::env::var("PEREGRINE_ADMIN_IDS")
+        .map(|value| parse_id_list(&value))
+        .unwrap_or_default()
+});
+
+#[cfg(test)]
+mod tests {
+    use super::{RegistrationMode, parse_id_list};
+
+    #[test]
+    fn test_registration_modes() {
+        assert_eq!("open".parse(), Ok(RegistrationMode::Open));
+        assert_eq!("invite-only".parse(), Ok(RegistrationMode::InviteOnly));
+        assert_eq!("closed".parse(), Ok(RegistrationMode::Closed));
+        assert_eq!("Open".parse::<RegistrationMode>(), Err(()));
+        assert_eq!(RegistrationMode::default(), RegistrationMode::Open);
+    }
+
+    #[test]
+    fn test_parse_id_list() {
+        assert_eq!(parse_id_list("1, 2,3"), vec![1, 2, 3]);
+        assert_eq!(parse_id_list("4,,x,5,"), vec![4, 5]);
+        assert_eq!(parse_id_list(""), Vec::<u64>::new());
+    }
+}
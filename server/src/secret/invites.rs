@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use chrono::{NaiveDateTime, TimeDelta};
+use shared::{limits::LIMITS, types::GroupPermissions};
+
+use crate::{
+    DetailedDmInvite, DetailedGroupInvite, DmInvite, GroupInvite, MultiUserGroup, ReceivedInvites,
+    UserAccount,
+};
+
+/// How long a newly sent invite stays valid.
+pub const INVITE_VALIDITY_PERIOD: TimeDelta = TimeDelta::days(14);
+
+/// Returns expiry time of an invite sent at `now`.
+pub fn default_invite_expiry(now: NaiveDateTime) -> NaiveDateTime {
+    now + INVITE_VALIDITY_PERIOD
+}
+
+/// Returns whether an invite with `expires_at` can't be accepted at `now` anymore. Invites
+/// without expiry time never expire.
+pub fn is_invite_expired(expires_at: Option<NaiveDateTime>, now: NaiveDateTime) -> bool {
+    expires_at.is_some_and(|expires_at| expires_at <= now)
+}
+
+/// Returns whether a user with `sent` pending invites may send one more invite.
+pub fn is_within_pending_invite_limit(sent: u64) -> bool {
+    sent < LIMITS.max_sent_pending_invites
+}
+
+/// Returns what a member with `inviter` permissions may offer out of `offered`. Admins may offer
+/// anything, other members only the general permissions they have themselves.
+pub fn offered_permissions(
+    offered: GroupPermissions,
+    inviter: &GroupPermissions,
+) -> GroupPermissions {
+    if inviter.is_admin() {
+        return offered;
+    }
+    GroupPermissions {
+        send_messages: offered.send_messages && inviter.send_messages,
+        read_messages: offered.read_messages && inviter.read_messages,
+        invite_users: offered.invite_users && inviter.invite_users,
+        custom_permissions: vec![],
+    }
+}
+
+/// Permissions given to the user accepting an invite which offers `permissions`. Invites stored
+/// with malformed permissions give the default ones.
+pub fn accepted_permissions(permissions: &[u8]) -> Box<[u8]> {
+    match GroupPermissions::from_bytes(permissions) {
+        Some(_) => permissions.into(),
+        None => GroupPermissions::default().to_bytes(),
+    }
+}
+
+/// Joins received invites with the accounts of their senders and the groups they invite into.
+/// Every account and group is looked up only once, even if several invites refer to it. A sender
+/// whose lookup fails with an error passing `is_corrupt_identity` is left out of its invites
+/// with a flag set instead of failing all of them.
+pub fn join_received_invites<E>(
+    dm_invites: Vec<DmInvite>,
+    group_invites: Vec<GroupInvite>,
+    mut get_user: impl FnMut(u64) -> Result<Option<UserAccount>, E>,
+    mut get_group: impl FnMut(u64) -> Result<Option<MultiUserGroup>, E>,
+    is_corrupt_identity: impl Fn(&E) -> bool,
+) -> Result<ReceivedInvites, E> {
+    // The flag tells whether the identity of the user is corrupt.
+    let mut users: HashMap<u64, (Option<UserAccount>, bool)> = HashMap::new();
+    let mut user = |user_id: u64| -> Result<(Option<UserAccount>, bool), E> {
+        if let Some(user) = users.get(&user_id) {
+            return Ok(user.clone());
+        }
+        let user = match get_user(user_id) {
+            Ok(user) => (user, false),
+            Err(err) if is_corrupt_identity(&err) => (None, true),
+            Err(err) => return Err(err),
+        };
+        users.insert(user_id, user.clone());
+        Ok(user)
+    };
+    let mut groups: HashMap<u64, Option<MultiUserGroup>> = HashMap::new();
+    let mut group = |group_id: u64| -> Result<Option<MultiUserGroup>, E> {
+        if let Some(group) = groups.get(&group_id) {
+            return Ok(group.clone());
+        }
+        let group = get_group(group_id)?;
+        groups.insert(group_id, group.clone());
+        Ok(group)
+    };
+
+    let mut detailed_dm_invites = Vec::with_capacity(dm_invites.len());
+    for invite in dm_invites {
+        let (initiator, initiator_identity_corrupt) = user(invite.initiator_id)?;
+        detailed_dm_invites.push(DetailedDmInvite {
+            initiator,
+            initiator_identity_corrupt,
+            invite,
+        });
+    }
+
+    let mut detailed_group_invites = Vec::with_capacity(group_invites.len());
+    for invite in group_invites {
+        let (inviter, inviter_identity_corrupt) = user(invite.inviter_id)?;
+        detailed_group_invites.push(DetailedGroupInvite {
+            inviter,
+            inviter_identity_corrupt,
+            group: group(invite.group_id)?,
+            invite,
+        });
+    }
+
+    Ok(ReceivedInvites {
+        dm_invites: detailed_dm_invites,
+        group_invites: detailed_group_invites,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use chrono::{DateTime, NaiveDateTime, TimeDelta};
+    use shared::crypto::{CryptoAlgorithms, PublicKey, x3dh::X3DhReceiverKeysPublic};
+
+    use shared::{limits::LIMITS, types::GroupPermissions};
+
+    use super::{
+        accepted_permissions, default_invite_expiry, is_invite_expired,
+        is_within_pending_invite_limit, join_received_invites, offered_permissions,
+    };
+    use crate::{DmInvite, GroupInvite, MultiUserGroup, UserAccount};
+
+    fn user(user_id: u64) -> Option<UserAccount> {
+        // Account 4 is deleted.
+        (user_id != 4).then(|| UserAccount {
+            cryptoidentity: X3DhReceiverKeysPublic {
+                algorithms: CryptoAlgorithms::from_string("test".to_owned()),
+                ik: PublicKey {
+                    pk: Box::new([user_id as u8]),
+                },
+                spk: PublicKey { pk: Box::new([]) },
+                spk_signature: Box::new([]),
+                opks: vec![],
+            },
+            public_key: Box::new([user_id as u8]),
+            email: None,
+            username: Some(format!("user{user_id}")),
+            icon: None,
+        })
+    }
+
+    fn group(group_id: u64) -> Option<MultiUserGroup> {
+        Some(MultiUserGroup {
+            id: group_id,
+            name: format!("Group {group_id}"),
+            icon: None,
+            encrypted: true,
+            public: false,
+            channel: false,
+        })
+    }
+
+    fn dm_invite(id: u64, initiator_id: u64) -> DmInvite {
+        DmInvite {
+            id,
+            initiator_id,
+            other_id: 1,
+            encryption_data: None,
+            expires_at: None,
+        }
+    }
+
+    fn group_invite(id: u64, inviter_id: u64, group_id: u64) -> GroupInvite {
+        GroupInvite {
+            id,
+            inviter_id,
+            invited_id: 1,
+            group_id,
+            permissions: Box::new([]),
+            encryption_data: None,
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn test_joined_data_matches_separate_fetches() {
+        let dm_invites = vec![dm_invite(1, 2), dm_invite(2, 3), dm_invite(3, 4)];
+        let group_invites = vec![group_invite(1, 2, 10), group_invite(2, 3, 11)];
+        let user_lookups = Cell::new(0);
+        let group_lookups = Cell::new(0);
+
+        let invites = join_received_invites::<()>(
+            dm_invites.clone(),
+            group_invites.clone(),
+            |user_id| {
+                user_lookups.set(user_lookups.get() + 1);
+                Ok(user(user_id))
+            },
+            |group_id| {
+                group_lookups.set(group_lookups.get() + 1);
+                Ok(group(group_id))
+            },
+            |_| false,
+        )
+        .unwrap();
+
+        assert_eq!(invites.dm_invites.len(), dm_invites.len());
+        for (detailed, invite) in invites.dm_invites.iter().zip(dm_invites) {
+            assert_eq!(detailed.initiator, user(invite.initiator_id));
+            assert!(!detailed.initiator_identity_corrupt);
+            assert_eq!(detailed.invite, invite);
+        }
+        assert_eq!(invites.group_invites.len(), group_invites.len());
+        for (detailed, invite) in invites.group_invites.iter().zip(group_invites) {
+            assert_eq!(detailed.inviter, user(invite.inviter_id));
+            assert_eq!(detailed.group, group(invite.group_id));
+            assert_eq!(detailed.invite, invite);
+        }
+        // Accounts 2 and 3 sent both kinds of invites but are looked up once.
+        assert_eq!(user_lookups.get(), 3);
+        assert_eq!(group_lookups.get(), 2);
+    }
+
+    #[test]
+    fn test_lookup_error() {
+        let result = join_received_invites(
+            vec![dm_invite(1, 2)],
+            vec![],
+            |_| Err("database is down"),
+            |group_id| Ok(group(group_id)),
+            |err| *err == "corrupt identity",
+        );
+        assert_eq!(result.err(), Some("database is down"));
+    }
+
+    #[test]
+    fn test_corrupt_identity() {
+        let invites = join_received_invites(
+            vec![dm_invite(1, 2), dm_invite(2, 3)],
+            vec![group_invite(1, 2, 10)],
+            |user_id| {
+                if user_id == 2 {
+                    Err("corrupt identity")
+                } else {
+                    Ok(user(user_id))
+                }
+            },
+            |group_id| Ok(group(group_id)),
+            |err| *err == "corrupt identity",
+        )
+        .unwrap();
+        // Only the invites of the account with the corrupt identity lack it.
+        assert_eq!(invites.dm_invites[0].initiator, None);
+        assert!(invites.dm_invites[0].initiator_identity_corrupt);
+        assert_eq!(invites.dm_invites[1].initiator, user(3));
+        assert!(!invites.dm_invites[1].initiator_identity_corrupt);
+        assert_eq!(invites.group_invites[0].inviter, None);
+        assert!(invites.group_invites[0].inviter_identity_corrupt);
+        assert_eq!(invites.group_invites[0].group, group(10));
+    }
+
+    #[test]
+    fn test_pending_invite_limit() {
+        let max_sent = LIMITS.max_sent_pending_invites;
+        assert!(is_within_pending_invite_limit(0));
+        assert!(is_within_pending_invite_limit(max_sent - 1));
+        // Sender has hit the cap.
+        assert!(!is_within_pending_invite_limit(max_sent));
+        assert!(!is_within_pending_invite_limit(max_sent + 1));
+    }
+
+    fn time(timestamp: i64) -> NaiveDateTime {
+        DateTime::from_timestamp(timestamp, 0).unwrap().naive_utc()
+    }
+
+    #[test]
+    fn test_accepting_expired_invite() {
+        let sent_at = time(1_700_000_000);
+        let expires_at = Some(default_invite_expiry(sent_at));
+        assert!(!is_invite_expired(expires_at, sent_at));
+        assert!(!is_invite_expired(
+            expires_at,
+            sent_at + TimeDelta::days(14) - TimeDelta::seconds(1)
+        ));
+        assert!(is_invite_expired(expires_at, sent_at + TimeDelta::days(14)));
+        assert!(is_invite_expired(expires_at, sent_at + TimeDelta::days(30)));
+        // Invites sent before expiry was introduced stay valid.
+        assert!(!is_invite_expired(None, sent_at + TimeDelta::days(365)));
+    }
+
+    #[test]
+    fn test_offered_permissions() {
+        let admin = GroupPermissions::admin();
+        assert_eq!(offered_permissions(admin.clone(), &admin), admin);
+        let moderator = GroupPermissions {
+            custom_permissions: vec!["moderator".to_owned()],
+            ..GroupPermissions::default()
+        };
+        assert_eq!(offered_permissions(moderator.clone(), &admin), moderator);
+
+        // Other members can't make anyone an admin or give custom permissions.
+        let member = GroupPermissions::default();
+        assert_eq!(offered_permissions(admin, &member), member);
+        assert_eq!(offered_permissions(moderator, &member), member);
+
+        // Nor can they give general permissions they don't have.
+        let reader = GroupPermissions {
+            send_messages: false,
+            invite_users: false,
+            ..GroupPermissions::default()
+        };
+        assert_eq!(offered_permissions(member.clone(), &reader), reader);
+        let silent = GroupPermissions {
+            send_messages: false,
+            ..GroupPermissions::default()
+        };
+        assert_eq!(offered_permissions(silent.clone(), &member), silent);
+    }
+
+    #[test]
+    fn test_accepted_permissions() {
+        let reader = GroupPermissions {
+            send_messages: false,
+            invite_users: false,
+            ..GroupPermissions::default()
+        };
+        assert_eq!(accepted_permissions(&reader.to_bytes()), reader.to_bytes());
+        let default = GroupPermissions::default().to_bytes();
+        assert_eq!(accepted_permissions(&[]), default);
+        assert_eq!(accepted_permissions(&[4]), default);
+    }
+}
@@ -1,2 +1,7 @@
+pub mod config;
 pub mod db;
+pub mod invites;
+pub mod registration;
 pub mod storage;
+pub mod upload;
+pub mod validation;
@@ -0,0 +1,311 @@
+use std::sync::LazyLock;
+
+use chrono::{DateTime, NaiveDateTime};
+use shared::crypto::{
+    self, CryptoAlgorithms,
+    proof_of_work::{self, Challenge, Proof},
+};
+
+use crate::ServerError;
+
+use super::config::RegistrationMode;
+
+/// How long a proof of work challenge can be solved after it was issued.
+pub const CHALLENGE_VALIDITY_SECONDS: u32 = 10 * 60;
+
+/// Key which challenges are signed with, so that they don't have to be stored until they are
+/// solved. It's generated on start, so challenges issued before a restart can't be solved.
+pub static CHALLENGE_KEY: LazyLock<[u8; 32]> = LazyLock::new(|| {
+    let mut key = [0u8; 32];
+    super::db::rng::fill_bytes(&mut key);
+    key
+});
+
+const TIMESTAMP_LENGTH: usize = 8;
+const NONCE_LENGTH: usize = 16;
+/// Block length of the HMAC construction. Keys must not be longer.
+const MAC_BLOCK_LENGTH: usize = 64;
+
+/// HMAC over the hash function of `algorithms`.
+fn mac(algorithms: &CryptoAlgorithms, key: &[u8], data: &[u8]) -> Option<Box<[u8]>> {
+    let mut inner = vec![0x36; MAC_BLOCK_LENGTH];
+    let mut outer = vec![0x5c; MAC_BLOCK_LENGTH];
+    for (i, byte) in key.iter().enumerate() {
+        inner[i] ^= byte;
+        outer[i] ^= byte;
+    }
+    inner.extend_from_slice(data);
+    outer.extend_from_slice(&crypto::hash(algorithms, &inner)?);
+    crypto::hash(algorithms, &outer)
+}
+
+/// Issues a challenge consisting of the time it was issued at, a random nonce and a MAC of both
+/// made with `key`. Returns `None` if the hash algorithm isn't supported.
+pub fn issue_challenge(
+    key: &[u8],
+    algorithms: &CryptoAlgorithms,
+    difficulty: u32,
+    now: NaiveDateTime,
+) -> Option<Challenge> {
+    let mut challenge = now.and_utc().timestamp().to_le_bytes().to_vec();
+    let mut nonce = [0u8; NONCE_LENGTH];
+    super::db::rng::fill_bytes(&mut nonce);
+    challenge.extend_from_slice(&nonce);
+    let tag = mac(algorithms, key, &challenge)?;
+    challenge.extend_from_slice(&tag);
+    Some(Challenge {
+        algorithms: algorithms.clone(),
+        challenge: challenge.into_boxed_slice(),
+        difficulty,
+    })
+}
+
+/// Returns the time the challenge was issued at, or `None` if it wasn't issued with `key`.
+fn challenge_issue_time(
+    key: &[u8],
+    algorithms: &CryptoAlgorithms,
+    challenge: &[u8],
+) -> Option<NaiveDateTime> {
+    if challenge.len() < TIMESTAMP_LENGTH + NONCE_LENGTH {
+        return None;
+    }
+    let (data, tag) = challenge.split_at(TIMESTAMP_LENGTH + NONCE_LENGTH);
+    let expected = mac(algorithms, key, data)?;
+    // Compared in constant time, so the tag can't be guessed byte by byte.
+    let difference = expected
+        .iter()
+        .zip(tag)
+        .fold(0, |difference, (a, b)| difference | (a ^ b));
+    if expected.len() != tag.len() || difference != 0 {
+        return None;
+    }
+    let timestamp = i64::from_le_bytes(data[..TIMESTAMP_LENGTH].try_into().ok()?);
+    Some(DateTime::from_timestamp(timestamp, 0)?.naive_utc())
+}
+
+/// Checks the proof of work sent with a new account at `now`. Does nothing if `difficulty` is 0.
+/// Otherwise the challenge must have been issued with `key` less than
+/// [`CHALLENGE_VALIDITY_SECONDS`] ago, and `consume_challenge` is called with the solved
+/// challenge and the time it was issued at and returns whether it hasn't been solved before.
+pub fn check_proof_of_work<E>(
+    key: &[u8],
+    algorithms: &CryptoAlgorithms,
+    difficulty: u32,
+    proof: Option<&Proof>,
+    now: NaiveDateTime,
+    consume_challenge: impl FnOnce(&[u8], NaiveDateTime) -> Result<bool, E>,
+) -> Result<Result<(), ServerError>, E> {
+    if difficulty == 0 {
+        return Ok(Ok(()));
+    }
+    let Some(proof) = proof else {
+        return Ok(Err(ServerError::InvalidProofOfWork));
+    };
+    let Some(issued_at) = challenge_issue_time(key, algorithms, &proof.challenge) else {
+        return Ok(Err(ServerError::InvalidProofOfWork));
+    };
+    if (now - issued_at).num_seconds() >= i64::from(CHALLENGE_VALIDITY_SECONDS) {
+        return Ok(Err(ServerError::InvalidProofOfWork));
+    }
+    let challenge = Challenge {
+        algorithms: algorithms.clone(),
+        challenge: proof.challenge.clone(),
+        difficulty,
+    };
+    // Verify first, so that a wrong solution doesn't use up the challenge.
+    if !proof_of_work::verify(&challenge, proof.nonce)
+        || !consume_challenge(&proof.challenge, issued_at)?
+    {
+        return Ok(Err(ServerError::InvalidProofOfWork));
+    }
+    Ok(Ok(()))
+}
+
+/// Decides whether a new account may be created in `mode`. In invite-only mode returns the
+/// provided signup code, which has to be consumed together with creating the account, so that a
+/// failed attempt doesn't use it up.
+pub fn check_registration(
+    mode: RegistrationMode,
+    signup_code: Option<&str>,
+) -> Result<Option<&str>, ServerError> {
+    match mode {
+        RegistrationMode::Open => Ok(None),
+        RegistrationMode::Closed => Err(ServerError::RegistrationClosed),
+        RegistrationMode::InviteOnly => signup_code.map(Some).ok_or(ServerError::InvalidSignupCode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, sync::atomic::AtomicBool};
+
+    use chrono::{Duration, NaiveDateTime, Utc};
+    use shared::crypto::{
+        CryptoAlgorithms,
+        proof_of_work::{self, Challenge, Proof},
+    };
+
+    use super::{
+        CHALLENGE_VALIDITY_SECONDS, check_proof_of_work, check_registration, issue_challenge,
+    };
+    use crate::{ServerError, secret::config::RegistrationMode};
+
+    const KEY: [u8; 32] = [3; 32];
+
+    fn solved(difficulty: u32, now: NaiveDateTime) -> Proof {
+        let challenge = issue_challenge(
+            &KEY,
+            &CryptoAlgorithms::prequantum_bee2rs(),
+            difficulty,
+            now,
+        )
+        .unwrap();
+        proof_of_work::solve(&challenge, &AtomicBool::new(false)).unwrap()
+    }
+
+    #[test]
+    fn test_proof_of_work_disabled() {
+        let algorithms = CryptoAlgorithms::prequantum_bee2rs();
+        let result = check_proof_of_work::<()>(
+            &KEY,
+            &algorithms,
+            0,
+            None,
+            Utc::now().naive_utc(),
+            |_, _| panic!("challenge must not be consumed"),
+        );
+        assert_eq!(result, Ok(Ok(())));
+    }
+
+    #[test]
+    fn test_valid_proof_of_work() {
+        let algorithms = CryptoAlgorithms::prequantum_bee2rs();
+        let now = Utc::now().naive_utc();
+        let proof = solved(8, now);
+        let solved_challenges = RefCell::new(Vec::new());
+        let consume = |challenge: &[u8], _| -> Result<bool, ()> {
+            let mut solved_challenges = solved_challenges.borrow_mut();
+            if solved_challenges.iter().any(|solved| *solved == challenge) {
+                return Ok(false);
+            }
+            solved_challenges.push(challenge.to_vec());
+            Ok(true)
+        };
+        assert_eq!(
+            check_proof_of_work(&KEY, &algorithms, 8, Some(&proof), now, consume),
+            Ok(Ok(()))
+        );
+        // The same solution can't be used twice.
+        assert_eq!(
+            check_proof_of_work(&KEY, &algorithms, 8, Some(&proof), now, consume),
+            Ok(Err(ServerError::InvalidProofOfWork))
+        );
+    }
+
+    #[test]
+    fn test_expired_challenge() {
+        let algorithms = CryptoAlgorithms::prequantum_bee2rs();
+        let issued_at = Utc::now().naive_utc();
+        let proof = solved(4, issued_at);
+        let consume = |_: &[u8], _| -> Result<bool, ()> { Ok(true) };
+        let almost_expired =
+            issued_at + Duration::seconds(i64::from(CHALLENGE_VALIDITY_SECONDS) - 1);
+        assert_eq!(
+            check_proof_of_work(&KEY, &algorithms, 4, Some(&proof), almost_expired, consume),
+            Ok(Ok(()))
+        );
+        let expired = issued_at + Duration::seconds(i64::from(CHALLENGE_VALIDITY_SECONDS));
+        assert_eq!(
+            check_proof_of_work(&KEY, &algorithms, 4, Some(&proof), expired, consume),
+            Ok(Err(ServerError::InvalidProofOfWork))
+        );
+    }
+
+    #[test]
+    fn test_forged_challenge() {
+        let algorithms = CryptoAlgorithms::prequantum_bee2rs();
+        let now = Utc::now().naive_utc();
+        let consume =
+            |_: &[u8], _| -> Result<bool, ()> { panic!("challenge must not be consumed") };
+        // Issued with another key.
+        let proof = solved(4, now);
+        assert_eq!(
+            check_proof_of_work(&[4; 32], &algorithms, 4, Some(&proof), now, consume),
+            Ok(Err(ServerError::InvalidProofOfWork))
+        );
+        // Issue time changed to extend the validity.
+        let mut challenge = issue_challenge(&KEY, &algorithms, 4, now).unwrap();
+        challenge.challenge[0] ^= 1;
+        let proof = proof_of_work::solve(&challenge, &AtomicBool::new(false)).unwrap();
+        assert_eq!(
+            check_proof_of_work(&KEY, &algorithms, 4, Some(&proof), now, consume),
+            Ok(Err(ServerError::InvalidProofOfWork))
+        );
+        let proof = Proof {
+            challenge: Box::new([7; 32]),
+            nonce: 0,
+        };
+        assert_eq!(
+            check_proof_of_work(&KEY, &algorithms, 4, Some(&proof), now, consume),
+            Ok(Err(ServerError::InvalidProofOfWork))
+        );
+    }
+
+    #[test]
+    fn test_insufficient_proof_of_work() {
+        let algorithms = CryptoAlgorithms::prequantum_bee2rs();
+        let now = Utc::now().naive_utc();
+        let consume =
+            |_: &[u8], _| -> Result<bool, ()> { panic!("challenge must not be consumed") };
+        assert_eq!(
+            check_proof_of_work(&KEY, &algorithms, 8, None, now, consume),
+            Ok(Err(ServerError::InvalidProofOfWork))
+        );
+        // Find a nonce which doesn't meet the difficulty.
+        let mut proof = solved(0, now);
+        proof.nonce = (0..)
+            .find(|nonce| {
+                !proof_of_work::verify(
+                    &Challenge {
+                        algorithms: algorithms.clone(),
+                        challenge: proof.challenge.clone(),
+                        difficulty: 8,
+                    },
+                    *nonce,
+                )
+            })
+            .unwrap();
+        assert_eq!(
+            check_proof_of_work(&KEY, &algorithms, 8, Some(&proof), now, consume),
+            Ok(Err(ServerError::InvalidProofOfWork))
+        );
+    }
+
+    #[test]
+    fn test_open_registration() {
+        assert_eq!(
+            check_registration(RegistrationMode::Open, Some("code")),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_closed_registration() {
+        assert_eq!(
+            check_registration(RegistrationMode::Closed, Some("code")),
+            Err(ServerError::RegistrationClosed)
+        );
+    }
+
+    #[test]
+    fn test_code_required() {
+        assert_eq!(
+            check_registration(RegistrationMode::InviteOnly, None),
+            Err(ServerError::InvalidSignupCode)
+        );
+        assert_eq!(
+            check_registration(RegistrationMode::InviteOnly, Some("code")),
+            Ok(Some("code"))
+        );
+    }
+}
@@ -1,6 +1,15 @@
-use std::{path::PathBuf, sync::LazyLock};
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+    sync::LazyLock,
+};
 
-use shared::storage::{GeneralStorage, RawStorage};
+use shared::{
+    crypto::fingerprint::to_hex,
+    storage::{GeneralStorage, RawStorage, StorageError},
+};
 
 pub static STORAGE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
     let mut path = PathBuf::new();
@@ -29,13 +38,8 @@ impl RawStorage for ServerStorage {
 impl GeneralStorage for ServerStorage {}
 
 impl ServerStorage {
-    pub fn store_dm_file(&self, message_id: u64, data: &[u8]) {
-        self.store(&format!("dm_file{message_id}.bin"), &data);
-    }
-
-    pub fn store_group_file(&self, message_id: u64, data: &[u8]) {
-        self.store(&format!("group_file{message_id}.bin"), &data);
-    }
+    // New attachments are stored as blobs, per-message files are only read for messages sent
+    // before that.
 
     pub fn load_dm_file(&self, message_id: u64) -> Option<Box<[u8]>> {
         self.load(&format!("dm_file{message_id}.bin"))
@@ -44,6 +48,91 @@ impl ServerStorage {
     pub fn load_group_file(&self, message_id: u64) -> Option<Box<[u8]>> {
         self.load(&format!("group_file{message_id}.bin"))
     }
+
+    // Attachments are stored by the hash of their (encrypted) content, so identical files sent
+    // many times take space only once. Reference counting is done in the database.
+
+    fn blob_path(hash: &[u8]) -> String {
+        format!("blobs/{}.bin", to_hex(hash))
+    }
+
+    pub fn has_blob(&self, hash: &[u8]) -> bool {
+        self.get_path(Self::blob_path(hash))
+            .is_ok_and(|path| path.exists())
+    }
+
+    pub fn store_blob(&self, hash: &[u8], data: &[u8]) -> Result<(), StorageError> {
+        self.store(&Self::blob_path(hash), &data)
+    }
+
+    pub fn load_blob(&self, hash: &[u8]) -> Option<Box<[u8]>> {
+        self.load(&Self::blob_path(hash))
+    }
+
+    pub fn remove_blob(&self, hash: &[u8]) -> Result<(), StorageError> {
+        self.remove(&Self::blob_path(hash))
+    }
+
+    // Uploaded files are stored as raw bytes instead of using `store`, so that they can be written
+    // and read in parts.
+
+    fn upload_path(&self, upload_id: u64) -> Result<PathBuf, StorageError> {
+        self.get_path(format!("uploads/{upload_id}.part"))
+    }
+
+    fn uploaded_file_path(&self, file_id: u64) -> Result<PathBuf, StorageError> {
+        self.get_path(format!("files/{file_id}.bin"))
+    }
+
+    pub fn write_upload_chunk(
+        &self,
+        upload_id: u64,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.upload_path(upload_id)?)?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(data)?;
+        Ok(())
+    }
+
+    /// Moves the fully received upload to the uploaded files. The file gets the same id as the
+    /// upload.
+    pub fn finish_upload(&self, upload_id: u64) -> Result<(), Box<dyn Error>> {
+        let path = self.upload_path(upload_id)?;
+        if !path.exists() {
+            // An empty file has no chunks, so nothing was written.
+            File::create(&path)?;
+        }
+        fs::rename(path, self.uploaded_file_path(upload_id)?)?;
+        Ok(())
+    }
+
+    /// Deletes the data received for the upload, if any.
+    pub fn remove_upload(&self, upload_id: u64) -> Result<(), Box<dyn Error>> {
+        let path = self.upload_path(upload_id)?;
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn read_uploaded_file_range(
+        &self,
+        file_id: u64,
+        offset: u64,
+        length: u64,
+    ) -> Result<Box<[u8]>, Box<dyn Error>> {
+        let mut file = File::open(self.uploaded_file_path(file_id)?)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut data = Vec::with_capacity(length as usize);
+        file.take(length).read_to_end(&mut data)?;
+        Ok(data.into_boxed_slice())
+    }
 }
 
 pub static STORAGE: LazyLock<ServerStorage> = LazyLock::new(Default::default);
@@ -0,0 +1,82 @@
+/// How long an upload can be finished after it was begun. Data of uploads which weren't finished
+/// in time is deleted.
+pub const UPLOAD_VALIDITY_SECONDS: u32 = 24 * 60 * 60;
+
+/// Returns whether the received chunks, given as `(offset, length)` pairs in any order and
+/// possibly overlapping or duplicated, cover the whole file of `size` bytes.
+pub fn is_fully_uploaded(size: u64, chunks: &[(u64, u64)]) -> bool {
+    let mut chunks = chunks.to_vec();
+    chunks.sort_unstable();
+    let mut covered_until = 0u64;
+    for (offset, length) in chunks {
+        if offset > covered_until {
+            return false;
+        }
+        covered_until = covered_until.max(offset.saturating_add(length));
+    }
+    covered_until >= size
+}
+
+/// Returns the number of bytes to send for a download of `length` bytes starting at `offset` of
+/// a file of `size` bytes. Ranges reaching past the end of the file are truncated, so that a
+/// client may request fixed-sized parts without knowing the exact size. Returns `None` if the
+/// range starts after the end of the file.
+pub fn download_range_length(size: u64, offset: u64, length: u64) -> Option<u64> {
+    if offset > size {
+        return None;
+    }
+    Some(length.min(size - offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{download_range_length, is_fully_uploaded};
+
+    #[test]
+    fn test_in_order_chunks() {
+        assert!(is_fully_uploaded(10, &[(0, 4), (4, 4), (8, 2)]));
+        assert!(!is_fully_uploaded(10, &[(0, 4), (4, 4)]));
+    }
+
+    #[test]
+    fn test_out_of_order_chunks() {
+        assert!(is_fully_uploaded(10, &[(8, 2), (0, 4), (4, 4)]));
+        assert!(!is_fully_uploaded(10, &[(8, 2), (0, 4)]));
+    }
+
+    #[test]
+    fn test_duplicate_and_overlapping_chunks() {
+        assert!(is_fully_uploaded(
+            10,
+            &[(0, 4), (0, 4), (2, 6), (8, 2), (8, 2)]
+        ));
+        assert!(!is_fully_uploaded(10, &[(0, 4), (0, 4), (5, 5)]));
+    }
+
+    #[test]
+    fn test_empty_file() {
+        assert!(is_fully_uploaded(0, &[]));
+        assert!(!is_fully_uploaded(1, &[]));
+    }
+
+    #[test]
+    fn test_download_range_inside_file() {
+        assert_eq!(download_range_length(10, 0, 4), Some(4));
+        assert_eq!(download_range_length(10, 6, 4), Some(4));
+    }
+
+    #[test]
+    fn test_download_range_past_eof() {
+        assert_eq!(download_range_length(10, 8, 4), Some(2));
+        assert_eq!(download_range_length(10, 10, 4), Some(0));
+        assert_eq!(download_range_length(10, 11, 4), None);
+        assert_eq!(download_range_length(10, u64::MAX, u64::MAX), None);
+    }
+
+    #[test]
+    fn test_zero_length_download_range() {
+        assert_eq!(download_range_length(10, 3, 0), Some(0));
+        assert_eq!(download_range_length(0, 0, 0), Some(0));
+        assert_eq!(download_range_length(0, 0, 4), Some(0));
+    }
+}
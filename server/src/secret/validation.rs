@@ -0,0 +1,46 @@
+use shared::crypto::PLAINTEXT_METHOD;
+
+/// Returns whether a message with `encryption_method` may be sent to a group. Encrypted groups
+/// only accept encrypted messages and unencrypted groups only accept plain ones, so that members
+/// always know how to read the messages.
+pub fn encryption_method_matches_group(encrypted_group: bool, encryption_method: &str) -> bool {
+    (encryption_method == PLAINTEXT_METHOD) != encrypted_group
+}
+
+/// Returns whether `encryption_method` names a method at all. Receivers pick how to decrypt a
+/// message by it, so it has to be non-empty and may only contain printable ASCII characters.
+pub fn is_valid_encryption_method(encryption_method: &str) -> bool {
+    !encryption_method.is_empty()
+        && encryption_method
+            .bytes()
+            .all(|byte| byte.is_ascii_graphic())
+}
+
+#[cfg(test)]
+mod tests {
+    use shared::crypto::PLAINTEXT_METHOD;
+
+    use super::{encryption_method_matches_group, is_valid_encryption_method};
+
+    #[test]
+    fn test_encryption_method_matches_group() {
+        assert!(encryption_method_matches_group(true, "aes-gcm"));
+        assert!(encryption_method_matches_group(false, PLAINTEXT_METHOD));
+        // Plain message in an encrypted group.
+        assert!(!encryption_method_matches_group(true, PLAINTEXT_METHOD));
+        // Encrypted message in an unencrypted group.
+        assert!(!encryption_method_matches_group(false, "aes-gcm"));
+    }
+
+    #[test]
+    fn test_is_valid_encryption_method() {
+        assert!(is_valid_encryption_method(PLAINTEXT_METHOD));
+        assert!(is_valid_encryption_method("aes-gcm"));
+        assert!(is_valid_encryption_method("belt-ctr"));
+        assert!(!is_valid_encryption_method(""));
+        assert!(!is_valid_encryption_method("aes gcm"));
+        assert!(!is_valid_encryption_method("aes-gcm\n"));
+        assert!(!is_valid_encryption_method("\0\x01\x7f"));
+        assert!(!is_valid_encryption_method("aes-gcm\u{00e9}"));
+    }
+}
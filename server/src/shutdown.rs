@@ -0,0 +1,77 @@
+use std::{
+    sync::{Condvar, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use dioxus::logger::tracing::{error, info};
+
+use crate::secret::db::DB;
+
+static SHUTTING_DOWN: Mutex<bool> = Mutex::new(false);
+static SHUTDOWN_REQUESTED: Condvar = Condvar::new();
+
+fn shutting_down() -> MutexGuard<'static, bool> {
+    SHUTTING_DOWN.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn request_shutdown() {
+    *shutting_down() = true;
+    SHUTDOWN_REQUESTED.notify_all();
+}
+
+/// Returns whether the server has been asked to stop. Background tasks check it to exit early.
+pub fn is_shutting_down() -> bool {
+    *shutting_down()
+}
+
+/// Blocks for up to `timeout`, returning early once the server is asked to stop. Background
+/// tasks use it instead of sleeping, so that they don't delay the shutdown. Returns whether the
+/// server is shutting down.
+pub fn wait_for_shutdown(timeout: Duration) -> bool {
+    let (guard, _) = SHUTDOWN_REQUESTED
+        .wait_timeout_while(shutting_down(), timeout, |shutting_down| !*shutting_down)
+        .unwrap_or_else(PoisonError::into_inner);
+    *guard
+}
+
+/// Resolves once the process receives SIGINT (Ctrl+C) or, on Unix, SIGTERM. Meant to be passed
+/// to the HTTP server's graceful shutdown, which then stops accepting new connections and waits
+/// for the requests in flight.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl+C: {err:?}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(err) => {
+                error!("Failed to listen for SIGTERM: {err:?}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {},
+        () = terminate => {},
+    }
+    info!("Shutdown requested, waiting for requests in flight");
+    request_shutdown();
+}
+
+/// Releases server resources. Must be called after the HTTP server has stopped, as requests
+/// can't access the database afterwards.
+pub fn finish_shutdown() {
+    request_shutdown();
+    DB.close();
+    info!("Server stopped");
+}
@@ -1,20 +1,39 @@
 use aes_gcm::{
     Aes128Gcm, Aes256Gcm, KeyInit,
-    aead::Aead,
+    aead::{Aead, Payload},
     aes::{
-        Aes128Enc, Aes192Dec, Aes192Enc, Aes256Enc,
+        Aes192Dec, Aes192Enc,
         cipher::{BlockDecrypt, BlockEncrypt},
     },
 };
 
 use crate::crypto::{PrivateKey, get_iv};
 
+/// Length of the AES-GCM authentication tag, which is returned as the MAC.
+const TAG_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+
+/// The nonce is prepended to the ciphertext, as in [`symmetric_encrypt`].
 pub(super) fn aead_wrap(
     plaintext: &[u8],
     key: PrivateKey,
     public_data: &[u8],
 ) -> (Box<[u8]>, Box<[u8]>) {
-    todo!()
+    let nonce: [u8; NONCE_LENGTH] = get_iv()[..NONCE_LENGTH].try_into().unwrap();
+    let aes = Aes256Gcm::new((&key.sk as &[u8]).into());
+    let mut ciphertext = aes
+        .encrypt(
+            &nonce.into(),
+            Payload {
+                msg: plaintext,
+                aad: public_data,
+            },
+        )
+        .unwrap();
+    let mac = ciphertext.split_off(ciphertext.len() - TAG_LENGTH);
+    let mut result = Vec::from(nonce);
+    result.extend(ciphertext);
+    (result.into_boxed_slice(), mac.into_boxed_slice())
 }
 
 pub(super) fn aead_unwrap(
@@ -23,7 +42,23 @@ pub(super) fn aead_unwrap(
     mac: &[u8],
     key: PrivateKey,
 ) -> Option<Box<[u8]>> {
-    todo!()
+    if key.sk.len() != 32 || ciphertext.len() < NONCE_LENGTH || mac.len() != TAG_LENGTH {
+        return None;
+    }
+    let (nonce, ciphertext) = ciphertext.split_at(NONCE_LENGTH);
+    let mut message = Vec::from(ciphertext);
+    message.extend(mac);
+    let aes = Aes256Gcm::new((&key.sk as &[u8]).into());
+    let plaintext = aes
+        .decrypt(
+            nonce.into(),
+            Payload {
+                msg: &message,
+                aad: public_data,
+            },
+        )
+        .ok()?;
+    Some(plaintext.into_boxed_slice())
 }
 
 pub(super) fn symmetric_encrypt(plaintext: &[u8], key: &[u8]) -> Box<[u8]> {
@@ -49,8 +84,10 @@ pub(super) fn symmetric_encrypt(plaintext: &[u8], key: &[u8]) -> Box<[u8]> {
     result.into_boxed_slice()
 }
 
+/// Returns `None` instead of panicking on a key of an invalid length, as stored keys may be
+/// corrupted.
 pub(super) fn symmetric_decrypt(ciphertext: &[u8], key: &[u8]) -> Option<Box<[u8]>> {
-    let Ok(nonce) = ciphertext[..12].try_into() else {
+    let Ok(nonce) = ciphertext.get(..12)?.try_into() else {
         return None;
     };
     let _: [u8; 12] = nonce;
@@ -59,6 +96,9 @@ pub(super) fn symmetric_decrypt(ciphertext: &[u8], key: &[u8]) -> Option<Box<[u8
         let aes = Aes128Gcm::new(key.into());
         aes.decrypt(&nonce.into(), ciphertext)
     } else if key.len() == 24 {
+        if ciphertext.len() % 16 != 0 {
+            return None;
+        }
         let mut ciphertext: Vec<u8> = Vec::from(ciphertext);
         let aes = Aes192Dec::new(key.into());
         for block in ciphertext.chunks_mut(16) {
@@ -69,7 +109,7 @@ pub(super) fn symmetric_decrypt(ciphertext: &[u8], key: &[u8]) -> Option<Box<[u8
         let aes = Aes256Gcm::new(key.into());
         aes.decrypt(&nonce.into(), ciphertext)
     } else {
-        panic!();
+        return None;
     };
     let Ok(value) = value else {
         return None;
@@ -121,7 +121,7 @@ pub(super) fn aead_unwrap(
 ) -> Option<Box<[u8]>> {
     let key = BeltKey256::new(((&key.sk) as &[u8]).try_into().unwrap());
     let iv = key.clone().to_key128().get_bytes();
-    BeltDwp::unwrap(ciphertext, public_data, mac.try_into().unwrap(), &key, *iv).ok()
+    BeltDwp::unwrap(ciphertext, public_data, mac.try_into().ok()?, &key, *iv).ok()
 }
 
 pub(super) fn symmetric_encrypt(plaintext: &[u8], key: &[u8]) -> Box<[u8]> {
@@ -146,8 +146,10 @@ pub(super) fn symmetric_encrypt(plaintext: &[u8], key: &[u8]) -> Box<[u8]> {
     result.into_boxed_slice()
 }
 
+/// Keys are loaded from the storage, so one of a wrong length fails the decryption rather than
+/// panicking.
 pub(super) fn symmetric_decrypt(ciphertext: &[u8], key: &[u8]) -> Option<Box<[u8]>> {
-    let Ok(iv) = ciphertext[..16].try_into() else {
+    let Ok(iv) = ciphertext.get(..16)?.try_into() else {
         return None;
     };
     let value = if key.len() == 32 {
@@ -163,7 +165,7 @@ pub(super) fn symmetric_decrypt(ciphertext: &[u8], key: &[u8]) -> Option<Box<[u8
         let mut ctr = key.ctr(iv);
         ctr.decrypt(ciphertext[16..].iter().cloned().collect())
     } else {
-        panic!();
+        return None;
     };
 
     if let Err(ref err) = value {
@@ -0,0 +1,62 @@
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use x25519_dalek::x25519;
+
+use crate::crypto::{PrivateKey, PublicKey, get_iv};
+
+fn load_signing_key(private_key: &PrivateKey) -> Option<SigningKey> {
+    Some(SigningKey::from_bytes(
+        private_key.sk.as_ref().try_into().ok()?,
+    ))
+}
+
+fn load_verifying_key(public_key: &PublicKey) -> Option<VerifyingKey> {
+    VerifyingKey::from_bytes(public_key.pk.as_ref().try_into().ok()?).ok()
+}
+
+/// Ed25519 keypair with the secret key `seed`. The same keypair is used for X25519 by converting
+/// it to the Montgomery form, the way a single bee2-rs keypair is used for both.
+pub(super) fn keypair_from_seed(seed: [u8; 32]) -> (PrivateKey, PublicKey) {
+    let key = SigningKey::from_bytes(&seed);
+    (
+        PrivateKey {
+            sk: Box::new(key.to_bytes()),
+        },
+        PublicKey {
+            pk: Box::new(key.verifying_key().to_bytes()),
+        },
+    )
+}
+
+pub(super) fn generate_keypair() -> (PrivateKey, PublicKey) {
+    keypair_from_seed(get_iv())
+}
+
+pub(super) fn sign(private_key: PrivateKey, hash: &[u8]) -> Option<Box<[u8]>> {
+    let key = load_signing_key(&private_key)?;
+    Some(Box::new(key.sign(hash).to_bytes()))
+}
+
+pub(super) fn verify(public_key: PublicKey, hash: &[u8], signature: &[u8]) -> bool {
+    let Some(key) = load_verifying_key(&public_key) else {
+        return false;
+    };
+    let Ok(signature) = Signature::from_slice(signature) else {
+        return false;
+    };
+    key.verify(hash, &signature).is_ok()
+}
+
+/// Returns `None` if the public key is malformed or of a low order, in which case the shared
+/// secret wouldn't depend on the private key.
+pub(super) fn diffie_hellman(
+    self_private_key: PrivateKey,
+    other_public_key: PublicKey,
+) -> Option<Box<[u8]>> {
+    let scalar = load_signing_key(&self_private_key)?.to_scalar_bytes();
+    let point = load_verifying_key(&other_public_key)?.to_montgomery();
+    let shared_secret = x25519(scalar, point.to_bytes());
+    if shared_secret == [0; 32] {
+        return None;
+    }
+    Some(Box::new(shared_secret))
+}
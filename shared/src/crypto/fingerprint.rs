@@ -0,0 +1,139 @@
+use super::{hash, x3dh::X3DhReceiverKeysPublic};
+
+/// Number of hash bytes shown to the user as a key fingerprint.
+pub const FINGERPRINT_LENGTH: usize = 16;
+
+/// Returns a short fingerprint of the identity key, or `None` if the hash algorithm of the
+/// identity is not supported.
+pub fn identity_fingerprint(identity: &X3DhReceiverKeysPublic) -> Option<Box<[u8]>> {
+    let digest = hash(&identity.algorithms, &identity.ik.pk)?;
+    Some(digest[..FINGERPRINT_LENGTH.min(digest.len())].into())
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Box<[u8]>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Number of decimal digits contributed by each party to a safety number.
+const SAFETY_NUMBER_PART_DIGITS: usize = 30;
+/// Every five hash bytes are reduced to a group of five decimal digits.
+const SAFETY_NUMBER_GROUP_DIGITS: usize = 5;
+
+fn safety_number_part(user_id: u64, identity: &X3DhReceiverKeysPublic) -> Option<String> {
+    let mut data = user_id.to_be_bytes().to_vec();
+    data.extend_from_slice(&identity.ik.pk);
+    let digest = hash(&identity.algorithms, &data)?;
+    let groups = SAFETY_NUMBER_PART_DIGITS / SAFETY_NUMBER_GROUP_DIGITS;
+    if digest.len() < groups * 5 {
+        return None;
+    }
+    Some(
+        digest
+            .chunks_exact(5)
+            .take(groups)
+            .map(|chunk| {
+                let value = chunk
+                    .iter()
+                    .fold(0u64, |value, &byte| (value << 8) | byte as u64);
+                format!("{:05}", value % 100_000)
+            })
+            .collect(),
+    )
+}
+
+/// Derives a safety number of two users which they can compare with each other to make sure
+/// that nobody has replaced their identity keys. The result doesn't depend on the order of the
+/// arguments, so both sides see the same number.
+pub fn safety_number(
+    user_a: u64,
+    identity_a: &X3DhReceiverKeysPublic,
+    user_b: u64,
+    identity_b: &X3DhReceiverKeysPublic,
+) -> Option<String> {
+    let mut parts = [
+        safety_number_part(user_a, identity_a)?,
+        safety_number_part(user_b, identity_b)?,
+    ];
+    parts.sort();
+    Some(parts.concat())
+}
+
+/// Formats the safety number as groups of five digits.
+pub fn format_safety_number(safety_number: &str) -> String {
+    safety_number
+        .as_bytes()
+        .chunks(SAFETY_NUMBER_GROUP_DIGITS)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Formats the fingerprint as groups of four hexadecimal digits, e.g. `1a2b 3c4d ...`.
+pub fn format_fingerprint(fingerprint: &[u8]) -> String {
+    fingerprint
+        .chunks(2)
+        .map(to_hex)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::{CryptoAlgorithms, x3dh::generate_receiver_keys};
+
+    use super::{
+        FINGERPRINT_LENGTH, format_fingerprint, format_safety_number, from_hex,
+        identity_fingerprint, safety_number, to_hex,
+    };
+
+    #[test]
+    fn test_identity_fingerprint() {
+        let (_, keys_a) = generate_receiver_keys(&CryptoAlgorithms::prequantum_bee2rs()).unwrap();
+        let (_, keys_b) = generate_receiver_keys(&CryptoAlgorithms::prequantum_bee2rs()).unwrap();
+        let fingerprint_a = identity_fingerprint(&keys_a).unwrap();
+        assert_eq!(fingerprint_a.len(), FINGERPRINT_LENGTH);
+        assert_eq!(fingerprint_a, identity_fingerprint(&keys_a).unwrap());
+        assert_ne!(fingerprint_a, identity_fingerprint(&keys_b).unwrap());
+    }
+
+    #[test]
+    fn test_safety_number() {
+        let (_, keys_a) = generate_receiver_keys(&CryptoAlgorithms::prequantum_bee2rs()).unwrap();
+        let (_, keys_b) = generate_receiver_keys(&CryptoAlgorithms::prequantum_bee2rs()).unwrap();
+        let (_, keys_c) = generate_receiver_keys(&CryptoAlgorithms::prequantum_bee2rs()).unwrap();
+        let number = safety_number(1, &keys_a, 2, &keys_b).unwrap();
+        assert_eq!(number.len(), 60);
+        assert!(number.bytes().all(|byte| byte.is_ascii_digit()));
+        // Stable between calls and symmetric between users.
+        assert_eq!(number, safety_number(1, &keys_a, 2, &keys_b).unwrap());
+        assert_eq!(number, safety_number(2, &keys_b, 1, &keys_a).unwrap());
+        // Changes when either key or user id changes.
+        assert_ne!(number, safety_number(1, &keys_a, 2, &keys_c).unwrap());
+        assert_ne!(number, safety_number(1, &keys_a, 3, &keys_b).unwrap());
+    }
+
+    #[test]
+    fn test_format_safety_number() {
+        assert_eq!(format_safety_number("123456789012"), "12345 67890 12");
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(to_hex(&[0x00, 0x1a, 0xff]), "001aff");
+        assert_eq!(from_hex("001aff").as_deref(), Some(&[0x00, 0x1a, 0xff][..]));
+        assert_eq!(from_hex("001AFF").as_deref(), Some(&[0x00, 0x1a, 0xff][..]));
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("zz"), None);
+        assert_eq!(format_fingerprint(&[0x12, 0x34, 0x56]), "1234 56");
+    }
+}
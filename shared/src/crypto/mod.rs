@@ -2,6 +2,14 @@
 pub mod aes_gcm;
 #[cfg(feature = "bee2-rs")]
 pub mod bee2rs;
+#[cfg(feature = "curve25519-dalek")]
+pub mod dalek;
+pub mod fingerprint;
+pub mod private_info;
+pub mod proof_of_work;
+pub mod recovery;
+#[cfg(feature = "pbkdf2")]
+pub mod rustcrypto;
 pub mod x3dh;
 
 use std::{
@@ -12,6 +20,10 @@ use std::{
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 
+/// Encryption method of messages and files sent without encryption. No algorithm suite uses it
+/// as its [`CryptoAlgorithms::encryption_method`].
+pub const PLAINTEXT_METHOD: &str = "plain";
+
 fn get_iv() -> [u8; 32] {
     let mut iv_buffer: [u8; 32] = [0; 32];
     let mut rng = rand::rng();
@@ -97,7 +109,7 @@ impl CryptoAlgorithms {
     #[cfg(all(feature = "aes-gcm", feature = "curve25519-dalek", feature = "pbkdf2"))]
     pub fn prequantum_standard() -> Self {
         Self {
-            hash: "rustcrypto::aes-gcm".to_owned(),
+            hash: "rustcrypto::sha512".to_owned(),
             kdf: "rustcrypto::pbkdf2".to_owned(),
             diffie_hellman: "dalek::x25519".to_owned(),
             signature: "dalek::ed25519".to_owned(),
@@ -113,12 +125,22 @@ impl CryptoAlgorithms {
             |(_, value)| value.to_owned(),
         )
     }
+
+    /// Returns the supported suite whose [`Self::encryption_method`] is `method`, which is sent
+    /// along with each encrypted message. `None` if the message can't be decrypted by this build.
+    pub fn from_encryption_method(method: &str) -> Option<Self> {
+        supported_algorithms()
+            .into_iter()
+            .find(|algorithms| algorithms.encryption_method() == method)
+    }
 }
 
 pub fn hash(algorithms: &CryptoAlgorithms, data: &[u8]) -> Option<Box<[u8]>> {
     match &algorithms.hash as &str {
         #[cfg(feature = "bee2-rs")]
         "bee2-rs::bash512" => Some(bee2rs::hash(data)),
+        #[cfg(feature = "pbkdf2")]
+        "rustcrypto::sha512" => Some(rustcrypto::hash(data)),
         _ => None,
     }
 }
@@ -127,6 +149,8 @@ pub fn generate_keypair(algorithms: &CryptoAlgorithms) -> Option<(PrivateKey, Pu
     match &algorithms.rng as &str {
         #[cfg(feature = "bee2-rs")]
         "bee2-rs::belt-ctr" => Some(bee2rs::generate_keypair(&algorithms.signature)),
+        #[cfg(feature = "curve25519-dalek")]
+        "default" if algorithms.signature == "dalek::ed25519" => Some(dalek::generate_keypair()),
         _ => None,
     }
 }
@@ -141,6 +165,8 @@ pub fn sign(
     match &algorithms.signature as &str {
         #[cfg(feature = "bee2-rs")]
         "bee2-rs::bignb3" => Some(bee2rs::sign(private_key, public_key, &hash)),
+        #[cfg(feature = "curve25519-dalek")]
+        "dalek::ed25519" => dalek::sign(private_key, &hash),
         _ => None,
     }
 }
@@ -155,6 +181,8 @@ pub fn verify(
     match &algorithms.signature as &str {
         #[cfg(feature = "bee2-rs")]
         "bee2-rs::bignb3" => Some(bee2rs::verify(public_key, &hash, signature)),
+        #[cfg(feature = "curve25519-dalek")]
+        "dalek::ed25519" => Some(dalek::verify(public_key, &hash, signature)),
         _ => None,
     }
 }
@@ -172,6 +200,8 @@ pub fn diffie_hellman(
             self_public_key,
             other_public_key,
         )),
+        #[cfg(feature = "curve25519-dalek")]
+        "dalek::x25519" => dalek::diffie_hellman(self_private_key, other_public_key),
         _ => None,
     }
 }
@@ -180,6 +210,8 @@ pub fn kdf(algorithms: &CryptoAlgorithms, data: &[u8], result_len: usize) -> Opt
     match &algorithms.kdf as &str {
         #[cfg(feature = "bee2-rs")]
         "bee2-rs::pbkdf2" => Some(bee2rs::kdf(data, result_len)),
+        #[cfg(feature = "pbkdf2")]
+        "rustcrypto::pbkdf2" => Some(rustcrypto::kdf(data, result_len)),
         _ => None,
     }
 }
@@ -188,6 +220,13 @@ pub fn kdf_keypair(algorithms: &CryptoAlgorithms, data: &[u8]) -> Option<(Privat
     match &algorithms.kdf as &str {
         #[cfg(feature = "bee2-rs")]
         "bee2-rs::pbkdf2" => Some(bee2rs::kdf_keypair(&algorithms.signature, data)),
+        #[cfg(all(feature = "pbkdf2", feature = "curve25519-dalek"))]
+        "rustcrypto::pbkdf2" if algorithms.signature == "dalek::ed25519" => {
+            let seed = rustcrypto::kdf(data, 32);
+            Some(dalek::keypair_from_seed(
+                (&seed as &[u8]).try_into().unwrap(),
+            ))
+        }
         _ => None,
     }
 }
@@ -266,6 +305,16 @@ pub fn symmetric_genkey(algorithms: &CryptoAlgorithms, strength: KeyStrength) ->
             &algorithms.symmetric_encryption,
             strength,
         )),
+        "default" => {
+            let length = match strength {
+                KeyStrength::High => 16,
+                KeyStrength::VeryHigh => 24,
+                KeyStrength::ExtremelyHigh => 32,
+            };
+            let mut key = vec![0; length].into_boxed_slice();
+            rand::rng().fill_bytes(&mut key);
+            Some(key)
+        }
         _ => None,
     }
 }
@@ -297,3 +346,301 @@ pub fn supported_algorithms() -> Vec<CryptoAlgorithms> {
 pub fn preferred_alogirthm() -> CryptoAlgorithms {
     supported_algorithms()[0].clone()
 }
+
+/// Returns whether every function above works with the suite in this build. Besides each of its
+/// algorithms being compiled in, the keypairs of the signature algorithm are also used for the key
+/// exchange, derived by the KDF and generated by the RNG, which the backends only do together.
+pub fn is_algorithm_supported(algorithms: &CryptoAlgorithms) -> bool {
+    if !supported_methods().supports(algorithms) {
+        return false;
+    }
+    let keypairs = match &algorithms.signature as &str {
+        "bee2-rs::bignb3" => {
+            algorithms.diffie_hellman == "bee2-rs::bignb3"
+                && algorithms.kdf == "bee2-rs::pbkdf2"
+                && algorithms.rng == "bee2-rs::belt-ctr"
+        }
+        "dalek::ed25519" => {
+            algorithms.diffie_hellman == "dalek::x25519"
+                && algorithms.kdf == "rustcrypto::pbkdf2"
+                && algorithms.rng == "default"
+        }
+        _ => false,
+    };
+    // Keys generated by the `bee2-rs` RNG are only meant for its own cipher.
+    keypairs
+        && (algorithms.rng != "bee2-rs::belt-ctr"
+            || algorithms.symmetric_encryption == "bee2-rs::belt-ctr")
+}
+
+/// Names of the algorithms of each kind this build can dispatch, as used in [`CryptoAlgorithms`].
+/// Unlike [`supported_algorithms`], it tells whether a peer's suite mixing the backends works.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SupportedMethods {
+    pub hash: Vec<String>,
+    pub kdf: Vec<String>,
+    pub diffie_hellman: Vec<String>,
+    pub signature: Vec<String>,
+    pub symmetric_encryption: Vec<String>,
+    pub aead: Vec<String>,
+    pub rng: Vec<String>,
+}
+
+impl SupportedMethods {
+    /// Returns whether every algorithm of the suite is supported. Some of them still can't be
+    /// combined, e.g. `rustcrypto::pbkdf2` only derives `dalek::ed25519` keypairs.
+    pub fn supports(&self, algorithms: &CryptoAlgorithms) -> bool {
+        self.hash.contains(&algorithms.hash)
+            && self.kdf.contains(&algorithms.kdf)
+            && self.diffie_hellman.contains(&algorithms.diffie_hellman)
+            && self.signature.contains(&algorithms.signature)
+            && self
+                .symmetric_encryption
+                .contains(&algorithms.symmetric_encryption)
+            && self.aead.contains(&algorithms.aead)
+            && self.rng.contains(&algorithms.rng)
+    }
+}
+
+/// Lists the names matched by the functions above in this build.
+pub fn supported_methods() -> SupportedMethods {
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| (*name).to_owned()).collect()
+    }
+
+    SupportedMethods {
+        hash: names(&[
+            #[cfg(feature = "bee2-rs")]
+            "bee2-rs::bash512",
+            #[cfg(feature = "pbkdf2")]
+            "rustcrypto::sha512",
+        ]),
+        kdf: names(&[
+            #[cfg(feature = "bee2-rs")]
+            "bee2-rs::pbkdf2",
+            #[cfg(feature = "pbkdf2")]
+            "rustcrypto::pbkdf2",
+        ]),
+        diffie_hellman: names(&[
+            #[cfg(feature = "bee2-rs")]
+            "bee2-rs::bignb3",
+            #[cfg(feature = "curve25519-dalek")]
+            "dalek::x25519",
+        ]),
+        signature: names(&[
+            #[cfg(feature = "bee2-rs")]
+            "bee2-rs::bignb3",
+            #[cfg(feature = "curve25519-dalek")]
+            "dalek::ed25519",
+        ]),
+        symmetric_encryption: names(&[
+            #[cfg(feature = "bee2-rs")]
+            "bee2-rs::belt-ctr",
+            #[cfg(feature = "aes-gcm")]
+            "rustcrypto::aes-gcm",
+        ]),
+        aead: names(&[
+            #[cfg(feature = "bee2-rs")]
+            "bee2-rs::belt256-dwp",
+            #[cfg(feature = "aes-gcm")]
+            "rustcrypto::aes-gcm",
+        ]),
+        rng: names(&[
+            #[cfg(feature = "bee2-rs")]
+            "bee2-rs::belt-ctr",
+            "default",
+        ]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CryptoAlgorithms, KeyStrength, PLAINTEXT_METHOD, PrivateKey, aead_wrap, hash,
+        is_algorithm_supported, kdf, rng_fill, supported_algorithms, supported_methods,
+        symmetric_encrypt, symmetric_genkey,
+    };
+
+    fn bee2rs_suite() -> CryptoAlgorithms {
+        CryptoAlgorithms {
+            hash: "bee2-rs::bash512".to_owned(),
+            kdf: "bee2-rs::pbkdf2".to_owned(),
+            diffie_hellman: "bee2-rs::bignb3".to_owned(),
+            signature: "bee2-rs::bignb3".to_owned(),
+            symmetric_encryption: "bee2-rs::belt-ctr".to_owned(),
+            aead: "bee2-rs::belt256-dwp".to_owned(),
+            rng: "bee2-rs::belt-ctr".to_owned(),
+        }
+    }
+
+    fn standard_suite() -> CryptoAlgorithms {
+        CryptoAlgorithms {
+            hash: "rustcrypto::sha512".to_owned(),
+            kdf: "rustcrypto::pbkdf2".to_owned(),
+            diffie_hellman: "dalek::x25519".to_owned(),
+            signature: "dalek::ed25519".to_owned(),
+            symmetric_encryption: "rustcrypto::aes-gcm".to_owned(),
+            aead: "rustcrypto::aes-gcm".to_owned(),
+            rng: "default".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_from_encryption_method() {
+        for algorithms in supported_algorithms() {
+            assert_eq!(
+                CryptoAlgorithms::from_encryption_method(&algorithms.encryption_method()),
+                Some(algorithms)
+            );
+        }
+        #[cfg(all(feature = "aes-gcm", feature = "curve25519-dalek", feature = "pbkdf2"))]
+        assert_eq!(
+            CryptoAlgorithms::from_encryption_method("aes-gcm"),
+            Some(CryptoAlgorithms::prequantum_standard())
+        );
+        assert_eq!(
+            CryptoAlgorithms::from_encryption_method(PLAINTEXT_METHOD),
+            None
+        );
+        assert_eq!(CryptoAlgorithms::from_encryption_method("kyber-aes"), None);
+        assert_eq!(CryptoAlgorithms::from_encryption_method(""), None);
+    }
+
+    #[test]
+    fn test_plaintext_method_is_not_encrypted() {
+        // Receivers treat messages with this method as plain text, so no suite may send it.
+        for algorithms in supported_algorithms() {
+            assert_ne!(algorithms.encryption_method(), PLAINTEXT_METHOD);
+        }
+    }
+
+    #[test]
+    fn test_supported_methods() {
+        let methods = supported_methods();
+        for algorithms in supported_algorithms() {
+            assert!(methods.supports(&algorithms));
+        }
+        assert!(!methods.supports(&CryptoAlgorithms::from_string("kyber".to_owned())));
+        assert_eq!(
+            methods.aead.contains(&"bee2-rs::belt256-dwp".to_owned()),
+            cfg!(feature = "bee2-rs")
+        );
+        assert_eq!(
+            methods.aead.contains(&"rustcrypto::aes-gcm".to_owned()),
+            cfg!(feature = "aes-gcm")
+        );
+        assert_eq!(
+            methods.signature.contains(&"dalek::ed25519".to_owned()),
+            cfg!(feature = "curve25519-dalek")
+        );
+
+        // Every listed method is dispatched.
+        let mut algorithms = CryptoAlgorithms::from_string(String::new());
+        for name in &methods.hash {
+            algorithms.hash = name.clone();
+            assert!(hash(&algorithms, b"data").is_some());
+        }
+        for name in &methods.kdf {
+            algorithms.kdf = name.clone();
+            assert!(kdf(&algorithms, b"data", 32).is_some());
+        }
+        for name in &methods.symmetric_encryption {
+            algorithms.symmetric_encryption = name.clone();
+            assert!(symmetric_encrypt(&algorithms, b"data", &[0x42; 32]).is_some());
+        }
+        for name in &methods.aead {
+            algorithms.aead = name.clone();
+            let key = PrivateKey {
+                sk: [0x42; 32].into(),
+            };
+            assert!(aead_wrap(&algorithms, b"data", key, b"").is_some());
+        }
+        for name in &methods.rng {
+            algorithms.rng = name.clone();
+            assert!(rng_fill(&algorithms, &mut [0; 16]).is_some());
+        }
+    }
+
+    #[test]
+    fn test_is_algorithm_supported() {
+        for algorithms in supported_algorithms() {
+            assert!(is_algorithm_supported(&algorithms));
+            assert!(symmetric_genkey(&algorithms, KeyStrength::ExtremelyHigh).is_some());
+        }
+        assert_eq!(
+            is_algorithm_supported(&bee2rs_suite()),
+            cfg!(feature = "bee2-rs")
+        );
+        assert_eq!(
+            is_algorithm_supported(&standard_suite()),
+            cfg!(all(
+                feature = "aes-gcm",
+                feature = "curve25519-dalek",
+                feature = "pbkdf2"
+            ))
+        );
+        assert!(!is_algorithm_supported(&CryptoAlgorithms::from_string(
+            "kyber".to_owned()
+        )));
+    }
+
+    #[test]
+    fn test_mixed_suites() {
+        // Primitives which don't depend on each other may come from different backends.
+        let mixed = CryptoAlgorithms {
+            hash: "rustcrypto::sha512".to_owned(),
+            aead: "rustcrypto::aes-gcm".to_owned(),
+            ..bee2rs_suite()
+        };
+        assert_eq!(
+            is_algorithm_supported(&mixed),
+            cfg!(all(
+                feature = "bee2-rs",
+                feature = "aes-gcm",
+                feature = "pbkdf2"
+            ))
+        );
+        let mixed = CryptoAlgorithms {
+            symmetric_encryption: "bee2-rs::belt-ctr".to_owned(),
+            ..standard_suite()
+        };
+        assert_eq!(
+            is_algorithm_supported(&mixed),
+            cfg!(all(
+                feature = "bee2-rs",
+                feature = "aes-gcm",
+                feature = "curve25519-dalek",
+                feature = "pbkdf2"
+            ))
+        );
+
+        // Keypairs of one backend can't be used by another.
+        for (signature, diffie_hellman) in [
+            ("bee2-rs::bignb3", "dalek::x25519"),
+            ("dalek::ed25519", "bee2-rs::bignb3"),
+        ] {
+            let mixed = CryptoAlgorithms {
+                signature: signature.to_owned(),
+                diffie_hellman: diffie_hellman.to_owned(),
+                ..bee2rs_suite()
+            };
+            assert!(!is_algorithm_supported(&mixed));
+        }
+        let mixed = CryptoAlgorithms {
+            kdf: "rustcrypto::pbkdf2".to_owned(),
+            ..bee2rs_suite()
+        };
+        assert!(!is_algorithm_supported(&mixed));
+        let mixed = CryptoAlgorithms {
+            rng: "default".to_owned(),
+            ..bee2rs_suite()
+        };
+        assert!(!is_algorithm_supported(&mixed));
+        // The `bee2-rs` RNG only generates keys for its own cipher.
+        let mixed = CryptoAlgorithms {
+            symmetric_encryption: "rustcrypto::aes-gcm".to_owned(),
+            ..bee2rs_suite()
+        };
+        assert!(!is_algorithm_supported(&mixed));
+    }
+}
@@ -0,0 +1,126 @@
+//! Encryption of the private account data stored on the server in `encrypted_private_info`.
+//!
+//! The base key is derived from the password as
+//! `kdf(PRIVATE_INFO_KEY_CONTEXT || account_id || password)`, with the account id as 8 little
+//! endian bytes, so that the same password gives every account a different key and keys can't be
+//! precomputed for all accounts at once. The login keypair is derived from the password alone, so
+//! knowing the public key or login signatures doesn't help the server to get the base key. Every time the data is encrypted, a random salt is
+//! generated and the actual key is derived as `kdf(base_key || salt)`, because the AEAD IV is bound
+//! to the key. The data is sealed with the AEAD of the algorithms with their name as associated
+//! data.
+
+use serde::{Deserialize, Serialize};
+
+use super::{CryptoAlgorithms, PrivateKey, aead_unwrap, aead_wrap, kdf, rng_fill};
+
+const PRIVATE_INFO_KEY_CONTEXT: &[u8] = b"peregrine-private-info";
+const KEY_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 16;
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedPrivateInfo {
+    algorithms: CryptoAlgorithms,
+    salt: Box<[u8]>,
+    ciphertext: Box<[u8]>,
+    mac: Box<[u8]>,
+}
+
+/// Derives the base key protecting the private info of account `account_id` from the user's
+/// password.
+pub fn private_info_key(
+    algorithms: &CryptoAlgorithms,
+    account_id: u64,
+    password: &str,
+) -> Option<Box<[u8]>> {
+    let mut data = PRIVATE_INFO_KEY_CONTEXT.to_vec();
+    data.extend_from_slice(&account_id.to_le_bytes());
+    data.extend_from_slice(password.as_bytes());
+    kdf(algorithms, &data, KEY_LENGTH)
+}
+
+fn salted_key(algorithms: &CryptoAlgorithms, key: &[u8], salt: &[u8]) -> Option<PrivateKey> {
+    let mut data = key.to_vec();
+    data.extend_from_slice(salt);
+    Some(PrivateKey {
+        sk: kdf(algorithms, &data, KEY_LENGTH)?,
+    })
+}
+
+pub fn encrypt_private_info(
+    algorithms: &CryptoAlgorithms,
+    key: &[u8],
+    plaintext: &[u8],
+) -> Option<Box<[u8]>> {
+    let mut salt = [0u8; SALT_LENGTH];
+    rng_fill(algorithms, &mut salt)?;
+    let (ciphertext, mac) = aead_wrap(
+        algorithms,
+        plaintext,
+        salted_key(algorithms, key, &salt)?,
+        algorithms.to_string().as_bytes(),
+    )?;
+    let data = EncryptedPrivateInfo {
+        algorithms: algorithms.clone(),
+        salt: salt.into(),
+        ciphertext,
+        mac,
+    };
+    Some(postcard::to_allocvec(&data).ok()?.into_boxed_slice())
+}
+
+/// Returns `None` if the data is corrupted, was encrypted with another key or with unsupported
+/// algorithms.
+pub fn decrypt_private_info(key: &[u8], data: &[u8]) -> Option<Box<[u8]>> {
+    let data: EncryptedPrivateInfo = postcard::from_bytes(data).ok()?;
+    let algorithms = &data.algorithms;
+    aead_unwrap(
+        algorithms,
+        &data.ciphertext,
+        algorithms.to_string().as_bytes(),
+        &data.mac,
+        salted_key(algorithms, key, &data.salt)?,
+    )?
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::CryptoAlgorithms;
+
+    use super::{decrypt_private_info, encrypt_private_info, private_info_key};
+
+    #[test]
+    fn test_private_info_round_trip() {
+        let algorithms = CryptoAlgorithms::prequantum_bee2rs();
+        let key = private_info_key(&algorithms, 1, "password1").unwrap();
+        let encrypted = encrypt_private_info(&algorithms, &key, b"private keys").unwrap();
+        assert_eq!(
+            decrypt_private_info(&key, &encrypted).as_deref(),
+            Some(&b"private keys"[..])
+        );
+        // Same data is encrypted differently every time.
+        assert_ne!(
+            encrypted,
+            encrypt_private_info(&algorithms, &key, b"private keys").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_private_info_wrong_password() {
+        let algorithms = CryptoAlgorithms::prequantum_bee2rs();
+        let key = private_info_key(&algorithms, 1, "password1").unwrap();
+        let other_key = private_info_key(&algorithms, 1, "password2").unwrap();
+        let encrypted = encrypt_private_info(&algorithms, &key, b"private keys").unwrap();
+        assert_eq!(decrypt_private_info(&other_key, &encrypted), None);
+        assert_eq!(decrypt_private_info(&key, &[]), None);
+    }
+
+    #[test]
+    fn test_private_info_key_per_account() {
+        let algorithms = CryptoAlgorithms::prequantum_bee2rs();
+        let key = private_info_key(&algorithms, 1, "password1").unwrap();
+        let other_key = private_info_key(&algorithms, 2, "password1").unwrap();
+        assert_ne!(key, other_key);
+        let encrypted = encrypt_private_info(&algorithms, &key, b"private keys").unwrap();
+        assert_eq!(decrypt_private_info(&other_key, &encrypted), None);
+    }
+}
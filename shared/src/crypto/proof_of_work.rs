@@ -0,0 +1,129 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{Deserialize, Serialize};
+
+use super::{CryptoAlgorithms, hash};
+
+/// Puzzle which a client has to solve before creating an account: find a nonce such that the
+/// hash of `challenge` followed by the nonce starts with at least `difficulty` zero bits.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Challenge {
+    pub algorithms: CryptoAlgorithms,
+    pub challenge: Box<[u8]>,
+    pub difficulty: u32,
+}
+
+/// Highest difficulty clients agree to solve. Harder challenges would keep an ordinary device busy
+/// for too long, so a server asking for them can't be registered on.
+pub const MAX_DIFFICULTY: u32 = 28;
+
+/// Number of nonces tried between checks whether solving was cancelled.
+const CANCEL_CHECK_INTERVAL: u64 = 1 << 12;
+
+/// Solution of a [`Challenge`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proof {
+    pub challenge: Box<[u8]>,
+    pub nonce: u64,
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in bytes {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+fn proof_hash(algorithms: &CryptoAlgorithms, challenge: &[u8], nonce: u64) -> Option<Box<[u8]>> {
+    let mut data = challenge.to_vec();
+    data.extend_from_slice(&nonce.to_le_bytes());
+    hash(algorithms, &data)
+}
+
+/// Returns whether `nonce` solves the challenge. Fails if the hash algorithm isn't supported.
+pub fn verify(challenge: &Challenge, nonce: u64) -> bool {
+    proof_hash(&challenge.algorithms, &challenge.challenge, nonce)
+        .is_some_and(|digest| leading_zero_bits(&digest) >= challenge.difficulty)
+}
+
+/// Searches for a nonce solving the challenge. Takes about `2^difficulty` hash computations.
+/// Returns `None` if the hash algorithm isn't supported, the difficulty is above
+/// [`MAX_DIFFICULTY`] or `cancelled` was set in the meantime.
+pub fn solve(challenge: &Challenge, cancelled: &AtomicBool) -> Option<Proof> {
+    if challenge.difficulty > MAX_DIFFICULTY {
+        return None;
+    }
+    // Being unable to compute the hash at all is the only way to fail.
+    proof_hash(&challenge.algorithms, &challenge.challenge, 0)?;
+    (0..=u64::MAX)
+        .take_while(|nonce| {
+            nonce % CANCEL_CHECK_INTERVAL != 0 || !cancelled.load(Ordering::Relaxed)
+        })
+        .find(|nonce| verify(challenge, *nonce))
+        .map(|nonce| Proof {
+            challenge: challenge.challenge.clone(),
+            nonce,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use crate::crypto::CryptoAlgorithms;
+
+    use super::{Challenge, MAX_DIFFICULTY, leading_zero_bits, proof_hash, solve, verify};
+
+    fn challenge(difficulty: u32) -> Challenge {
+        Challenge {
+            algorithms: CryptoAlgorithms::prequantum_bee2rs(),
+            challenge: Box::new([0x42; 32]),
+            difficulty,
+        }
+    }
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff]), 0);
+        assert_eq!(leading_zero_bits(&[0x00, 0x10]), 11);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00]), 16);
+        assert_eq!(leading_zero_bits(&[]), 0);
+    }
+
+    #[test]
+    fn test_valid_proof() {
+        let challenge = challenge(8);
+        let proof = solve(&challenge, &AtomicBool::new(false)).unwrap();
+        assert_eq!(proof.challenge, challenge.challenge);
+        assert!(verify(&challenge, proof.nonce));
+        // Zero difficulty accepts anything.
+        assert!(verify(&self::challenge(0), 12345));
+    }
+
+    #[test]
+    fn test_insufficient_proof() {
+        let easy = challenge(4);
+        let proof = solve(&easy, &AtomicBool::new(false)).unwrap();
+        let digest = proof_hash(&easy.algorithms, &easy.challenge, proof.nonce).unwrap();
+        // The same nonce falls one bit short of a harder challenge.
+        let hard = challenge(leading_zero_bits(&digest) + 1);
+        assert!(!verify(&hard, proof.nonce));
+        // An unsupported hash algorithm never verifies.
+        let mut unsupported = challenge(0);
+        unsupported.algorithms.hash = "unknown".to_owned();
+        assert!(!verify(&unsupported, 0));
+        assert!(solve(&unsupported, &AtomicBool::new(false)).is_none());
+    }
+
+    #[test]
+    fn test_unsolvable_challenges() {
+        // Too hard challenges are rejected without trying.
+        assert!(solve(&challenge(MAX_DIFFICULTY + 1), &AtomicBool::new(false)).is_none());
+        // A cancelled search gives up, even though the challenge can be solved.
+        assert!(solve(&challenge(MAX_DIFFICULTY), &AtomicBool::new(true)).is_none());
+    }
+}
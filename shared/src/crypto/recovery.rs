@@ -0,0 +1,127 @@
+//! Recovery codes, an opt-in way back into an account whose password is forgotten.
+//!
+//! The code is random and shown to the user once. Like the password, it never leaves the client.
+//! A recovery keypair is derived from it as `kdf_keypair(RECOVERY_KEYPAIR_CONTEXT || code)` to
+//! prove its knowledge to the server, and a copy of the private info is encrypted with the
+//! [`private_info_key`] derived from the code.
+
+use super::{
+    CryptoAlgorithms, PrivateKey, PublicKey, kdf_keypair, private_info::private_info_key, rng_fill,
+};
+
+const RECOVERY_KEYPAIR_CONTEXT: &[u8] = b"peregrine-recovery-keypair";
+const CODE_BYTES: usize = 20;
+/// Base32 alphabet without the letters easily confused with digits.
+const CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_GROUP_LENGTH: usize = 4;
+
+/// Generates a code of 32 characters split by dashes into groups of 4.
+pub fn generate_recovery_code(algorithms: &CryptoAlgorithms) -> Option<String> {
+    let mut bytes = [0u8; CODE_BYTES];
+    rng_fill(algorithms, &mut bytes)?;
+    let mut characters = vec![];
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            characters.push(CODE_ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    Some(
+        characters
+            .chunks(CODE_GROUP_LENGTH)
+            .map(|group| group.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("-"),
+    )
+}
+
+/// Code as typed by the user without separators, whitespace and case.
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|character| character.to_ascii_uppercase())
+        .collect()
+}
+
+/// Returns whether `code` may have been generated by [`generate_recovery_code`].
+pub fn is_recovery_code(code: &str) -> bool {
+    let code = normalize(code);
+    code.len() == CODE_BYTES * 8 / 5 && code.bytes().all(|byte| CODE_ALPHABET.contains(&byte))
+}
+
+pub fn recovery_keypair(
+    algorithms: &CryptoAlgorithms,
+    code: &str,
+) -> Option<(PrivateKey, PublicKey)> {
+    let mut data = RECOVERY_KEYPAIR_CONTEXT.to_vec();
+    data.extend_from_slice(normalize(code).as_bytes());
+    kdf_keypair(algorithms, &data)
+}
+
+/// Derives the key of the private info copy of account `account_id` which can be decrypted with
+/// the code.
+pub fn recovery_info_key(
+    algorithms: &CryptoAlgorithms,
+    account_id: u64,
+    code: &str,
+) -> Option<Box<[u8]>> {
+    private_info_key(algorithms, account_id, &normalize(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto::{
+        CryptoAlgorithms,
+        private_info::{decrypt_private_info, encrypt_private_info},
+    };
+
+    use super::{generate_recovery_code, is_recovery_code, recovery_info_key, recovery_keypair};
+
+    #[test]
+    fn test_generate_recovery_code() {
+        let algorithms = CryptoAlgorithms::prequantum_bee2rs();
+        let code = generate_recovery_code(&algorithms).unwrap();
+        assert_eq!(code.len(), 39);
+        assert_eq!(code.split('-').count(), 8);
+        assert!(is_recovery_code(&code));
+        assert_ne!(code, generate_recovery_code(&algorithms).unwrap());
+    }
+
+    #[test]
+    fn test_is_recovery_code() {
+        assert!(is_recovery_code("ABCD-EFGH-JKLM-NPQR-STUV-WXYZ-2345-6789"));
+        assert!(is_recovery_code("abcd efgh jklm npqr stuv wxyz 2345 6789"));
+        assert!(!is_recovery_code("ABCD-EFGH-JKLM-NPQR-STUV-WXYZ-2345"));
+        // `0`, `1`, `I` and `O` are never generated.
+        assert!(!is_recovery_code("ABCD-EFGH-JKLM-NPQR-STUV-WXYZ-2345-6781"));
+        assert!(!is_recovery_code(""));
+    }
+
+    #[test]
+    fn test_recovery_keys() {
+        let algorithms = CryptoAlgorithms::prequantum_bee2rs();
+        let code = "ABCD-EFGH-JKLM-NPQR-STUV-WXYZ-2345-6789";
+        let (_, public_key) = recovery_keypair(&algorithms, code).unwrap();
+        // The code may be typed in any case and grouping.
+        let typed = "abcdefgh jklmnpqr stuvwxyz 23456789";
+        assert_eq!(recovery_keypair(&algorithms, typed).unwrap().1, public_key);
+        let (_, other_public_key) =
+            recovery_keypair(&algorithms, "ABCD-EFGH-JKLM-NPQR-STUV-WXYZ-2345-6788").unwrap();
+        assert_ne!(other_public_key, public_key);
+
+        let key = recovery_info_key(&algorithms, 1, code).unwrap();
+        let encrypted = encrypt_private_info(&algorithms, &key, b"private keys").unwrap();
+        let typed_key = recovery_info_key(&algorithms, 1, typed).unwrap();
+        assert_eq!(
+            decrypt_private_info(&typed_key, &encrypted).as_deref(),
+            Some(&b"private keys"[..])
+        );
+        // Knowing the recovery public key doesn't help to decrypt the private info.
+        assert_eq!(decrypt_private_info(&public_key.pk, &encrypted), None);
+    }
+}
@@ -0,0 +1,15 @@
+use pbkdf2::pbkdf2_hmac;
+use sha2::{Digest, Sha512};
+
+/// Number of PBKDF2 iterations. Callers mix their own salts and contexts into the data.
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+pub(super) fn hash(data: &[u8]) -> Box<[u8]> {
+    Box::from(&Sha512::digest(data)[..])
+}
+
+pub(super) fn kdf(data: &[u8], result_len: usize) -> Box<[u8]> {
+    let mut result = vec![0u8; result_len];
+    pbkdf2_hmac::<Sha512>(data, &[], PBKDF2_ROUNDS, &mut result);
+    result.into_boxed_slice()
+}
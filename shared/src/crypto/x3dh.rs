@@ -20,20 +20,31 @@ pub struct X3DhReceiverKeysPrivate {
     pub opks: Vec<PrivateKey>,
 }
 
-pub fn generate_receiver_keys(
-    algorithms: &CryptoAlgorithms,
-) -> Option<(X3DhReceiverKeysPrivate, X3DhReceiverKeysPublic)> {
-    let (ik_priv, ik_pub) = generate_keypair(algorithms)?;
-    let (spk_priv, spk_pub) = generate_keypair(algorithms)?;
-    let spk_signature = sign(algorithms, ik_priv.clone(), ik_pub.clone(), &spk_pub.pk)?;
+/// Number of one-time prekeys generated at once.
+pub const OPK_BATCH_SIZE: usize = 10;
 
+pub fn generate_opks(
+    algorithms: &CryptoAlgorithms,
+    count: usize,
+) -> Option<(Vec<PrivateKey>, Vec<PublicKey>)> {
     let mut opks_priv = Vec::new();
     let mut opks_pub = Vec::new();
-    for _ in 0..10 {
+    for _ in 0..count {
         let (opk_priv, opk_pub) = generate_keypair(algorithms)?;
         opks_priv.push(opk_priv);
         opks_pub.push(opk_pub);
     }
+    Some((opks_priv, opks_pub))
+}
+
+pub fn generate_receiver_keys(
+    algorithms: &CryptoAlgorithms,
+) -> Option<(X3DhReceiverKeysPrivate, X3DhReceiverKeysPublic)> {
+    let (ik_priv, ik_pub) = generate_keypair(algorithms)?;
+    let (spk_priv, spk_pub) = generate_keypair(algorithms)?;
+    let spk_signature = sign(algorithms, ik_priv.clone(), ik_pub.clone(), &spk_pub.pk)?;
+
+    let (opks_priv, opks_pub) = generate_opks(algorithms, OPK_BATCH_SIZE)?;
 
     Some((
         X3DhReceiverKeysPrivate {
@@ -62,6 +73,7 @@ pub struct X3DhData {
 
 #[derive(Debug, Clone)]
 pub enum X3DhError {
+    /// Some algorithm of the suite isn't supported by this build, or a key is malformed for it.
     AlgorithmNotSupported,
     InvalidSignature,
     DecryptionFailure,
@@ -81,11 +93,51 @@ impl Display for X3DhError {
 
 impl Error for X3DhError {}
 
+/// Same as [`encode_x3dh_with_opk`], but picks a random one-time prekey of the receiver. The same
+/// key may be picked by several senders, so prefer claiming one from the server.
 pub fn encode_x3dh(
     data: &[u8],
     ik_priv: PrivateKey,
     ik_pub: PublicKey,
     other_keys: X3DhReceiverKeysPublic,
+) -> Result<X3DhData, X3DhError> {
+    let opk_id = if other_keys.opks.is_empty() {
+        None
+    } else {
+        let mut buffer = [0u8; 4];
+        rng_fill(&other_keys.algorithms, &mut buffer);
+        Some(u32::from_ne_bytes(buffer) % other_keys.opks.len() as u32)
+    };
+    encode_x3dh_with_opk(data, ik_priv, ik_pub, other_keys, opk_id)
+}
+
+/// Encrypts `data` to the receiver using the one-time prekey with index `opk_id`. If it is
+/// `None`, the key agreement has weaker forward secrecy.
+pub fn encode_x3dh_with_opk(
+    data: &[u8],
+    ik_priv: PrivateKey,
+    ik_pub: PublicKey,
+    other_keys: X3DhReceiverKeysPublic,
+    opk_id: Option<u32>,
+) -> Result<X3DhData, X3DhError> {
+    let opk = match opk_id {
+        Some(opk_id) => match other_keys.opks.get(opk_id as usize) {
+            Some(opk) => Some((opk_id, opk.clone())),
+            None => return Err(X3DhError::InvalidOpkKeyId),
+        },
+        None => None,
+    };
+    encode_x3dh_with_claimed_opk(data, ik_priv, ik_pub, other_keys, opk)
+}
+
+/// Same as [`encode_x3dh_with_opk`], but the one-time prekey is given along with its index
+/// instead of being looked up in `other_keys`, which may lack it.
+pub fn encode_x3dh_with_claimed_opk(
+    data: &[u8],
+    ik_priv: PrivateKey,
+    ik_pub: PublicKey,
+    other_keys: X3DhReceiverKeysPublic,
+    opk: Option<(u32, PublicKey)>,
 ) -> Result<X3DhData, X3DhError> {
     let algorithms = &other_keys.algorithms;
 
@@ -110,56 +162,49 @@ pub fn encode_x3dh(
         ik_pub.clone(),
         other_keys.spk.clone(),
     )
-    .unwrap();
+    .ok_or(X3DhError::AlgorithmNotSupported)?;
     let dh2 = diffie_hellman(
         algorithms,
         ek_priv.clone(),
         ek_pub.clone(),
         other_keys.ik.clone(),
     )
-    .unwrap();
-    let dh3 = diffie_hellman(algorithms, ek_priv, ek_pub.clone(), other_keys.spk).unwrap();
+    .ok_or(X3DhError::AlgorithmNotSupported)?;
+    let dh3 = diffie_hellman(algorithms, ek_priv, ek_pub.clone(), other_keys.spk)
+        .ok_or(X3DhError::AlgorithmNotSupported)?;
     let mut combined_dh = vec![];
     combined_dh.extend(dh1);
     combined_dh.extend(dh2);
     combined_dh.extend(dh3);
 
-    let opk_id = if other_keys.opks.is_empty() {
-        None
-    } else {
-        let mut buffer = [0u8; 4];
-        rng_fill(algorithms, &mut buffer);
-        Some(u32::from_ne_bytes(buffer) % other_keys.opks.len() as u32)
-    };
-    let opk = if let Some(opk_id) = opk_id {
-        other_keys.opks.get(opk_id as usize)
-    } else {
-        None
-    };
+    let opk_id = opk.as_ref().map(|(opk_id, _)| *opk_id);
+    let opk = opk.map(|(_, opk)| opk);
 
-    if let Some(opk) = opk {
+    if let Some(opk) = &opk {
         combined_dh.extend(opk.pk.clone());
     }
 
-    let sk = kdf(algorithms, &combined_dh, 32).unwrap();
-    let sk2 = kdf(algorithms, &sk, 32).unwrap();
+    let sk = kdf(algorithms, &combined_dh, 32).ok_or(X3DhError::AlgorithmNotSupported)?;
+    let sk2 = kdf(algorithms, &sk, 32).ok_or(X3DhError::AlgorithmNotSupported)?;
     let sk2 = PrivateKey { sk: sk2 };
 
     let mut ad = vec![];
     ad.extend(ik_pub.pk.clone());
     ad.extend(other_keys.ik.pk);
 
-    let (ciphertext, mac) = aead_wrap(algorithms, data, sk2, &ad).unwrap();
+    let (ciphertext, mac) =
+        aead_wrap(algorithms, data, sk2, &ad).ok_or(X3DhError::AlgorithmNotSupported)?;
 
     let mut signed_data = vec![];
     signed_data.extend(ek_pub.pk.clone());
     if let Some(opk) = opk {
-        signed_data.extend(opk.pk.clone());
+        signed_data.extend(opk.pk);
     }
     signed_data.extend(ciphertext.clone());
     // TODO: Idk with which key to sign as it's not specified by documentation provided. So I
     // assume it's `ik_priv`.
-    let signature = sign(algorithms, ik_priv, ik_pub, &signed_data).unwrap();
+    let signature = sign(algorithms, ik_priv, ik_pub, &signed_data)
+        .ok_or(X3DhError::AlgorithmNotSupported)?;
 
     Ok(X3DhData {
         ek_pub,
@@ -207,21 +252,21 @@ pub fn decode_x3dh(
         self_keys_public.spk.clone(),
         other_ik_pub.clone(),
     )
-    .unwrap();
+    .ok_or(X3DhError::AlgorithmNotSupported)?;
     let dh2 = diffie_hellman(
         algorithms,
         self_keys_private.ik,
         self_keys_public.ik.clone(),
         data.ek_pub.clone(),
     )
-    .unwrap();
+    .ok_or(X3DhError::AlgorithmNotSupported)?;
     let dh3 = diffie_hellman(
         algorithms,
         self_keys_private.spk,
         self_keys_public.spk,
         data.ek_pub,
     )
-    .unwrap();
+    .ok_or(X3DhError::AlgorithmNotSupported)?;
     let mut combined_dh = vec![];
     combined_dh.extend(dh1);
     combined_dh.extend(dh2);
@@ -231,8 +276,8 @@ pub fn decode_x3dh(
         combined_dh.extend(opk.pk.clone());
     }
 
-    let sk = kdf(algorithms, &combined_dh, 32).unwrap();
-    let sk2 = kdf(algorithms, &sk, 32).unwrap();
+    let sk = kdf(algorithms, &combined_dh, 32).ok_or(X3DhError::AlgorithmNotSupported)?;
+    let sk2 = kdf(algorithms, &sk, 32).ok_or(X3DhError::AlgorithmNotSupported)?;
     let sk2 = PrivateKey { sk: sk2 };
 
     let mut ad = vec![];
@@ -249,8 +294,11 @@ pub fn decode_x3dh(
 #[cfg(test)]
 mod tests {
     use crate::crypto::{
-        CryptoAlgorithms,
-        x3dh::{decode_x3dh, encode_x3dh, generate_receiver_keys},
+        CryptoAlgorithms, sign,
+        x3dh::{
+            X3DhError, decode_x3dh, encode_x3dh, encode_x3dh_with_claimed_opk,
+            encode_x3dh_with_opk, generate_receiver_keys,
+        },
     };
 
     #[test]
@@ -274,4 +322,212 @@ mod tests {
         .unwrap();
         assert_eq!(*message, *decoded_data);
     }
+
+    #[test]
+    fn test_x3dh_claimed_opk() {
+        let keys_a = generate_receiver_keys(&CryptoAlgorithms::prequantum_bee2rs()).unwrap();
+        let keys_b = generate_receiver_keys(&CryptoAlgorithms::prequantum_bee2rs()).unwrap();
+        let message = "Hello, World!".as_bytes();
+        // The sender only knows the claimed prekey, not the whole list.
+        let mut bundle = keys_b.1.clone();
+        let opk = bundle.opks.remove(3);
+        bundle.opks.clear();
+        let encode_data = encode_x3dh_with_claimed_opk(
+            message,
+            keys_a.0.ik,
+            keys_a.1.ik.clone(),
+            bundle,
+            Some((3, opk)),
+        )
+        .unwrap();
+        assert_eq!(encode_data.opk_id, Some(3));
+        let decoded_data = decode_x3dh(encode_data, keys_a.1.ik, keys_b.1, keys_b.0).unwrap();
+        assert_eq!(*message, *decoded_data);
+    }
+
+    #[test]
+    fn test_x3dh_opk_exhausted() {
+        let keys_a = generate_receiver_keys(&CryptoAlgorithms::prequantum_bee2rs()).unwrap();
+        let mut keys_b = generate_receiver_keys(&CryptoAlgorithms::prequantum_bee2rs()).unwrap();
+        let message = "Hello, World!".as_bytes();
+        for opk_id in [None, Some(3)] {
+            let encode_data = encode_x3dh_with_opk(
+                message,
+                keys_a.0.ik.clone(),
+                keys_a.1.ik.clone(),
+                keys_b.1.clone(),
+                opk_id,
+            )
+            .unwrap();
+            assert_eq!(encode_data.opk_id, opk_id);
+            let decoded_data = decode_x3dh(
+                encode_data,
+                keys_a.1.ik.clone(),
+                keys_b.1.clone(),
+                keys_b.0.clone(),
+            )
+            .unwrap();
+            assert_eq!(*message, *decoded_data);
+        }
+
+        let opk_count = keys_b.1.opks.len() as u32;
+        assert!(matches!(
+            encode_x3dh_with_opk(
+                message,
+                keys_a.0.ik.clone(),
+                keys_a.1.ik.clone(),
+                keys_b.1.clone(),
+                Some(opk_count),
+            ),
+            Err(X3DhError::InvalidOpkKeyId)
+        ));
+
+        // Random choice falls back to no OPK when the receiver has none.
+        keys_b.0.opks.clear();
+        keys_b.1.opks.clear();
+        let encode_data =
+            encode_x3dh(message, keys_a.0.ik, keys_a.1.ik.clone(), keys_b.1.clone()).unwrap();
+        assert_eq!(encode_data.opk_id, None);
+        let decoded_data = decode_x3dh(encode_data, keys_a.1.ik, keys_b.1, keys_b.0).unwrap();
+        assert_eq!(*message, *decoded_data);
+    }
+
+    #[test]
+    fn test_x3dh_standard() {
+        let algorithms = CryptoAlgorithms::prequantum_standard();
+        let keys_a = generate_receiver_keys(&algorithms).unwrap();
+        let keys_b = generate_receiver_keys(&algorithms).unwrap();
+        let message = "Hello, World!".as_bytes();
+        let encode = |opk_id| {
+            encode_x3dh_with_opk(
+                message,
+                keys_a.0.ik.clone(),
+                keys_a.1.ik.clone(),
+                keys_b.1.clone(),
+                opk_id,
+            )
+            .unwrap()
+        };
+        let decode = |data| {
+            decode_x3dh(
+                data,
+                keys_a.1.ik.clone(),
+                keys_b.1.clone(),
+                keys_b.0.clone(),
+            )
+        };
+
+        for opk_id in [None, Some(3)] {
+            assert_eq!(*message, *decode(encode(opk_id)).unwrap());
+        }
+
+        let mut data = encode(Some(3));
+        data.ciphertext[0] ^= 1;
+        assert!(matches!(decode(data), Err(X3DhError::InvalidSignature)));
+
+        let mut data = encode(Some(3));
+        data.mac[0] ^= 1;
+        assert!(matches!(decode(data), Err(X3DhError::DecryptionFailure)));
+
+        let mut data = encode(Some(3));
+        data.opk_id = Some(4);
+        assert!(matches!(decode(data), Err(X3DhError::InvalidSignature)));
+
+        let mut data = encode(Some(3));
+        data.signature[0] ^= 1;
+        assert!(matches!(decode(data), Err(X3DhError::InvalidSignature)));
+
+        let mut keys_b_public = keys_b.1.clone();
+        keys_b_public.spk_signature[0] ^= 1;
+        assert!(matches!(
+            encode_x3dh(
+                message,
+                keys_a.0.ik.clone(),
+                keys_a.1.ik.clone(),
+                keys_b_public
+            ),
+            Err(X3DhError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_x3dh_decode_errors() {
+        let algorithms = CryptoAlgorithms::prequantum_bee2rs();
+        let keys_a = generate_receiver_keys(&algorithms).unwrap();
+        let keys_b = generate_receiver_keys(&algorithms).unwrap();
+        let message = "Hello, World!".as_bytes();
+        let encode = || {
+            encode_x3dh_with_opk(
+                message,
+                keys_a.0.ik.clone(),
+                keys_a.1.ik.clone(),
+                keys_b.1.clone(),
+                Some(3),
+            )
+            .unwrap()
+        };
+        let decode = |data, keys_public| {
+            decode_x3dh(data, keys_a.1.ik.clone(), keys_public, keys_b.0.clone())
+        };
+
+        let mut data = encode();
+        let last = data.signature.len() - 1;
+        data.signature[last] ^= 1;
+        assert!(matches!(
+            decode(data, keys_b.1.clone()),
+            Err(X3DhError::InvalidSignature)
+        ));
+
+        let mut data = encode();
+        data.ciphertext[0] ^= 1;
+        assert!(matches!(
+            decode(data.clone(), keys_b.1.clone()),
+            Err(X3DhError::InvalidSignature)
+        ));
+        // A sender re-signing the tampered ciphertext is still caught by the AEAD.
+        let mut signed_data = vec![];
+        signed_data.extend(data.ek_pub.pk.clone());
+        signed_data.extend(keys_b.1.opks[3].pk.clone());
+        signed_data.extend(data.ciphertext.clone());
+        data.signature = sign(
+            &algorithms,
+            keys_a.0.ik.clone(),
+            keys_a.1.ik.clone(),
+            &signed_data,
+        )
+        .unwrap();
+        assert!(matches!(
+            decode(data, keys_b.1.clone()),
+            Err(X3DhError::DecryptionFailure)
+        ));
+
+        let mut data = encode();
+        data.mac = data.mac[1..].into();
+        assert!(matches!(
+            decode(data, keys_b.1.clone()),
+            Err(X3DhError::DecryptionFailure)
+        ));
+
+        let mut data = encode();
+        data.opk_id = Some(keys_b.1.opks.len() as u32);
+        assert!(matches!(
+            decode(data, keys_b.1.clone()),
+            Err(X3DhError::InvalidOpkKeyId)
+        ));
+
+        let mut keys_public = keys_b.1.clone();
+        keys_public.algorithms = CryptoAlgorithms::from_string("unknown".to_owned());
+        assert!(matches!(
+            decode(encode(), keys_public),
+            Err(X3DhError::AlgorithmNotSupported)
+        ));
+
+        // Only the key agreement is unsupported, which is found after the signature check.
+        let mut keys_public = keys_b.1.clone();
+        keys_public.algorithms.diffie_hellman = "unknown".to_owned();
+        assert!(matches!(
+            decode(encode(), keys_public),
+            Err(X3DhError::AlgorithmNotSupported)
+        ));
+    }
 }
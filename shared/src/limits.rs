@@ -3,28 +3,75 @@ pub struct Limits {
     pub max_username_length: usize,
     pub max_email_length: usize,
     pub max_public_key_length: usize,
+    /// One-time prekeys which are not consumed yet.
+    pub max_one_time_prekeys: usize,
+    /// One-time prekeys of a user which another user may claim in `opk_claim_period` seconds.
+    pub max_opk_claims: u32,
+    pub opk_claim_period: u32,
+    pub max_encrypted_private_info_size: usize,
     pub max_session_before_period: u32,
     pub max_session_after_period: u32,
     pub max_session_validity_period: u32,
+    /// Sessions in use are extended to end this many seconds after the last request, so that
+    /// only idle ones expire. 0 disables the extension.
+    pub session_extension_period: u32,
+    /// Sessions aren't extended past this many seconds after they began.
+    pub max_session_lifetime: u32,
 
     pub max_encryption_method_length: usize,
     pub max_message_length: usize,
     pub max_user_icon_size: usize,
     pub max_group_icon_size: usize,
     pub max_file_name_length: usize,
+    pub max_fetch_batch: u32,
+    pub max_group_members_page: u32,
+    pub max_upload_size: u64,
+    pub max_upload_chunk_size: usize,
+    /// Chunks received for a single upload, so that tiny chunks can't bloat the database.
+    pub max_upload_chunks: u32,
+    pub max_download_range_size: u64,
+    /// Uploads which are begun but not finished yet.
+    pub max_pending_uploads: u64,
+
+    // Invites which are neither accepted, rejected, cancelled nor expired. Only senders are
+    // capped, since a cap on received invites would let anyone block invites to a victim.
+    pub max_sent_pending_invites: u64,
+
+    pub max_report_reason_length: usize,
+    /// Reports sent by a single account in the last 24 hours. Reports are never closed, so older
+    /// ones don't count.
+    pub max_daily_reports: u64,
 }
 
 pub static LIMITS: Limits = Limits {
     max_username_length: 32,
     max_email_length: 254,
     max_public_key_length: 16 * 1024,
+    max_one_time_prekeys: 100,
+    max_opk_claims: 10,
+    opk_claim_period: 24 * 60 * 60,
+    max_encrypted_private_info_size: 1024 * 1024,
     max_session_before_period: 3 * 24 * 60 * 60,
     max_session_after_period: 7 * 24 * 60 * 60,
     max_session_validity_period: 365 * 24 * 60 * 60,
+    session_extension_period: 30 * 24 * 60 * 60,
+    max_session_lifetime: 2 * 365 * 24 * 60 * 60,
 
     max_encryption_method_length: 16,
     max_message_length: 16 * 1024,
     max_user_icon_size: 4 * 1024 * 1024,
     max_group_icon_size: 4 * 1024 * 1024,
     max_file_name_length: 256,
+    max_fetch_batch: 200,
+    max_group_members_page: 100,
+    max_upload_size: 512 * 1024 * 1024,
+    max_upload_chunk_size: 1024 * 1024,
+    max_upload_chunks: 4096,
+    max_download_range_size: 1024 * 1024,
+    max_pending_uploads: 10,
+
+    max_sent_pending_invites: 50,
+
+    max_report_reason_length: 1024,
+    max_daily_reports: 20,
 };
@@ -1,19 +1,98 @@
 use std::{
+    collections::HashMap,
     error::Error,
-    fmt::Debug,
+    fmt::{Debug, Display},
     fs::{self, File},
-    io::{Read, Write},
+    io::{self, ErrorKind, Read, Write},
     path::{Path, PathBuf},
+    sync::Mutex,
 };
 
 use atomic_write_file::AtomicWriteFile;
 use postcard::{from_bytes, to_allocvec};
 use serde::{Serialize, de::DeserializeOwned};
 
+/// Reason why a file of the storage couldn't be accessed.
+#[derive(Debug)]
+pub enum StorageError {
+    /// The file system refused the operation. The kind of the error tells apart a full disk,
+    /// a denied access or a missing file.
+    Io(io::Error),
+    /// The data couldn't be encoded, or the file doesn't contain data of the requested type.
+    Encoding(postcard::Error),
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Encoding(err) => write!(f, "Invalid data: {err}"),
+        }
+    }
+}
+
+impl Error for StorageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Encoding(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<postcard::Error> for StorageError {
+    fn from(value: postcard::Error) -> Self {
+        Self::Encoding(value)
+    }
+}
+
+/// Contents of files kept in memory instead of the disk, by their path. They are lost once it's
+/// dropped.
+#[derive(Debug, Default)]
+pub struct MemoryFiles {
+    files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+}
+
+impl MemoryFiles {
+    fn write(&self, path: PathBuf, bytes: Vec<u8>) {
+        self.files.lock().unwrap().insert(path, bytes);
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| ErrorKind::NotFound.into())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        self.files
+            .lock()
+            .unwrap()
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| ErrorKind::NotFound.into())
+    }
+}
+
 pub trait RawStorage {
     fn get_base_path(&self) -> &PathBuf;
 
-    fn get_path<P: AsRef<Path>>(&self, original_path: P) -> Result<PathBuf, Box<dyn Error>> {
+    /// Files of a storage which doesn't touch the disk at all. `None` for storages keeping
+    /// files on the disk.
+    fn memory_files(&self) -> Option<&MemoryFiles> {
+        None
+    }
+
+    fn get_path<P: AsRef<Path>>(&self, original_path: P) -> Result<PathBuf, StorageError> {
         let path = self.get_base_path().join(original_path);
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)?;
@@ -25,10 +104,14 @@ pub trait RawStorage {
         &self,
         file_path: P,
         data: &impl Serialize,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(), StorageError> {
+        let bytes = to_allocvec(data)?;
+        if let Some(files) = self.memory_files() {
+            files.write(self.get_base_path().join(file_path), bytes);
+            return Ok(());
+        }
         let path = self.get_path(file_path)?;
         println!("Storing data to file {:?}", path.as_path());
-        let bytes = to_allocvec(data)?;
         let mut file = AtomicWriteFile::options().open(path)?;
         file.write_all(&bytes)?;
         file.commit()?;
@@ -38,7 +121,11 @@ pub trait RawStorage {
     fn raw_load<P: AsRef<Path>, T: DeserializeOwned>(
         &self,
         file_path: P,
-    ) -> Result<T, Box<dyn Error>> {
+    ) -> Result<T, StorageError> {
+        if let Some(files) = self.memory_files() {
+            let bytes = files.read(&self.get_base_path().join(file_path))?;
+            return Ok(from_bytes(&bytes)?);
+        }
         let path = self.get_path(file_path)?;
         println!("Loading data from file {:?}", path.as_path());
         let mut bytes: Vec<u8> = vec![];
@@ -50,20 +137,45 @@ pub trait RawStorage {
         Ok(data)
     }
 
-    fn raw_remove<P: AsRef<Path>>(&self, file_path: P) -> Result<(), Box<dyn Error>> {
+    fn raw_remove<P: AsRef<Path>>(&self, file_path: P) -> Result<(), StorageError> {
+        if let Some(files) = self.memory_files() {
+            return Ok(files.remove(&self.get_base_path().join(file_path))?);
+        }
         let path = self.get_path(file_path)?;
         Ok(std::fs::remove_file(path)?)
     }
 }
 
+/// Storage which keeps everything in memory, for tests and for data which mustn't outlive the
+/// process.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    base_path: PathBuf,
+    files: MemoryFiles,
+}
+
+impl RawStorage for MemoryStorage {
+    fn get_base_path(&self) -> &PathBuf {
+        &self.base_path
+    }
+
+    fn memory_files(&self) -> Option<&MemoryFiles> {
+        Some(&self.files)
+    }
+}
+
+impl GeneralStorage for MemoryStorage {}
+
+/// Same as [`RawStorage`], but logs the errors.
 pub trait GeneralStorage: RawStorage {
-    fn store<P: AsRef<Path> + Debug>(&self, file_path: &P, data: &impl Serialize) -> bool {
-        if let Err(err) = self.raw_store(file_path, data) {
+    fn store<P: AsRef<Path> + Debug>(
+        &self,
+        file_path: &P,
+        data: &impl Serialize,
+    ) -> Result<(), StorageError> {
+        self.raw_store(file_path, data).inspect_err(|err| {
             eprintln!("Unexpected error while trying to store data to file {file_path:?}: {err:?}");
-            false
-        } else {
-            true
-        }
+        })
     }
 
     fn load<P: AsRef<Path> + Debug, T: DeserializeOwned>(&self, file_path: &P) -> Option<T> {
@@ -78,12 +190,178 @@ pub trait GeneralStorage: RawStorage {
         }
     }
 
-    fn remove<P: AsRef<Path> + Debug>(&self, file_path: &P) -> bool {
-        if let Err(err) = self.raw_remove(file_path) {
+    fn remove<P: AsRef<Path> + Debug>(&self, file_path: &P) -> Result<(), StorageError> {
+        self.raw_remove(file_path).inspect_err(|err| {
             eprintln!("Unexpected error while trying to remove file {file_path:?}: {err:?}");
-            false
-        } else {
-            true
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::ErrorKind, path::PathBuf};
+
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+    use serde::de::DeserializeOwned;
+
+    use super::{GeneralStorage, MemoryStorage, RawStorage, StorageError};
+    use crate::crypto::{
+        CryptoAlgorithms, PrivateKey, PublicKey,
+        x3dh::{X3DhReceiverKeysPrivate, X3DhReceiverKeysPublic},
+    };
+
+    struct TestStorage {
+        base_path: PathBuf,
+    }
+
+    impl TestStorage {
+        fn new(name: &str) -> Self {
+            let base_path = std::env::temp_dir().join(format!(
+                "peregrine-test-{}-{name}",
+                std::process::id()
+            ));
+            Self { base_path }
+        }
+
+        fn write(&self, file_path: &str, bytes: &[u8]) {
+            fs::write(self.get_path(file_path).unwrap(), bytes).unwrap();
         }
     }
+
+    impl Drop for TestStorage {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.base_path);
+        }
+    }
+
+    impl RawStorage for TestStorage {
+        fn get_base_path(&self) -> &PathBuf {
+            &self.base_path
+        }
+    }
+
+    impl GeneralStorage for TestStorage {}
+
+    type X3DhData = (X3DhReceiverKeysPrivate, X3DhReceiverKeysPublic);
+
+    /// Loads `bytes` as each type holding key material. Only checks that nothing panics.
+    fn load_all(storage: &TestStorage, bytes: &[u8]) {
+        fn load<T: DeserializeOwned>(storage: &TestStorage, bytes: &[u8]) {
+            storage.write("data.bin", bytes);
+            let _: Option<T> = storage.load(&"data.bin");
+        }
+
+        load::<X3DhData>(storage, bytes);
+        load::<(CryptoAlgorithms, Box<[u8]>)>(storage, bytes);
+        load::<CryptoAlgorithms>(storage, bytes);
+        load::<Box<[u8]>>(storage, bytes);
+        load::<String>(storage, bytes);
+    }
+
+    fn x3dh_data() -> X3DhData {
+        let key = |byte: u8| {
+            (
+                PrivateKey {
+                    sk: Box::new([byte; 32]),
+                },
+                PublicKey {
+                    pk: Box::new([byte; 33]),
+                },
+            )
+        };
+        let (ik_priv, ik_pub) = key(1);
+        let (spk_priv, spk_pub) = key(2);
+        let (opk_priv, opk_pub) = key(3);
+        (
+            X3DhReceiverKeysPrivate {
+                ik: ik_priv,
+                spk: spk_priv,
+                opks: vec![opk_priv],
+            },
+            X3DhReceiverKeysPublic {
+                algorithms: CryptoAlgorithms::from_string("test".to_owned()),
+                ik: ik_pub,
+                spk: spk_pub,
+                spk_signature: Box::new([4; 48]),
+                opks: vec![opk_pub],
+            },
+        )
+    }
+
+    #[test]
+    fn test_load_round_trip() {
+        let storage = TestStorage::new("round-trip");
+        let data = x3dh_data();
+        storage.store(&"keys.bin", &data).unwrap();
+        assert!(storage.load::<_, X3DhData>(&"keys.bin") == Some(data));
+        storage.remove(&"keys.bin").unwrap();
+        assert!(storage.load::<_, X3DhData>(&"keys.bin").is_none());
+        assert!(matches!(
+            storage.remove(&"keys.bin"),
+            Err(StorageError::Io(err)) if err.kind() == ErrorKind::NotFound
+        ));
+    }
+
+    #[test]
+    fn test_load_random_bytes() {
+        let storage = TestStorage::new("random-bytes");
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            let mut bytes = vec![0u8; rng.random_range(0..256)];
+            rng.fill(&mut bytes[..]);
+            load_all(&storage, &bytes);
+        }
+    }
+
+    #[test]
+    fn test_load_corrupted_keys() {
+        let storage = TestStorage::new("corrupted-keys");
+        let bytes = postcard::to_allocvec(&x3dh_data()).unwrap();
+        for length in 0..bytes.len() {
+            storage.write("keys.bin", &bytes[..length]);
+            assert!(storage.load::<_, X3DhData>(&"keys.bin").is_none());
+        }
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            let mut corrupted = bytes.clone();
+            for _ in 0..rng.random_range(1..4) {
+                let index = rng.random_range(0..corrupted.len());
+                corrupted[index] = rng.random();
+            }
+            load_all(&storage, &corrupted);
+        }
+    }
+
+    #[test]
+    fn test_memory_storage() {
+        let storage = MemoryStorage::default();
+        let data = x3dh_data();
+        assert!(storage.load::<_, X3DhData>(&"keys.bin").is_none());
+        storage.store(&"keys.bin", &data).unwrap();
+        assert!(storage.load::<_, X3DhData>(&"keys.bin") == Some(data));
+        assert!(!storage.get_base_path().join("keys.bin").exists());
+
+        storage.store(&"keys.bin", &"replaced").unwrap();
+        assert_eq!(
+            storage.load::<_, String>(&"keys.bin").as_deref(),
+            Some("replaced")
+        );
+        assert!(matches!(
+            storage.raw_load::<_, X3DhData>("keys.bin"),
+            Err(StorageError::Encoding(_))
+        ));
+
+        storage.store(&"other/keys.bin", &1u64).unwrap();
+        storage.remove(&"keys.bin").unwrap();
+        assert!(storage.load::<_, String>(&"keys.bin").is_none());
+        assert!(matches!(
+            storage.remove(&"keys.bin"),
+            Err(StorageError::Io(err)) if err.kind() == ErrorKind::NotFound
+        ));
+        assert_eq!(storage.load::<_, u64>(&"other/keys.bin"), Some(1));
+
+        // Storages don't share files.
+        assert!(MemoryStorage::default().load::<_, u64>(&"other/keys.bin").is_none());
+    }
 }
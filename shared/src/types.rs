@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 // TODO: Really check for permissions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct GroupPermissions {
     pub send_messages: bool,
     pub read_messages: bool,
@@ -21,6 +22,7 @@ impl Default for GroupPermissions {
 }
 
 impl GroupPermissions {
+    /// Panics if a custom permission is 256 bytes or longer.
     pub fn to_bytes(&self) -> Box<[u8]> {
         let mut general_permissions: u128 = 0;
         if self.send_messages {
@@ -45,25 +47,28 @@ impl GroupPermissions {
         bytes.into_boxed_slice()
     }
 
-    pub fn from_bytes(bytes: &[u8]) -> Self {
-        let general_permissions: u128 = u128::from_le_bytes(bytes[..16].try_into().unwrap());
+    /// Returns `None` if `bytes` weren't produced by [`Self::to_bytes`]: the general permissions
+    /// are missing or a custom permission is cut off. The bytes come from clients, so this must
+    /// not panic on anything.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let general_permissions: u128 = u128::from_le_bytes(bytes.get(..16)?.try_into().unwrap());
         let mut custom_permissions: Vec<String> = vec![];
         let mut index = 16;
 
         while index < bytes.len() {
             let length = bytes[index] as usize;
             index += 1;
-            let permission_name = &bytes[index..index + length];
+            let permission_name = bytes.get(index..index + length)?;
             custom_permissions.push(String::from_utf8_lossy(permission_name).to_string());
             index += length;
         }
 
-        Self {
+        Some(Self {
             send_messages: general_permissions & 1 != 0,
             read_messages: general_permissions & 2 != 0,
             invite_users: general_permissions & 4 != 0,
             custom_permissions,
-        }
+        })
     }
 
     pub fn admin() -> Self {
@@ -88,3 +93,84 @@ pub struct File {
     pub content: Box<[u8]>,
     pub encryption_method: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::GroupPermissions;
+
+    fn random_permissions(rng: &mut StdRng) -> GroupPermissions {
+        let custom_permissions = (0..rng.random_range(0..8))
+            .map(|_| {
+                (0..rng.random_range(0..64))
+                    .map(|_| rng.random::<char>())
+                    .collect::<String>()
+            })
+            // Lengths are stored in a single byte.
+            .filter(|permission| permission.len() < 256)
+            .collect();
+        GroupPermissions {
+            send_messages: rng.random(),
+            read_messages: rng.random(),
+            invite_users: rng.random(),
+            custom_permissions,
+        }
+    }
+
+    #[test]
+    fn test_permissions_round_trip() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            let permissions = random_permissions(&mut rng);
+            assert_eq!(
+                GroupPermissions::from_bytes(&permissions.to_bytes()),
+                Some(permissions)
+            );
+        }
+        for permissions in [
+            GroupPermissions::default(),
+            GroupPermissions::admin(),
+            GroupPermissions {
+                send_messages: false,
+                read_messages: false,
+                invite_users: false,
+                custom_permissions: vec![String::new(), "x".repeat(255)],
+            },
+        ] {
+            assert_eq!(
+                GroupPermissions::from_bytes(&permissions.to_bytes()),
+                Some(permissions)
+            );
+        }
+    }
+
+    #[test]
+    fn test_permissions_from_random_bytes() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..10000 {
+            let mut bytes = vec![0u8; rng.random_range(0..64)];
+            rng.fill(&mut bytes[..]);
+            let _ = GroupPermissions::from_bytes(&bytes);
+        }
+    }
+
+    #[test]
+    fn test_permissions_from_truncated_bytes() {
+        assert_eq!(GroupPermissions::from_bytes(&[]), None);
+        assert_eq!(GroupPermissions::from_bytes(&[4]), None);
+        assert_eq!(GroupPermissions::from_bytes(&[0; 15]), None);
+
+        let bytes = GroupPermissions::admin().to_bytes();
+        assert_eq!(
+            GroupPermissions::from_bytes(&bytes[..16]),
+            Some(GroupPermissions {
+                custom_permissions: vec![],
+                ..GroupPermissions::admin()
+            })
+        );
+        for length in 17..bytes.len() {
+            assert_eq!(GroupPermissions::from_bytes(&bytes[..length]), None);
+        }
+    }
+}